@@ -0,0 +1,164 @@
+/*!
+Benchmarks of the hot paths of an evolution, run with `cargo bench`.
+
+Every benchmark works on the same fixtures: a 1 MB corpus generated from a
+fixed seed and the neo baseline on the default base layout, so the numbers of
+two runs (or two machines) can be compared. The ballparks in the doc comments
+were measured with a release build on a current desktop processor; a result
+far above them in review is worth a closer look.
+
+The harness is a small loop around `Instant` instead of criterion, which isn't
+a dependency of klo. It prints the median of several samples.
+*/
+
+use std::{
+    fs,
+    hint::black_box,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use klo::{
+    geometry::Geometry,
+    interner::Interner,
+    layout::{builtin_baseline, Blueprint, BlueprintHelpers, Layout},
+    ngrams::NGrams,
+    scoring::cost_breakdown,
+    weights::CostWeights,
+};
+
+/// The number of samples whose median is printed.
+const SAMPLES: usize = 11;
+
+/// The seed of the fixture corpus. Changing it makes the numbers incomparable to earlier ones.
+const CORPUS_SEED: u64 = 0x6b6c_6f21;
+
+/// The rough size of the fixture corpus.
+const CORPUS_BYTES: usize = 1 << 20;
+
+/// The words the fixture corpus is made of, with the characters of the neo baseline.
+#[rustfmt::skip]
+const WORDS: &[&str] = &[
+    "die", "der", "und", "in", "zu", "den", "das", "nicht", "von", "sie", "ist", "des", "sich",
+    "mit", "dem", "dass", "er", "es", "ein", "ich", "auf", "so", "eine", "auch", "als", "an",
+    "nach", "wie", "im", "für", "man", "aber", "aus", "durch", "wenn", "nur", "war", "noch",
+    "werden", "bei", "hat", "wir", "was", "wird", "sein", "einen", "welche", "sind", "oder", "zur",
+    "um", "haben", "einer", "mir", "über", "ihm", "diese", "einem", "ihr", "uns", "da", "zum",
+    "kann", "doch", "vor", "dieser", "mich", "ihn", "du", "hatte", "seine", "mehr", "straße",
+    "größer", "müssen", "können", "schön", "früh", "würde", "jedoch", "zwischen", "tastatur",
+    "belegung", "qualität", "xylophon", "quer", "bequem", "vielleicht",
+];
+
+/// Run `f` `iterations` times per sample and print the median time of one call.
+fn bench<T>(name: &str, iterations: u32, mut f: impl FnMut() -> T) {
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..iterations {
+                black_box(f());
+            }
+            start.elapsed() / iterations
+        })
+        .collect();
+    samples.sort();
+    println!("{:<24} {:>12.3?} per call", name, samples[SAMPLES / 2]);
+}
+
+/// Write the fixture corpus once and return its path.
+fn fixture_corpus() -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("bench_corpus.txt");
+    if path.is_file() {
+        return path;
+    }
+
+    // xorshift64, so the corpus doesn't depend on the version of rand.
+    let mut state = CORPUS_SEED;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut corpus = String::with_capacity(CORPUS_BYTES + 64);
+    while corpus.len() < CORPUS_BYTES {
+        corpus.push_str(WORDS[next() as usize % WORDS.len()]);
+        corpus.push(match next() % 16 {
+            0 => ',',
+            1 => '.',
+            2 => '\n',
+            _ => ' ',
+        });
+    }
+    fs::write(&path, corpus).unwrap();
+    path
+}
+
+/// The neo baseline on the default base layout.
+fn fixture_blueprint() -> Blueprint {
+    let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+    blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+    blueprint
+}
+
+/// Parsing the fixture corpus into normalized ngrams: about 250 ms.
+fn bench_corpus_parsing(corpus: &str) {
+    bench("corpus parsing", 3, || {
+        NGrams::from_text_file(corpus).unwrap()
+    });
+}
+
+/// Indexing the characters of a blueprint: about 12 µs.
+fn bench_layout_from_blueprint(blueprint: &Blueprint, interner: &Arc<Interner>) {
+    let key_costs = CostWeights::default().key_costs;
+    bench("Layout::from_blueprint", 10_000, || {
+        Layout::from_blueprint(blueprint.clone(), interner, &key_costs)
+    });
+}
+
+/// The cost of a layout against the ngrams of the fixture corpus: about 4.2 µs.
+fn bench_cost_breakdown(layout: &Layout, ngrams: &NGrams, geometry: &Geometry) {
+    let weights = CostWeights::default();
+    bench("cost_breakdown", 100_000, || {
+        cost_breakdown(layout, ngrams, &weights, geometry)
+    });
+}
+
+/**
+One step of an evolution: swapping two characters of layer 0 with the
+incremental `Layout::swap_keys` and scoring the result, the way every
+mutation is evaluated: a few µs more than `cost_breakdown` alone.
+*/
+fn bench_swap(layout: &Layout, ngrams: &NGrams, geometry: &Geometry) {
+    let weights = CostWeights::default();
+    let pairs: Vec<_> = [("e", "n"), ("a", "r"), ("x", "q"), ("ß", ","), ("u", "t")]
+        .iter()
+        .map(|&(first, second)| {
+            let key = |char| layout.blueprint.get_key_pos(char).unwrap();
+            (key(first), key(second))
+        })
+        .collect();
+    let mut layout = layout.clone();
+    let mut round = 0;
+    bench("swap and rescore", 10_000, || {
+        let (first, second) = pairs[round % pairs.len()];
+        round += 1;
+        layout.swap_keys(first, second, 0);
+        cost_breakdown(&layout, ngrams, &weights, geometry)
+    });
+}
+
+fn main() {
+    let corpus = fixture_corpus();
+    let corpus = corpus.to_str().unwrap();
+    let ngrams = NGrams::from_text_file(corpus).unwrap();
+    let blueprint = fixture_blueprint();
+    let geometry = Geometry::neo();
+    let key_costs = CostWeights::default().key_costs;
+    let layout = Layout::from_blueprint(blueprint.clone(), &ngrams.interner, &key_costs);
+
+    bench_corpus_parsing(corpus);
+    bench_layout_from_blueprint(&blueprint, &ngrams.interner);
+    bench_cost_breakdown(&layout, &ngrams, &geometry);
+    bench_swap(&layout, &ngrams, &geometry);
+}
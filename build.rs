@@ -0,0 +1,24 @@
+use std::{path::Path, process::Command};
+
+/// Record `git describe` of the checkout klo is built from as `KLO_GIT_DESCRIBE`, for the reproduction headers.
+fn main() {
+    let describe = Command::new("git")
+        .args(["describe", "--always", "--dirty", "--tags"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|describe| describe.trim().to_string())
+        .filter(|describe| !describe.is_empty());
+    if let Some(describe) = describe {
+        println!("cargo:rustc-env=KLO_GIT_DESCRIBE={}", describe);
+    }
+
+    // Builds from a source archive have no checkout to watch.
+    for path in &[".git/HEAD", ".git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,368 @@
+use std::{collections::BTreeSet, fs};
+
+use log::warn;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    geometry::{Geometry, KeyPos},
+    layout::Blueprint,
+};
+
+/**
+Expand an --alphabet specification into the plain list of characters.
+
+- `@path` reads the characters from a file: one character per line or all of
+  them on a single line. Lines starting with `#` are comments.
+- Otherwise the specification is a comma separated list of items. An item of
+  the form `a-z` is an inclusive range, every other item is taken literally.
+  `\,` and `\-` stand for a literal comma and dash, so `a-z,\-` is the
+  lowercase letters plus the dash, and a lone `-` is a dash as well.
+
+Characters are deduplicated, except in a specification that is a single
+literal item (the historical form) so that typos there are still reported by
+the option validation. Characters made of more than one codepoint aren't
+supported.
+*/
+pub fn expand_alphabet(spec: &str) -> Result<String, String> {
+    if let Some(path) = spec.strip_prefix('@') {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read the alphabet file {}: {}", path, e))?;
+        let chars: Vec<char> = contents
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+            .map(|line| single_codepoints(line, path))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
+        return Ok(deduplicate(&chars));
+    }
+
+    let items = split_items(spec);
+    let mut chars = vec![];
+    let mut has_range = false;
+    for item in &items {
+        match item.as_slice() {
+            [(start, false), ('-', false), (end, false)] => {
+                if start > end {
+                    return Err(format!(
+                        "Invalid alphabet range {}-{}: the start comes after the end",
+                        start, end
+                    ));
+                }
+                chars.extend(*start..=*end);
+                has_range = true;
+            }
+            literal => {
+                let literal: String = literal.iter().map(|(char, _)| char).collect();
+                chars.extend(single_codepoints(&literal, "--alphabet")?);
+            }
+        }
+    }
+
+    if items.len() == 1 && !has_range {
+        Ok(chars.into_iter().collect())
+    } else {
+        Ok(deduplicate(&chars))
+    }
+}
+
+/// Split at unescaped commas. Every character is marked with whether it was escaped.
+fn split_items(spec: &str) -> Vec<Vec<(char, bool)>> {
+    let mut items = vec![vec![]];
+    let mut chars = spec.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some(escaped) => items.last_mut().unwrap().push((escaped, true)),
+                None => items.last_mut().unwrap().push(('\\', false)),
+            },
+            ',' => items.push(vec![]),
+            char => items.last_mut().unwrap().push((char, false)),
+        }
+    }
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+fn single_codepoints(text: &str, source: &str) -> Result<Vec<char>, String> {
+    match text.graphemes(true).find(|grapheme| grapheme.chars().count() > 1) {
+        Some(grapheme) => Err(format!(
+            "{} contains {:?}, which consists of more than one codepoint. Such characters aren't supported yet.",
+            source, grapheme
+        )),
+        None => Ok(text.chars().collect()),
+    }
+}
+
+fn deduplicate(chars: &[char]) -> String {
+    let mut unique = String::new();
+    for char in chars {
+        if !unique.contains(*char) {
+            unique.push(*char);
+        }
+    }
+    unique
+}
+
+/// How the alphabet and layer 0 of a blueprint fit together.
+pub struct Coverage {
+    /// Alphabet characters that aren't on layer 0. The evolution can't place them.
+    pub missing: Vec<char>,
+    /// Alphabet characters on more than one key of layer 0, with those keys.
+    pub duplicated: Vec<(char, Vec<KeyPos>)>,
+    /// Letters on layer 0 that aren't in the alphabet, so they never move.
+    pub unused: Vec<char>,
+    /// Keys without a character on layer 0.
+    pub empty_keys: Vec<KeyPos>,
+}
+
+impl Coverage {
+    pub fn of(blueprint: &Blueprint, alphabet: &str) -> Self {
+        let layer_0: BTreeSet<&str> = blueprint
+            .iter()
+            .flatten()
+            .filter_map(|key| key.first().map(String::as_str))
+            .collect();
+        let missing = alphabet
+            .chars()
+            .filter(|char| !layer_0.contains(char.to_string().as_str()))
+            .collect();
+        let duplicated = alphabet
+            .chars()
+            .map(|char| {
+                let keys: Vec<KeyPos> = blueprint
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(row_idx, row)| {
+                        row.iter()
+                            .enumerate()
+                            .filter(|(_, key)| {
+                                key.first().is_some_and(|first| first.chars().eq([char]))
+                            })
+                            .map(move |(key_idx, _)| (row_idx, key_idx))
+                    })
+                    .collect();
+                (char, keys)
+            })
+            .filter(|(_, keys)| keys.len() > 1)
+            .collect();
+        let unused = layer_0
+            .iter()
+            .filter_map(|key| {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) if char.is_alphabetic() && !alphabet.contains(char) => {
+                        Some(char)
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        let empty_keys = blueprint
+            .iter()
+            .enumerate()
+            .flat_map(|(row_idx, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, key)| key.first().is_none_or(String::is_empty))
+                    .map(move |(key_idx, _)| (row_idx, key_idx))
+            })
+            .collect();
+        Coverage {
+            missing,
+            duplicated,
+            unused,
+            empty_keys,
+        }
+    }
+
+    /// Explain which alphabet characters are missing and where they could go.
+    pub fn missing_message(&self, geometry: &Geometry) -> String {
+        let missing: Vec<String> = self
+            .missing
+            .iter()
+            .map(|char| format!("{:?}", char))
+            .collect();
+        let names = geometry.position_names();
+        let empty_keys: Vec<String> = self
+            .empty_keys
+            .iter()
+            .map(|&pos| match names.iter().find(|(_, named)| *named == pos) {
+                Some((name, _)) => format!("{} {:?}", name, pos),
+                None => format!("{:?}", pos),
+            })
+            .collect();
+        let candidates = if empty_keys.is_empty() {
+            "The base layout has no empty keys for them.".to_string()
+        } else {
+            format!(
+                "Empty keys that could take them: {}.",
+                empty_keys.join(", ")
+            )
+        };
+        format!(
+            "The alphabet has {} characters that aren't on layer 0 of the layout: {}. {} Add them to the base layout, remove them from --alphabet or pass --allow-partial-alphabet to leave them out.",
+            missing.len(),
+            missing.join(", "),
+            candidates
+        )
+    }
+}
+
+/**
+The alphabet without the characters that aren't on layer 0 of the blueprint.
+Missing characters are an error, or with `allow_partial` a warning. Characters
+on more than one key of layer 0 are always an error, the evolution would move
+them as two characters. Letters of the layout that the alphabet lacks are only
+warned about.
+*/
+pub fn covered_alphabet(
+    blueprint: &Blueprint,
+    alphabet: &str,
+    geometry: &Geometry,
+    allow_partial: bool,
+) -> Result<String, String> {
+    let coverage = Coverage::of(blueprint, alphabet);
+    if !coverage.duplicated.is_empty() {
+        let duplicated: Vec<String> = coverage
+            .duplicated
+            .iter()
+            .map(|(char, keys)| format!("{:?} on {:?}", char, keys))
+            .collect();
+        return Err(format!(
+            "The alphabet has characters on more than one key of layer 0 of the layout: {}. Every alphabet character must be on exactly one key.",
+            duplicated.join(", ")
+        ));
+    }
+    if !coverage.unused.is_empty() {
+        let unused: String = coverage.unused.iter().collect();
+        warn!(
+            "The layout has letters that aren't in the alphabet, they stay where they are: {}",
+            unused
+        );
+    }
+    if coverage.missing.is_empty() {
+        return Ok(alphabet.to_string());
+    }
+    if !allow_partial {
+        return Err(coverage.missing_message(geometry));
+    }
+    let missing: String = coverage.missing.iter().collect();
+    warn!(
+        "Leaving out the alphabet characters that aren't on layer 0 of the layout: {}",
+        missing
+    );
+    Ok(alphabet
+        .chars()
+        .filter(|char| !coverage.missing.contains(char))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_and_literals_are_expanded_in_order() {
+        let cases = [
+            ("a-e,0-3,äöüß", "abcde0123äöüß"),
+            ("x-z,a", "xyza"),
+            ("a-a", "a"),
+            ("a-c,b-d", "abcd"),
+            ("a-c,-", "abc-"),
+            ("a-c,\\-", "abc-"),
+            ("a\\-c", "a-c"),
+            ("a-", "a-"),
+            ("-a", "-a"),
+            ("-", "-"),
+            ("a-c,\\,", "abc,"),
+            ("a-c,,", "abc"),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(expand_alphabet(spec).as_deref(), Ok(expected), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn a_single_literal_keeps_its_duplicates_for_the_validation() {
+        assert_eq!(expand_alphabet("abca").as_deref(), Ok("abca"));
+        assert_eq!(expand_alphabet("abc,a").as_deref(), Ok("abc"));
+    }
+
+    #[test]
+    fn a_backwards_range_is_rejected() {
+        assert_eq!(
+            expand_alphabet("z-a"),
+            Err("Invalid alphabet range z-a: the start comes after the end".to_string())
+        );
+    }
+
+    #[test]
+    fn characters_of_several_codepoints_are_rejected() {
+        let error = expand_alphabet("a-c,e\u{301}").unwrap_err();
+        assert!(error.contains("more than one codepoint"), "{}", error);
+    }
+
+    #[test]
+    fn an_alphabet_file_has_a_character_per_line_or_a_single_line() {
+        let path = std::env::temp_dir().join("klo_alphabet_file.txt");
+        fs::write(&path, "# German\na\nb\n\nb\nxyz\n").unwrap();
+        let alphabet = expand_alphabet(&format!("@{}", path.display()));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(alphabet.as_deref(), Ok("abxyz"));
+    }
+
+    /// A row of keys with the given characters on layer 0, `""` for an empty key.
+    fn blueprint(keys: &[&str]) -> Blueprint {
+        vec![keys.iter().map(|key| vec![key.to_string()]).collect()]
+    }
+
+    #[test]
+    fn alphabet_characters_off_the_layout_are_an_error_naming_the_empty_keys() {
+        let blueprint = blueprint(&["a", "b", "", "c"]);
+        let coverage = Coverage::of(&blueprint, "abcx");
+        assert_eq!(coverage.missing, vec!['x']);
+        assert_eq!(coverage.empty_keys, vec![(0, 2)]);
+
+        let error = covered_alphabet(&blueprint, "abcx", &Geometry::neo(), false).unwrap_err();
+        assert!(
+            error.starts_with(
+                "The alphabet has 1 characters that aren't on layer 0 of the layout: 'x'."
+            ),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("Empty keys that could take them: L_ring_number (0, 2)."),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn a_partial_alphabet_leaves_out_the_missing_characters() {
+        let blueprint = blueprint(&["a", "b", "c"]);
+        assert_eq!(
+            covered_alphabet(&blueprint, "axbyc", &Geometry::neo(), true),
+            Ok("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn letters_of_the_layout_missing_from_the_alphabet_are_only_unused() {
+        let blueprint = blueprint(&["a", "b", "q", ",", "Entf"]);
+        let coverage = Coverage::of(&blueprint, "ab");
+        assert_eq!(coverage.unused, vec!['q']);
+        assert_eq!(
+            covered_alphabet(&blueprint, "ab", &Geometry::neo(), false),
+            Ok("ab".to_string())
+        );
+    }
+
+    #[test]
+    fn alphabet_characters_on_two_keys_are_always_an_error() {
+        let blueprint = blueprint(&["a", "b", "a"]);
+        let error = covered_alphabet(&blueprint, "ab", &Geometry::neo(), true).unwrap_err();
+        assert!(error.contains("'a' on [(0, 0), (0, 2)]"), "{}", error);
+    }
+}
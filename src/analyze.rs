@@ -0,0 +1,167 @@
+use log::info;
+
+use crate::{
+    corpus_matrix,
+    error::{KloError, LayoutError},
+    explain,
+    geometry::Geometry,
+    klo_options::{AnalyzeOptions, CompareOptions, KloOptions},
+    layout::{BlueprintHelpers, Layout},
+    ngrams::NGrams,
+    report,
+    scoring::{cost_model, CostBreakdown},
+    simulate,
+    statistics::{high_layer_characters, missing_characters, LayoutStatistics},
+};
+
+/// Score a single layout and log its cost breakdown.
+pub fn analyze_layout(options: &KloOptions, analyze: &AnalyzeOptions) -> Result<(), KloError> {
+    let ngram_data = NGrams::from_options(options)?;
+    let layout = Layout::from_layout_arg(options, &analyze.layout, &ngram_data.interner)?;
+    let geometry = Geometry::from_options(options, &layout.blueprint)?;
+    let model = cost_model(options, geometry.clone());
+    let cost = model.score(&layout, &ngram_data);
+
+    info!("Layout:");
+    for line in layout.blueprint.layout_string().lines() {
+        info!("    {}", line);
+    }
+    log_cost_breakdown(&cost);
+    report::log_missing_characters(
+        &missing_characters(&layout, &ngram_data, options.missing_char_threshold),
+        &options.cost_weights,
+    );
+    if options.verbose {
+        explain::log_component_attributions(&layout, &ngram_data, model.as_ref(), &cost);
+    }
+    report::log_hand_split(&cost, &options.cost_weights);
+    report::log_statistics(&LayoutStatistics::new(&layout, &ngram_data, &geometry));
+    report::log_high_layer_characters(&high_layer_characters(
+        &layout,
+        &ngram_data,
+        options.high_layer_threshold,
+    ));
+    explain::log_explanation(&layout, &ngram_data, &geometry, model.as_ref(), &cost);
+    if let Some(sample) = &analyze.simulate {
+        simulate::log_simulation(&layout.blueprint, &geometry, &layout.key_costs, sample)
+            .map_err(KloError::Other)?;
+    }
+    Ok(())
+}
+
+/// A layout of `klo compare` with its cost and statistics.
+struct Compared {
+    layout_string: String,
+    cost: CostBreakdown,
+    statistics: LayoutStatistics,
+}
+
+/**
+Score several layouts against the same ngrams and log them ordered by total
+cost, followed by a table of their costs, same finger bigrams, hand
+alternation and finger loads with the cheapest layout first.
+*/
+pub fn compare_layouts(options: &KloOptions, compare: &CompareOptions) -> Result<(), KloError> {
+    if !compare.compare_corpora.is_empty() {
+        return corpus_matrix::compare_corpora(options, compare);
+    }
+    let ngram_data = NGrams::from_options(options)?;
+
+    let mut compared: Vec<Compared> = compare
+        .layouts
+        .iter()
+        .map(|arg| {
+            let layout = Layout::from_layout_arg(options, arg, &ngram_data.interner)?;
+            let geometry = Geometry::from_options(options, &layout.blueprint)?;
+            Ok(Compared {
+                layout_string: layout.blueprint.layout_string(),
+                cost: cost_model(options, geometry.clone()).score(&layout, &ngram_data),
+                statistics: LayoutStatistics::new(&layout, &ngram_data, &geometry),
+            })
+        })
+        .collect::<Result<_, KloError>>()?;
+    check_shapes(&compare.layouts, &compared)?;
+    compared.sort_by(|a, b| a.cost.total().total_cmp(&b.cost.total()));
+
+    for (rank, layout) in compared.iter().enumerate() {
+        info!(
+            "#{} with a total cost of {:.2}:",
+            rank + 1,
+            layout.cost.total()
+        );
+        for line in layout.layout_string.lines() {
+            info!("    {}", line);
+        }
+    }
+
+    let fingers: Vec<String> = compared[0]
+        .statistics
+        .finger_loads
+        .iter()
+        .map(|load| load.finger.clone())
+        .collect();
+    let mut table = vec![["Layout", "Total cost", "Same finger", "Hand alternation"]
+        .iter()
+        .map(|name| name.to_string())
+        .chain(fingers.iter().cloned())
+        .collect::<Vec<_>>()];
+    for (rank, layout) in compared.iter().enumerate() {
+        let statistics = &layout.statistics;
+        let mut row = vec![
+            format!("#{}", rank + 1),
+            format!("{:.2}", layout.cost.total()),
+            format!("{:.2}%", statistics.same_finger_bigrams * 100.0),
+            format!("{:.2}%", statistics.hand_alternation * 100.0),
+        ];
+        row.extend(fingers.iter().map(|finger| {
+            let share = statistics
+                .finger_loads
+                .iter()
+                .find(|load| &load.finger == finger)
+                .map_or(0.0, |load| load.share);
+            format!("{:.1}%", share * 100.0)
+        }));
+        table.push(row);
+    }
+    for line in corpus_matrix::aligned(&table) {
+        info!("{}", line);
+    }
+    Ok(())
+}
+
+/// Every layout string must have as many rows and keys per row as the first one, or their statistics can't be compared.
+fn check_shapes(args: &[String], compared: &[Compared]) -> Result<(), KloError> {
+    let shape = |layout: &Compared| -> Vec<usize> {
+        layout
+            .layout_string
+            .lines()
+            .map(|row| row.chars().filter(|char| !char.is_whitespace()).count())
+            .collect()
+    };
+    let first = shape(&compared[0]);
+    for (arg, layout) in args.iter().zip(compared).skip(1) {
+        let other = shape(layout);
+        let rows = first.len().max(other.len());
+        if let Some(row) = (0..rows).find(|&row| first.get(row) != other.get(row)) {
+            return Err(KloError::Layout(LayoutError(format!(
+                "Row {} of the layout {} has {} keys, but the one of {} has {}.",
+                row + 1,
+                arg,
+                other.get(row).unwrap_or(&0),
+                args[0],
+                first.get(row).unwrap_or(&0)
+            ))));
+        }
+    }
+    Ok(())
+}
+
+fn log_cost_breakdown(cost: &CostBreakdown) {
+    info!("Key position cost: {:.2}", cost.key_position);
+    if cost.hand_balance > 0.0 {
+        info!("Hand balance cost: {:.2}", cost.hand_balance);
+    }
+    info!("Same finger cost: {:.2}", cost.same_finger);
+    info!("Same hand cost: {:.2}", cost.same_hand);
+    info!("Total cost: {:.2}", cost.total());
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+/// What one anneal level of `--anneal` did, summed over the runs.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct AnnealLevel {
+    /// The number of keys switched per candidate on this level.
+    pub switches: u128,
+    /// The candidate layouts scored.
+    pub candidates: u64,
+    /// The candidates that replaced the layout before, cheaper or not.
+    pub accepted: u64,
+    /// The candidates accepted although they cost more than the layout before.
+    pub uphill_accepted: u64,
+    /// The accepted uphill moves after which the run still ended below the cost it had before the move.
+    pub vindicated: u64,
+}
+
+/**
+Counts the candidates and the accepted uphill moves of the anneal levels of a
+run. Every uphill move is kept with the cost before it until the run ends,
+because only its final cost tells whether the move was worth it.
+*/
+pub struct AnnealStats {
+    levels: Vec<AnnealLevel>,
+    /// The level and the cost before the move of every accepted uphill move.
+    uphill: Vec<(usize, f64)>,
+}
+
+impl AnnealStats {
+    /// The counters of a run with the levels of `--anneal`, which switch `anneal + 1` keys down to 2.
+    pub fn new(anneal: u128) -> Self {
+        AnnealStats {
+            levels: (0..anneal)
+                .map(|level| AnnealLevel {
+                    switches: anneal + 1 - level,
+                    candidates: 0,
+                    accepted: 0,
+                    uphill_accepted: 0,
+                    vindicated: 0,
+                })
+                .collect(),
+            uphill: vec![],
+        }
+    }
+
+    /// Count a candidate of the level counted from 0.
+    pub fn candidate(&mut self, level: usize) {
+        self.levels[level].candidates += 1;
+    }
+
+    /// Count a candidate that is cheaper than the layout it replaced.
+    pub fn accepted(&mut self, level: usize) {
+        self.levels[level].accepted += 1;
+    }
+
+    /// Count an accepted candidate that costs more than `cost_before`, the cost of the layout it replaced.
+    pub fn uphill_accepted(&mut self, level: usize, cost_before: f64) {
+        self.levels[level].accepted += 1;
+        self.levels[level].uphill_accepted += 1;
+        self.uphill.push((level, cost_before));
+    }
+
+    /// The counters of the run, with the uphill moves the `final_cost` of the run vindicated.
+    pub fn finish(mut self, final_cost: f64) -> Vec<AnnealLevel> {
+        for &(level, cost_before) in &self.uphill {
+            if final_cost < cost_before {
+                self.levels[level].vindicated += 1;
+            }
+        }
+        self.levels
+    }
+}
+
+/// Add the counters of a run to the ones of the runs before it.
+pub fn add_run(total: &mut Vec<AnnealLevel>, run: &[AnnealLevel]) {
+    if total.is_empty() {
+        total.extend_from_slice(run);
+        return;
+    }
+    for (total, run) in total.iter_mut().zip(run) {
+        total.candidates += run.candidates;
+        total.accepted += run.accepted;
+        total.uphill_accepted += run.uphill_accepted;
+        total.vindicated += run.vindicated;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_final_cost_vindicates_the_uphill_moves_it_is_below() {
+        let mut stats = AnnealStats::new(2);
+        stats.candidate(0);
+        stats.candidate(0);
+        stats.uphill_accepted(0, 10.0);
+        stats.candidate(1);
+        stats.accepted(1);
+        stats.candidate(1);
+        stats.uphill_accepted(1, 7.0);
+        let levels = stats.finish(8.0);
+
+        assert_eq!(
+            levels,
+            [
+                AnnealLevel {
+                    switches: 3,
+                    candidates: 2,
+                    accepted: 1,
+                    uphill_accepted: 1,
+                    vindicated: 1,
+                },
+                AnnealLevel {
+                    switches: 2,
+                    candidates: 2,
+                    accepted: 2,
+                    uphill_accepted: 1,
+                    vindicated: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn the_counters_of_the_runs_are_summed() {
+        let run = |vindicated_below: f64| {
+            let mut stats = AnnealStats::new(1);
+            stats.candidate(0);
+            stats.uphill_accepted(0, 5.0);
+            stats.finish(vindicated_below)
+        };
+        let mut total = vec![];
+        add_run(&mut total, &run(4.0));
+        add_run(&mut total, &run(6.0));
+        assert_eq!(
+            total,
+            [AnnealLevel {
+                switches: 2,
+                candidates: 2,
+                accepted: 2,
+                uphill_accepted: 2,
+                vindicated: 1,
+            }]
+        );
+    }
+}
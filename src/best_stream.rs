@@ -0,0 +1,106 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+
+use chrono::Local;
+use serde::Serialize;
+
+/// A line of the --best-stream file.
+#[derive(Serialize)]
+struct BestLine<'a> {
+    /// The local time the layout was found, in RFC 3339.
+    timestamp: String,
+    run: u128,
+    step: u128,
+    cost: f64,
+    layout: &'a str,
+}
+
+/**
+The --best-stream file, which gets a json line every time the evolution finds
+a layout cheaper than every one before, so it can be followed with `tail -f`
+during a long evolution and survives a crash. The file and the best cost are
+behind one lock, so runs on several threads can share it and its costs only
+ever go down.
+*/
+pub struct BestStream {
+    path: String,
+    state: Mutex<(File, f64)>,
+}
+
+impl BestStream {
+    /// Append to the file, creating it if needed. Only layouts cheaper than `best_cost` are written.
+    pub fn open(path: &str, best_cost: f64) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Unable to open {}: {}", path, e))?;
+        Ok(BestStream {
+            path: path.to_string(),
+            state: Mutex::new((file, best_cost)),
+        })
+    }
+
+    /// Write a line for the layout if it is cheaper than every one before, and flush it.
+    pub fn offer(&self, run: u128, step: u128, cost: f64, layout: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let (file, best_cost) = &mut *state;
+        if cost >= *best_cost {
+            return Ok(());
+        }
+        *best_cost = cost;
+        let line = serde_json::to_string(&BestLine {
+            timestamp: Local::now().to_rfc3339(),
+            run,
+            step,
+            cost,
+            layout,
+        })
+        .unwrap();
+        // A single write per line, so a reader never sees half of one.
+        file.write_all(format!("{}\n", line).as_bytes())
+            .and_then(|_| file.flush())
+            .map_err(|e| format!("Unable to write {}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn only_layouts_cheaper_than_every_one_before_are_written() {
+        let path = env::temp_dir().join(format!("klo-best-stream-{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let _ = fs::remove_file(&path);
+
+        let stream = BestStream::open(&path, 10.0).unwrap();
+        for (run, cost) in [(1, 12.0), (1, 9.0), (2, 9.0), (2, 8.5), (1, 8.7)] {
+            stream.offer(run, 0, cost, "abc").unwrap();
+        }
+        drop(stream);
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        let written: Vec<(u64, f64)> = lines
+            .iter()
+            .map(|line| {
+                (
+                    line["run"].as_u64().unwrap(),
+                    line["cost"].as_f64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(written, [(1, 9.0), (2, 8.5)]);
+        assert_eq!(lines[0]["layout"], "abc");
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fmt, fs,
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::layout::Blueprint;
+
+/// The version of the checkpoint file. Bump it whenever a field is removed or changes its meaning.
+pub const CHECKPOINT_SCHEMA_VERSION: u32 = 1;
+
+/// How often the checkpoint is rewritten: after some time or after some finished runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckpointInterval {
+    Duration(Duration),
+    Runs(u128),
+}
+
+impl FromStr for CheckpointInterval {
+    type Err = String;
+
+    /// Parse `50runs` (or `1run`) and durations like `90s`, `5m` or `2h`.
+    fn from_str(interval: &str) -> Result<Self, Self::Err> {
+        let interval = interval.trim();
+        let split = interval
+            .find(|char: char| !char.is_ascii_digit())
+            .unwrap_or(interval.len());
+        let (number, unit) = interval.split_at(split);
+        let number: u64 = number.parse().map_err(|_| {
+            format!(
+                "invalid interval {:?}, expected a number followed by s, m, h or runs",
+                interval
+            )
+        })?;
+        if number == 0 {
+            return Err(format!("invalid interval {:?}, it must not be 0", interval));
+        }
+
+        let seconds = |factor: u64| {
+            number
+                .checked_mul(factor)
+                .map(|seconds| CheckpointInterval::Duration(Duration::from_secs(seconds)))
+                .ok_or_else(|| format!("invalid interval {:?}, it is too long", interval))
+        };
+        match unit.trim() {
+            "runs" | "run" => Ok(CheckpointInterval::Runs(number as u128)),
+            "s" => seconds(1),
+            "m" => seconds(60),
+            "h" => seconds(3600),
+            unit => Err(format!(
+                "invalid interval unit {:?}, expected s, m, h or runs",
+                unit
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CheckpointInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointInterval::Runs(runs) => write!(f, "{}runs", runs),
+            CheckpointInterval::Duration(duration) => {
+                let seconds = duration.as_secs();
+                if seconds % 3600 == 0 {
+                    write!(f, "{}h", seconds / 3600)
+                } else if seconds % 60 == 0 {
+                    write!(f, "{}m", seconds / 60)
+                } else {
+                    write!(f, "{}s", seconds)
+                }
+            }
+        }
+    }
+}
+
+/// The state of an evolution that is saved to the checkpoint file.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub schema_version: u32,
+    pub completed_runs: u128,
+    pub runs: u128,
+    pub best_layout: String,
+    pub best_cost: f64,
+    /// The `config_hash` of the options the evolution was started with.
+    #[serde(default)]
+    pub config_hash: String,
+    /// The --top-n layouts so far, best first.
+    #[serde(default)]
+    pub top_layouts: Vec<CheckpointLayout>,
+}
+
+/// One of the kept layouts, with the whole blueprint so every optimized layer survives.
+#[derive(Serialize, Deserialize)]
+pub struct CheckpointLayout {
+    pub run: u128,
+    pub blueprint: Blueprint,
+}
+
+impl Checkpoint {
+    /// Read a checkpoint written by a [`Checkpointer`].
+    pub fn read(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        let checkpoint: Checkpoint =
+            serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+        if checkpoint.schema_version != CHECKPOINT_SCHEMA_VERSION {
+            return Err(format!(
+                "{} has checkpoint schema version {}, but this klo reads version {}",
+                path, checkpoint.schema_version, CHECKPOINT_SCHEMA_VERSION
+            ));
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// Decides when the checkpoint is due and writes it.
+pub struct Checkpointer {
+    path: String,
+    interval: CheckpointInterval,
+    keep_backup: bool,
+    last_write: Instant,
+    runs_since_write: u128,
+}
+
+impl Checkpointer {
+    pub fn new(path: &str, interval: CheckpointInterval, keep_backup: bool) -> Self {
+        Checkpointer {
+            path: path.to_string(),
+            interval,
+            keep_backup,
+            last_write: Instant::now(),
+            runs_since_write: 0,
+        }
+    }
+
+    /// Call after every finished run. Writes the checkpoint if the interval has passed.
+    pub fn run_finished(&mut self, checkpoint: &Checkpoint) -> Result<(), String> {
+        self.runs_since_write += 1;
+        let due = match self.interval {
+            CheckpointInterval::Runs(runs) => self.runs_since_write >= runs,
+            CheckpointInterval::Duration(duration) => self.last_write.elapsed() >= duration,
+        };
+        if due {
+            self.write(checkpoint)?;
+        }
+        Ok(())
+    }
+
+    /// Call after the last run, writes the checkpoint unless it is up to date already.
+    pub fn finish(&mut self, checkpoint: &Checkpoint) -> Result<(), String> {
+        if self.runs_since_write > 0 {
+            self.write(checkpoint)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(checkpoint)
+            .map_err(|e| format!("Unable to serialize the checkpoint: {}", e))?;
+        write_atomically(&self.path, &(json + "\n"), self.keep_backup)?;
+        debug!(
+            "Wrote the checkpoint after {} runs to {}",
+            checkpoint.completed_runs, self.path
+        );
+        self.last_write = Instant::now();
+        self.runs_since_write = 0;
+        Ok(())
+    }
+}
+
+/**
+Write `contents` to a temporary file next to `path` and rename it over `path`,
+so a crash never leaves a half written file behind. Missing directories are
+created. With `keep_backup` the previous file is kept as `<path>.bak`.
+*/
+pub fn write_atomically(path: &str, contents: &str, keep_backup: bool) -> Result<(), String> {
+    let target = Path::new(path);
+    if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+    }
+
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, contents).map_err(|e| format!("Unable to write {}: {}", temporary, e))?;
+    if keep_backup && target.is_file() {
+        let backup = format!("{}.bak", path);
+        fs::copy(target, &backup).map_err(|e| format!("Unable to write {}: {}", backup, e))?;
+    }
+    fs::rename(&temporary, target).map_err(|e| format!("Unable to replace {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_are_parsed_with_their_unit() {
+        assert_eq!("50runs".parse(), Ok(CheckpointInterval::Runs(50)));
+        assert_eq!("1run".parse(), Ok(CheckpointInterval::Runs(1)));
+        assert_eq!(
+            "90s".parse(),
+            Ok(CheckpointInterval::Duration(Duration::from_secs(90)))
+        );
+        assert_eq!(
+            "5m".parse(),
+            Ok(CheckpointInterval::Duration(Duration::from_secs(300)))
+        );
+        assert_eq!(
+            "2h".parse(),
+            Ok(CheckpointInterval::Duration(Duration::from_secs(7200)))
+        );
+    }
+
+    #[test]
+    fn too_long_intervals_are_an_error_instead_of_an_overflow() {
+        let hours = format!("{}h", u64::MAX / 3600 + 1);
+        assert!(hours
+            .parse::<CheckpointInterval>()
+            .unwrap_err()
+            .contains("too long"));
+        let minutes = format!("{}m", u64::MAX);
+        assert!(minutes
+            .parse::<CheckpointInterval>()
+            .unwrap_err()
+            .contains("too long"));
+        assert!(format!("{}s", u64::MAX)
+            .parse::<CheckpointInterval>()
+            .is_ok());
+    }
+
+    #[test]
+    fn zero_and_unknown_units_are_rejected() {
+        assert!("0s".parse::<CheckpointInterval>().is_err());
+        assert!("5d".parse::<CheckpointInterval>().is_err());
+        assert!("s".parse::<CheckpointInterval>().is_err());
+    }
+}
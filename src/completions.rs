@@ -0,0 +1,21 @@
+use std::io;
+
+use clap::IntoApp;
+use clap_generate::{
+    generate,
+    generators::{Bash, Fish, PowerShell, Zsh},
+};
+
+use crate::klo_options::{KloOptions, Shell};
+
+/// Write the completion script for the given shell to stdout.
+pub fn print_completions(shell: Shell) {
+    let mut app = KloOptions::into_app();
+    let mut stdout = io::stdout();
+    match shell {
+        Shell::Bash => generate::<Bash, _>(&mut app, "klo", &mut stdout),
+        Shell::Zsh => generate::<Zsh, _>(&mut app, "klo", &mut stdout),
+        Shell::Fish => generate::<Fish, _>(&mut app, "klo", &mut stdout),
+        Shell::Powershell => generate::<PowerShell, _>(&mut app, "klo", &mut stdout),
+    }
+}
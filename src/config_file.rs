@@ -0,0 +1,809 @@
+use std::{collections::BTreeMap, convert::TryFrom, env, fmt, fs, path::Path};
+
+use clap::{App, ArgMatches, Clap, IntoApp};
+use log::debug;
+use toml::{value::Table, Value};
+
+use crate::{
+    checkpoint::CheckpointInterval,
+    klo_options::{Command, EvolveOptions, KloOptions, LogLevel, OutputFormat, ProgressFormat},
+};
+
+/// The configuration file that is picked up from the working directory if no --config is given.
+pub const DEFAULT_CONFIG_FILE: &str = "klo.toml";
+
+/// Every option can also be set with an environment variable made of this prefix and its name in capitals.
+pub const ENV_PREFIX: &str = "KLO_";
+
+/// Where the effective value of an option came from.
+#[derive(Debug, Clone)]
+pub enum ValueSource {
+    /// The option wasn't set anywhere.
+    Default,
+    /// The configuration file at this path.
+    ConfigFile(String),
+    /// The environment variable of this name.
+    Environment(String),
+    /// The command line.
+    CommandLine,
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueSource::Default => write!(f, "default"),
+            ValueSource::ConfigFile(path) => write!(f, "config file {}", path),
+            ValueSource::Environment(var) => write!(f, "environment variable {}", var),
+            ValueSource::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// The source of every option that wasn't left at its default, keyed like the configuration file (`evolve.steps`).
+#[derive(Debug, Default)]
+pub struct ValueSources(BTreeMap<String, ValueSource>);
+
+impl ValueSources {
+    /// Where the option `key` came from, `Default` for options that weren't set.
+    pub fn get(&self, key: &str) -> ValueSource {
+        self.0.get(key).cloned().unwrap_or(ValueSource::Default)
+    }
+
+    fn set(&mut self, key: String, source: ValueSource) {
+        self.0.insert(key, source);
+    }
+}
+
+/// The name of the environment variable for an option, e.g. `KLO_NGRAMS_CONFIG`.
+fn env_var_name(field: &str) -> String {
+    format!("{}{}", ENV_PREFIX, field.to_uppercase())
+}
+
+/// A value of an option that can be read from and written to the configuration file.
+trait ConfigValue: Sized {
+    fn from_toml(value: Value) -> Result<Self, String>;
+    fn to_toml(&self) -> Option<Value>;
+
+    fn from_env(value: &str) -> Result<Self, String> {
+        Self::from_toml(Value::String(value.to_string()))
+    }
+}
+
+impl ConfigValue for String {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        value.try_into().map_err(|e| e.to_string())
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::String(self.clone()))
+    }
+}
+
+impl ConfigValue for Option<String> {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        String::from_toml(value).map(Some)
+    }
+
+    /// An empty variable unsets the option.
+    fn from_env(value: &str) -> Result<Self, String> {
+        Ok(Some(value.to_string()).filter(|value| !value.is_empty()))
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        self.as_ref().and_then(String::to_toml)
+    }
+}
+
+impl ConfigValue for Option<f64> {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Integer(number) => Ok(Some(number as f64)),
+            value => value.try_into().map(Some).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        self.map(Value::Float)
+    }
+
+    fn from_env(value: &str) -> Result<Self, String> {
+        if value.is_empty() {
+            return Ok(None);
+        }
+        value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("{}: {:?}", e, value))
+    }
+}
+
+impl ConfigValue for Option<u64> {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        let number: i64 = value.try_into().map_err(|e| e.to_string())?;
+        u64::try_from(number)
+            .map(Some)
+            .map_err(|_| format!("expected a non-negative number, found {}", number))
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        self.map(|number| Value::Integer(number as i64))
+    }
+
+    /// An empty variable unsets the option.
+    fn from_env(value: &str) -> Result<Self, String> {
+        if value.is_empty() {
+            return Ok(None);
+        }
+        value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("{}: {:?}", e, value))
+    }
+}
+
+impl ConfigValue for Vec<String> {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        value.try_into().map_err(|e| e.to_string())
+    }
+
+    /// A comma separated list.
+    fn from_env(value: &str) -> Result<Self, String> {
+        Ok(value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::Array(
+            self.iter().cloned().map(Value::String).collect(),
+        ))
+    }
+}
+
+impl ConfigValue for Vec<usize> {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        let numbers: Vec<i64> = value.try_into().map_err(|e| e.to_string())?;
+        numbers
+            .into_iter()
+            .map(|number| usize::try_from(number).map_err(|e| format!("{}: {}", e, number)))
+            .collect()
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::Array(
+            self.iter()
+                .map(|&number| Value::Integer(number as i64))
+                .collect(),
+        ))
+    }
+
+    /// A comma separated list.
+    fn from_env(value: &str) -> Result<Self, String> {
+        value
+            .split(',')
+            .map(|number| {
+                number
+                    .trim()
+                    .parse()
+                    .map_err(|e| format!("{}: {:?}", e, number))
+            })
+            .collect()
+    }
+}
+
+impl ConfigValue for bool {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        value.try_into().map_err(|e| e.to_string())
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::Boolean(*self))
+    }
+
+    fn from_env(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "true" | "1" | "yes" => Ok(true),
+            "false" | "0" | "no" | "" => Ok(false),
+            _ => Err(format!("expected true or false, found {:?}", value)),
+        }
+    }
+}
+
+impl ConfigValue for u128 {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        let number: i64 = value.try_into().map_err(|e| e.to_string())?;
+        if number < 0 {
+            return Err(format!("expected a non-negative number, found {}", number));
+        }
+        Ok(number as u128)
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::Integer(*self as i64))
+    }
+
+    fn from_env(value: &str) -> Result<Self, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|e| format!("{}: {:?}", e, value))
+    }
+}
+
+impl ConfigValue for usize {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        let number: i64 = value.try_into().map_err(|e| e.to_string())?;
+        if number < 0 {
+            return Err(format!("expected a non-negative number, found {}", number));
+        }
+        Ok(number as usize)
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::Integer(*self as i64))
+    }
+
+    fn from_env(value: &str) -> Result<Self, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|e| format!("{}: {:?}", e, value))
+    }
+}
+
+impl ConfigValue for f64 {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Integer(number) => Ok(number as f64),
+            value => value.try_into().map_err(|e| e.to_string()),
+        }
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::Float(*self))
+    }
+
+    fn from_env(value: &str) -> Result<Self, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|e| format!("{}: {:?}", e, value))
+    }
+}
+
+impl ConfigValue for LogLevel {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        <LogLevel as clap::ArgEnum>::from_str(&String::from_toml(value)?, true)
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        let name = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+        Some(Value::String(name.to_string()))
+    }
+}
+
+impl ConfigValue for OutputFormat {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        <OutputFormat as clap::ArgEnum>::from_str(&String::from_toml(value)?, true)
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        let name = match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+        };
+        Some(Value::String(name.to_string()))
+    }
+}
+
+impl ConfigValue for ProgressFormat {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        <ProgressFormat as clap::ArgEnum>::from_str(&String::from_toml(value)?, true)
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        let name = match self {
+            ProgressFormat::Text => "text",
+            ProgressFormat::Jsonl => "jsonl",
+        };
+        Some(Value::String(name.to_string()))
+    }
+}
+
+impl ConfigValue for CheckpointInterval {
+    fn from_toml(value: Value) -> Result<Self, String> {
+        String::from_toml(value)?.parse()
+    }
+
+    fn to_toml(&self) -> Option<Value> {
+        Some(Value::String(self.to_string()))
+    }
+}
+
+/// A group of options that is stored as one table of the configuration file.
+trait ConfigSection {
+    const KEYS: &'static [&'static str];
+
+    /// The prefix of the keys of this section in [`ValueSources`].
+    const SECTION: &'static str;
+
+    /// Take every known key out of `table` and apply it unless the option was set explicitly.
+    fn merge_table(
+        &mut self,
+        table: &mut Table,
+        is_explicit: &dyn Fn(&str) -> bool,
+        source: &ValueSource,
+        sources: &mut ValueSources,
+    ) -> Result<(), String>;
+
+    /// Apply every `KLO_` environment variable unless the option was set explicitly.
+    fn merge_env(
+        &mut self,
+        is_explicit: &dyn Fn(&str) -> bool,
+        sources: &mut ValueSources,
+    ) -> Result<(), String>;
+
+    fn to_table(&self) -> Table;
+}
+
+macro_rules! config_section {
+    ($name:ident, $section:expr, { $($field:ident),* $(,)? }) => {
+        impl ConfigSection for $name {
+            const KEYS: &'static [&'static str] = &[$(stringify!($field)),*];
+            const SECTION: &'static str = $section;
+
+            fn merge_table(
+                &mut self,
+                table: &mut Table,
+                is_explicit: &dyn Fn(&str) -> bool,
+                source: &ValueSource,
+                sources: &mut ValueSources,
+            ) -> Result<(), String> {
+                $(
+                    if let Some(value) = table.remove(stringify!($field)) {
+                        let value = ConfigValue::from_toml(value)
+                            .map_err(|e| format!("Invalid value for {}: {}", stringify!($field), e))?;
+                        if is_explicit(stringify!($field)) {
+                            debug!("{} was given on the command line, ignoring the config file value.", stringify!($field));
+                        } else {
+                            self.$field = value;
+                            sources.set(format!("{}{}", Self::SECTION, stringify!($field)), source.clone());
+                        }
+                    }
+                )*
+                Ok(())
+            }
+
+            fn merge_env(
+                &mut self,
+                is_explicit: &dyn Fn(&str) -> bool,
+                sources: &mut ValueSources,
+            ) -> Result<(), String> {
+                $(
+                    let var = env_var_name(stringify!($field));
+                    if let Ok(value) = env::var(&var) {
+                        let value = ConfigValue::from_env(&value)
+                            .map_err(|e| format!("Invalid value for {}: {}", var, e))?;
+                        if is_explicit(stringify!($field)) {
+                            debug!("{} was given on the command line, ignoring {}.", stringify!($field), var);
+                        } else {
+                            self.$field = value;
+                            sources.set(format!("{}{}", Self::SECTION, stringify!($field)), ValueSource::Environment(var));
+                        }
+                    }
+                )*
+                $(
+                    if is_explicit(stringify!($field)) {
+                        sources.set(format!("{}{}", Self::SECTION, stringify!($field)), ValueSource::CommandLine);
+                    }
+                )*
+                Ok(())
+            }
+
+            fn to_table(&self) -> Table {
+                let mut table = Table::new();
+                $(
+                    if let Some(value) = self.$field.to_toml() {
+                        table.insert(stringify!($field).to_string(), value);
+                    }
+                )*
+                table
+            }
+        }
+    };
+}
+
+config_section!(KloOptions, "", {
+    ngrams_config,
+    ngrams_paths_from_cwd,
+    corpus,
+    alphabet,
+    allow_partial_alphabet,
+    optimize_layers,
+    layer_alphabet,
+    base_layout,
+    geometry,
+    threads,
+    weights,
+    weight,
+    hand_target,
+    missing_char_cost,
+    off_table_cost,
+    key_costs,
+    missing_char_threshold,
+    high_layer_threshold,
+    quiet,
+    verbose,
+    ascii,
+    profile,
+    log_file,
+    log_level,
+    log_rotate_mb,
+});
+
+config_section!(EvolveOptions, "evolve.", {
+    num_layouts,
+    top_n,
+    min_diff,
+    filename,
+    output_format,
+    progress_format,
+    json_stdout,
+    runs_csv,
+    best_stream,
+    output_layout_string,
+    export,
+    convergence_csv,
+    explain,
+    steps,
+    prerandomize,
+    seed,
+    controlled,
+    controlled_tail,
+    anneal,
+    anneal_step,
+    limit_ngrams,
+    starting_layout,
+    pin_keys,
+    pin_positions,
+    fix,
+    hand_lock,
+    constraints,
+    compare_with,
+    baselines,
+    checkpoint,
+    checkpoint_interval,
+    keep_checkpoint_backup,
+    resume,
+    resume_strict,
+});
+
+/**
+Layer the options: values given on the command line win over `KLO_`
+environment variables, which win over the configuration file given with
+--config (or klo.toml in the working directory if it exists), which wins over
+the defaults. Unknown configuration file keys are an error. Returns where
+every value came from.
+*/
+pub fn apply_config_layers(
+    options: &mut KloOptions,
+    matches: &ArgMatches,
+) -> Result<ValueSources, String> {
+    let mut sources = ValueSources::default();
+    let is_explicit = |id: &str| is_given_on_command_line(matches, id);
+
+    if options.config.is_none() {
+        options.config = env::var(env_var_name("config")).ok();
+    }
+    apply_config_file(options, &is_explicit, &mut sources)?;
+
+    options.merge_env(&is_explicit, &mut sources)?;
+    if let Some(Command::Evolve(evolve)) = &mut options.command {
+        evolve.merge_env(&is_explicit, &mut sources)?;
+    }
+
+    // The default ngrams config doesn't count, so --corpus alone works.
+    match sources.get("ngrams_config") {
+        ValueSource::Default => {}
+        source if !options.corpus.is_empty() => {
+            return Err(format!(
+                "--corpus and --ngrams-config (from the {}) can't be used together",
+                source
+            ))
+        }
+        _ => {}
+    }
+    Ok(sources)
+}
+
+fn apply_config_file(
+    options: &mut KloOptions,
+    is_explicit: &dyn Fn(&str) -> bool,
+    sources: &mut ValueSources,
+) -> Result<(), String> {
+    let path = match &options.config {
+        Some(path) => path.clone(),
+        None if Path::new(DEFAULT_CONFIG_FILE).is_file() => DEFAULT_CONFIG_FILE.to_string(),
+        None => return Ok(()),
+    };
+    debug!("Reading configuration file {}", path);
+
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let mut table: Table = toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    let mut unknown_keys = vec![];
+    let source = ValueSource::ConfigFile(path.clone());
+
+    let evolve_table = table.remove("evolve");
+    options.merge_table(&mut table, is_explicit, &source, sources)?;
+    unknown_keys.extend(table.keys().cloned());
+
+    match evolve_table {
+        Some(Value::Table(mut evolve_table)) => {
+            match &mut options.command {
+                Some(Command::Evolve(evolve)) => {
+                    evolve.merge_table(&mut evolve_table, is_explicit, &source, sources)?
+                }
+                // Evolve options don't apply to other subcommands but are still valid keys.
+                _ => EvolveOptions::KEYS.iter().for_each(|key| {
+                    evolve_table.remove(*key);
+                }),
+            }
+            unknown_keys.extend(evolve_table.keys().map(|key| format!("evolve.{}", key)));
+        }
+        Some(_) => return Err(format!("{}: evolve must be a table", path)),
+        None => {}
+    }
+
+    if !unknown_keys.is_empty() {
+        return Err(format!(
+            "Unknown keys in {}: {}. Known keys are {} and evolve.{}.",
+            path,
+            unknown_keys.join(", "),
+            KloOptions::KEYS.join(", "),
+            EvolveOptions::KEYS.join(", evolve.")
+        ));
+    }
+    Ok(())
+}
+
+/// Global options may be given after any (nested) subcommand, so all levels are checked.
+fn is_given_on_command_line(matches: &ArgMatches, field: &str) -> bool {
+    // clap names the arguments after their long flags.
+    let id = field.replace('_', "-");
+    matches.occurrences_of(&id) > 0
+        || matches
+            .subcommand()
+            .is_some_and(|(_, sub_matches)| is_given_on_command_line(sub_matches, field))
+}
+
+/**
+Render the effective options in the configuration file format, so a run can be
+reproduced. Every value is followed by a comment naming its source.
+*/
+pub fn effective_config(
+    options: &KloOptions,
+    evolve: Option<&EvolveOptions>,
+    sources: &ValueSources,
+) -> String {
+    let render_table = |table: Table, section: &str| {
+        table
+            .into_iter()
+            .map(|(key, value)| {
+                let source = sources.get(&format!("{}{}", section, key));
+                format!("{} = {} # {}\n", key, value, source)
+            })
+            .collect::<String>()
+    };
+    let mut config = render_table(options.to_table(), KloOptions::SECTION);
+    if let Some(evolve) = evolve {
+        config.push_str("\n[evolve]\n");
+        config.push_str(&render_table(evolve.to_table(), EvolveOptions::SECTION));
+    }
+    config
+}
+
+/// The effective options as a structured value for embedding them into other formats.
+pub fn effective_config_value(options: &KloOptions, evolve: Option<&EvolveOptions>) -> Value {
+    let mut table = options.to_table();
+    if let Some(evolve) = evolve {
+        table.insert("evolve".to_string(), Value::Table(evolve.to_table()));
+    }
+    Value::Table(table)
+}
+
+/// Options that don't change the layouts an evolution finds, so they may change between a checkpoint and --resume.
+const RESUMABLE_KEYS: &[&str] = &[
+    "threads",
+    "quiet",
+    "verbose",
+    "ascii",
+    "profile",
+    "log_file",
+    "log_level",
+    "log_rotate_mb",
+    "evolve.filename",
+    "evolve.output_format",
+    "evolve.progress_format",
+    "evolve.json_stdout",
+    "evolve.runs_csv",
+    "evolve.best_stream",
+    "evolve.output_layout_string",
+    "evolve.convergence_csv",
+    "evolve.export",
+    "evolve.explain",
+    "evolve.checkpoint",
+    "evolve.checkpoint_interval",
+    "evolve.keep_checkpoint_backup",
+    "evolve.resume",
+    "evolve.resume_strict",
+];
+
+/**
+A hash of the effective options that decide which layouts an evolution finds,
+to tell whether a checkpoint was written with the same configuration. It is a
+64 bit FNV-1a hash of the options as TOML, so it stays the same across builds.
+*/
+pub fn config_hash(options: &KloOptions, evolve: &EvolveOptions) -> String {
+    let mut table = options.to_table();
+    let mut evolve_table = evolve.to_table();
+    for key in RESUMABLE_KEYS {
+        match key.strip_prefix("evolve.") {
+            Some(key) => evolve_table.remove(key),
+            None => table.remove(*key),
+        };
+    }
+    table.insert("evolve".to_string(), Value::Table(evolve_table));
+
+    fnv1a(&Value::Table(table).to_string())
+}
+
+/// The 64 bit FNV-1a hash of a text as 16 hex digits.
+pub fn fnv1a(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/**
+The contents of a klo.toml with every option at its default, commented out and
+documented with its help text. The keys of `active` are set to their value
+instead, like `("weights", "weights.toml")`.
+*/
+pub fn starter_config(active: &[(&str, &str)]) -> String {
+    let mut defaults = KloOptions::try_parse_from(["klo", "evolve"]).unwrap();
+    let evolve = match defaults.command.take() {
+        Some(Command::Evolve(evolve)) => evolve,
+        _ => unreachable!("the arguments select evolve"),
+    };
+    let app = KloOptions::into_app();
+    let evolve_app = app.find_subcommand("evolve").unwrap();
+
+    let render_table = |table: Table, app: &App| {
+        table
+            .into_iter()
+            .map(|(key, value)| {
+                let help = app
+                    .get_arguments()
+                    .find(|arg| arg.get_long() == Some(key.replace('_', "-").as_str()))
+                    .and_then(|arg| arg.get_about())
+                    .unwrap_or_default();
+                let line = match active.iter().find(|(active_key, _)| *active_key == key) {
+                    Some((_, value)) => format!("{} = {}", key, Value::String(value.to_string())),
+                    None => format!("# {} = {}", key, value),
+                };
+                format!("{}{}\n\n", comment(help), line)
+            })
+            .collect::<String>()
+    };
+    let mut table = defaults.to_table();
+    // Options without a default only show up when they are active.
+    for (key, _) in active {
+        table
+            .entry(key.to_string())
+            .or_insert_with(|| Value::String(String::new()));
+    }
+
+    format!(
+        "# Default values for the klo options, written by `klo config init`.\n\
+         # Remove the `# ` in front of an option to change it. The command line wins over\n\
+         # {}* environment variables, which win over this file.\n\n{}[evolve]\n\n{}",
+        ENV_PREFIX,
+        render_table(table, &app),
+        render_table(evolve.to_table(), evolve_app).trim_end_matches('\n')
+    ) + "\n"
+}
+
+/// A help text as comment lines of at most 78 characters.
+fn comment(text: &str) -> String {
+    let mut lines = vec![];
+    let mut line = String::from("#");
+    for word in text.split_whitespace() {
+        if line.len() + 1 + word.len() > 78 && line.len() > 1 {
+            lines.push(std::mem::replace(&mut line, String::from("#")));
+        }
+        line.push(' ');
+        line.push_str(word);
+    }
+    if line.len() > 1 {
+        lines.push(line);
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::FromArgMatches;
+
+    use super::*;
+
+    /// The options of a command line after layering the environment and the configuration file on them.
+    fn layered(args: &[&str]) -> Result<(KloOptions, ValueSources), String> {
+        let matches = KloOptions::into_app().try_get_matches_from(args).unwrap();
+        let mut options = KloOptions::from_arg_matches(&matches).unwrap();
+        let sources = apply_config_layers(&mut options, &matches)?;
+        Ok((options, sources))
+    }
+
+    /// The environment is shared by the whole test process, so every case that sets variables is in this one test.
+    #[test]
+    fn the_command_line_wins_over_the_environment_which_wins_over_the_file() {
+        let path = env::temp_dir().join("klo_config_layers.toml");
+        fs::write(
+            &path,
+            "off_table_cost = 70.0\n\n[evolve]\nsteps = 10\nanneal = 1\nanneal_step = 7\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+        env::set_var("KLO_STEPS", "50");
+        env::set_var("KLO_ANNEAL", "2");
+        let result = layered(&["klo", "--config", path, "evolve", "--anneal", "4"]);
+        env::set_var("KLO_STEPS", "many");
+        let invalid = layered(&["klo", "evolve"]).map(|_| ());
+        env::remove_var("KLO_STEPS");
+        env::remove_var("KLO_ANNEAL");
+        fs::remove_file(path).unwrap();
+
+        let (options, sources) = result.unwrap();
+        let evolve = match options.command {
+            Some(Command::Evolve(evolve)) => evolve,
+            _ => unreachable!("the arguments select evolve"),
+        };
+        assert_eq!(evolve.steps, 50);
+        assert_eq!(evolve.anneal, 4);
+        assert_eq!(evolve.anneal_step, 7);
+        assert_eq!(options.off_table_cost, 70.0);
+        let source = |key| sources.get(key).to_string();
+        assert_eq!(source("evolve.steps"), "environment variable KLO_STEPS");
+        assert_eq!(source("evolve.anneal"), "command line");
+        assert_eq!(
+            source("evolve.anneal_step"),
+            format!("config file {}", path)
+        );
+        assert_eq!(source("off_table_cost"), format!("config file {}", path));
+        assert_eq!(source("evolve.top_n"), "default");
+
+        assert!(
+            invalid
+                .as_ref()
+                .unwrap_err()
+                .starts_with("Invalid value for KLO_STEPS"),
+            "{:?}",
+            invalid
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fs, path::Path};
+
+use log::info;
+
+use crate::{
+    config_file::{starter_config, DEFAULT_CONFIG_FILE},
+    error::KloError,
+    geometry::Geometry,
+    klo_options::{ConfigCommand, ConfigInitOptions, ConfigOptions},
+    layout::BUILTIN_BASE_LAYOUTS,
+    weights::CostWeights,
+};
+
+/// The corpus the example ngrams.config points to. It has to be replaced with a real text.
+const PLACEHOLDER_CORPUS: &str = "my_corpus.txt";
+
+pub fn run(config: &ConfigOptions) -> Result<(), KloError> {
+    match &config.command {
+        ConfigCommand::Init(init) => init_config(init),
+    }
+}
+
+/// Write the starter files, but only if none of them would overwrite a file (unless --force).
+fn init_config(init: &ConfigInitOptions) -> Result<(), KloError> {
+    let mut active = vec![("weights", "weights.toml")];
+    let mut files = vec![
+        ("ngrams.config", ngrams_config()),
+        ("weights.toml", weights()),
+    ];
+    if init.with_base_layout {
+        active.push(("base_layout", "base_layout.json"));
+        files.push(("base_layout.json", BUILTIN_BASE_LAYOUTS[0].json.to_string()));
+    }
+    if init.with_geometry {
+        active.push(("geometry", "geometry.toml"));
+        let geometry = toml::to_string(&Geometry::neo())
+            .map_err(|e| format!("Unable to serialize the geometry: {}", e))?;
+        files.push(("geometry.toml", geometry));
+    }
+    files.insert(0, (DEFAULT_CONFIG_FILE, starter_config(&active)));
+
+    let dir = Path::new(&init.dir);
+    let existing: Vec<String> = files
+        .iter()
+        .map(|(name, _)| dir.join(name))
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+    if !existing.is_empty() && !init.force {
+        return Err(KloError::Config(format!(
+            "Not overwriting {}, pass --force to replace them",
+            existing.join(", ")
+        )));
+    }
+
+    fs::create_dir_all(dir).map_err(|e| KloError::io("create", dir.display(), e))?;
+    for (name, contents) in &files {
+        let path = dir.join(name);
+        fs::write(&path, contents).map_err(|e| KloError::io("write", path.display(), e))?;
+        info!("Wrote {}", path.display());
+    }
+    info!(
+        "Point ngrams.config at your own texts instead of {}, then run `klo evolve` in {}.",
+        PLACEHOLDER_CORPUS,
+        dir.display()
+    );
+    Ok(())
+}
+
+fn ngrams_config() -> String {
+    format!(
+        "# The texts to optimize for, one per line: `<weight> <type> <path>`.\n\
+         # `text` reads a plain text file, `pregenerated` reads the 1-, 2- and 3-gram\n\
+         # files of `klo ngrams export`, as three paths or separated by `;`. Relative\n\
+         # paths are relative to this file, quote paths with spaces. The weight says\n\
+         # how much a source counts compared to the others.\n\
+         1.0 text {}\n",
+        PLACEHOLDER_CORPUS
+    )
+}
+
+fn weights() -> String {
+    let mut weights =
+        String::from("# The weight of every cost component. --weight overrides single ones.\n");
+    for (name, weight) in CostWeights::default().to_map() {
+        weights.push_str(&format!("{} = {:?}\n", name, weight));
+    }
+    weights
+}
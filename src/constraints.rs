@@ -0,0 +1,378 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    geometry::{Geometry, Hand, KeyPos},
+    klo_options::EvolveOptions,
+    layout::Blueprint,
+};
+
+/// The contents of a --constraints file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConstraintsFile {
+    /// Characters that must stay where they are.
+    #[serde(default)]
+    pin_keys: Vec<String>,
+    /// Position names whose characters must stay where they are.
+    #[serde(default)]
+    pin_positions: Vec<String>,
+    /// Assignments like `n=L_index_home` that are made before the evolution.
+    #[serde(default)]
+    fix: Vec<String>,
+    /// Characters that must stay on one hand, like `aeiou=right`.
+    #[serde(default)]
+    hand_lock: Vec<String>,
+}
+
+/// A character that is placed at a named position and never moved from there.
+#[derive(Debug, Clone)]
+pub struct FixedKey {
+    pub char: String,
+    pub position: String,
+    pub pos: KeyPos,
+}
+
+/// Restrictions on which characters the evolution may move.
+#[derive(Debug, Default)]
+pub struct Constraints {
+    /// Every character the evolution must not move, including the fixed ones.
+    pub pinned: BTreeSet<String>,
+    pub fixed: Vec<FixedKey>,
+    /// Characters that may move, but only between the keys of one hand.
+    pub hand_locks: BTreeMap<String, Hand>,
+}
+
+impl Constraints {
+    /**
+    Collect the pins from the --constraints file, --pin-keys, --pin-positions,
+    --fix and --hand-lock. Pinned and fixed characters must be on layer 0 of the
+    blueprint, position names must exist in the geometry and the assignments
+    must not contradict each other. Hand locked characters must be in the
+    alphabet and fit on their hand. Returns every problem found.
+    */
+    pub fn from_options(
+        evolve: &EvolveOptions,
+        blueprint: &Blueprint,
+        geometry: &Geometry,
+        alphabet: &str,
+    ) -> Result<Self, Vec<String>> {
+        let mut problems = vec![];
+
+        let file = match &evolve.constraints {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {}", path, e))
+                .and_then(|contents| {
+                    toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))
+                })
+                .unwrap_or_else(|problem| {
+                    problems.push(problem);
+                    ConstraintsFile::default()
+                }),
+            None => ConstraintsFile::default(),
+        };
+
+        let layer_0: BTreeSet<&str> = blueprint
+            .iter()
+            .flatten()
+            .filter_map(|key| key.first().map(String::as_str))
+            .collect();
+
+        let mut pinned = BTreeSet::new();
+        for pin_keys in file.pin_keys.iter().chain(&evolve.pin_keys) {
+            for char in pin_keys.chars() {
+                let char = char.to_string();
+                if layer_0.contains(char.as_str()) {
+                    pinned.insert(char);
+                } else {
+                    problems.push(format!(
+                        "Can't pin {:?}, it is not in the base layout",
+                        char
+                    ));
+                }
+            }
+        }
+
+        let position_names = file
+            .pin_positions
+            .iter()
+            .chain(&evolve.pin_positions)
+            .flat_map(|names| names.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+        for name in position_names {
+            match geometry.position_by_name(name) {
+                Some(pos) => match key_at(blueprint, pos) {
+                    Some(char) => {
+                        pinned.insert(char.to_string());
+                    }
+                    None => problems.push(format!(
+                        "Can't pin position {}, the base layout has no key there",
+                        name
+                    )),
+                },
+                None => problems.push(format!(
+                    "Unknown position {} for the {} geometry",
+                    name, geometry.name
+                )),
+            }
+        }
+
+        let mut fixed = vec![];
+        for assignment in file.fix.iter().chain(&evolve.fix) {
+            let (char, position) = match assignment.split_once('=') {
+                Some((char, position)) if char.chars().count() == 1 => (char, position.trim()),
+                _ => {
+                    problems.push(format!(
+                        "Invalid assignment {:?}, expected `char=position`",
+                        assignment
+                    ));
+                    continue;
+                }
+            };
+            if !layer_0.contains(char) {
+                problems.push(format!(
+                    "Can't fix {:?}, it is not in the base layout",
+                    char
+                ));
+                continue;
+            }
+            match geometry.position_by_name(position) {
+                Some(pos) if key_at(blueprint, pos).is_some() => fixed.push(FixedKey {
+                    char: char.to_string(),
+                    position: position.to_string(),
+                    pos,
+                }),
+                Some(_) => problems.push(format!(
+                    "Can't fix {:?} at {}, the base layout has no key there",
+                    char, position
+                )),
+                None => problems.push(format!(
+                    "Unknown position {} for the {} geometry",
+                    position, geometry.name
+                )),
+            }
+        }
+
+        let mut chars_per_position: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        let mut positions_per_char: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for fixed_key in &fixed {
+            chars_per_position
+                .entry(&fixed_key.position)
+                .or_default()
+                .insert(&fixed_key.char);
+            positions_per_char
+                .entry(&fixed_key.char)
+                .or_default()
+                .insert(&fixed_key.position);
+        }
+        for (position, chars) in chars_per_position.iter().filter(|(_, c)| c.len() > 1) {
+            problems.push(format!(
+                "Conflicting assignments: {:?} are all fixed at {}",
+                chars, position
+            ));
+        }
+        for (char, positions) in positions_per_char.iter().filter(|(_, p)| p.len() > 1) {
+            problems.push(format!(
+                "Conflicting assignments: {:?} is fixed at {:?}",
+                char, positions
+            ));
+        }
+        for fixed_key in &fixed {
+            let displaced = key_at(blueprint, fixed_key.pos).unwrap();
+            if displaced != fixed_key.char && pinned.contains(displaced) {
+                problems.push(format!(
+                    "Fixing {:?} at {} would move the pinned {:?}",
+                    fixed_key.char, fixed_key.position, displaced
+                ));
+            }
+            if pinned.contains(&fixed_key.char) {
+                problems.push(format!(
+                    "{:?} is pinned and can't also be fixed at {}",
+                    fixed_key.char, fixed_key.position
+                ));
+            }
+        }
+
+        pinned.extend(fixed.iter().map(|fixed_key| fixed_key.char.clone()));
+
+        let mut hand_locks = BTreeMap::new();
+        for lock in file.hand_lock.iter().chain(&evolve.hand_lock) {
+            let (chars, hand) = match lock.rsplit_once('=').map(|(c, h)| (c, h.parse::<Hand>())) {
+                Some((chars, Ok(hand))) => (chars, hand),
+                Some((_, Err(e))) => {
+                    problems.push(format!("Invalid hand lock {:?}: {}", lock, e));
+                    continue;
+                }
+                None => {
+                    problems.push(format!(
+                        "Invalid hand lock {:?}, expected `chars=left` or `chars=right`",
+                        lock
+                    ));
+                    continue;
+                }
+            };
+            for char in chars.chars().map(String::from) {
+                if !alphabet.contains(char.as_str()) {
+                    problems.push(format!(
+                        "Can't lock {:?} to a hand, it is not in the alphabet",
+                        char
+                    ));
+                } else if !layer_0.contains(char.as_str()) {
+                    problems.push(format!(
+                        "Can't lock {:?} to a hand, it is not in the base layout",
+                        char
+                    ));
+                } else if pinned.contains(&char) {
+                    problems.push(format!(
+                        "{:?} is pinned and can't also be locked to a hand",
+                        char
+                    ));
+                } else if hand_locks
+                    .insert(char.clone(), hand)
+                    .is_some_and(|other| other != hand)
+                {
+                    problems.push(format!("{:?} is locked to both hands", char));
+                }
+            }
+        }
+        for hand in [Hand::Left, Hand::Right] {
+            let locked = hand_locks
+                .values()
+                .filter(|&&locked| locked == hand)
+                .count();
+            let free_keys = movable_keys(blueprint, geometry, alphabet, &pinned, hand).len();
+            if locked > free_keys {
+                problems.push(format!(
+                    "{} characters are locked to the {} hand, but it only has {} keys for them",
+                    locked, hand, free_keys
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(Constraints {
+                pinned,
+                fixed,
+                hand_locks,
+            })
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Move every fixed character to its position by swapping it with the character there.
+    pub fn apply_fixed(&self, blueprint: &mut Blueprint) {
+        for fixed_key in &self.fixed {
+            let (row, key) = fixed_key.pos;
+            let displaced = blueprint[row][key][0].clone();
+            for other_key in blueprint.iter_mut().flatten() {
+                if other_key.first() == Some(&fixed_key.char) {
+                    other_key[0] = displaced.clone();
+                }
+            }
+            blueprint[row][key][0] = fixed_key.char.clone();
+        }
+    }
+
+    /**
+    Move every hand locked character that is on the wrong hand (e.g. after the
+    prerandomization) by swapping it with a character of the other hand that
+    isn't locked to it.
+    */
+    pub fn apply_hand_locks(&self, blueprint: &mut Blueprint, geometry: &Geometry, alphabet: &str) {
+        for (char, &hand) in &self.hand_locks {
+            let pos = match layer_0_position(blueprint, char) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            if geometry.hand_of(pos) == Some(hand) {
+                continue;
+            }
+            let target = movable_keys(blueprint, geometry, alphabet, &self.pinned, hand)
+                .into_iter()
+                .find(|&target| {
+                    let other = key_at(blueprint, target).unwrap_or_default();
+                    self.hand_locks.get(other) != Some(&hand)
+                });
+            if let Some((row, key)) = target {
+                let other = blueprint[row][key][0].clone();
+                blueprint[pos.0][pos.1][0] = other;
+                blueprint[row][key][0] = char.clone();
+            }
+        }
+    }
+
+    /// Whether swapping the layer 0 characters of two keys keeps every hand locked one on its hand.
+    pub fn allows_swap(
+        &self,
+        blueprint: &Blueprint,
+        geometry: &Geometry,
+        first: KeyPos,
+        second: KeyPos,
+    ) -> bool {
+        let stays_on_hand = |from: KeyPos, to: KeyPos| {
+            key_at(blueprint, from)
+                .and_then(|char| self.hand_locks.get(char))
+                .is_none_or(|&hand| geometry.hand_of(to) == Some(hand))
+        };
+        stays_on_hand(first, second) && stays_on_hand(second, first)
+    }
+
+    pub fn is_fixed(&self, char: &str) -> bool {
+        self.fixed.iter().any(|fixed_key| fixed_key.char == char)
+    }
+
+    /// The characters of the alphabet that the evolution may move.
+    pub fn mutable_alphabet(&self, alphabet: &str) -> String {
+        alphabet
+            .chars()
+            .filter(|char| !self.pinned.contains(&char.to_string()))
+            .collect()
+    }
+}
+
+/// The keys of a hand whose layer 0 character the evolution may move.
+fn movable_keys(
+    blueprint: &Blueprint,
+    geometry: &Geometry,
+    alphabet: &str,
+    pinned: &BTreeSet<String>,
+    hand: Hand,
+) -> Vec<KeyPos> {
+    let mut keys: Vec<KeyPos> = geometry
+        .fingers
+        .iter()
+        .filter(|finger| finger.hand() == hand)
+        .flat_map(|finger| finger.positions.iter().copied())
+        .filter(|&pos| geometry.hand_of(pos) == Some(hand))
+        .filter(|&pos| {
+            key_at(blueprint, pos)
+                .is_some_and(|char| alphabet.contains(char) && !pinned.contains(char))
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+fn layer_0_position(blueprint: &Blueprint, char: &str) -> Option<KeyPos> {
+    blueprint.iter().enumerate().find_map(|(row_idx, row)| {
+        row.iter()
+            .position(|key| key.first().map(String::as_str) == Some(char))
+            .map(|key_idx| (row_idx, key_idx))
+    })
+}
+
+fn key_at(blueprint: &Blueprint, (row, key): KeyPos) -> Option<&str> {
+    blueprint
+        .get(row)
+        .and_then(|row| row.get(key))
+        .and_then(|key| key.first())
+        .map(String::as_str)
+        .filter(|char| !char.is_empty())
+}
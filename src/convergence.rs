@@ -0,0 +1,173 @@
+use serde::Serialize;
+
+/// The best cost of a run after a step.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConvergencePoint {
+    /// The run, counted from 1.
+    pub run: u128,
+    /// The step, counted from 1.
+    pub step: u128,
+    /// The lowest total cost of the run up to and including the step.
+    pub best_cost: f64,
+}
+
+/**
+Samples the best cost of a run so far at the steps 1, 2, 5, 10, 20, 50, … and
+the last step, so many runs can be plotted together without a trace of every
+step. Recording a step only compares numbers, except on the few sampled steps.
+*/
+pub struct ConvergenceSampler {
+    run: u128,
+    best_cost: f64,
+    /// The next step to sample.
+    next_sample: u128,
+    points: Vec<ConvergencePoint>,
+}
+
+impl ConvergenceSampler {
+    /// Start a run with the cost of its starting layout, before the first step.
+    pub fn new(run: u128, starting_cost: f64) -> Self {
+        ConvergenceSampler {
+            run,
+            best_cost: starting_cost,
+            next_sample: 1,
+            points: vec![],
+        }
+    }
+
+    /**
+    Record the cost of the layout after `step`. Steps that weren't recorded
+    kept the best cost of the last recorded one, so the samples before `step`
+    are taken with it.
+    */
+    pub fn record(&mut self, step: u128, cost: f64) {
+        self.sample_before(step);
+        if cost < self.best_cost {
+            self.best_cost = cost;
+        }
+        if step == self.next_sample {
+            self.sample();
+        }
+    }
+
+    /// The samples of a run that took `steps` steps, ending with the best cost after the last one.
+    pub fn finish(mut self, steps: u128) -> Vec<ConvergencePoint> {
+        self.sample_before(steps + 1);
+        if steps > 0 && self.points.last().map(|point| point.step) != Some(steps) {
+            self.points.push(ConvergencePoint {
+                run: self.run,
+                step: steps,
+                best_cost: self.best_cost,
+            });
+        }
+        self.points
+    }
+
+    fn sample_before(&mut self, step: u128) {
+        while self.next_sample < step {
+            self.sample();
+        }
+    }
+
+    fn sample(&mut self) {
+        self.points.push(ConvergencePoint {
+            run: self.run,
+            step: self.next_sample,
+            best_cost: self.best_cost,
+        });
+        self.next_sample = next_sample(self.next_sample);
+    }
+}
+
+/// The sampled step after `step`: 1, 2, 5 and their multiples of 10.
+fn next_sample(step: u128) -> u128 {
+    let mut magnitude = 1;
+    while step >= magnitude * 10 {
+        magnitude *= 10;
+    }
+    match step / magnitude {
+        1 => 2 * magnitude,
+        2..=4 => 5 * magnitude,
+        _ => 10 * magnitude,
+    }
+}
+
+/// The samples of every run as tidy csv with the columns `run`, `step` and `best_cost`.
+pub fn to_csv(points: &[ConvergencePoint]) -> String {
+    let mut csv = String::from("run,step,best_cost\n");
+    for point in points {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            point.run, point.step, point.best_cost
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(points: &[ConvergencePoint]) -> Vec<u128> {
+        points.iter().map(|point| point.step).collect()
+    }
+
+    #[test]
+    fn the_samples_are_logarithmically_spaced() {
+        let samples: Vec<u128> = std::iter::successors(Some(1), |&step| Some(next_sample(step)))
+            .take(10)
+            .collect();
+        assert_eq!(samples, [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000]);
+    }
+
+    #[test]
+    fn a_run_is_sampled_up_to_its_last_step_with_the_best_cost_so_far() {
+        let mut sampler = ConvergenceSampler::new(3, 100.0);
+        let costs = [
+            90.0, 95.0, 80.0, 85.0, 70.0, 75.0, 72.0, 60.0, 65.0, 66.0, 50.0, 55.0,
+        ];
+        for (step, &cost) in costs.iter().enumerate() {
+            sampler.record(step as u128 + 1, cost);
+        }
+        let points = sampler.finish(costs.len() as u128);
+
+        assert_eq!(steps(&points), [1, 2, 5, 10, 12]);
+        assert!(points.iter().all(|point| point.run == 3));
+        let best: Vec<f64> = points.iter().map(|point| point.best_cost).collect();
+        assert_eq!(best, [90.0, 90.0, 70.0, 60.0, 50.0]);
+        assert!(best.windows(2).all(|pair| pair[1] <= pair[0]));
+    }
+
+    #[test]
+    fn skipped_steps_are_sampled_with_the_cost_before_them() {
+        let mut sampler = ConvergenceSampler::new(1, 10.0);
+        sampler.record(1, 9.0);
+        sampler.record(30, 4.0);
+        let points = sampler.finish(50);
+        assert_eq!(steps(&points), [1, 2, 5, 10, 20, 50]);
+        let best: Vec<f64> = points.iter().map(|point| point.best_cost).collect();
+        assert_eq!(best, [9.0, 9.0, 9.0, 9.0, 9.0, 4.0]);
+    }
+
+    #[test]
+    fn a_run_without_steps_has_no_samples() {
+        assert!(ConvergenceSampler::new(1, 10.0).finish(0).is_empty());
+    }
+
+    #[test]
+    fn the_csv_has_a_line_per_sample() {
+        let points = [
+            ConvergencePoint {
+                run: 1,
+                step: 1,
+                best_cost: 2.5,
+            },
+            ConvergencePoint {
+                run: 2,
+                step: 10,
+                best_cost: 1.25,
+            },
+        ];
+        assert_eq!(to_csv(&points), "run,step,best_cost\n1,1,2.5\n2,10,1.25\n");
+    }
+}
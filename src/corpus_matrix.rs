@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use log::{debug, info};
+
+use crate::{
+    error::KloError,
+    geometry::Geometry,
+    klo_options::{weighted_corpus, CompareOptions, KloOptions, MatrixFormat},
+    layout::{baseline_arg, Blueprint, BlueprintHelpers, Layout},
+    markdown::{self, Align},
+    ngrams::NGrams,
+    runs_csv::quote,
+    scoring::cost_model,
+};
+
+/// A layout of the matrix with its cost on every corpus.
+struct Row {
+    name: String,
+    layout_string: String,
+    /// The cost on every corpus, divided by the lowest cost on that corpus.
+    normalized: Vec<f64>,
+    weighted_average: f64,
+}
+
+/**
+Score every layout of `klo compare` against every --compare-corpora config
+and print the matrix in the --output-format. Like with --baselines, the
+layouts can also be the names of built-in baselines. Every config is loaded once. The
+costs of a corpus are divided by the lowest cost on it, so the best layout of
+every column has 1.000 and the columns can be averaged with the weights of
+their corpora, which don't need to add up to 1. The best average comes first.
+*/
+pub fn compare_corpora(options: &KloOptions, compare: &CompareOptions) -> Result<(), KloError> {
+    let layouts = compare
+        .layouts
+        .iter()
+        .map(|arg| {
+            let (name, arg) = baseline_arg(arg);
+            let blueprint = Blueprint::from_layout_arg(options, &arg)?;
+            let geometry = Geometry::from_options(options, &blueprint)?;
+            Ok((name, blueprint, geometry))
+        })
+        .collect::<Result<Vec<_>, KloError>>()?;
+
+    let corpora: Vec<(&str, f64)> = compare
+        .compare_corpora
+        .iter()
+        .filter_map(|arg| weighted_corpus(arg))
+        .collect();
+    let mut costs = vec![vec![]; layouts.len()];
+    for (path, _) in &corpora {
+        debug!("Scoring the layouts against {}", path);
+        let ngrams = NGrams::from_config(path, options.ngrams_paths_from_cwd)?;
+        let column: Vec<f64> = layouts
+            .iter()
+            .map(|(_, blueprint, geometry)| {
+                let layout = Layout::from_blueprint(
+                    blueprint.clone(),
+                    &ngrams.interner,
+                    &options.cost_weights.key_costs,
+                );
+                cost_model(options, geometry.clone())
+                    .score(&layout, &ngrams)
+                    .total()
+            })
+            .collect();
+        let best = column.iter().copied().fold(f64::INFINITY, f64::min);
+        for (layout_costs, cost) in costs.iter_mut().zip(column) {
+            layout_costs.push(if best > 0.0 { cost / best } else { cost });
+        }
+    }
+
+    let total_weight: f64 = corpora.iter().map(|(_, weight)| weight).sum();
+    let mut rows: Vec<Row> = layouts
+        .iter()
+        .zip(costs)
+        .map(|((name, blueprint, _), normalized)| Row {
+            name: name.clone(),
+            layout_string: blueprint.layout_string(),
+            weighted_average: normalized
+                .iter()
+                .zip(&corpora)
+                .map(|(cost, (_, weight))| cost * weight)
+                .sum::<f64>()
+                / total_weight,
+            normalized,
+        })
+        .collect();
+    rows.sort_by(|a, b| a.weighted_average.total_cmp(&b.weighted_average));
+
+    let header: Vec<String> = std::iter::once("Layout".to_string())
+        .chain(corpora.iter().map(|&(path, weight)| {
+            format!(
+                "{} ({:.0}%)",
+                corpus_name(path),
+                weight / total_weight * 100.0
+            )
+        }))
+        .chain(std::iter::once("Weighted average".to_string()))
+        .collect();
+    let cells = |row: &Row| -> Vec<String> {
+        row.normalized
+            .iter()
+            .chain(std::iter::once(&row.weighted_average))
+            .map(|cost| format!("{:.3}", cost))
+            .collect()
+    };
+
+    match compare.output_format {
+        MatrixFormat::Text => {
+            let table: Vec<Vec<String>> = std::iter::once(header)
+                .chain(rows.iter().map(|row| {
+                    std::iter::once(row.name.clone())
+                        .chain(cells(row))
+                        .collect()
+                }))
+                .collect();
+            for line in aligned(&table) {
+                info!("{}", line);
+            }
+        }
+        MatrixFormat::Csv => {
+            let mut csv_header: Vec<String> = header.iter().map(|name| quote(name)).collect();
+            csv_header.push(quote("Layout string"));
+            println!("{}", csv_header.join(","));
+            for row in &rows {
+                let mut fields = vec![quote(&row.name)];
+                fields.extend(cells(row));
+                fields.push(quote(&row.layout_string));
+                println!("{}", fields.join(","));
+            }
+        }
+        MatrixFormat::Markdown => {
+            let header: Vec<&str> = header.iter().map(String::as_str).collect();
+            let mut align = vec![Align::Left];
+            align.extend(vec![Align::Right; header.len() - 1]);
+            let rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| {
+                    std::iter::once(markdown::cell(&row.name))
+                        .chain(cells(row))
+                        .collect()
+                })
+                .collect();
+            print!("{}", markdown::table(&header, &align, &rows));
+        }
+    }
+    Ok(())
+}
+
+/// The name of a corpus in the header, the file name of its config without the extension.
+fn corpus_name(path: &str) -> String {
+    Path::new(path).file_stem().map_or_else(
+        || path.to_string(),
+        |stem| stem.to_string_lossy().to_string(),
+    )
+}
+
+/// The lines of a table with the first column aligned left and the others right.
+pub fn aligned(table: &[Vec<String>]) -> Vec<String> {
+    let columns = table.first().map_or(0, Vec::len);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            table
+                .iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    table
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, &width))| {
+                    if column == 0 {
+                        format!("{:<width$}", cell, width = width)
+                    } else {
+                        format!("{:>width$}", cell, width = width)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use log::{error, info};
+
+use crate::{
+    alphabet,
+    error::KloError,
+    geometry::Geometry,
+    klo_options::{EvolveOptions, KloOptions},
+    layout::{Blueprint, BlueprintHelpers},
+    ngrams::NGrams,
+};
+
+/// Check everything an evolution would load, without loading the corpora or optimizing.
+pub fn dry_run(options: &KloOptions, evolve: &EvolveOptions) -> Result<(), KloError> {
+    let mut problems = NGrams::check_options(options);
+
+    match Blueprint::try_get_base_layout(&options.base_layout) {
+        Ok(mut blueprint) => {
+            if let Err(problem) = blueprint.check_layers(&options.optimize_layers) {
+                problems.push(problem);
+            }
+            let geometry = Geometry::from_options(options, &blueprint);
+            let layout_problems =
+                blueprint.check_layout_string(&evolve.starting_layout, &options.alphabet);
+            if let (Ok(geometry), true) = (&geometry, layout_problems.is_empty()) {
+                blueprint.merge_layout_string(&evolve.starting_layout);
+                if let Err(problem) = alphabet::covered_alphabet(
+                    &blueprint,
+                    &options.alphabet,
+                    geometry,
+                    options.allow_partial_alphabet,
+                ) {
+                    problems.push(problem);
+                }
+                for &layer in options.optimize_layers.iter().filter(|&&layer| layer != 0) {
+                    if let Some(alphabet) = options.layer_alphabet(layer) {
+                        problems.extend(check_alphabet(&blueprint, layer, &alphabet));
+                    }
+                }
+            }
+            if let Err(problem) = geometry {
+                problems.push(problem.to_string());
+            }
+            problems.extend(layout_problems);
+        }
+        Err(e) => problems.push(e.to_string()),
+    }
+
+    if problems.is_empty() {
+        info!("Dry run found no problems. The evolution would do:");
+        #[cfg(feature = "parallel")]
+        let threads = rayon::current_num_threads();
+        #[cfg(not(feature = "parallel"))]
+        let threads = 1;
+        info!(
+            "{} runs × {} steps on {} threads",
+            evolve.num_layouts, evolve.steps, threads
+        );
+        info!("≈ {} layout evaluations", evolve.num_layouts * evolve.steps);
+        Ok(())
+    } else {
+        for problem in &problems {
+            error!("{}", problem);
+        }
+        Err(KloError::Config(format!(
+            "The dry run found {} problems.",
+            problems.len()
+        )))
+    }
+}
+
+/// Every character of the alphabet must be on its layer of the blueprint.
+fn check_alphabet(blueprint: &Blueprint, layer: usize, alphabet: &str) -> Vec<String> {
+    let chars: HashSet<&str> = blueprint
+        .iter()
+        .flatten()
+        .filter_map(|key| key.get(layer).map(String::as_str))
+        .collect();
+
+    alphabet
+        .chars()
+        .filter(|char| !chars.contains(char.to_string().as_str()))
+        .map(|char| {
+            format!(
+                "Alphabet character {:?} is not on layer {} of the layout",
+                char, layer
+            )
+        })
+        .collect()
+}
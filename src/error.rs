@@ -0,0 +1,245 @@
+use std::{error::Error, fmt, io};
+
+use crate::exit_code::ExitCode;
+#[cfg(feature = "cli")]
+use crate::klo_options::OptionsError;
+
+/// The ngrams config, a corpus or a pregenerated ngram file can't be loaded.
+#[derive(Debug)]
+pub struct NgramsError(pub String);
+
+/// A base layout, a layout string or a blueprint is invalid or can't be read.
+#[derive(Debug)]
+pub struct LayoutError(pub String);
+
+/// A geometry is unknown, can't be read or doesn't fit the layout.
+#[derive(Debug)]
+pub struct GeometryError(pub String);
+
+/// A layout or ngrams can't be converted to another format.
+#[derive(Debug)]
+pub struct ExportError(pub String);
+
+/// Every error of klo. The variant decides the exit code, see `exit_code`.
+#[derive(Debug)]
+pub enum KloError {
+    /// The ngrams can't be loaded.
+    Ngrams(NgramsError),
+    /// A layout can't be loaded.
+    Layout(LayoutError),
+    /// The geometry can't be loaded.
+    Geometry(GeometryError),
+    /// An export failed.
+    Export(ExportError),
+    /// Every problem `KloOptions::validate` found. They are logged one by one before returning it.
+    #[cfg(feature = "cli")]
+    Options(Vec<OptionsError>),
+    /**
+    Invalid configuration outside of the options themselves: a configuration
+    file, the alphabet, the weights, the constraints or a checkpoint, with the
+    message to show.
+    */
+    Config(String),
+    /// A file or directory can't be accessed.
+    Io {
+        /// What failed, like `write` or `create`.
+        action: &'static str,
+        /// The file or directory.
+        path: String,
+        /// The error of the operating system.
+        source: io::Error,
+    },
+    /// Any other error, like an output file that can't be written.
+    Other(String),
+    /// SIGINT stopped an evolution before all its runs were done. The result of the finished runs was written.
+    Interrupted {
+        /// The runs that finished, including resumed ones.
+        completed_runs: u128,
+        /// The runs the evolution was started with.
+        runs: u128,
+    },
+}
+
+impl KloError {
+    /// The exit code klo ends with because of this error.
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            KloError::Ngrams(_) => ExitCode::NgramData,
+            KloError::Layout(_) => ExitCode::Layout,
+            KloError::Geometry(_) | KloError::Config(_) => ExitCode::InvalidOptions,
+            #[cfg(feature = "cli")]
+            KloError::Options(_) => ExitCode::InvalidOptions,
+            KloError::Export(_) | KloError::Io { .. } | KloError::Other(_) => ExitCode::Failure,
+            KloError::Interrupted { .. } => ExitCode::Interrupted,
+        }
+    }
+
+    /// An error for the failed `action` on `path`, like `KloError::io("write", path, e)`.
+    pub fn io(action: &'static str, path: impl fmt::Display, source: io::Error) -> Self {
+        KloError::Io {
+            action,
+            path: path.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for KloError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KloError::Ngrams(NgramsError(message))
+            | KloError::Layout(LayoutError(message))
+            | KloError::Geometry(GeometryError(message))
+            | KloError::Export(ExportError(message))
+            | KloError::Config(message)
+            | KloError::Other(message) => write!(f, "{}", message),
+            #[cfg(feature = "cli")]
+            KloError::Options(errors) => {
+                write!(f, "Found {} problems with the given options.", errors.len())
+            }
+            KloError::Io {
+                action,
+                path,
+                source,
+            } => write!(f, "Unable to {} {}: {}", action, path, source),
+            KloError::Interrupted {
+                completed_runs,
+                runs,
+            } => write!(
+                f,
+                "Interrupted after {} of {} runs, the result has the runs so far.",
+                completed_runs, runs
+            ),
+        }
+    }
+}
+
+impl Error for KloError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            KloError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Implement `Display` and `Error` for the message errors and wrap them in their `KloError` variant.
+macro_rules! message_error {
+    ($error:ident, $variant:ident) => {
+        impl fmt::Display for $error {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl Error for $error {}
+
+        impl From<$error> for KloError {
+            fn from(error: $error) -> Self {
+                KloError::$variant(error)
+            }
+        }
+    };
+}
+
+message_error!(NgramsError, Ngrams);
+message_error!(LayoutError, Layout);
+message_error!(GeometryError, Geometry);
+message_error!(ExportError, Export);
+
+#[cfg(feature = "cli")]
+impl From<Vec<OptionsError>> for KloError {
+    fn from(errors: Vec<OptionsError>) -> Self {
+        KloError::Options(errors)
+    }
+}
+
+/// The plain string errors used inside of klo end it with the generic exit code.
+impl From<String> for KloError {
+    fn from(message: String) -> Self {
+        KloError::Other(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_error_has_its_message_and_exit_code() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
+        let errors = vec![
+            (
+                KloError::from(NgramsError(
+                    "ngrams.config line 2: unknown type".to_string(),
+                )),
+                "ngrams.config line 2: unknown type",
+                ExitCode::NgramData,
+            ),
+            (
+                LayoutError("The layout string has 40 keys".to_string()).into(),
+                "The layout string has 40 keys",
+                ExitCode::Layout,
+            ),
+            (
+                GeometryError("Unable to read geometry.toml".to_string()).into(),
+                "Unable to read geometry.toml",
+                ExitCode::InvalidOptions,
+            ),
+            (
+                ExportError("Unable to serialize the blueprint".to_string()).into(),
+                "Unable to serialize the blueprint",
+                ExitCode::Failure,
+            ),
+            (
+                KloError::Config("klo.toml: unknown key".to_string()),
+                "klo.toml: unknown key",
+                ExitCode::InvalidOptions,
+            ),
+            (
+                KloError::io("write", "result.txt", missing),
+                "Unable to write result.txt: No such file or directory",
+                ExitCode::Failure,
+            ),
+            (
+                KloError::from("Unable to draw the heatmap".to_string()),
+                "Unable to draw the heatmap",
+                ExitCode::Failure,
+            ),
+            (
+                KloError::Interrupted {
+                    completed_runs: 3,
+                    runs: 10,
+                },
+                "Interrupted after 3 of 10 runs, the result has the runs so far.",
+                ExitCode::Interrupted,
+            ),
+        ];
+        for (error, message, exit_code) in errors {
+            assert_eq!(error.to_string(), message);
+            assert_eq!(error.exit_code(), exit_code, "{}", message);
+        }
+    }
+
+    #[test]
+    fn only_io_errors_have_a_source() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        assert!(KloError::io("create", "out", denied).source().is_some());
+        assert!(KloError::Other("failed".to_string()).source().is_none());
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn invalid_options_count_their_problems() {
+        use clap::Clap;
+
+        use crate::klo_options::KloOptions;
+
+        let options = KloOptions::try_parse_from(["klo", "evolve", "--num-layouts", "0"]).unwrap();
+        let problems = options.validate().unwrap_err();
+        let message = format!("Found {} problems with the given options.", problems.len());
+        let error = KloError::from(problems);
+        assert_eq!(error.to_string(), message);
+        assert_eq!(error.exit_code(), ExitCode::InvalidOptions);
+    }
+}
@@ -0,0 +1,54 @@
+/**
+The exit codes of klo. Scripts rely on them, so a code never changes its
+meaning once released. `klo list exit-codes` prints this table.
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitCode {
+    /// Everything went fine.
+    Success = 0,
+    /// Any failure without a more specific code.
+    Failure = 1,
+    /// The options, the configuration file or the constraints are invalid.
+    InvalidOptions = 2,
+    /// The ngrams can't be loaded.
+    NgramData = 3,
+    /// A layout can't be read.
+    Layout = 4,
+    /// Stopped by SIGINT.
+    Interrupted = 5,
+    /// Rust's exit code for a panic.
+    InternalError = 101,
+}
+
+impl ExitCode {
+    /// Every exit code, in the order of `klo list exit-codes`.
+    pub const ALL: [ExitCode; 7] = [
+        ExitCode::Success,
+        ExitCode::Failure,
+        ExitCode::InvalidOptions,
+        ExitCode::NgramData,
+        ExitCode::Layout,
+        ExitCode::Interrupted,
+        ExitCode::InternalError,
+    ];
+
+    /// The number the process exits with.
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// What the exit code means, for the log and `klo list exit-codes`.
+    pub fn description(self) -> &'static str {
+        match self {
+            ExitCode::Success => "success",
+            ExitCode::Failure => {
+                "failure without a more specific code, e.g. an output file that can't be written"
+            }
+            ExitCode::InvalidOptions => "invalid options, configuration file or constraints",
+            ExitCode::NgramData => "the ngrams config or the corpora it names can't be loaded",
+            ExitCode::Layout => "the base layout or a layout string is invalid",
+            ExitCode::Interrupted => "interrupted by Ctrl-C (SIGINT), a partial result was written",
+            ExitCode::InternalError => "internal error (a bug in klo, please report it)",
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use log::info;
+
+use crate::{
+    geometry::{Geometry, KeyPos},
+    layout::{Layout, Pos},
+    ngrams::NGrams,
+    scoring::{Attribution, CostBreakdown, CostModel},
+    statistics::{KeyFingers, Transition},
+};
+
+/// The number of ngrams in each list of the explanation.
+const EXPLAINED_NGRAMS: usize = 10;
+
+/// The number of ngrams listed for every cost component by `log_component_attributions`.
+const COMPONENT_NGRAMS: usize = 15;
+
+/// A movement that makes an ngram hard to type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Penalty {
+    /// Two keys in a row with the same finger.
+    SameFinger,
+    /// Neighbouring fingers stretch over two or more rows.
+    Scissor,
+    /// One hand jumps over two or more rows.
+    RowJump,
+    /// Three keys of one hand that change direction, like `ring, index, middle`.
+    Redirect,
+    /// The first and the last key of a trigram with the same finger.
+    SameFingerSkip,
+}
+
+impl Penalty {
+    fn label(self) -> &'static str {
+        match self {
+            Penalty::SameFinger => "same finger",
+            Penalty::Scissor => "scissor",
+            Penalty::RowJump => "row jump",
+            Penalty::Redirect => "redirect",
+            Penalty::SameFingerSkip => "same finger skip",
+        }
+    }
+}
+
+/**
+Log why a layout costs what it costs: the ngrams with the largest share of the
+cost, as the model attributes it, and the most frequent bigrams and trigrams
+with hard movements, with the positions of their characters.
+*/
+pub fn log_explanation(
+    layout: &Layout,
+    ngrams: &NGrams,
+    geometry: &Geometry,
+    model: &dyn CostModel,
+    cost: &CostBreakdown,
+) {
+    let positions = PositionNames::new(geometry);
+    let mut attributions = model.attributions(layout, ngrams);
+    let mut costs: HashMap<Vec<u16>, f64> = HashMap::new();
+    for attribution in &attributions {
+        *costs.entry(attribution.ngram.clone()).or_default() += attribution.cost;
+    }
+
+    if attributions.is_empty() {
+        info!(
+            "The {} model doesn't attribute its cost to single ngrams.",
+            model.name()
+        );
+    } else {
+        attributions.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+        info!("Most costly ngrams:");
+        for attribution in attributions.iter().take(EXPLAINED_NGRAMS) {
+            let described: Vec<String> = attribution
+                .ngram
+                .iter()
+                .map(|&id| match layout.positions[usize::from(id)] {
+                    Some(pos) => positions.describe(&pos),
+                    None => "not on the layout".to_string(),
+                })
+                .collect();
+            info!(
+                "    {:?} {}: {} {:.2} ({:.1}% of the total)",
+                ngrams.interner.text(&attribution.ngram),
+                described.join(" → "),
+                attribution.component,
+                attribution.cost,
+                attribution.cost / cost.total() * 100.0
+            );
+        }
+    }
+
+    let hard = hard_ngrams(layout, ngrams, geometry);
+    if hard.is_empty() {
+        return;
+    }
+    info!("Most frequent bigrams and trigrams with hard movements:");
+    for (ngram, penalties, share) in hard.iter().take(EXPLAINED_NGRAMS) {
+        let names: Vec<String> = ngram
+            .iter()
+            .filter_map(|&id| layout.positions[usize::from(id)])
+            .map(|pos| positions.name(&pos))
+            .collect();
+        let labels: Vec<&str> = penalties.iter().map(|penalty| penalty.label()).collect();
+        let contribution = match costs.get(ngram) {
+            Some(ngram_cost) => format!(
+                "{:.2} ({:.1}% of the total)",
+                ngram_cost,
+                ngram_cost / cost.total() * 100.0
+            ),
+            None => format!("no cost in the {} model", model.name()),
+        };
+        info!(
+            "    {:?} {}: {}; {:.3}% of the {}, {}",
+            ngrams.interner.text(ngram),
+            names.join(" → "),
+            labels.join(", "),
+            share * 100.0,
+            if ngram.len() == 2 {
+                "bigrams"
+            } else {
+                "trigrams"
+            },
+            contribution
+        );
+    }
+}
+
+/**
+Log the ngrams that contribute most to every cost component of the model,
+with their weight, their cost and their share of the component, and the share
+of the component they make up together. This shows whether the corpus or the
+weights drive a cost.
+*/
+pub fn log_component_attributions(
+    layout: &Layout,
+    ngrams: &NGrams,
+    model: &dyn CostModel,
+    cost: &CostBreakdown,
+) {
+    let mut components: Vec<(&str, Vec<Attribution>)> = vec![];
+    for attribution in model.attributions(layout, ngrams) {
+        match components
+            .iter_mut()
+            .find(|(component, _)| *component == attribution.component)
+        {
+            Some((_, attributions)) => attributions.push(attribution),
+            None => components.push((attribution.component, vec![attribution])),
+        }
+    }
+
+    for (component, mut attributions) in components {
+        let total: f64 = attributions
+            .iter()
+            .map(|attribution| attribution.cost)
+            .sum();
+        attributions.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+        let share = |part: f64| {
+            if total > 0.0 {
+                part / total * 100.0
+            } else {
+                0.0
+            }
+        };
+        info!(
+            "Top {} ngrams of the {} cost of {:.2}:",
+            COMPONENT_NGRAMS.min(attributions.len()),
+            component,
+            total
+        );
+        let mut cumulative = 0.0;
+        for attribution in attributions.iter().take(COMPONENT_NGRAMS) {
+            cumulative += attribution.cost;
+            info!(
+                "    {:<8} weight {:.5}, cost {:.4} ({:.1}%, {:.1}% so far)",
+                format!("{:?}", ngrams.interner.text(&attribution.ngram)),
+                attribution.weight,
+                attribution.cost,
+                share(attribution.cost),
+                share(cumulative)
+            );
+        }
+        info!(
+            "    Together {:.1}% of the {} cost, {:.1}% of the total.",
+            share(cumulative),
+            component,
+            if cost.total() > 0.0 {
+                cumulative / cost.total() * 100.0
+            } else {
+                0.0
+            }
+        );
+    }
+}
+
+/// The bigrams and trigrams with penalties and their share of their kind, most frequent first.
+fn hard_ngrams(
+    layout: &Layout,
+    ngrams: &NGrams,
+    geometry: &Geometry,
+) -> Vec<(Vec<u16>, Vec<Penalty>, f64)> {
+    let fingers = KeyFingers::new(geometry);
+    let typed = |ngram: &[u16]| -> Option<Vec<Pos>> {
+        ngram
+            .iter()
+            .map(|&id| layout.positions[usize::from(id)])
+            .collect()
+    };
+
+    let mut hard = vec![];
+    let bigrams: Vec<(&[u16], f64)> = ngrams
+        .bigrams
+        .iter()
+        .map(|(bigram, weight)| (&bigram[..], *weight))
+        .collect();
+    let trigrams: Vec<(&[u16], f64)> = ngrams
+        .trigrams
+        .iter()
+        .map(|(trigram, weight)| (&trigram[..], *weight))
+        .collect();
+    for kind in [bigrams, trigrams] {
+        let total: f64 = kind.iter().map(|(_, weight)| weight).sum();
+        for (ngram, weight) in kind {
+            let keys = match typed(ngram) {
+                Some(keys) => keys,
+                None => continue,
+            };
+            let penalties = if keys.len() == 2 {
+                bigram_penalties(&fingers, &keys[0], &keys[1])
+            } else {
+                trigram_penalties(&fingers, &keys)
+            };
+            if !penalties.is_empty() && total > 0.0 {
+                hard.push((ngram.to_vec(), penalties, weight / total));
+            }
+        }
+    }
+    hard.sort_by(|a, b| b.2.total_cmp(&a.2));
+    hard
+}
+
+fn bigram_penalties(fingers: &KeyFingers, first: &Pos, second: &Pos) -> Vec<Penalty> {
+    let mut penalties = match Transition::of(fingers, first, second) {
+        Some(Transition::SameFinger) => vec![Penalty::SameFinger],
+        Some(Transition::Roll { scissor: true }) => vec![Penalty::Scissor],
+        Some(Transition::Roll { scissor: false }) => vec![],
+        _ => return vec![],
+    };
+    if first.row_distance(second) >= 2 {
+        penalties.push(Penalty::RowJump);
+    }
+    penalties
+}
+
+fn trigram_penalties(fingers: &KeyFingers, keys: &[Pos]) -> Vec<Penalty> {
+    let trigram_fingers: Option<Vec<_>> = keys.iter().map(|pos| fingers.only(pos)).collect();
+    let trigram_fingers = match trigram_fingers {
+        Some(trigram_fingers) => trigram_fingers,
+        None => return vec![],
+    };
+    let (first, second, third) = (trigram_fingers[0], trigram_fingers[1], trigram_fingers[2]);
+    let mut penalties = vec![];
+    let one_hand = first.hand() == second.hand() && second.hand() == third.hand();
+    let columns = [first.home_column, second.home_column, third.home_column];
+    let turns = (columns[1] as isize - columns[0] as isize).signum()
+        * (columns[2] as isize - columns[1] as isize).signum()
+        < 0;
+    if one_hand && turns {
+        penalties.push(Penalty::Redirect);
+    }
+    if first.name == third.name && first.name != second.name && !keys[0].same_key(&keys[2]) {
+        penalties.push(Penalty::SameFingerSkip);
+    }
+    penalties
+}
+
+/// The position names of a geometry, to describe where the characters of an ngram are.
+pub struct PositionNames<'a> {
+    geometry: &'a Geometry,
+    fingers: KeyFingers<'a>,
+    names: HashMap<KeyPos, String>,
+}
+
+impl<'a> PositionNames<'a> {
+    pub fn new(geometry: &'a Geometry) -> Self {
+        let mut names = HashMap::new();
+        for (name, pos) in geometry.position_names() {
+            // Keys of both thumbs keep the name of the first one.
+            names.entry(pos).or_insert(name);
+        }
+        PositionNames {
+            geometry,
+            fingers: KeyFingers::new(geometry),
+            names,
+        }
+    }
+
+    /// The position name of a key, like `L_index_home`, with the layer if it isn't 0.
+    pub fn name(&self, pos: &Pos) -> String {
+        let name = match self.names.get(&pos.key()) {
+            Some(name) => name.clone(),
+            None => format!("row {} key {}", pos.row, pos.col),
+        };
+        if pos.layer == 0 {
+            name
+        } else {
+            format!("{} layer {}", name, pos.layer)
+        }
+    }
+
+    /// The position name of a key with its row, column and fingers.
+    pub fn describe(&self, pos: &Pos) -> String {
+        let fingers: Vec<&str> = self
+            .fingers
+            .all(pos)
+            .iter()
+            .map(|&finger| self.geometry.fingers[finger].name.as_str())
+            .collect();
+        format!(
+            "{} (row {}, column {}, {})",
+            self.name(pos),
+            pos.row,
+            pos.col,
+            if fingers.is_empty() {
+                "no finger".to_string()
+            } else {
+                fingers.join("/")
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// The position of a key of the Neo geometry by its name, on layer 0.
+    fn key(geometry: &Geometry, name: &str) -> Pos {
+        let (row, col) = geometry.position_by_name(name).unwrap();
+        Pos::new(row, col, 0)
+    }
+
+    #[test]
+    fn trigrams_of_one_hand_that_turn_are_redirects() {
+        let geometry = Geometry::neo();
+        let fingers = KeyFingers::new(&geometry);
+        let penalties = |names: [&str; 3]| {
+            let keys: Vec<Pos> = names.iter().map(|name| key(&geometry, name)).collect();
+            trigram_penalties(&fingers, &keys)
+        };
+        assert_eq!(
+            penalties(["L_ring_home", "L_index_home", "L_middle_home"]),
+            [Penalty::Redirect]
+        );
+        assert_eq!(
+            penalties(["L_pinky_home", "L_ring_home", "L_middle_home"]),
+            []
+        );
+        assert_eq!(
+            penalties(["L_ring_home", "R_index_home", "L_middle_home"]),
+            []
+        );
+        assert_eq!(
+            penalties(["L_index_home", "L_middle_home", "L_index_top"]),
+            [Penalty::Redirect, Penalty::SameFingerSkip]
+        );
+        assert_eq!(
+            penalties(["L_index_home", "R_middle_home", "L_index_top"]),
+            [Penalty::SameFingerSkip]
+        );
+        // The same key again is no skip.
+        assert_eq!(
+            penalties(["L_index_home", "R_middle_home", "L_index_home"]),
+            []
+        );
+    }
+
+    #[cfg(feature = "text-corpus")]
+    #[test]
+    fn the_hard_bigrams_are_the_same_finger_bigrams_and_scissors_of_the_statistics() {
+        use crate::{
+            key_costs::KeyCosts,
+            layout::{builtin_baseline, Blueprint, BlueprintHelpers},
+            statistics::LayoutStatistics,
+        };
+
+        let ngrams =
+            NGrams::from_text("zwölf boxkämpfer jagen viktor quer über den großen sylter deich\n")
+                .unwrap();
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint.merge_layout_string(builtin_baseline("qwertz").unwrap().layout_string);
+        let layout =
+            Layout::from_blueprint(blueprint, &ngrams.interner, &Arc::new(KeyCosts::default()));
+        let geometry = Geometry::neo();
+        let statistics = LayoutStatistics::new(&layout, &ngrams, &geometry);
+
+        let share = |penalty: Penalty| -> f64 {
+            hard_ngrams(&layout, &ngrams, &geometry)
+                .iter()
+                .filter(|(ngram, penalties, _)| ngram.len() == 2 && penalties.contains(&penalty))
+                .map(|(_, _, share)| share)
+                .sum()
+        };
+        assert!(statistics.same_finger_bigrams > 0.0 && statistics.scissors > 0.0);
+        assert!((share(Penalty::SameFinger) - statistics.same_finger_bigrams).abs() < 1e-12);
+        assert!((share(Penalty::Scissor) - statistics.scissors).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+use log::{debug, info};
+
+use crate::{
+    error::{ExportError, KloError},
+    geometry::Geometry,
+    heatmap::Heatmap,
+    html,
+    klo_options::{ExportFormat, ExportOptions, KloOptions, DEFAULT_PNG_WIDTH},
+    layout::{Blueprint, BlueprintHelpers, Layout},
+    ngrams::NGrams,
+    png, report,
+    repro::ReproHeader,
+    scoring::cost_model,
+    statistics::LayoutStatistics,
+};
+
+/// Write the given layout in the requested format to a file or stdout.
+pub fn export_layout(options: &KloOptions, export: &ExportOptions) -> Result<(), KloError> {
+    let blueprint = Blueprint::from_args(options, &export.layout)?;
+    let ngrams = if export.format.uses_ngrams() {
+        Some(NGrams::from_options(options)?)
+    } else {
+        None
+    };
+    let contents = render(
+        options,
+        export.format,
+        blueprint,
+        ngrams.as_ref(),
+        &ReproHeader::new(options, None),
+        export.png_width,
+    )?;
+
+    match &export.output {
+        Some(path) => {
+            debug!("Writing {:?} export to {}", export.format, path);
+            fs::write(path, contents).map_err(|e| KloError::io("write", path, e))?;
+            info!("Exported layout to {}", path);
+        }
+        None => io::stdout()
+            .write_all(&contents)
+            .map_err(|e| KloError::io("write", "stdout", e))?,
+    }
+    Ok(())
+}
+
+/// Write the result of an evolution to the path of an --export like `legacy:result.txt`.
+pub fn export_result(
+    options: &KloOptions,
+    (format, path): (ExportFormat, &str),
+    blueprint: &Blueprint,
+    ngrams: &NGrams,
+    reproduction: &ReproHeader,
+) -> Result<(), KloError> {
+    let contents = render(
+        options,
+        format,
+        blueprint.clone(),
+        Some(ngrams),
+        reproduction,
+        DEFAULT_PNG_WIDTH,
+    )?;
+    debug!("Writing {:?} export to {}", format, path);
+    fs::write(path, contents).map_err(|e| KloError::io("write", path, e))?;
+    info!("Exported the layout to {}", path);
+    Ok(())
+}
+
+/**
+The contents of an export. `ngrams` are only needed for the formats that
+`uses_ngrams`, which also start with the reproduction header. The other ones
+are read back by klo as they are.
+*/
+fn render(
+    options: &KloOptions,
+    format: ExportFormat,
+    blueprint: Blueprint,
+    ngrams: Option<&NGrams>,
+    reproduction: &ReproHeader,
+    png_width: u32,
+) -> Result<Vec<u8>, KloError> {
+    let ngrams = || ngrams.expect("the formats with keystrokes are exported with ngrams");
+    let contents = match format {
+        ExportFormat::Blueprint => serde_json::to_string_pretty(&blueprint)
+            .map_err(|e| ExportError(format!("Unable to serialize the blueprint: {}", e)))?,
+        ExportFormat::LayoutString => {
+            blueprint
+                .check_layers(&options.optimize_layers)
+                .map_err(KloError::Config)?;
+            let layers: Vec<String> = options
+                .optimize_layers
+                .iter()
+                .map(|&layer| blueprint.layer_string(layer))
+                .collect();
+            layers.join("\n\n")
+        }
+        ExportFormat::Svg => {
+            reproduction.xml_comment() + &heatmap(options, blueprint, ngrams())?.svg(0)
+        }
+        ExportFormat::Png => {
+            return Ok(png::heatmap_png(
+                &heatmap(options, blueprint, ngrams())?,
+                png_width,
+                &reproduction.lines().join("\n"),
+            ));
+        }
+        ExportFormat::Legacy => reproduction.comment("# ") + &legacy(options, blueprint, ngrams())?,
+        ExportFormat::Html => {
+            return Ok(html(options, blueprint, ngrams(), reproduction)?.into_bytes())
+        }
+    };
+    Ok((contents + "\n").into_bytes())
+}
+
+/// The heatmap of a layout with the keystrokes of the ngrams.
+fn heatmap(
+    options: &KloOptions,
+    blueprint: Blueprint,
+    ngrams: &NGrams,
+) -> Result<Heatmap, KloError> {
+    let geometry = Geometry::from_options(options, &blueprint)?;
+    let layout =
+        Layout::from_blueprint(blueprint, &ngrams.interner, &options.cost_weights.key_costs);
+    Ok(Heatmap::new(&layout, ngrams, &geometry))
+}
+
+/// The html report of a layout: its keyboard, cost and statistics, see `report::layout_document`.
+fn html(
+    options: &KloOptions,
+    blueprint: Blueprint,
+    ngrams: &NGrams,
+    reproduction: &ReproHeader,
+) -> Result<String, KloError> {
+    let geometry = Geometry::from_options(options, &blueprint)?;
+    let layout =
+        Layout::from_blueprint(blueprint, &ngrams.interner, &options.cost_weights.key_costs);
+    let statistics = LayoutStatistics::new(&layout, ngrams, &geometry);
+    let result = report::layout_document(
+        options,
+        reproduction,
+        &layout,
+        ngrams,
+        &geometry,
+        &statistics,
+    );
+    Ok(html::render(
+        &result,
+        &Heatmap::new(&layout, ngrams, &geometry),
+    ))
+}
+
+/**
+The layout and its statistics in the text format of the Python
+evolve-keyboard-layout (`check_neo.py`) that klo is based on: the layout
+string, the full layout as a Python list of rows of key tuples and the metric
+lines starting with `#` that its scripts grep for. Only the metrics klo has are
+written. klo's costs are per keystroke, so they are not in billions like the
+ones of the Python tool.
+*/
+fn legacy(options: &KloOptions, blueprint: Blueprint, ngrams: &NGrams) -> Result<String, KloError> {
+    let geometry = Geometry::from_options(options, &blueprint)?;
+    let layout =
+        Layout::from_blueprint(blueprint, &ngrams.interner, &options.cost_weights.key_costs);
+    let cost = cost_model(options, geometry.clone()).score(&layout, ngrams);
+    let statistics = LayoutStatistics::new(&layout, ngrams, &geometry);
+
+    let mut lines = vec!["# Evolved Layout".to_string()];
+    lines.extend(layout.blueprint.layout_string().lines().map(str::to_string));
+    lines.push(String::new());
+    lines.push(python_list(&layout.blueprint));
+    lines.push(format!(
+        "# {} total penalty compared to notime-noeffort",
+        cost.total()
+    ));
+    lines.push(format!(
+        "# {} mean key position cost in file 1gramme.txt",
+        cost.key_position
+    ));
+    lines.push(format!(
+        "# {} % finger repeats in file 2gramme.txt",
+        statistics.same_finger_bigrams * 100.0
+    ));
+    lines.push(format!(
+        "# {} % of the bigrams are rolls, {} % scissors",
+        statistics.rolls * 100.0,
+        statistics.scissors * 100.0
+    ));
+    lines.push(format!(
+        "# {} % of the keystrokes on the left hand",
+        statistics.left_hand_share * 100.0
+    ));
+    let fingers: Vec<String> = statistics
+        .finger_loads
+        .iter()
+        .map(|load| format!("{} {:.1}", load.finger, load.share * 100.0))
+        .collect();
+    lines.push(format!("# finger load %: {}", fingers.join(", ")));
+    Ok(lines.join("\n"))
+}
+
+/// A blueprint as a Python list of rows of tuples of the layers of every key, like `[[('^', 'ˇ'), …], …]`.
+fn python_list(blueprint: &Blueprint) -> String {
+    let python_str = |text: &str| {
+        format!(
+            "'{}'",
+            text.replace('\\', "\\\\")
+                .replace('\'', "\\'")
+                .replace('\n', "\\n")
+        )
+    };
+    let rows: Vec<String> = blueprint
+        .iter()
+        .map(|row| {
+            let keys: Vec<String> = row
+                .iter()
+                .map(|key| {
+                    let layers: Vec<String> = key.iter().map(|layer| python_str(layer)).collect();
+                    match layers.len() {
+                        // A tuple of one element needs its comma.
+                        1 => format!("({},)", layers[0]),
+                        _ => format!("({})", layers.join(", ")),
+                    }
+                })
+                .collect();
+            format!("[{}]", keys.join(", "))
+        })
+        .collect();
+    format!("[{}]", rows.join(", "))
+}
@@ -0,0 +1,770 @@
+use std::{fmt, fs, str::FromStr, sync::OnceLock};
+#[cfg(feature = "cli")]
+use std::{path::Path, sync::Mutex};
+
+#[cfg(feature = "cli")]
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error::GeometryError;
+#[cfg(feature = "cli")]
+use crate::klo_options::KloOptions;
+#[cfg(feature = "cli")]
+use crate::layout::Blueprint;
+
+/// A physical key, addressed by (row, key) in the blueprint.
+pub type KeyPos = (usize, usize);
+
+/// The names of the rows of the Neo geometry, used to derive position names.
+const NEO_ROW_NAMES: [&str; 5] = ["number", "top", "home", "bottom", "thumb"];
+
+/// The names of the built-in geometries, in the order auto-detection tries them.
+pub const BUILTIN_GEOMETRIES: [&str; 4] = ["neo", "ansi60", "ortho4x12", "split3x6+3"];
+
+/// One of the two hands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hand {
+    /// The left hand, its fingers start with `L_`.
+    Left,
+    /// The right hand, its fingers start with `R_`.
+    Right,
+}
+
+impl FromStr for Hand {
+    type Err = String;
+
+    fn from_str(hand: &str) -> Result<Self, Self::Err> {
+        match hand.trim().to_lowercase().as_str() {
+            "left" | "l" => Ok(Hand::Left),
+            "right" | "r" => Ok(Hand::Right),
+            _ => Err(format!("unknown hand {:?}, expected left or right", hand)),
+        }
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hand::Left => write!(f, "left"),
+            Hand::Right => write!(f, "right"),
+        }
+    }
+}
+
+/**
+Flat indices for the keys of rows of different lengths, so tables about keys
+can be vectors instead of maps keyed by position.
+*/
+#[derive(Debug, Clone)]
+pub struct KeyIndex {
+    /// The index of the first key of every row, and the number of keys at the end.
+    offsets: Vec<usize>,
+}
+
+impl KeyIndex {
+    /// The index of rows with these numbers of keys.
+    pub fn new(rows: &[usize]) -> Self {
+        let mut offsets = Vec::with_capacity(rows.len() + 1);
+        let mut offset = 0;
+        offsets.push(offset);
+        for keys in rows {
+            offset += keys;
+            offsets.push(offset);
+        }
+        KeyIndex { offsets }
+    }
+
+    /// The index of a key, or none for keys outside of the rows.
+    pub fn index(&self, (row, key): KeyPos) -> Option<usize> {
+        let start = *self.offsets.get(row)?;
+        let end = *self.offsets.get(row + 1)?;
+        let index = start + key;
+        if index < end {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// The number of keys of all rows.
+    pub fn len(&self) -> usize {
+        self.offsets[self.offsets.len() - 1]
+    }
+
+    /// Whether there are no keys at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A finger of a geometry and the keys it types.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Finger {
+    /// The name of the finger, e.g. `L_pinky`.
+    pub name: String,
+    /// The column of the key the finger rests on, before the stagger of the row is added.
+    pub home_column: usize,
+    /// The keys the finger types.
+    pub positions: Vec<KeyPos>,
+}
+
+impl Finger {
+    /// Whether the finger belongs to the left hand.
+    pub fn is_left(&self) -> bool {
+        self.name.starts_with("L_")
+    }
+
+    /// Whether the finger is a thumb, which has no row in its position names.
+    pub fn is_thumb(&self) -> bool {
+        self.name.ends_with("_thumb")
+    }
+
+    /// The hand of the finger.
+    pub fn hand(&self) -> Hand {
+        if self.is_left() {
+            Hand::Left
+        } else {
+            Hand::Right
+        }
+    }
+}
+
+/**
+Which fingers type which keys of a blueprint.
+
+A geometry file is a toml file like
+
+```toml
+name = "my-planck"
+rows = [12, 12, 12, 12]
+row_names = ["top", "home", "bottom", "mod"]
+stagger = [0, 0, 0, 0]
+
+[[fingers]]
+name = "L_pinky"
+home_column = 1
+positions = [[0, 0], [0, 1], [1, 0], [1, 1]]
+```
+
+where `rows` is the number of keys of each row of the blueprint and `stagger`
+(optional) shifts the home column of every finger on a row for the position
+names. Finger names start with `L_` or `R_`; keys that both hands reach are
+listed for both thumbs.
+*/
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Geometry {
+    /// The name of a built-in geometry or the path of the file it was read from.
+    #[serde(default)]
+    pub name: String,
+    /// The number of keys of every row of a matching blueprint.
+    pub rows: Vec<usize>,
+    /// The names of the rows in the position names, like `home`.
+    pub row_names: Vec<String>,
+    /// How many columns the home columns are shifted on every row.
+    #[serde(default)]
+    pub stagger: Vec<usize>,
+    /// Every finger and the keys it types.
+    pub fingers: Vec<Finger>,
+    /// The hand of every key of the rows, filled on the first `hand_of`.
+    #[serde(skip)]
+    hands: OnceLock<(KeyIndex, Vec<Option<Hand>>)>,
+    /// The one finger of every key of the rows, filled on the first `finger_of`.
+    #[serde(skip)]
+    key_fingers: OnceLock<(KeyIndex, Vec<Option<usize>>)>,
+}
+
+impl Geometry {
+    /**
+    The geometry of --geometry, or the built-in one whose rows match the
+    blueprint. Without a match, warn and use the Neo finger assignments like
+    klo always did, see `neo_for_shape`. An explicit geometry must match the
+    blueprint. Warns about every row of the blueprint with keys outside of the
+    built-in key cost table, the rows and keys of --key-costs must match the
+    blueprint.
+    */
+    #[cfg(feature = "cli")]
+    pub fn from_options(
+        options: &KloOptions,
+        blueprint: &Blueprint,
+    ) -> Result<Self, GeometryError> {
+        let shape: Vec<usize> = blueprint.iter().map(Vec::len).collect();
+        let key_costs = &options.cost_weights.key_costs;
+        if options.key_costs.is_some() {
+            let mismatches = key_costs.mismatches(blueprint);
+            if !mismatches.is_empty() {
+                return Err(GeometryError(format!(
+                    "The --key-costs don't fit the base layout:\n{}",
+                    mismatches.join("\n")
+                )));
+            }
+        }
+        for (row, &keys) in shape.iter().enumerate() {
+            let covered = key_costs.keys_in_row(row);
+            if keys > covered {
+                let off_table = match keys - covered {
+                    1 => format!("key {}", covered),
+                    _ => format!("keys {} to {}", covered, keys - 1),
+                };
+                warn!(
+                    "Row {} of the base layout has {} keys, but the key cost table has costs for only {}. Its {} cost {} (see --off-table-cost).",
+                    row,
+                    keys,
+                    covered,
+                    off_table,
+                    key_costs.off_table_cost
+                );
+            }
+        }
+        let name = match &options.geometry {
+            Some(name) => name,
+            None => {
+                return Ok(BUILTIN_GEOMETRIES
+                    .iter()
+                    .filter_map(|&name| Geometry::builtin(name))
+                    .find(|geometry| geometry.rows == shape)
+                    .unwrap_or_else(|| {
+                        warn!(
+                            "No built-in geometry has rows of {:?} keys like the base layout, using the neo finger assignments. Pass --geometry to choose one.",
+                            shape
+                        );
+                        Geometry::neo_for_shape(&shape)
+                    }));
+            }
+        };
+
+        let geometry = match Geometry::builtin(name) {
+            Some(geometry) if !Path::new(name).is_file() => geometry,
+            _ => Geometry::from_file(name)?,
+        };
+        if geometry.rows != shape {
+            return Err(GeometryError(format!(
+                "The {} geometry has rows of {:?} keys, but the base layout has rows of {:?} keys",
+                geometry.name, geometry.rows, shape
+            )));
+        }
+        if Geometry::builtin(name).is_none() || Path::new(name).is_file() {
+            geometry.warn_uncovered_keys(blueprint);
+        }
+        Ok(geometry)
+    }
+
+    /**
+    Warn about the keys of the blueprint with a character that no finger of a
+    geometry file types, once per geometry. They count for no finger and no
+    hand, which is easy to miss in a hand-written file.
+    */
+    #[cfg(feature = "cli")]
+    fn warn_uncovered_keys(&self, blueprint: &Blueprint) {
+        static WARNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let uncovered: Vec<String> = blueprint
+            .iter()
+            .enumerate()
+            .flat_map(|(row, keys)| {
+                keys.iter()
+                    .enumerate()
+                    .filter_map(move |(key, layers)| match layers.first() {
+                        Some(char) if !char.is_empty() => Some(((row, key), char)),
+                        _ => None,
+                    })
+            })
+            .filter(|&(pos, _)| {
+                !self
+                    .fingers
+                    .iter()
+                    .any(|finger| finger.positions.contains(&pos))
+            })
+            .map(|((row, key), char)| format!("row {} key {} ({:?})", row, key, char))
+            .collect();
+        let mut warned = WARNED.lock().unwrap();
+        if uncovered.is_empty() || warned.contains(&self.name) {
+            return;
+        }
+        warned.push(self.name.clone());
+        warn!(
+            "No finger of the {} geometry types {}, they count for no finger and no hand.",
+            self.name,
+            uncovered.join(", ")
+        );
+    }
+
+    /// The built-in geometry of that name, see [`BUILTIN_GEOMETRIES`].
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "neo" => Some(Geometry::neo()),
+            "ansi60" => Some(Geometry::from_columns(
+                "ansi60",
+                &NEO_ROW_NAMES,
+                &[0, 0, 0, 0, 0],
+                &[
+                    "pprmiiIIMRPPPP",
+                    "pprmiiIIMRPPPP",
+                    "pprmiiIIMRPPP",
+                    "pprmiiIIMRPP",
+                    "...b....",
+                ],
+                [3, 3],
+            )),
+            "ortho4x12" => Some(Geometry::from_columns(
+                "ortho4x12",
+                &["top", "home", "bottom", "mod"],
+                &[0, 0, 0, 0],
+                &[
+                    "pprmiiIIMRPP",
+                    "pprmiiIIMRPP",
+                    "pprmiiIIMRPP",
+                    "pprmttTTMRPP",
+                ],
+                [5, 6],
+            )),
+            "split3x6+3" => Some(Geometry::from_columns(
+                "split3x6+3",
+                &["top", "home", "bottom", "thumb"],
+                &[0, 0, 0, 0],
+                &["pprmiiIIMRPP", "pprmiiIIMRPP", "pprmiiIIMRPP", "tttTTT"],
+                [1, 4],
+            )),
+            _ => None,
+        }
+    }
+
+    /// A geometry toml file, checked for finger names and positions that don't fit its rows.
+    pub fn from_file(path: &str) -> Result<Self, GeometryError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| GeometryError(format!("Unable to read {}: {}", path, e)))?;
+        let mut geometry: Geometry =
+            toml::from_str(&contents).map_err(|e| GeometryError(format!("{}: {}", path, e)))?;
+        if geometry.name.is_empty() {
+            geometry.name = path.to_string();
+        }
+
+        let mut problems = vec![];
+        if geometry.row_names.len() != geometry.rows.len() {
+            problems.push(format!(
+                "{} row names for {} rows",
+                geometry.row_names.len(),
+                geometry.rows.len()
+            ));
+        }
+        if !geometry.stagger.is_empty() && geometry.stagger.len() != geometry.rows.len() {
+            problems.push(format!(
+                "a stagger for {} rows, but there are {} rows",
+                geometry.stagger.len(),
+                geometry.rows.len()
+            ));
+        }
+        for finger in &geometry.fingers {
+            if !finger.name.starts_with("L_") && !finger.name.starts_with("R_") {
+                problems.push(format!(
+                    "the finger name {} doesn't start with L_ or R_",
+                    finger.name
+                ));
+            }
+            for &(row, key) in &finger.positions {
+                if geometry.rows.get(row).is_none_or(|&keys| key >= keys) {
+                    problems.push(format!(
+                        "{} has the position {:?}, which isn't on the keyboard",
+                        finger.name,
+                        (row, key)
+                    ));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(geometry)
+        } else {
+            Err(GeometryError(format!(
+                "Invalid geometry {}: {}",
+                path,
+                problems.join(", ")
+            )))
+        }
+    }
+
+    /**
+    A geometry from one string per row with a finger code per key: `p`, `r`,
+    `m`, `i` and `t` are the left pinky, ring, middle, index and thumb,
+    uppercase letters are the right hand, `b` is a key of both thumbs and `.`
+    is a key no finger types. The fingers rest on the columns 1 to 4 and 7 to
+    10, the thumbs on the given columns.
+    */
+    fn from_columns(
+        name: &str,
+        row_names: &[&str],
+        stagger: &[usize],
+        rows: &[&str],
+        thumb_columns: [usize; 2],
+    ) -> Self {
+        let fingers = [
+            ("L_pinky", "p", 1),
+            ("L_ring", "r", 2),
+            ("L_middle", "m", 3),
+            ("L_index", "i", 4),
+            ("L_thumb", "tb", thumb_columns[0]),
+            ("R_thumb", "Tb", thumb_columns[1]),
+            ("R_index", "I", 7),
+            ("R_middle", "M", 8),
+            ("R_ring", "R", 9),
+            ("R_pinky", "P", 10),
+        ];
+        Geometry {
+            name: name.to_string(),
+            rows: rows.iter().map(|row| row.chars().count()).collect(),
+            row_names: row_names.iter().map(|name| name.to_string()).collect(),
+            stagger: stagger.to_vec(),
+            fingers: fingers
+                .iter()
+                .map(|&(finger, codes, home_column)| Finger {
+                    name: finger.to_string(),
+                    home_column,
+                    positions: rows
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(row, keys)| {
+                            keys.chars()
+                                .enumerate()
+                                .filter(|(_, code)| codes.contains(*code))
+                                .map(move |(key, _)| (row, key))
+                        })
+                        .collect(),
+                })
+                .collect(),
+            hands: OnceLock::new(),
+            key_fingers: OnceLock::new(),
+        }
+    }
+
+    /// The finger assignment of the python optimizer for the staggered Neo blueprint.
+    pub fn neo() -> Self {
+        let finger = |name: &str, home_column, positions: &[KeyPos]| Finger {
+            name: name.to_string(),
+            home_column,
+            positions: positions.to_vec(),
+        };
+
+        Geometry {
+            name: "neo".to_string(),
+            rows: vec![14, 14, 14, 13, 8],
+            row_names: NEO_ROW_NAMES.iter().map(|name| name.to_string()).collect(),
+            // Row 3 is staggered by one key.
+            stagger: vec![0, 0, 0, 1, 0],
+            fingers: vec![
+                finger(
+                    "L_pinky",
+                    1,
+                    &[
+                        (0, 0),
+                        (0, 1),
+                        (1, 0),
+                        (1, 1),
+                        (2, 0),
+                        (2, 1),
+                        (3, 0),
+                        (3, 1),
+                        (3, 2),
+                    ],
+                ),
+                finger("L_ring", 2, &[(0, 2), (1, 2), (2, 2), (3, 3)]),
+                finger("L_middle", 3, &[(0, 3), (1, 3), (2, 3), (3, 4)]),
+                finger(
+                    "L_index",
+                    4,
+                    &[
+                        (0, 4),
+                        (1, 4),
+                        (2, 4),
+                        (3, 5),
+                        (0, 5),
+                        (1, 5),
+                        (2, 5),
+                        (3, 6),
+                    ],
+                ),
+                finger("L_thumb", 3, &[(4, 3)]),
+                finger("R_thumb", 3, &[(4, 3)]),
+                finger(
+                    "R_index",
+                    7,
+                    &[
+                        (0, 6),
+                        (1, 6),
+                        (2, 6),
+                        (3, 7),
+                        (0, 7),
+                        (1, 7),
+                        (2, 7),
+                        (3, 8),
+                    ],
+                ),
+                finger("R_middle", 8, &[(0, 8), (1, 8), (2, 8), (3, 9)]),
+                finger("R_ring", 9, &[(0, 9), (1, 9), (2, 9), (3, 10)]),
+                finger(
+                    "R_pinky",
+                    10,
+                    &[
+                        (0, 10),
+                        (1, 10),
+                        (2, 10),
+                        (3, 11),
+                        (0, 11),
+                        (1, 11),
+                        (2, 11),
+                        (0, 12),
+                        (1, 12),
+                        (2, 12),
+                        (0, 13),
+                        (1, 13),
+                        (2, 13),
+                        (3, 12),
+                    ],
+                ),
+            ],
+            hands: OnceLock::new(),
+            key_fingers: OnceLock::new(),
+        }
+    }
+
+    /**
+    The Neo finger assignments for a blueprint of another shape. The keys of
+    rows the Neo geometry doesn't have go to the pinkies, split at the middle
+    of the row, so every key still belongs to a hand.
+    */
+    #[cfg(feature = "cli")]
+    fn neo_for_shape(shape: &[usize]) -> Self {
+        let mut geometry = Geometry::neo();
+        for (row, &keys) in shape.iter().enumerate().skip(geometry.rows.len()) {
+            warn!(
+                "Row {} of the base layout isn't in the neo geometry, its keys are split between the pinkies at the middle of the row.",
+                row
+            );
+            for key in 0..keys {
+                let pinky = if key < keys / 2 { "L_pinky" } else { "R_pinky" };
+                geometry
+                    .fingers
+                    .iter_mut()
+                    .find(|finger| finger.name == pinky)
+                    .expect("the neo geometry has both pinkies")
+                    .positions
+                    .push((row, key));
+            }
+        }
+        geometry
+    }
+
+    /**
+    The names of all positions, like `L_index_home` for the key the left index
+    finger rests on. Keys beside a finger's column get `inner`/`outer` (and a
+    count for keys further out), e.g. `R_pinky_outer2_top`. Thumb keys are
+    named after the thumb without a row, e.g. `L_thumb` or `L_thumb_inner`.
+    */
+    pub fn position_names(&self) -> Vec<(String, KeyPos)> {
+        let mut names = vec![];
+        for finger in &self.fingers {
+            for &(row, key) in &finger.positions {
+                let row_name = self.row_names.get(row).map_or("extra", String::as_str);
+                let home_column = finger.home_column + self.stagger.get(row).copied().unwrap_or(0);
+                let outward = if finger.is_left() {
+                    home_column as isize - key as isize
+                } else {
+                    key as isize - home_column as isize
+                };
+                let column = match outward {
+                    0 => "".to_string(),
+                    1 => "outer_".to_string(),
+                    -1 => "inner_".to_string(),
+                    n if n > 0 => format!("outer{}_", n),
+                    n => format!("inner{}_", -n),
+                };
+                if finger.is_thumb() {
+                    let name = format!("{}_{}", finger.name, column);
+                    names.push((name.trim_end_matches('_').to_string(), (row, key)));
+                    continue;
+                }
+                names.push((
+                    format!("{}_{}{}", finger.name, column, row_name),
+                    (row, key),
+                ));
+            }
+        }
+        names
+    }
+
+    /// The hand that types a key. Keys that both hands can reach (like the thumb key) have none.
+    pub fn hand_of(&self, pos: KeyPos) -> Option<Hand> {
+        let (index, hands) = self.hands.get_or_init(|| {
+            let index = KeyIndex::new(&self.rows);
+            let mut hands = Vec::with_capacity(index.len());
+            for (row, &keys) in self.rows.iter().enumerate() {
+                hands.extend((0..keys).map(|key| self.find_hand((row, key))));
+            }
+            (index, hands)
+        });
+        match index.index(pos) {
+            Some(index) => hands[index],
+            // Only the neo fallback for blueprints of another shape scores keys outside of the rows.
+            None => self.find_hand(pos),
+        }
+    }
+
+    /// Like `hand_of`, by looking through the fingers.
+    fn find_hand(&self, pos: KeyPos) -> Option<Hand> {
+        let mut hands = self
+            .fingers
+            .iter()
+            .filter(|finger| finger.positions.contains(&pos))
+            .map(Finger::hand);
+        let hand = hands.next()?;
+        if hands.all(|other| other == hand) {
+            Some(hand)
+        } else {
+            None
+        }
+    }
+
+    /// The index into `fingers` of the finger that types a key. Keys that several fingers type have none.
+    pub fn finger_of(&self, pos: KeyPos) -> Option<usize> {
+        let (index, fingers) = self.key_fingers.get_or_init(|| {
+            let index = KeyIndex::new(&self.rows);
+            let mut fingers = Vec::with_capacity(index.len());
+            for (row, &keys) in self.rows.iter().enumerate() {
+                fingers.extend((0..keys).map(|key| self.find_finger((row, key))));
+            }
+            (index, fingers)
+        });
+        match index.index(pos) {
+            Some(index) => fingers[index],
+            None => self.find_finger(pos),
+        }
+    }
+
+    /// Like `finger_of`, by looking through the fingers.
+    fn find_finger(&self, pos: KeyPos) -> Option<usize> {
+        let mut fingers = self
+            .fingers
+            .iter()
+            .enumerate()
+            .filter(|(_, finger)| finger.positions.contains(&pos))
+            .map(|(index, _)| index);
+        match (fingers.next(), fingers.next()) {
+            (Some(finger), None) => Some(finger),
+            _ => None,
+        }
+    }
+
+    /// The key of a position name like `L_index_home`.
+    pub fn position_by_name(&self, name: &str) -> Option<KeyPos> {
+        self.position_names()
+            .into_iter()
+            .find(|(position_name, _)| position_name == name)
+            .map(|(_, pos)| pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// A geometry with rows of random lengths whose keys get up to 2 random fingers, some of them outside of the rows.
+    fn random_geometry(rng: &mut StdRng) -> Geometry {
+        let rows: Vec<usize> = (0..rng.gen_range(1..6))
+            .map(|_| rng.gen_range(0..15))
+            .collect();
+        let names = ["L_pinky", "L_index", "R_index", "R_pinky", "L_thumb"];
+        let mut fingers: Vec<Finger> = names
+            .iter()
+            .map(|name| Finger {
+                name: name.to_string(),
+                home_column: 0,
+                positions: vec![],
+            })
+            .collect();
+        for (row, &keys) in rows.iter().enumerate() {
+            for key in 0..keys + 1 {
+                for _ in 0..rng.gen_range(0..3) {
+                    let finger = rng.gen_range(0..fingers.len());
+                    let positions = &mut fingers[finger].positions;
+                    if !positions.contains(&(row, key)) {
+                        positions.push((row, key));
+                    }
+                }
+            }
+        }
+        Geometry {
+            name: "random".to_string(),
+            row_names: vec![String::new(); rows.len()],
+            rows,
+            stagger: vec![],
+            fingers,
+            hands: OnceLock::new(),
+            key_fingers: OnceLock::new(),
+        }
+    }
+
+    #[test]
+    fn the_key_index_numbers_the_keys_row_by_row() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let rows: Vec<usize> = (0..rng.gen_range(0..8))
+                .map(|_| rng.gen_range(0..20))
+                .collect();
+            let mut oracle = HashMap::new();
+            for (row, &keys) in rows.iter().enumerate() {
+                for key in 0..keys {
+                    oracle.insert((row, key), oracle.len());
+                }
+            }
+
+            let index = KeyIndex::new(&rows);
+            assert_eq!(index.len(), oracle.len());
+            assert_eq!(index.is_empty(), oracle.is_empty());
+            for row in 0..rows.len() + 2 {
+                for key in 0..22 {
+                    assert_eq!(
+                        index.index((row, key)),
+                        oracle.get(&(row, key)).copied(),
+                        "{:?} of rows {:?}",
+                        (row, key),
+                        rows
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_hand_and_finger_tables_agree_with_the_fingers() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..100 {
+            let geometry = random_geometry(&mut rng);
+            let mut oracle: HashMap<KeyPos, Vec<usize>> = HashMap::new();
+            for (index, finger) in geometry.fingers.iter().enumerate() {
+                for &pos in &finger.positions {
+                    oracle.entry(pos).or_default().push(index);
+                }
+            }
+
+            for row in 0..geometry.rows.len() + 1 {
+                for key in 0..16 {
+                    let fingers = oracle.get(&(row, key)).cloned().unwrap_or_default();
+                    let finger = match fingers.as_slice() {
+                        [finger] => Some(*finger),
+                        _ => None,
+                    };
+                    let mut hands = fingers
+                        .iter()
+                        .map(|&finger| geometry.fingers[finger].hand());
+                    let hand = hands
+                        .next()
+                        .filter(|&hand| hands.all(|other| other == hand));
+                    assert_eq!(geometry.finger_of((row, key)), finger, "{:?}", (row, key));
+                    assert_eq!(geometry.hand_of((row, key)), hand, "{:?}", (row, key));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt::Write;
+
+use crate::{geometry::Geometry, layout::Layout, ngrams::NGrams};
+
+/// The size of a key in the drawings, in pixels.
+const KEY_SIZE: f64 = 48.0;
+
+/// The space between two keys, in pixels.
+const KEY_GAP: f64 = 4.0;
+
+/// The color of a key nobody types.
+const COLD: [u8; 3] = [255, 255, 255];
+
+/// The color of the key with the most keystrokes.
+const HOT: [u8; 3] = [214, 48, 32];
+
+/**
+The keys of a layout with their characters and how much they are typed, laid
+out on a grid for drawing. A key is one unit wide and rows are one unit apart;
+the stagger of the geometry shifts a row to the left, so the home columns of
+the fingers line up.
+*/
+pub struct Heatmap {
+    /// Every key of the blueprint.
+    pub keys: Vec<HeatKey>,
+    /// The width of the widest row, in keys.
+    pub columns: f64,
+    /// The number of rows.
+    pub rows: usize,
+    /// The share of the keystrokes of the most typed character, which gets the hottest color.
+    pub max_share: f64,
+}
+
+/// A key of a `Heatmap`.
+pub struct HeatKey {
+    /// The column of the left edge of the key, in keys.
+    pub column: f64,
+    /// The row of the key.
+    pub row: usize,
+    /// The character (or key name) of every layer.
+    pub labels: Vec<String>,
+    /// The share of the keystrokes of the character of every layer.
+    pub shares: Vec<f64>,
+}
+
+impl Heatmap {
+    /// The keys of the layout with the shares of the letters of the ngrams.
+    pub fn new(layout: &Layout, ngrams: &NGrams, geometry: &Geometry) -> Self {
+        let mut shares: Vec<Vec<Vec<f64>>> = layout
+            .blueprint
+            .iter()
+            .map(|row| row.iter().map(|key| vec![0.0; key.len()]).collect())
+            .collect();
+        let mut keystrokes = 0.0;
+        for &(letter, weight) in &ngrams.letters {
+            if let Some(pos) = layout.positions[usize::from(letter)] {
+                shares[usize::from(pos.row)][usize::from(pos.col)][usize::from(pos.layer)] +=
+                    weight;
+                keystrokes += weight;
+            }
+        }
+
+        let stagger = |row: usize| geometry.stagger.get(row).copied().unwrap_or(0) as f64;
+        let shift = (0..layout.blueprint.len()).map(stagger).fold(0.0, f64::max);
+        let mut keys = vec![];
+        for (row, (blueprint_row, row_shares)) in layout.blueprint.iter().zip(shares).enumerate() {
+            for (key, (labels, key_shares)) in blueprint_row.iter().zip(row_shares).enumerate() {
+                keys.push(HeatKey {
+                    column: key as f64 - stagger(row) + shift,
+                    row,
+                    labels: labels.clone(),
+                    shares: key_shares
+                        .into_iter()
+                        .map(|share| {
+                            if keystrokes > 0.0 {
+                                share / keystrokes
+                            } else {
+                                0.0
+                            }
+                        })
+                        .collect(),
+                });
+            }
+        }
+        Heatmap {
+            columns: keys.iter().map(|key| key.column + 1.0).fold(0.0, f64::max),
+            rows: layout.blueprint.len(),
+            max_share: keys
+                .iter()
+                .flat_map(|key| key.shares.iter().copied())
+                .fold(0.0, f64::max),
+            keys,
+        }
+    }
+
+    /// The color of a key with `share` of the keystrokes, from white to red.
+    pub fn color(&self, share: f64) -> [u8; 3] {
+        let heat = if self.max_share > 0.0 {
+            (share / self.max_share).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let mut color = COLD;
+        for (channel, (&cold, &hot)) in color.iter_mut().zip(COLD.iter().zip(HOT.iter())) {
+            *channel = (f64::from(cold) + (f64::from(hot) - f64::from(cold)) * heat).round() as u8;
+        }
+        color
+    }
+
+    /// The width and height of the drawing in pixels.
+    pub fn size(&self) -> (f64, f64) {
+        let unit = KEY_SIZE + KEY_GAP;
+        (
+            (self.columns * unit + KEY_GAP).ceil(),
+            (self.rows as f64 * unit + KEY_GAP).ceil(),
+        )
+    }
+
+    /// The left edge, the top edge and the size of a key in pixels.
+    pub fn key_rect(&self, key: &HeatKey) -> (f64, f64, f64) {
+        let unit = KEY_SIZE + KEY_GAP;
+        (
+            KEY_GAP + key.column * unit,
+            KEY_GAP + key.row as f64 * unit,
+            KEY_SIZE,
+        )
+    }
+
+    /// The drawing of one layer as an svg element, with the share of every key as its tooltip.
+    pub fn svg(&self, layer: usize) -> String {
+        let (width, height) = self.size();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\">\n",
+            width, height
+        );
+        for key in &self.keys {
+            let label = key.labels.get(layer).map_or("", String::as_str);
+            let share = key.shares.get(layer).copied().unwrap_or(0.0);
+            let [red, green, blue] = self.color(share);
+            let (x, y, size) = self.key_rect(key);
+            writeln!(
+                svg,
+                "<g><title>{} {:.2}%</title><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\" fill=\"#{:02x}{:02x}{:02x}\" stroke=\"#888888\"/><text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"{}\">{}</text></g>",
+                escape(label),
+                share * 100.0,
+                x,
+                y,
+                size,
+                size,
+                red,
+                green,
+                blue,
+                x + size / 2.0,
+                y + size * 0.62,
+                // Key names like `Entf` get a smaller font so they fit.
+                if label.chars().count() > 1 { 11 } else { 20 },
+                escape(label)
+            )
+            .unwrap();
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+/// Text with the characters html and svg interpret escaped.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            // Control characters aren't allowed in xml, not even escaped.
+            char if char.is_control() && char != '\n' && char != '\t' => escaped.push('\u{fffd}'),
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,370 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    convergence::ConvergencePoint,
+    heatmap::{escape, Heatmap},
+    report::JsonResult,
+};
+
+/// The size of the convergence chart in pixels, and its margin for the axis labels.
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_MARGIN: f64 = 48.0;
+
+/// The colors of the runs of the convergence chart, repeated for more runs.
+const RUN_COLORS: [&str; 6] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#8c564b",
+];
+
+/// The styles of the report, embedded so the file works on its own.
+const STYLE: &str =
+    "body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222222; }
+pre { background: #f4f4f4; padding: 0.5em 1em; font-size: 1.2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #cccccc; padding: 0.25em 0.75em; }
+td.number { text-align: right; }
+code { white-space: pre; }
+summary { cursor: pointer; font-weight: bold; margin: 0.5em 0; }";
+
+/**
+The self-contained html report of `--output-format html` and of the html
+exports: the layout, every layer of the keyboard drawn as an svg heatmap of
+its keystrokes, the cost, the statistics, the convergence of the runs and the
+comparisons. Like the markdown it is rendered from the `JsonResult`, and it
+needs no assets or scripts, so it can be sent as a single file. The output is
+well-formed xml.
+*/
+pub fn render(result: &JsonResult, heatmap: &Heatmap) -> String {
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n{}<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\"/>\n<title>klo result</title>\n<style>\n{}\n</style>\n</head>\n<body>",
+        result.reproduction.xml_comment(),
+        STYLE
+    )
+    .unwrap();
+    writeln!(html, "<h1>klo result</h1>").unwrap();
+    writeln!(
+        html,
+        "<p>The layout, as <code>--starting-layout</code> accepts it:</p>"
+    )
+    .unwrap();
+    writeln!(html, "<pre>{}</pre>", escape(&result.layout.layout_string)).unwrap();
+    if result.top_layouts.is_empty() {
+        // A layout of `klo export` wasn't evolved from a starting layout.
+    } else if result.diff.moved_keys == 0 {
+        writeln!(html, "<p>No key moved from the starting layout.</p>").unwrap();
+    } else {
+        writeln!(
+            html,
+            "<p>{} keys moved from the starting layout, marked with <code>^</code>:</p>",
+            result.diff.moved_keys
+        )
+        .unwrap();
+        let lines = result.diff.marked_lines(&|_| 1);
+        writeln!(html, "<pre>{}</pre>", escape(&lines.join("\n"))).unwrap();
+        writeln!(
+            html,
+            "<p>{} characters stayed on their key: <code>{}</code></p>",
+            result.diff.anchors.len(),
+            escape(&result.diff.anchors.join(" "))
+        )
+        .unwrap();
+    }
+
+    writeln!(html, "<h2>Keyboard</h2>").unwrap();
+    let layers = heatmap
+        .keys
+        .iter()
+        .map(|key| key.labels.len())
+        .max()
+        .unwrap_or(0);
+    for layer in 0..layers {
+        writeln!(
+            html,
+            "<details{}>\n<summary>Layer {}</summary>\n{}\n</details>",
+            if layer == 0 { " open=\"open\"" } else { "" },
+            layer,
+            heatmap.svg(layer)
+        )
+        .unwrap();
+    }
+
+    writeln!(html, "<h2>Cost</h2>").unwrap();
+    writeln!(
+        html,
+        "<p>Scored with the {} geometry.</p>",
+        escape(result.geometry)
+    )
+    .unwrap();
+    let mut cost_rows: Vec<Vec<Cell>> = result
+        .cost_rows()
+        .into_iter()
+        .map(|(component, weight, cost)| {
+            vec![
+                Cell::Text(component.to_string()),
+                Cell::Number(format!("{}", weight)),
+                Cell::Number(format!("{:.2}", cost)),
+            ]
+        })
+        .collect();
+    cost_rows.push(vec![
+        Cell::Text("total".to_string()),
+        Cell::Text(String::new()),
+        Cell::Number(format!("{:.2}", result.cost.total)),
+    ]);
+    html.push_str(&table(&["Component", "Weight", "Cost"], &cost_rows));
+
+    let statistics = result.statistics;
+    writeln!(html, "<h2>Statistics</h2>").unwrap();
+    html.push_str(&table(
+        &["Finger", "Keystrokes"],
+        &statistics
+            .finger_loads
+            .iter()
+            .map(|load| {
+                vec![
+                    Cell::Text(load.finger.clone()),
+                    Cell::Number(format!("{:.1}%", load.share * 100.0)),
+                ]
+            })
+            .collect::<Vec<_>>(),
+    ));
+    html.push_str(&table(
+        &["Statistic", "Share"],
+        &result
+            .share_rows()
+            .into_iter()
+            .map(|(name, share)| vec![Cell::Text(name.to_string()), Cell::Number(share)])
+            .collect::<Vec<_>>(),
+    ));
+    html.push_str(&table(
+        &["Layer", "Keystrokes", "Most frequent characters"],
+        &statistics
+            .layer_loads
+            .iter()
+            .map(|load| {
+                let characters: Vec<String> = load
+                    .characters
+                    .iter()
+                    .map(|character| {
+                        format!("{:?} {:.2}%", character.char, character.share * 100.0)
+                    })
+                    .collect();
+                vec![
+                    Cell::Number(load.layer.to_string()),
+                    Cell::Number(format!("{:.1}%", load.share * 100.0)),
+                    Cell::Text(characters.join(", ")),
+                ]
+            })
+            .collect::<Vec<_>>(),
+    ));
+    if !statistics.worst_bigrams.is_empty() {
+        writeln!(
+            html,
+            "<details open=\"open\">\n<summary>Most frequent same finger bigrams and scissors</summary>"
+        )
+        .unwrap();
+        html.push_str(&table(
+            &["Bigram", "Kind", "Bigrams"],
+            &statistics
+                .worst_bigrams
+                .iter()
+                .map(|worst| {
+                    vec![
+                        Cell::Code(worst.bigram.clone()),
+                        Cell::Text(worst.kind.label().to_string()),
+                        Cell::Number(format!("{:.3}%", worst.share * 100.0)),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ));
+        writeln!(html, "</details>").unwrap();
+    }
+    if !result.missing_characters.is_empty() {
+        writeln!(html, "<p>Characters on no key of the layout:</p>").unwrap();
+        html.push_str(&table(
+            &["Character", "Keystrokes"],
+            &result
+                .missing_characters
+                .iter()
+                .map(|missing| {
+                    vec![
+                        Cell::Code(missing.char.clone()),
+                        Cell::Number(format!("{:.3}%", missing.share * 100.0)),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ));
+    }
+
+    if !result.convergence.is_empty() {
+        writeln!(html, "<h2>Convergence</h2>").unwrap();
+        writeln!(
+            html,
+            "<p>The best cost of every run so far, over its steps on a logarithmic scale:</p>"
+        )
+        .unwrap();
+        writeln!(html, "{}", convergence_svg(result.convergence)).unwrap();
+    }
+
+    if !result.comparisons.is_empty() {
+        writeln!(html, "<h2>Comparison</h2>").unwrap();
+        let result_row = vec![
+            Cell::Text("result".to_string()),
+            Cell::Number(format!("{:.2}", result.cost.total)),
+            Cell::Text(String::new()),
+            Cell::Number(format!("{:.2}%", statistics.same_finger_bigrams * 100.0)),
+        ];
+        let rows: Vec<Vec<Cell>> = std::iter::once(result_row)
+            .chain(result.comparisons.iter().map(|comparison| {
+                vec![
+                    Cell::Text(comparison.name.clone()),
+                    Cell::Number(format!("{:.2}", comparison.cost.total)),
+                    Cell::Number(format!("{:+.2}%", comparison.delta_percent)),
+                    Cell::Number(format!(
+                        "{:.2}%",
+                        comparison.statistics.same_finger_bigrams * 100.0
+                    )),
+                ]
+            }))
+            .collect();
+        html.push_str(&table(
+            &["Layout", "Total cost", "Difference", "Same finger bigrams"],
+            &rows,
+        ));
+    }
+
+    writeln!(html, "<h2>Reproduce</h2>").unwrap();
+    writeln!(html, "<p><code>{}</code></p>", escape(&result.command)).unwrap();
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/**
+The convergence samples of the runs as an svg chart with a polyline per run:
+the steps on a logarithmic x axis, like they were sampled, and the best cost
+on the y axis, lowest at the bottom.
+*/
+fn convergence_svg(points: &[ConvergencePoint]) -> String {
+    let mut runs: BTreeMap<u128, Vec<&ConvergencePoint>> = BTreeMap::new();
+    for point in points {
+        runs.entry(point.run).or_default().push(point);
+    }
+    let last_step = points.iter().map(|point| point.step).max().unwrap_or(1);
+    let costs = points.iter().map(|point| point.best_cost);
+    let (lowest, highest) = (
+        costs.clone().fold(f64::INFINITY, f64::min),
+        costs.fold(f64::NEG_INFINITY, f64::max),
+    );
+    let (width, height) = (
+        CHART_WIDTH - 2.0 * CHART_MARGIN,
+        CHART_HEIGHT - 2.0 * CHART_MARGIN,
+    );
+    let x = |step: u128| {
+        let span = (last_step as f64).log10();
+        let offset = if span > 0.0 {
+            (step as f64).log10() / span * width
+        } else {
+            0.0
+        };
+        CHART_MARGIN + offset
+    };
+    let y = |cost: f64| {
+        let offset = if highest > lowest {
+            (highest - cost) / (highest - lowest) * height
+        } else {
+            height / 2.0
+        };
+        CHART_MARGIN + offset
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">\n",
+        CHART_WIDTH, CHART_HEIGHT
+    );
+    writeln!(
+        svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#cccccc\"/>",
+        CHART_MARGIN, CHART_MARGIN, width, height
+    )
+    .unwrap();
+    for (label, label_x, label_y, anchor) in [
+        (
+            format!("{:.2}", highest),
+            CHART_MARGIN - 4.0,
+            CHART_MARGIN + 4.0,
+            "end",
+        ),
+        (
+            format!("{:.2}", lowest),
+            CHART_MARGIN - 4.0,
+            CHART_MARGIN + height + 4.0,
+            "end",
+        ),
+        (
+            "1".to_string(),
+            CHART_MARGIN,
+            CHART_HEIGHT - CHART_MARGIN + 16.0,
+            "middle",
+        ),
+        (
+            last_step.to_string(),
+            CHART_MARGIN + width,
+            CHART_HEIGHT - CHART_MARGIN + 16.0,
+            "middle",
+        ),
+    ] {
+        writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"{}\">{}</text>",
+            label_x, label_y, anchor, label
+        )
+        .unwrap();
+    }
+    for (idx, (run, points)) in runs.iter().enumerate() {
+        let coordinates: Vec<String> = points
+            .iter()
+            .map(|point| format!("{:.1},{:.1}", x(point.step), y(point.best_cost)))
+            .collect();
+        writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"><title>run {}</title></polyline>",
+            RUN_COLORS[idx % RUN_COLORS.len()],
+            coordinates.join(" "),
+            run
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// A cell of a table of the report.
+enum Cell {
+    Text(String),
+    Number(String),
+    Code(String),
+}
+
+fn table(header: &[&str], rows: &[Vec<Cell>]) -> String {
+    let mut html = String::from("<table>\n<tr>");
+    for name in header {
+        write!(html, "<th>{}</th>", escape(name)).unwrap();
+    }
+    html.push_str("</tr>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            match cell {
+                Cell::Text(text) => write!(html, "<td>{}</td>", escape(text)),
+                Cell::Number(text) => write!(html, "<td class=\"number\">{}</td>", escape(text)),
+                Cell::Code(text) => write!(html, "<td><code>{}</code></td>", escape(text)),
+            }
+            .unwrap();
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    html
+}
@@ -0,0 +1,226 @@
+use std::{
+    fs,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    geometry::KeyPos,
+    layout::{Blueprint, BlueprintHelpers, Layout},
+    ngrams::NGrams,
+    output_profile,
+    scoring::CostModel,
+};
+
+const HELP: &str = "Commands:
+  swap <a> <b>  swap two characters of layer 0
+  undo          take back the last swap
+  score         show the cost breakdown
+  diff          show which characters moved since the session started
+  save <path>   write the layout string to a file
+  help          show this help
+  quit          end the session";
+
+/**
+Let the user nudge a layout by hand, re-scoring it with the same cost model
+after every change. Commands are read line by line from `input` until `quit`
+or the end of the input, so a session can also be scripted.
+*/
+pub fn run_session(
+    input: impl BufRead,
+    mut output: impl Write,
+    blueprint: Blueprint,
+    ngrams: &NGrams,
+    model: &dyn CostModel,
+) -> Result<(), String> {
+    let write_error = |e: std::io::Error| format!("Unable to write to the session: {}", e);
+    let start = blueprint.clone();
+    let mut current = blueprint;
+    let mut history: Vec<Blueprint> = vec![];
+
+    writeln!(output, "{}", HELP).map_err(write_error)?;
+    show_layout(&mut output, &current, ngrams, model).map_err(write_error)?;
+    prompt(&mut output).map_err(write_error)?;
+
+    for line in input.lines() {
+        let line = line.map_err(|e| format!("Unable to read the session input: {}", e))?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let response = match words.as_slice() {
+            [] => None,
+            ["quit"] | ["exit"] => break,
+            ["help"] => Some(HELP.to_string()),
+            ["swap", a, b] => match swap_chars(&current, a, b) {
+                Ok(swapped) => {
+                    history.push(std::mem::replace(&mut current, swapped));
+                    show_layout(&mut output, &current, ngrams, model).map_err(write_error)?;
+                    None
+                }
+                Err(message) => Some(message),
+            },
+            ["undo"] => match history.pop() {
+                Some(previous) => {
+                    current = previous;
+                    show_layout(&mut output, &current, ngrams, model).map_err(write_error)?;
+                    None
+                }
+                None => Some("Nothing to undo.".to_string()),
+            },
+            ["score"] => {
+                let cost = model.score(
+                    &Layout::from_blueprint(current.clone(), &ngrams.interner, &model.key_costs()),
+                    ngrams,
+                );
+                Some(format!(
+                    "key position cost: {}\nhand balance cost: {}\nsame finger cost: {}\nsame hand cost: {}\nleft hand share: {:.1}%\ntotal cost: {}",
+                    cost.key_position,
+                    cost.hand_balance,
+                    cost.same_finger,
+                    cost.same_hand,
+                    cost.left_hand_share * 100.0,
+                    cost.total()
+                ))
+            }
+            ["diff"] => Some(diff(&start, &current)),
+            ["save", path] => Some(match fs::write(path, current.layout_string() + "\n") {
+                Ok(()) => format!("Saved the layout to {}", path),
+                Err(e) => format!("Unable to write {}: {}", path, e),
+            }),
+            _ => Some(format!(
+                "Unknown command {:?}. Type `help` to see the commands.",
+                line.trim()
+            )),
+        };
+        if let Some(response) = response {
+            writeln!(output, "{}", output_profile::render(&response)).map_err(write_error)?;
+        }
+        prompt(&mut output).map_err(write_error)?;
+    }
+    writeln!(output).map_err(write_error)
+}
+
+fn prompt(output: &mut impl Write) -> std::io::Result<()> {
+    write!(output, "> ")?;
+    output.flush()
+}
+
+fn show_layout(
+    output: &mut impl Write,
+    blueprint: &Blueprint,
+    ngrams: &NGrams,
+    model: &dyn CostModel,
+) -> std::io::Result<()> {
+    let cost = model.score(
+        &Layout::from_blueprint(blueprint.clone(), &ngrams.interner, &model.key_costs()),
+        ngrams,
+    );
+    writeln!(
+        output,
+        "{}\ncost: {}",
+        output_profile::render(&blueprint.layout_string()),
+        cost.total()
+    )
+}
+
+fn layer_0_position(blueprint: &Blueprint, char: &str) -> Option<KeyPos> {
+    blueprint.iter().enumerate().find_map(|(row_idx, row)| {
+        row.iter()
+            .position(|key| key.first().map(String::as_str) == Some(char))
+            .map(|key_idx| (row_idx, key_idx))
+    })
+}
+
+fn swap_chars(blueprint: &Blueprint, a: &str, b: &str) -> Result<Blueprint, String> {
+    let find = |char| {
+        layer_0_position(blueprint, char)
+            .ok_or_else(|| format!("{:?} is not on layer 0 of the layout.", char))
+    };
+    let (pos_a, pos_b) = (find(a)?, find(b)?);
+    let mut swapped = blueprint.clone();
+    swapped[pos_a.0][pos_a.1][0] = b.to_string();
+    swapped[pos_b.0][pos_b.1][0] = a.to_string();
+    Ok(swapped)
+}
+
+fn diff(start: &Blueprint, current: &Blueprint) -> String {
+    let mut moved = vec![];
+    for (row_idx, row) in start.iter().enumerate() {
+        for (key_idx, key) in row.iter().enumerate() {
+            let before = key.first();
+            let after = current[row_idx][key_idx].first();
+            if before != after {
+                moved.push(format!(
+                    "{} -> {}",
+                    before.map_or("", String::as_str),
+                    after.map_or("", String::as_str)
+                ));
+            }
+        }
+    }
+    if moved.is_empty() {
+        return "No changes since the session started.".to_string();
+    }
+    format!(
+        "Started with:\n{}\nNow:\n{}\nKeys that changed (old -> new): {}",
+        start.layout_string(),
+        current.layout_string(),
+        moved.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::Geometry,
+        layout::builtin_baseline,
+        scoring::{cost_breakdown, EffortModel},
+        weights::CostWeights,
+    };
+
+    fn neo_blueprint() -> Blueprint {
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+        blueprint
+    }
+
+    #[test]
+    fn a_scripted_session_swaps_undoes_and_saves() {
+        let ngrams = NGrams::from_pregenerated("5 e\n3 n\n", "2 en\n", "").unwrap();
+        let weights = CostWeights::default();
+        let model = EffortModel {
+            weights: &weights,
+            geometry: Geometry::neo(),
+        };
+        let path = std::env::temp_dir().join("klo_interactive_session.txt");
+        let script = format!(
+            "swap e n\nswap e ☃\ndiff\nundo\nundo\nfrobnicate\nsave {}\nquit\nswap a b\n",
+            path.display()
+        );
+
+        let mut output = vec![];
+        run_session(
+            script.as_bytes(),
+            &mut output,
+            neo_blueprint(),
+            &ngrams,
+            &model,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut swapped = neo_blueprint();
+        swapped.set_new_key("e".to_string(), "n".to_string());
+        let cost = |blueprint: Blueprint| {
+            let layout = Layout::from_blueprint(blueprint, &ngrams.interner, &weights.key_costs);
+            cost_breakdown(&layout, &ngrams, &weights, &model.geometry).total()
+        };
+        assert!(output.contains(&format!("cost: {}\n", cost(swapped))));
+        assert!(output.contains("\"☃\" is not on layer 0 of the layout."));
+        assert!(output.contains("Keys that changed (old -> new): e -> n, n -> e"));
+        assert!(output.contains("Nothing to undo."));
+        assert!(output.contains("Unknown command \"frobnicate\""));
+        assert_eq!(saved, neo_blueprint().layout_string() + "\n");
+        assert_eq!(output.matches("cost: ").count(), 3, "{}", output);
+    }
+}
@@ -0,0 +1,60 @@
+use std::{collections::HashMap, convert::TryFrom};
+
+/**
+Small ids for the characters of a set of ngrams, so scoring indexes vectors
+instead of hashing strings. Ids are handed out in the order the characters are
+first seen and never change; a set of ngrams has at most `u16::MAX + 1`
+different characters.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    ids: HashMap<char, u16>,
+    chars: Vec<char>,
+}
+
+impl Interner {
+    /// The id of `char`, which gets the next free id if it hasn't got one yet.
+    pub fn intern(&mut self, char: char) -> Result<u16, String> {
+        if let Some(&id) = self.ids.get(&char) {
+            return Ok(id);
+        }
+        let id = u16::try_from(self.chars.len()).map_err(|_| {
+            format!(
+                "The ngrams have more than {} different characters",
+                self.chars.len()
+            )
+        })?;
+        self.ids.insert(char, id);
+        self.chars.push(char);
+        Ok(id)
+    }
+
+    /// The id of a key of a blueprint, if it is a single character that occurs in the ngrams.
+    pub fn id(&self, key: &str) -> Option<u16> {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) => self.ids.get(&char).copied(),
+            _ => None,
+        }
+    }
+
+    /// The character of an id handed out by `intern`.
+    pub fn char(&self, id: u16) -> char {
+        self.chars[usize::from(id)]
+    }
+
+    /// The characters of some ids as a string.
+    pub fn text(&self, ids: &[u16]) -> String {
+        ids.iter().map(|&id| self.char(id)).collect()
+    }
+
+    /// The number of ids handed out.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Whether no id was handed out yet.
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::warn;
+
+use crate::progress::StopHandle;
+
+/// How often the watcher looks for an interrupt.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Set by the SIGINT handler, which may do nothing but store an atomic.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    // A second Ctrl-C ends klo right away.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+/**
+Stops an evolution on the first SIGINT: the current runs finish, the result
+of the runs so far is written and klo exits with `ExitCode::Interrupted`. A
+second SIGINT ends klo at once. Elsewhere than on unix, Ctrl-C ends klo at
+once as before.
+*/
+pub struct InterruptWatcher {
+    done: Arc<AtomicBool>,
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl InterruptWatcher {
+    /// Install the SIGINT handler and ask `stop` to stop the evolution once it fires.
+    pub fn start(stop: StopHandle) -> Self {
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(
+                libc::SIGINT,
+                on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let watching = Arc::clone(&done);
+        let watcher = thread::spawn(move || {
+            while !watching.load(Ordering::Relaxed) {
+                if INTERRUPTED.load(Ordering::Relaxed) {
+                    warn!("Interrupted, stopping after the current runs. Press Ctrl-C again to quit at once.");
+                    stop.request_stop();
+                    return;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+        InterruptWatcher {
+            done,
+            watcher: Some(watcher),
+        }
+    }
+
+    /// Stop watching and tell whether SIGINT stopped the evolution.
+    pub fn finish(mut self) -> bool {
+        self.done.store(true, Ordering::Relaxed);
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+        if let Some(watcher) = self.watcher.take() {
+            watcher
+                .join()
+                .expect("watching for an interrupt doesn't panic");
+        }
+        INTERRUPTED.load(Ordering::Relaxed)
+    }
+}
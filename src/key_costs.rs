@@ -0,0 +1,303 @@
+use std::{collections::HashSet, fs, path::Path, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::warn;
+use serde::Deserialize;
+
+use crate::{
+    geometry::KeyPos,
+    layout::{Blueprint, Pos},
+};
+
+/// The effort of hitting each key on layer 0, taken from the python optimizer.
+/// The 0 values aren’t filled in at the moment.
+/// Don’t put mutated keys there, otherwise the best keys will end up there!
+const COST_PER_KEY: [&[f64]; 5] = [
+    // Number row (0)
+    &[
+        80.0, 70.0, 60.0, 50.0, 50.0, 60.0, 60.0, 50.0, 50.0, 50.0, 50.0, 60.0, 70.0, 80.0,
+    ],
+    // Row 1
+    &[
+        24.0, 16.0, 10.0, 5.0, 12.0, 17.0, 20.0, 13.0, 5.0, 9.0, 11.0, 20.0, 36.0, 0.0,
+    ],
+    // Row 2; enter low to make it preferred over the layer 4 enter.
+    &[
+        9.0, 5.0, 3.0, 3.0, 3.0, 6.0, 6.0, 3.0, 3.0, 3.0, 5.0, 9.0, 30.0, 6.0,
+    ],
+    // Row 3
+    &[
+        20.0, 16.0, 35.0, 24.0, 20.0, 9.0, 30.0, 7.0, 9.0, 18.0, 22.0, 35.0, 20.0,
+    ],
+    // Row 4 with the space bar
+    &[0.0, 0.0, 0.0, 3.0, 7.0, 0.0, 0.0, 0.0],
+];
+
+/// The additional effort of reaching a layer via its modifiers.
+const COST_LAYER_ADDITION: [f64; 6] = [0.0, 15.0, 12.0, 10.0, 27.0, 22.0];
+
+/// The default effort of a key or layer that the table has no cost for, like the hardest keys of the number row.
+pub const DEFAULT_OFF_TABLE_COST: f64 = 80.0;
+
+lazy_static! {
+    /// The keys and layers outside of a key cost table that were already logged.
+    static ref OFF_TABLE_LOGGED: Mutex<HashSet<(Option<KeyPos>, usize)>> =
+        Mutex::new(HashSet::new());
+}
+
+/**
+The effort of hitting every key of a blueprint, before weighting: a cost for
+every key on layer 0, row by row in the order of the blueprint, plus the
+additional cost of the layer. Keys and layers outside of the table cost the
+`off_table_cost`. The default is the table of the python optimizer, made for a
+staggered Neo keyboard.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCosts {
+    /// The effort of hitting each key on layer 0, row by row.
+    pub keys: Vec<Vec<f64>>,
+    /// The additional effort of reaching each layer via its modifiers, starting with layer 0.
+    pub layers: Vec<f64>,
+    /// The effort of a key or a layer the table has no cost for, see --off-table-cost.
+    pub off_table_cost: f64,
+}
+
+impl Default for KeyCosts {
+    fn default() -> Self {
+        KeyCosts {
+            keys: COST_PER_KEY.iter().map(|row| row.to_vec()).collect(),
+            layers: COST_LAYER_ADDITION.to_vec(),
+            off_table_cost: DEFAULT_OFF_TABLE_COST,
+        }
+    }
+}
+
+/**
+A --key-costs file. `keys` has a row of costs for every row of the base
+layout and a cost for every key of the row. The layers that `layers` has no
+cost for cost what they cost with the built-in table.
+*/
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyCostsFile {
+    keys: Vec<Vec<f64>>,
+    layers: Option<Vec<f64>>,
+}
+
+impl KeyCosts {
+    /// Read a key cost table from a json file, or a toml file if its name ends with `.toml`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        let file: KeyCostsFile = if Path::new(path).extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?
+        } else {
+            serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?
+        };
+        let layers = file.layers.iter().flatten();
+        if file
+            .keys
+            .iter()
+            .flatten()
+            .chain(layers)
+            .any(|cost| !cost.is_finite())
+        {
+            return Err(format!("{}: every cost must be a finite number", path));
+        }
+
+        let mut costs = KeyCosts {
+            keys: file.keys,
+            ..KeyCosts::default()
+        };
+        if let Some(layers) = file.layers {
+            let builtin = costs.layers.iter().skip(layers.len()).copied();
+            costs.layers = layers.iter().copied().chain(builtin).collect();
+        }
+        Ok(costs)
+    }
+
+    /// The number of keys of a blueprint row that the table has costs for.
+    pub fn keys_in_row(&self, row: usize) -> usize {
+        self.keys.get(row).map_or(0, Vec::len)
+    }
+
+    /// The cost of typing the key at `pos` once, before weighting.
+    pub fn position_cost(&self, pos: &Pos) -> f64 {
+        let layer = usize::from(pos.layer);
+        let layer_cost = self
+            .layers
+            .get(layer)
+            .copied()
+            .unwrap_or_else(|| self.off_table(None, layer));
+        self.key_cost(pos.key()) + layer_cost
+    }
+
+    /// The effort of hitting a key on layer 0.
+    fn key_cost(&self, (row, key): KeyPos) -> f64 {
+        match self.keys.get(row).and_then(|costs| costs.get(key)) {
+            Some(&cost) => cost,
+            None => self.off_table(Some((row, key)), 0),
+        }
+    }
+
+    /// The `off_table_cost` of a key or a layer outside of the table, which is logged the first time.
+    fn off_table(&self, key: Option<KeyPos>, layer: usize) -> f64 {
+        if OFF_TABLE_LOGGED.lock().unwrap().insert((key, layer)) {
+            match key {
+                Some((row, key)) => warn!(
+                    "Row {} key {} is outside of the key cost table, it costs {}.",
+                    row, key, self.off_table_cost
+                ),
+                None => warn!(
+                    "Layer {} is outside of the key cost table, reaching it costs {}.",
+                    layer, self.off_table_cost
+                ),
+            }
+        }
+        self.off_table_cost
+    }
+
+    /// Every mismatch between the rows and keys of the table and the ones of `blueprint`, with their indices.
+    pub fn mismatches(&self, blueprint: &Blueprint) -> Vec<String> {
+        let mut mismatches = vec![];
+        if self.keys.len() != blueprint.len() {
+            mismatches.push(format!(
+                "The key costs have {} rows, but the base layout has {}",
+                self.keys.len(),
+                blueprint.len()
+            ));
+        }
+        for (row, (costs, keys)) in self.keys.iter().zip(blueprint.iter()).enumerate() {
+            if costs.len() != keys.len() {
+                mismatches.push(format!(
+                    "Row {} of the key costs has {} keys, but row {} of the base layout has {}",
+                    row,
+                    costs.len(),
+                    row,
+                    keys.len()
+                ));
+            }
+        }
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_table_adds_the_layer_cost() {
+        let costs = KeyCosts::default();
+        assert_eq!(costs.position_cost(&Pos::new(2, 3, 0)), 3.0);
+        assert_eq!(costs.position_cost(&Pos::new(2, 3, 1)), 18.0);
+    }
+
+    #[test]
+    fn keys_and_layers_off_the_table_cost_the_off_table_cost() {
+        let costs = KeyCosts {
+            off_table_cost: 50.0,
+            ..KeyCosts::default()
+        };
+        assert_eq!(costs.position_cost(&Pos::new(4, 20, 0)), 50.0);
+        assert_eq!(costs.position_cost(&Pos::new(2, 3, 6)), 53.0);
+    }
+
+    #[test]
+    fn a_blueprint_one_key_wider_in_every_row_costs_the_off_table_cost_there() {
+        let costs = KeyCosts {
+            off_table_cost: 50.0,
+            ..KeyCosts::default()
+        };
+        let blueprint: Blueprint = costs
+            .keys
+            .iter()
+            .map(|row| vec![vec!["x".to_string()]; row.len() + 1])
+            .collect();
+        for (row, keys) in blueprint.iter().enumerate() {
+            let extra = keys.len() - 1;
+            assert_eq!(costs.keys_in_row(row), extra);
+            assert_eq!(costs.position_cost(&Pos::new(row, extra, 0)), 50.0);
+            assert_eq!(
+                costs.position_cost(&Pos::new(row, extra - 1, 0)),
+                costs.keys[row][extra - 1]
+            );
+        }
+        assert_eq!(costs.keys_in_row(blueprint.len()), 0);
+
+        let mismatches = costs.mismatches(&blueprint);
+        assert_eq!(mismatches.len(), blueprint.len(), "{:?}", mismatches);
+        assert_eq!(
+            mismatches[3],
+            "Row 3 of the key costs has 13 keys, but row 3 of the base layout has 14"
+        );
+        let narrow: Blueprint = blueprint[..4].to_vec();
+        assert_eq!(
+            costs.mismatches(&narrow)[0],
+            "The key costs have 5 rows, but the base layout has 4"
+        );
+    }
+
+    #[test]
+    fn a_file_without_every_layer_keeps_the_builtin_ones() {
+        let path = std::env::temp_dir().join("klo_key_costs_partial_layers.json");
+        fs::write(&path, r#"{"keys": [[1.0, 2.0]], "layers": [0.0, 5.0]}"#).unwrap();
+        let costs = KeyCosts::load(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(costs.keys, vec![vec![1.0, 2.0]]);
+        assert_eq!(costs.layers, vec![0.0, 5.0, 12.0, 10.0, 27.0, 22.0]);
+    }
+
+    #[test]
+    fn a_file_with_a_non_finite_cost_is_rejected() {
+        let path = std::env::temp_dir().join("klo_key_costs_non_finite.toml");
+        fs::write(&path, "keys = [[1.0, inf]]\n").unwrap();
+        let result = KeyCosts::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.unwrap_err().contains("finite"));
+    }
+
+    #[cfg(feature = "text-corpus")]
+    #[test]
+    fn two_tables_score_the_same_layout_differently_in_one_process() {
+        use std::sync::Arc;
+
+        use crate::{
+            geometry::Geometry,
+            layout::{builtin_baseline, BlueprintHelpers, Layout},
+            ngrams::NGrams,
+            scoring::cost_breakdown,
+            weights::CostWeights,
+        };
+
+        let ngrams = NGrams::from_text("the quick brown fox jumps over the lazy dog").unwrap();
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+        let geometry = Geometry::neo();
+
+        let builtin = CostWeights::default();
+        let flat = CostWeights {
+            key_costs: Arc::new(KeyCosts {
+                keys: builtin
+                    .key_costs
+                    .keys
+                    .iter()
+                    .map(|row| vec![1.0; row.len()])
+                    .collect(),
+                ..KeyCosts::default()
+            }),
+            ..CostWeights::default()
+        };
+        let cost = |weights: &CostWeights| {
+            let layout =
+                Layout::from_blueprint(blueprint.clone(), &ngrams.interner, &weights.key_costs);
+            cost_breakdown(&layout, &ngrams, weights, &geometry).key_position
+        };
+
+        let builtin_cost = cost(&builtin);
+        assert_ne!(builtin_cost, cost(&flat));
+        assert_eq!(builtin_cost, cost(&builtin));
+    }
+}
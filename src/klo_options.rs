@@ -1,40 +1,254 @@
-use std::cmp::max;
+use std::{cmp::max, collections::HashSet, ffi::OsString, fmt, path::Path};
 
-use clap::Clap;
+use clap::{ArgEnum, ArgMatches, Clap, FromArgMatches, IntoApp};
+use log::info;
+use serde::Serialize;
+
+use crate::{
+    checkpoint::CheckpointInterval,
+    geometry::Geometry,
+    layout::{builtin_base_layout, builtin_baseline, BUILTIN_BASELINES},
+    weights::CostWeights,
+};
 
 // Keyboard Layout Optimizer based on https://hg.sr.ht/~arnebab/evolve-keyboard-layout/browse?rev=tip
+// The options of klo, from the command line, the environment and klo.toml. A doc comment would
+// become the about text of `klo --help`.
+#[allow(missing_docs)]
 #[derive(Clap, Debug)]
 #[clap(name = "klo")]
 pub struct KloOptions {
+    /// Path to a klo.toml holding default values for the options. klo.toml in the working directory is used if present. Every option can also be set with a KLO_ environment variable like KLO_STEPS (lists are comma separated). The command line wins over the environment, which wins over the file.
+    #[clap(long, global = true)]
+    pub config: Option<String>,
+
+    /// Path to your ngrams.config
+    #[clap(long, global = true, default_value = "ngrams.config")]
+    pub ngrams_config: String,
+
+    /// Read the relative paths of the ngrams config relative to the working directory, like klo did before, instead of relative to the directory of the config.
+    #[clap(long, global = true)]
+    pub ngrams_paths_from_cwd: bool,
+
+    /// Use this text file instead of an ngrams config, optionally with a weight like `novel.txt:2`. Can be repeated. Can't be combined with --ngrams-config.
+    #[clap(
+        long,
+        global = true,
+        multiple_occurrences = true,
+        multiple_values = false
+    )]
+    pub corpus: Vec<String>,
+
+    /// The characters the evolution may move. Either the characters themselves, ranges like
+    /// `a-z,0-9,äöüß` (`\,` and `\-` are a literal comma and dash) or `@file` with the characters
+    /// one per line or on a single line, `#` starts a comment line. The ngrams aren't filtered by
+    /// the alphabet: characters outside of it still count for the cost where the layout has them.
+    #[clap(long, global = true, default_value = "abcdefghijklmnopqrstuvwxyzäöüß")]
+    pub alphabet: String,
+
+    /// Only warn about alphabet characters that aren't on layer 0 of the layout and leave them out, instead of failing.
+    #[clap(long, global = true)]
+    pub allow_partial_alphabet: bool,
+
+    /// The layers whose characters the evolution rearranges, like `0`, `3` or `0,3`. The layout output and exports show these layers.
+    #[clap(
+        long,
+        global = true,
+        multiple_occurrences = true,
+        multiple_values = false,
+        use_delimiter = true,
+        require_delimiter = true,
+        default_value = "0"
+    )]
+    pub optimize_layers: Vec<usize>,
+
+    /// The characters to rearrange on a layer other than 0, like `3=…`. Defaults to every character of the layer. Can be repeated.
+    #[clap(
+        long,
+        global = true,
+        multiple_occurrences = true,
+        multiple_values = false
+    )]
+    pub layer_alphabet: Vec<String>,
+
+    /// Path to your base_layout.json or the name of a built-in base layout (see `klo list base-layouts`). If non is supplied the neo layout is used.
+    #[clap(long, global = true)]
+    pub base_layout: Option<String>,
+
+    /// Which fingers type which keys: path to a geometry toml or one of neo, ansi60, ortho4x12 and split3x6+3. By default the built-in geometry whose rows match the base layout is used, or the neo geometry with the keys of extra rows split between the pinkies.
+    #[clap(long, global = true)]
+    pub geometry: Option<String>,
+
+    /// The number of threads to use for parallel work, like parsing the ngrams and the runs of an evolution. Use 0 to use all cores.
+    #[clap(long, global = true, default_value = "0")]
+    pub threads: usize,
+
+    /// Only show warnings and errors on the shell.
+    #[clap(long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Should we give additional statistics for the final layout?
+    #[clap(long, global = true)]
+    pub verbose: bool,
+
+    /// Show only ASCII on the shell: `ä` becomes `ae`, `⇧` becomes `Shift` and other characters are escaped like `\u{2603}`. Files stay UTF-8. On by default in the classic Windows console.
+    #[clap(long, global = true)]
+    pub ascii: bool,
+
+    /// Path to a weights.toml with the weight of each cost component. See --weight for the names.
+    #[clap(long, global = true)]
+    pub weights: Option<String>,
+
+    /// Override a single weight after loading --weights, like `key_position=2`. Can be repeated.
+    #[clap(
+        long,
+        global = true,
+        multiple_occurrences = true,
+        multiple_values = false
+    )]
+    pub weight: Vec<String>,
+
+    /// The desired share of keystrokes typed by the left hand, between 0 and 1. Deviations cost `hand_balance` per percentage point.
+    #[clap(long, global = true)]
+    pub hand_target: Option<f64>,
+
+    /// Charge the characters of the ngrams that are on no key of the layout this effort per keystroke, like a key that is very hard to reach (the hardest keys cost 80). Without it they cost nothing.
+    #[clap(long, global = true)]
+    pub missing_char_cost: Option<f64>,
+
+    /// The effort of the keys and layers of the base layout that the key cost table has no cost for, like the keys of an extra row or a seventh layer.
+    #[clap(long, global = true, default_value = "80")]
+    pub off_table_cost: f64,
+
+    /// A json file (or toml if it ends with .toml) with the effort of every key instead of the built-in key cost table, which was made for a staggered Neo keyboard: `{"keys": [[80, 70, …], …], "layers": [0, 15, …]}`. `keys` has a row of costs for every row of the base layout with a cost for every key, `layers` the additional effort of the layers, the ones it leaves out cost the built-in amount.
+    #[clap(long, global = true)]
+    pub key_costs: Option<String>,
+
+    /// Report the characters the layout can't type that make up at least this share of the keystrokes.
+    #[clap(long, global = true, default_value = "0.0001")]
+    pub missing_char_threshold: f64,
+
+    /// Warn about the characters on layer 2 or higher that make up at least this share of the keystrokes.
+    #[clap(long, global = true, default_value = "0.005")]
+    pub high_layer_threshold: f64,
+
+    /// The weights after loading --weights and applying --weight.
+    #[clap(skip)]
+    pub cost_weights: CostWeights,
+
+    /// Time the phases of the run (config, corpora, base layout, runs, scoring, report) and print a table at the end. Also part of the json result.
+    #[clap(long, global = true)]
+    pub profile: bool,
+
+    /// Write a log with timestamps to this file, independent of --quiet and --verbose. Lines logged during a run are prefixed with the run.
+    #[clap(long, global = true)]
+    pub log_file: Option<String>,
+
+    /// The most detailed messages --log-file receives.
+    #[clap(long, global = true, arg_enum, default_value = "trace")]
+    pub log_level: LogLevel,
+
+    /// Rotate --log-file once it is larger than this many megabytes: it becomes `<log file>.1` and older logs move up to `.5`, the oldest is deleted.
+    #[clap(long, global = true)]
+    pub log_rotate_mb: Option<u64>,
+
+    /// What to do. Without a subcommand, klo evolves a layout like `klo evolve` (deprecated).
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+// The command is parsed once, so the size of the evolve options doesn't matter.
+// A doc comment would become the about text of klo, the variants are documented by their help.
+#[allow(clippy::large_enum_variant, missing_docs)]
+#[derive(Clap, Debug)]
+pub enum Command {
+    /// Evolve a layout by selecting the fittest of random mutations step by step.
+    Evolve(EvolveOptions),
+    /// Score a single layout against the configured ngrams.
+    Analyze(AnalyzeOptions),
+    /// Score several layouts against the same ngrams and rank them.
+    Compare(CompareOptions),
+    /// Tools for inspecting and converting ngram data.
+    Ngrams(NgramsOptions),
+    /// Write a layout in another format.
+    #[cfg(feature = "exporters")]
+    Export(ExportOptions),
+    /// Print a shell completion script to stdout.
+    Completions(CompletionsOptions),
+    /// Print the built-in base layouts, the position names or the fingers to stdout.
+    List(ListOptions),
+    /// Manage klo.toml configurations.
+    Config(ConfigOptions),
+}
+
+/// The options of `klo evolve`.
+#[derive(Clap, Debug)]
+pub struct EvolveOptions {
     /// The number of new layouts to create. Can be overwritten with the -n parameter. 500 should have a 50% chance of finding the best possible layout (the global minimum).
     #[clap(short = 'n', long, default_value = "500")]
     pub num_layouts: u128,
 
-    /// The output filename. Can be overwritten with the -o parameter.
+    /// The number of best layouts to keep from all runs and report.
+    #[clap(long, default_value = "1")]
+    pub top_n: usize,
+
+    /// Only keep a layout in the --top-n if at least this many characters of the --optimize-layers are on other keys than in every other kept layout.
+    #[clap(long, default_value = "0")]
+    pub min_diff: usize,
+
+    /// The output filename. Can be overwritten with the -o parameter. Text results are appended after a line with the time, the other formats replace the file.
     #[clap(short = 'o', long, default_value = "output.txt")]
     pub filename: String,
 
-    /// The number of random evolution steps to take.
+    /// The format of the result written to the output file.
+    #[clap(long, arg_enum, default_value = "text")]
+    pub output_format: OutputFormat,
+
+    /// How to report the progress. jsonl prints one json event per line on stdout (see `progress::ProgressEvent` for the schema), the log goes to stderr as always.
+    #[clap(long, arg_enum, default_value = "text")]
+    pub progress_format: ProgressFormat,
+
+    /// Also print the json result as the last line on stdout. Only used with --output-format json.
+    #[clap(long)]
+    pub json_stdout: bool,
+
+    /// Append a row with the seed, cost, hand split, same finger bigrams and layout string of every finished run to this csv file. The header is only written when the file is created, so several evolutions can share one file. What the rows of each configuration were made with is added to the file of the same name ending in .configs.
+    #[clap(long)]
+    pub runs_csv: Option<String>,
+
+    /// Append a json line with the `timestamp`, `run`, `step`, `cost` and `layout` string to this file whenever the evolution finds a layout cheaper than every one before, to follow a long evolution with `tail -f`.
+    #[clap(long)]
+    pub best_stream: Option<String>,
+
+    /// Write the layout to this file exactly as --starting-layout accepts it, so `--starting-layout "$(cat <file>)"` continues from it.
+    #[clap(long)]
+    pub output_layout_string: Option<String>,
+
+    /// Also write the layout in a format of `klo export`, given as `format:path` like `legacy:result.txt`. Can be given more than once.
+    #[clap(long, multiple_occurrences = true, multiple_values = false)]
+    pub export: Vec<String>,
+
+    /// Write the best cost of every run at the steps 1, 2, 5, 10, 20, 50, … and the last one to this csv file, to plot how the runs converge.
+    #[clap(long)]
+    pub convergence_csv: Option<String>,
+
+    /// The number of random evolution steps to take. With 0 (and --prerandomize 0) the runs only score the starting layout, without the controlled tail.
     #[clap(long, default_value = "10000")]
     pub steps: u128,
 
-    /// The number of random mutations to do before the evolution to get a random layout.
+    /// The number of random swaps of two alphabet characters to do on every --optimize-layers layer before the evolution to get a random layout.
     #[clap(long, default_value = "3000")]
     pub prerandomize: u128,
 
-    /// Should we always do the locally best step? (very slow and *not* optimal)
-    #[clap(long, parse(try_from_str), default_value = "false")]
-    pub controlled: bool,
-
-    /// Should we avoid giving information on the shell? (Windows users enable this, cause the default shell can’t take Unicode)
+    /// Seed the random layouts, mutations and annealing, so the same options find the same layouts again. Without it a random seed is chosen and logged.
     #[clap(long)]
-    pub quiet: bool,
+    pub seed: Option<u64>,
 
-    /// Should we give additional statistics for the final layout?
-    #[clap(long)]
-    pub verbose: bool,
+    /// Should we always do the locally best step? (very slow and *not* optimal) After the annealing, every step scores all swaps of two keys and does the best one; the run ends early once no swap improves it.
+    #[clap(long, parse(try_from_str), default_value = "false")]
+    pub controlled: bool,
 
-    /// Should we finalize the layout with as many controlled steps as needed, so a single keyswitch can’t improve it further?
+    /// Should we finalize the layout with as many controlled steps as needed, so a single keyswitch can’t improve it further? The swaps it needed and the cost they took off are logged for every run; many of them mean that --steps is too low.
     #[clap(long, parse(try_from_str), default_value = "true")]
     pub controlled_tail: bool,
 
@@ -50,30 +264,962 @@ pub struct KloOptions {
     #[clap(long, default_value = "0")]
     pub limit_ngrams: u128,
 
-    /// The layout to use as base for mutations. If you want a given starting layout, also set prerandomize = 0.
-    #[clap(long, default_value = "bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q")]
+    /// Characters that must not be moved, e.g. "xäöüß,.". Can be given more than once.
+    #[clap(long, multiple_occurrences = true, multiple_values = false)]
+    pub pin_keys: Vec<String>,
+
+    /// Comma separated position names whose characters must not be moved, e.g. "L_pinky_top,R_pinky_home". Can be given more than once.
+    #[clap(long, multiple_occurrences = true, multiple_values = false)]
+    pub pin_positions: Vec<String>,
+
+    /// Place a character at a named position before the evolution and never move it, e.g. "n=L_index_home". Can be given more than once.
+    #[clap(long, multiple_occurrences = true, multiple_values = false)]
+    pub fix: Vec<String>,
+
+    /// Keep characters on one hand, like `aeiou=right`. Can be repeated.
+    #[clap(long, multiple_occurrences = true, multiple_values = false)]
+    pub hand_lock: Vec<String>,
+
+    /// Path to a toml file with constraints (pin_keys, pin_positions, fix, hand_lock). Combined with the pins given on the command line.
+    #[clap(long)]
+    pub constraints: Option<String>,
+
+    /// Score this layout against the result at the end, like the layouts of `klo compare`. Can be repeated.
+    #[clap(long, multiple_occurrences = true, multiple_values = false)]
+    pub compare_with: Vec<String>,
+
+    /// The well-known layouts to compare the result with, like `neo,qwertz` or `none`. Paths to layout files can be mixed in.
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        multiple_values = false,
+        use_delimiter = true,
+        require_delimiter = true,
+        default_values = &["neo", "bone", "qwertz"]
+    )]
+    pub baselines: Vec<String>,
+
+    /// Save the progress of the evolution to this file regularly.
+    #[clap(long)]
+    pub checkpoint: Option<String>,
+
+    /// How often to rewrite the checkpoint: a duration like `90s`, `5m` or `2h`, or a number of runs like `50runs`. The checkpoint is always written after the last run.
+    #[clap(long, default_value = "5m")]
+    pub checkpoint_interval: CheckpointInterval,
+
+    /// Keep the previous checkpoint as <checkpoint>.bak when writing a new one.
+    #[clap(long)]
+    pub keep_checkpoint_backup: bool,
+
+    /// Continue the evolution saved in this checkpoint until all its runs are done. Without --checkpoint, the checkpoint keeps being written to this file. If the evolution is already complete, only the report is repeated.
+    #[clap(long)]
+    pub resume: Option<String>,
+
+    /// Fail instead of warning if the options differ from the ones the --resume checkpoint was written with.
+    #[clap(long)]
+    pub resume_strict: bool,
+
+    /// Validate the configuration and print what would run, without optimizing.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Explain the cost of the result: the ngrams that cost the most, and the most frequent bigrams and trigrams with hard movements and where their characters are.
+    #[clap(long)]
+    pub explain: bool,
+
+    /// After the run, start an interactive session for swapping keys by hand. Type `help` to see the commands.
+    #[clap(long)]
+    pub interactive: bool,
+
+    /// The layout to use as base for mutations. If you want a given starting layout, also set prerandomize = 0. Characters
+    /// outside of --alphabet, like `⇘` and punctuation, must be the ones the base layout has on their key.
+    #[clap(long, default_value = DEFAULT_STARTING_LAYOUT)]
     pub starting_layout: String,
 
-    /// Path to your ngrams.config
-    #[clap(long, default_value = "ngrams.config")]
-    pub ngrams_config: String,
+    /// The adjustments `derive_effective_options` made to the options above.
+    #[clap(skip)]
+    pub notes: Vec<OptionNote>,
+}
 
-    /// The alphabet to use
-    #[clap(long, default_value = "abcdefghijklmnopqrstuvwxyzäöüß")]
-    pub alphabet: String,
+/// The options of `klo analyze`.
+#[derive(Clap, Debug)]
+pub struct AnalyzeOptions {
+    /// The layout to analyze, in the same format as --starting-layout, or a file with a layout string or a blueprint json.
+    pub layout: String,
 
-    /// Path to your base_layout.json. If non is supplied the neo layout is used.
+    /// Show how this text is typed on the layout: the position, finger and hand of every character and whether it is an alternation, roll, same finger bigram or scissor, with a summary of every sentence.
     #[clap(long)]
-    pub base_layout: Option<String>,
+    pub simulate: Option<String>,
+}
+
+/// The options of `klo compare`.
+#[derive(Clap, Debug)]
+pub struct CompareOptions {
+    /// The layouts to compare, each in the same format as --starting-layout, or a file with a layout string or a blueprint json.
+    #[clap(required = true, min_values = 2)]
+    pub layouts: Vec<String>,
+
+    /// Score the layouts against each of these ngrams configs instead, given as `path:weight`, and print a matrix of their costs with the weighted average. The layouts can also be built-in baselines like neo. Give it after the layouts.
+    #[clap(long, min_values = 1)]
+    pub compare_corpora: Vec<String>,
+
+    /// The format of the --compare-corpora matrix.
+    #[clap(long, arg_enum, default_value = "text")]
+    pub output_format: MatrixFormat,
+}
+
+/// How `klo compare --compare-corpora` prints its matrix, see --output-format.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MatrixFormat {
+    /// An aligned table in the log.
+    Text,
+    /// Comma separated values on stdout.
+    Csv,
+    /// A markdown table on stdout.
+    Markdown,
+}
+
+/// The path and weight of a --compare-corpora entry like `german.config:0.5`.
+pub fn weighted_corpus(arg: &str) -> Option<(&str, f64)> {
+    let (path, weight) = arg.rsplit_once(':')?;
+    let weight: f64 = weight.parse().ok()?;
+    if path.is_empty() || !weight.is_finite() || weight <= 0.0 {
+        return None;
+    }
+    Some((path, weight))
+}
+
+/// The options of `klo ngrams`.
+#[derive(Clap, Debug)]
+pub struct NgramsOptions {
+    /// The ngram tool to run.
+    #[clap(subcommand)]
+    pub command: NgramsCommand,
+}
+
+/// The subcommands of `klo ngrams`.
+#[derive(Clap, Debug)]
+pub enum NgramsCommand {
+    /// Count the ngrams of a text file and write them as pregenerated files.
+    #[cfg(feature = "text-corpus")]
+    Generate(NgramsGenerateOptions),
+    /// Print the most frequent ngrams of the configured sources.
+    Top(NgramsTopOptions),
+    /// Mix the configured sources and write them as pregenerated files.
+    Export(NgramsExportOptions),
+}
+
+/// The options of `klo ngrams generate`.
+#[cfg(feature = "text-corpus")]
+#[derive(Clap, Debug)]
+pub struct NgramsGenerateOptions {
+    /// The text file to count ngrams in.
+    pub text: String,
+
+    /// The directory to write 1gramme.txt, 2gramme.txt and 3gramme.txt to.
+    #[clap(long, default_value = ".")]
+    pub output_dir: String,
+}
+
+/// The options of `klo ngrams top`.
+#[derive(Clap, Debug)]
+pub struct NgramsTopOptions {
+    /// The number of ngrams to print per kind.
+    #[clap(long, default_value = "20")]
+    pub count: usize,
+}
+
+/// The options of `klo ngrams export`.
+#[derive(Clap, Debug)]
+pub struct NgramsExportOptions {
+    /// The directory to write 1gramme.txt, 2gramme.txt and 3gramme.txt to.
+    #[clap(long, default_value = ".")]
+    pub output_dir: String,
+
+    /// The total number of keystrokes the exported counts should add up to.
+    #[clap(long, default_value = "100000000")]
+    pub keystrokes: f64,
+}
+
+/// The options of `klo export`.
+#[cfg(feature = "exporters")]
+#[derive(Clap, Debug)]
+pub struct ExportOptions {
+    /// The format to write the layout in.
+    #[clap(arg_enum)]
+    pub format: ExportFormat,
+
+    /// The layout to export, in the same format as --starting-layout.
+    #[clap(long, default_value = DEFAULT_STARTING_LAYOUT)]
+    pub layout: String,
+
+    /// Where to write the export. Writes to stdout if omitted.
+    #[clap(short = 'o', long)]
+    pub output: Option<String>,
+
+    /// The width of the png heatmap in pixels. Its height follows from the geometry.
+    #[clap(long, default_value = "800")]
+    pub png_width: u32,
+}
+
+/// The options of `klo completions`.
+#[derive(Clap, Debug)]
+pub struct CompletionsOptions {
+    /// The shell to generate the completions for.
+    #[clap(arg_enum)]
+    pub shell: Shell,
+}
+
+/// The options of `klo list`.
+#[derive(Clap, Debug)]
+pub struct ListOptions {
+    /// What to list.
+    #[clap(arg_enum)]
+    pub topic: ListTopic,
+}
+
+/// The options of `klo config`.
+#[derive(Clap, Debug)]
+pub struct ConfigOptions {
+    /// The configuration tool to run.
+    #[clap(subcommand)]
+    pub command: ConfigCommand,
+}
+
+/// The subcommands of `klo config`.
+#[derive(Clap, Debug)]
+pub enum ConfigCommand {
+    /// Write a commented klo.toml, an example ngrams.config and a weights.toml to get started.
+    Init(ConfigInitOptions),
+}
+
+/// The options of `klo config init`.
+#[derive(Clap, Debug)]
+pub struct ConfigInitOptions {
+    /// The directory to write the files to. Run klo from there, the paths in the files are relative to it.
+    #[clap(long, default_value = ".")]
+    pub dir: String,
+
+    /// Overwrite files that already exist.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Also write the built-in base layout to base_layout.json for editing, and use it in klo.toml.
+    #[clap(long)]
+    pub with_base_layout: bool,
+
+    /// Also write the built-in geometry to geometry.toml for editing, and use it in klo.toml.
+    #[clap(long)]
+    pub with_geometry: bool,
+}
+
+/// What `klo list` prints.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ListTopic {
+    /// The base layouts that can be given to --base-layout by name.
+    BaseLayouts,
+    /// The position names of the geometry, arranged like the keyboard.
+    Positions,
+    /// The fingers and the positions they type.
+    Fingers,
+    /// The exit codes and their meaning.
+    ExitCodes,
+}
+
+/// The shells `klo completions` writes scripts for.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    /// Bash
+    Bash,
+    /// Zsh
+    Zsh,
+    /// Fish
+    Fish,
+    /// PowerShell
+    Powershell,
+}
+
+/// The levels --log-level accepts, from the fewest to the most messages.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogLevel {
+    /// Only errors.
+    Error,
+    /// Errors and warnings.
+    Warn,
+    /// What the console shows by default.
+    Info,
+    /// Details about every phase.
+    Debug,
+    /// Everything, like --verbose on the console.
+    Trace,
+}
+
+impl LogLevel {
+    /// The level as the `log` crate knows it.
+    pub fn to_level_filter(self) -> log::LevelFilter {
+        match self {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// How `klo evolve` writes its result, see --output-format.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text: the layout string and its cost.
+    Text,
+    /// A single json document, see `report::JsonResult` for the schema.
+    Json,
+    /// A markdown report to paste into forum posts and issues.
+    Markdown,
+    /// A single html file with the keyboard drawn as a heatmap, which needs nothing else to be viewed.
+    Html,
+}
+
+/// How `klo evolve` reports its progress, see --progress-format.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ProgressFormat {
+    /// Progress is part of the log.
+    Text,
+    /// One json object per line on stdout.
+    Jsonl,
+}
+
+/// The formats of `klo export`.
+#[cfg(feature = "exporters")]
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// The full base layout json with all layers.
+    Blueprint,
+    /// The three letter rows as accepted by --starting-layout.
+    LayoutString,
+    /// The heatmap of the keystrokes on layer 0 as an svg image.
+    Svg,
+    /// The heatmap of the keystrokes on layer 0 as a png image, with a legend.
+    Png,
+    /// The layout and its statistics in the text format of the Python evolve-keyboard-layout.
+    Legacy,
+    /// The self-contained html report of the layout. With --export, the report of the evolution, like --output-format html.
+    Html,
+}
+
+#[cfg(feature = "exporters")]
+impl ExportFormat {
+    /// Whether the export shows keystrokes, so the ngrams must be loaded.
+    pub fn uses_ngrams(self) -> bool {
+        matches!(
+            self,
+            ExportFormat::Svg | ExportFormat::Png | ExportFormat::Legacy | ExportFormat::Html
+        )
+    }
+}
+
+/// The format and path of an --export like `legacy:result.txt`.
+#[cfg(feature = "exporters")]
+pub fn export_arg(arg: &str) -> Option<(ExportFormat, &str)> {
+    let (format, path) = arg.split_once(':')?;
+    let format = <ExportFormat as ArgEnum>::from_str(format, true).ok()?;
+    if path.is_empty() {
+        return None;
+    }
+    Some((format, path))
+}
+
+/// The --png-width of `klo export`, which the png files of --export use.
+#[cfg(feature = "exporters")]
+pub const DEFAULT_PNG_WIDTH: u32 = 800;
+
+/// Below this width the characters of a png heatmap don't fit on its keys.
+#[cfg(feature = "exporters")]
+const MIN_PNG_WIDTH: u32 = 100;
+
+/// More prerandomization switches than this can't make a layout any more random.
+const MAX_PRERANDOMIZE: u128 = 1_000_000;
+
+/// A problem with the given options, found by `KloOptions::validate`.
+#[derive(Debug, PartialEq)]
+#[allow(missing_docs)] // The fields are named after the parts of the message.
+pub enum OptionsError {
+    /// A number or list outside of what the option accepts.
+    OutOfRange {
+        option: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    /// --alphabet expanded to no characters.
+    EmptyAlphabet,
+    /// --alphabet has a character twice.
+    DuplicateAlphabetCharacter(char),
+    /// Two options that can't be used together.
+    Conflict(&'static str, &'static str),
+    /// An option that points to a file that doesn't exist.
+    MissingFile { option: &'static str, path: String },
+    /// A --baselines entry that is neither built in nor a file.
+    UnknownBaseline(String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::OutOfRange {
+                option,
+                value,
+                expected,
+            } => write!(f, "--{} is {}, but it must be {}", option, value, expected),
+            OptionsError::EmptyAlphabet => write!(f, "--alphabet must not be empty"),
+            OptionsError::DuplicateAlphabetCharacter(char) => {
+                write!(f, "--alphabet contains {:?} more than once", char)
+            }
+            OptionsError::Conflict(a, b) => write!(f, "--{} can't be used with --{}", a, b),
+            OptionsError::MissingFile { option, path } => {
+                write!(f, "--{} points to {}, which doesn't exist", option, path)?;
+                if *option == "ngrams-config" {
+                    write!(f, " (`klo config init` writes an example)")?;
+                }
+                Ok(())
+            }
+            OptionsError::UnknownBaseline(name) => {
+                let names: Vec<&str> = BUILTIN_BASELINES
+                    .iter()
+                    .map(|baseline| baseline.name)
+                    .collect();
+                write!(
+                    f,
+                    "--baselines has {}, which is neither a file nor one of {} or none",
+                    name,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// An option that klo changed from what was asked for, and why.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OptionNote {
+    /// The name of the option, like `anneal_step`.
+    pub field: &'static str,
+    /// The value that was given.
+    pub requested: String,
+    /// The value klo uses instead.
+    pub effective: String,
+    /// Why the value was changed.
+    pub reason: String,
+}
+
+impl fmt::Display for OptionNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Using --{} {} instead of {}: {}",
+            self.field, self.effective, self.requested, self.reason
+        )
+    }
+}
+
+/// The layout --starting-layout and the layout of `klo export` default to.
+pub const DEFAULT_STARTING_LAYOUT: &str = "bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q";
+
+impl KloOptions {
+    /// Parse the command line. Invocations without a subcommand are treated as
+    /// `evolve` for backwards compatibility, which is signalled by the returned bool.
+    /// The matches are returned as well to tell explicitly given options from defaults.
+    pub fn parse_with_legacy_fallback() -> (Self, ArgMatches, bool) {
+        let args: Vec<OsString> = std::env::args_os().collect();
+
+        let first_attempt = match Self::into_app().try_get_matches_from(&args) {
+            Ok(matches) if matches.subcommand_name().is_some() => {
+                return (Self::from_arg_matches(&matches).unwrap(), matches, false)
+            }
+            attempt => attempt,
+        };
+
+        let mut legacy_args = args;
+        legacy_args.insert(1, "evolve".into());
+        match Self::into_app().try_get_matches_from(&legacy_args) {
+            Ok(matches) => (Self::from_arg_matches(&matches).unwrap(), matches, true),
+            Err(legacy_error) => match first_attempt {
+                Err(error) => error.exit(),
+                Ok(_) => legacy_error.exit(),
+            },
+        }
+    }
 }
 
 impl KloOptions {
-    pub fn post_parse_checks(&mut self) {
+    /// The --layer-alphabet of a layer, or None if every character of the layer may move. Layer 0 uses --alphabet.
+    pub fn layer_alphabet(&self, layer: usize) -> Option<String> {
+        if layer == 0 {
+            return Some(self.alphabet.clone());
+        }
+        self.layer_alphabet.iter().find_map(|layer_alphabet| {
+            let (number, chars) = layer_alphabet.split_once('=')?;
+            (number.trim().parse() == Ok(layer)).then(|| chars.to_string())
+        })
+    }
+
+    /// Check the options for problems that would otherwise only surface later, reporting all of them at once.
+    pub fn validate(&self) -> Result<(), Vec<OptionsError>> {
+        let mut errors = vec![];
+
+        if self.alphabet.is_empty() {
+            errors.push(OptionsError::EmptyAlphabet);
+        }
+        let mut seen = HashSet::new();
+        for char in self.alphabet.chars() {
+            if !seen.insert(char) {
+                errors.push(OptionsError::DuplicateAlphabetCharacter(char));
+            }
+        }
+
+        if self.quiet && self.verbose {
+            errors.push(OptionsError::Conflict("quiet", "verbose"));
+        }
+
+        if self.optimize_layers.is_empty() {
+            errors.push(OptionsError::OutOfRange {
+                option: "optimize-layers",
+                value: "empty".to_string(),
+                expected: "at least one layer",
+            });
+        }
+        for layer_alphabet in &self.layer_alphabet {
+            match layer_alphabet.split_once('=').map(|(layer, _)| layer.trim().parse::<usize>()) {
+                Some(Ok(layer)) if self.optimize_layers.contains(&layer) && layer != 0 => {}
+                _ => errors.push(OptionsError::OutOfRange {
+                    option: "layer-alphabet",
+                    value: layer_alphabet.clone(),
+                    expected: "`layer=characters` for a layer other than 0 selected with --optimize-layers",
+                }),
+            }
+        }
+
+        if let Some(target) = self.hand_target {
+            if !(0.0..=1.0).contains(&target) {
+                errors.push(OptionsError::OutOfRange {
+                    option: "hand-target",
+                    value: target.to_string(),
+                    expected: "between 0 and 1",
+                });
+            }
+        }
+
+        for (option, cost) in [
+            ("missing-char-cost", self.missing_char_cost),
+            ("off-table-cost", Some(self.off_table_cost)),
+        ] {
+            if let Some(cost) = cost.filter(|cost| !(*cost >= 0.0 && cost.is_finite())) {
+                errors.push(OptionsError::OutOfRange {
+                    option,
+                    value: cost.to_string(),
+                    expected: "0 or more",
+                });
+            }
+        }
+        for (option, threshold) in [
+            ("missing-char-threshold", self.missing_char_threshold),
+            ("high-layer-threshold", self.high_layer_threshold),
+        ] {
+            if !(0.0..=1.0).contains(&threshold) {
+                errors.push(OptionsError::OutOfRange {
+                    option,
+                    value: threshold.to_string(),
+                    expected: "between 0 and 1",
+                });
+            }
+        }
+
+        if self.log_rotate_mb == Some(0) {
+            errors.push(OptionsError::OutOfRange {
+                option: "log-rotate-mb",
+                value: "0".to_string(),
+                expected: "at least 1",
+            });
+        }
+
+        if let Some(path) = &self.key_costs {
+            if !Path::new(path).is_file() {
+                errors.push(OptionsError::MissingFile {
+                    option: "key-costs",
+                    path: path.clone(),
+                });
+            }
+        }
+        if let Some(path) = &self.base_layout {
+            if builtin_base_layout(path).is_none() && !Path::new(path).is_file() {
+                errors.push(OptionsError::MissingFile {
+                    option: "base-layout",
+                    path: path.clone(),
+                });
+            }
+        }
+        if let Some(path) = &self.geometry {
+            if Geometry::builtin(path).is_none() && !Path::new(path).is_file() {
+                errors.push(OptionsError::MissingFile {
+                    option: "geometry",
+                    path: path.clone(),
+                });
+            }
+        }
+        let uses_ngrams = match &self.command {
+            Some(Command::Evolve(evolve)) => !evolve.dry_run,
+            Some(Command::Analyze(_)) => true,
+            Some(Command::Compare(compare)) => compare.compare_corpora.is_empty(),
+            #[cfg(feature = "text-corpus")]
+            Some(Command::Ngrams(ngrams)) => !matches!(ngrams.command, NgramsCommand::Generate(_)),
+            #[cfg(not(feature = "text-corpus"))]
+            Some(Command::Ngrams(_)) => true,
+            #[cfg(feature = "exporters")]
+            Some(Command::Export(export)) => export.format.uses_ngrams(),
+            Some(Command::Completions(_))
+            | Some(Command::List(_))
+            | Some(Command::Config(_))
+            | None => false,
+        };
+        if uses_ngrams && self.corpus.is_empty() && !Path::new(&self.ngrams_config).is_file() {
+            errors.push(OptionsError::MissingFile {
+                option: "ngrams-config",
+                path: self.ngrams_config.clone(),
+            });
+        }
+
+        if let Some(Command::Evolve(evolve)) = &self.command {
+            errors.extend(evolve.validate());
+        }
+        if let Some(Command::Compare(compare)) = &self.command {
+            for arg in &compare.compare_corpora {
+                match weighted_corpus(arg) {
+                    Some((path, _)) if !Path::new(path).is_file() => {
+                        errors.push(OptionsError::MissingFile {
+                            option: "compare-corpora",
+                            path: path.to_string(),
+                        })
+                    }
+                    Some(_) => {}
+                    None => errors.push(OptionsError::OutOfRange {
+                        option: "compare-corpora",
+                        value: arg.clone(),
+                        expected: "an ngrams config and a positive weight like german.config:0.5",
+                    }),
+                }
+            }
+        }
+        #[cfg(feature = "exporters")]
+        if let Some(Command::Export(export)) = &self.command {
+            if export.format == ExportFormat::Png && export.png_width < MIN_PNG_WIDTH {
+                errors.push(OptionsError::OutOfRange {
+                    option: "png-width",
+                    value: export.png_width.to_string(),
+                    expected: "at least 100",
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl EvolveOptions {
+    fn validate(&self) -> Vec<OptionsError> {
+        let mut errors = vec![];
+
+        if self.num_layouts == 0 {
+            errors.push(OptionsError::OutOfRange {
+                option: "num-layouts",
+                value: self.num_layouts.to_string(),
+                expected: "at least 1",
+            });
+        }
+        if self.top_n == 0 {
+            errors.push(OptionsError::OutOfRange {
+                option: "top-n",
+                value: self.top_n.to_string(),
+                expected: "at least 1",
+            });
+        }
+        for arg in &self.export {
+            #[cfg(feature = "exporters")]
+            let (valid, expected) = (
+                export_arg(arg).is_some(),
+                "a format of klo export and a path like legacy:result.txt",
+            );
+            #[cfg(not(feature = "exporters"))]
+            let (valid, expected) = (false, "a klo built with the exporters feature");
+            if !valid {
+                errors.push(OptionsError::OutOfRange {
+                    option: "export",
+                    value: arg.clone(),
+                    expected,
+                });
+            }
+        }
+        if self.prerandomize > MAX_PRERANDOMIZE {
+            errors.push(OptionsError::OutOfRange {
+                option: "prerandomize",
+                value: self.prerandomize.to_string(),
+                expected: "at most 1000000",
+            });
+        }
+        if self.anneal > 0 && self.anneal_step == 0 {
+            errors.push(OptionsError::OutOfRange {
+                option: "anneal-step",
+                value: self.anneal_step.to_string(),
+                expected: "at least 1 when annealing",
+            });
+        }
+        if self.baselines.len() > 1 && self.baselines.iter().any(|name| name == "none") {
+            errors.push(OptionsError::OutOfRange {
+                option: "baselines",
+                value: self.baselines.join(","),
+                expected: "either none or a list of layouts",
+            });
+        }
+        for baseline in &self.baselines {
+            let known = baseline == "none"
+                || builtin_baseline(baseline).is_some()
+                || Path::new(baseline).is_file();
+            if !known {
+                errors.push(OptionsError::UnknownBaseline(baseline.clone()));
+            }
+        }
+        if let Some(path) = &self.resume {
+            if !Path::new(path).is_file() {
+                errors.push(OptionsError::MissingFile {
+                    option: "resume",
+                    path: path.clone(),
+                });
+            }
+        }
+        if self.json_stdout {
+            match self.output_format {
+                OutputFormat::Json => {}
+                OutputFormat::Text => {
+                    errors.push(OptionsError::Conflict("json-stdout", "output-format text"))
+                }
+                OutputFormat::Markdown => errors.push(OptionsError::Conflict(
+                    "json-stdout",
+                    "output-format markdown",
+                )),
+                OutputFormat::Html => {
+                    errors.push(OptionsError::Conflict("json-stdout", "output-format html"))
+                }
+            }
+        }
+        if self.interactive && self.json_stdout {
+            errors.push(OptionsError::Conflict("interactive", "json-stdout"));
+        }
+        if self.progress_format == ProgressFormat::Jsonl {
+            if self.json_stdout {
+                errors.push(OptionsError::Conflict(
+                    "progress-format jsonl",
+                    "json-stdout",
+                ));
+            }
+            if self.interactive {
+                errors.push(OptionsError::Conflict(
+                    "progress-format jsonl",
+                    "interactive",
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Make every adjustment to the options, log it and keep it in `notes` for the result.
+    pub fn derive_effective_options(&mut self) {
+        let notes = self.post_parse_checks();
+        for note in &notes {
+            info!("{}", note);
+        }
+        self.notes.extend(notes);
+    }
+
+    /// Adjust options that don't fit together and return a note for every change.
+    pub fn post_parse_checks(&mut self) -> Vec<OptionNote> {
+        let mut notes = vec![];
         // ensure that at most half the time is spent annealing
-        if self.anneal * self.anneal_step > self.steps {
+        if self.steps > 0 && self.anneal * self.anneal_step > self.steps {
             let half_steps = 0.5 * self.steps as f64;
             let calculated_anneals = half_steps / (1 + self.anneal) as f64;
-            self.anneal_step = max(1, calculated_anneals as u128);
+            let anneal_step = max(1, calculated_anneals as u128);
+            notes.push(OptionNote {
+                field: "anneal-step",
+                requested: self.anneal_step.to_string(),
+                effective: anneal_step.to_string(),
+                reason: format!(
+                    "at most half of the {} steps may be spent annealing",
+                    self.steps
+                ),
+            });
+            self.anneal_step = anneal_step;
+        }
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `klo <args>` with a corpus, so that the missing ngrams.config isn't a problem of its own.
+    fn validate(args: &[&str]) -> Result<(), Vec<OptionsError>> {
+        let mut all = vec!["klo", "--corpus", "corpus.txt"];
+        all.extend(args);
+        KloOptions::try_parse_from(all).unwrap().validate()
+    }
+
+    fn out_of_range(option: &'static str, value: &str, expected: &'static str) -> OptionsError {
+        OptionsError::OutOfRange {
+            option,
+            value: value.to_string(),
+            expected,
         }
     }
+
+    #[test]
+    fn the_defaults_are_valid() {
+        assert_eq!(validate(&["evolve"]), Ok(()));
+    }
+
+    #[test]
+    fn every_rejected_configuration_has_its_error() {
+        let cases = [
+            (
+                vec!["--alphabet", "", "evolve"],
+                OptionsError::EmptyAlphabet,
+            ),
+            (
+                vec!["--alphabet", "abca", "evolve"],
+                OptionsError::DuplicateAlphabetCharacter('a'),
+            ),
+            (
+                vec!["evolve", "--num-layouts", "0"],
+                out_of_range("num-layouts", "0", "at least 1"),
+            ),
+            (
+                vec!["evolve", "--top-n", "0"],
+                out_of_range("top-n", "0", "at least 1"),
+            ),
+            (
+                vec!["evolve", "--prerandomize", "1000001"],
+                out_of_range("prerandomize", "1000001", "at most 1000000"),
+            ),
+            (
+                vec!["evolve", "--anneal-step", "0"],
+                out_of_range("anneal-step", "0", "at least 1 when annealing"),
+            ),
+            (
+                vec!["--hand-target", "1.5", "evolve"],
+                out_of_range("hand-target", "1.5", "between 0 and 1"),
+            ),
+            (
+                vec!["--off-table-cost=-1", "evolve"],
+                out_of_range("off-table-cost", "-1", "0 or more"),
+            ),
+            (
+                vec!["--high-layer-threshold", "2", "evolve"],
+                out_of_range("high-layer-threshold", "2", "between 0 and 1"),
+            ),
+            (
+                vec!["evolve", "--baselines", "neo,none"],
+                out_of_range("baselines", "neo,none", "either none or a list of layouts"),
+            ),
+            (
+                vec!["evolve", "--baselines", "dvorak-ish"],
+                OptionsError::UnknownBaseline("dvorak-ish".to_string()),
+            ),
+            (
+                vec!["evolve", "--json-stdout"],
+                OptionsError::Conflict("json-stdout", "output-format text"),
+            ),
+            (
+                vec!["--key-costs", "no-such-costs.json", "evolve"],
+                OptionsError::MissingFile {
+                    option: "key-costs",
+                    path: "no-such-costs.json".to_string(),
+                },
+            ),
+            (
+                vec!["evolve", "--resume", "no-such-checkpoint.json"],
+                OptionsError::MissingFile {
+                    option: "resume",
+                    path: "no-such-checkpoint.json".to_string(),
+                },
+            ),
+        ];
+        for (args, error) in cases {
+            assert_eq!(validate(&args), Err(vec![error]), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let errors = validate(&[
+            "--alphabet",
+            "",
+            "evolve",
+            "--num-layouts",
+            "0",
+            "--top-n",
+            "0",
+        ])
+        .unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+    }
+
+    #[test]
+    fn the_missing_ngrams_config_is_reported_without_a_corpus() {
+        let options =
+            KloOptions::try_parse_from(["klo", "--ngrams-config", "no-such.config", "analyze", ""])
+                .unwrap();
+        assert_eq!(
+            options.validate(),
+            Err(vec![OptionsError::MissingFile {
+                option: "ngrams-config",
+                path: "no-such.config".to_string(),
+            }])
+        );
+    }
+
+    /// The notes of `post_parse_checks` for `klo evolve <args>`, and the anneal step it settles on.
+    fn notes(args: &[&str]) -> (Vec<OptionNote>, u128) {
+        let mut all = vec!["klo", "evolve"];
+        all.extend(args);
+        let mut options = KloOptions::try_parse_from(all).unwrap();
+        match options.command.as_mut() {
+            Some(Command::Evolve(evolve)) => (evolve.post_parse_checks(), evolve.anneal_step),
+            _ => unreachable!("the arguments select evolve"),
+        }
+    }
+
+    #[test]
+    fn rebalancing_the_annealing_leaves_a_note() {
+        let (notes, anneal_step) = notes(&["--steps", "100", "--anneal", "4"]);
+        assert_eq!(anneal_step, 10);
+        assert_eq!(
+            notes,
+            vec![OptionNote {
+                field: "anneal-step",
+                requested: "1000".to_string(),
+                effective: "10".to_string(),
+                reason: "at most half of the 100 steps may be spent annealing".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn annealing_that_fits_into_half_of_the_steps_leaves_no_note() {
+        let args = ["--steps", "10000", "--anneal", "4", "--anneal-step", "1000"];
+        assert_eq!(notes(&args), (vec![], 1000));
+        assert_eq!(notes(&["--steps", "0"]), (vec![], 1000));
+    }
 }
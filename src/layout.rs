@@ -1,129 +1,1349 @@
-use std::{
-    collections::HashMap,
-    fmt::{self, Debug},
-    fs::read_to_string,
-};
+use std::{convert::TryFrom, fmt, fs::read_to_string, path::Path, sync::Arc};
 
 use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "cli")]
 use crate::klo_options::KloOptions;
-use rand::{seq::SliceRandom, thread_rng};
+use crate::{
+    error::LayoutError, geometry::KeyPos, interner::Interner, key_costs::KeyCosts, profile,
+};
+use rand::{seq::SliceRandom, RngCore};
 
 type Layer = String;
 type Key = Vec<Layer>;
 type Row = Vec<Key>;
-pub type Layout = Vec<Row>;
+/// A keyboard: rows of keys, each with a character (or key name) per layer.
+pub type Blueprint = Vec<Row>;
+
+/// The most rows, keys per row and layers per key a blueprint can have, so every slot fits a `Pos`.
+pub const MAX_BLUEPRINT_DIMENSION: usize = u8::MAX as usize + 1;
+
+/// A position on the keyboard: the row and column of a key and one of its layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Pos {
+    /// The row of the blueprint.
+    pub row: u8,
+    /// The key within the row.
+    pub col: u8,
+    /// The layer of the key.
+    pub layer: u8,
+}
+
+impl Pos {
+    /**
+    The position of a slot of a blueprint. Blueprints are checked against
+    `MAX_BLUEPRINT_DIMENSION` when they are read, so every slot fits.
+    */
+    pub fn new(row: usize, col: usize, layer: usize) -> Self {
+        let fit = |index: usize| {
+            u8::try_from(index).expect("blueprints are checked against MAX_BLUEPRINT_DIMENSION")
+        };
+        Pos {
+            row: fit(row),
+            col: fit(col),
+            layer: fit(layer),
+        }
+    }
+
+    /// The same key on another layer.
+    pub fn with_layer(self, layer: u8) -> Self {
+        Pos { layer, ..self }
+    }
+
+    /// Whether both positions are on the same key, on any layer.
+    pub fn same_key(&self, other: &Pos) -> bool {
+        self.key() == other.key()
+    }
+
+    /// How many rows apart the keys of both positions are.
+    pub fn row_distance(&self, other: &Pos) -> u8 {
+        self.row.abs_diff(other.row)
+    }
+
+    /// The physical key, as the geometry addresses it.
+    pub fn key(&self) -> KeyPos {
+        (usize::from(self.row), usize::from(self.col))
+    }
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {} key {} layer {}", self.row, self.col, self.layer)
+    }
+}
+
+/// A base layout that is compiled into klo and can be selected by name.
+pub struct BuiltinBaseLayout {
+    /// The name to give to --base-layout.
+    pub name: &'static str,
+    /// What `klo list base-layouts` shows.
+    pub description: &'static str,
+    /// The blueprint json.
+    pub json: &'static str,
+}
+
+/// Every built-in base layout. The first one is the default.
+pub const BUILTIN_BASE_LAYOUTS: &[BuiltinBaseLayout] = &[BuiltinBaseLayout {
+    name: "neo",
+    description: "Neo 2 with its six layers on a staggered ISO keyboard",
+    json: include_str!("../default_base_layout.json"),
+}];
+
+/// A well-known layout that results can be compared with, see --baselines.
+pub struct BuiltinBaseline {
+    /// The name to give to --baselines.
+    pub name: &'static str,
+    /// The layout in the format of --starting-layout, with the same characters as its default.
+    pub layout_string: &'static str,
+}
+
+/// Every built-in baseline.
+pub const BUILTIN_BASELINES: &[BuiltinBaseline] = &[
+    BuiltinBaseline {
+        name: "neo",
+        layout_string: "xvlcw khgfqyß\nuiaeo snrtd⇘\nüöäpz bm,.j",
+    },
+    BuiltinBaseline {
+        name: "bone",
+        layout_string: "jduax phlmwqß\nctieo bnrsg⇘\nfvüäö yz,.k",
+    },
+    // `⇘` has to stay on its Neo key, so `ä` takes the place of the `j` of Neo.
+    BuiltinBaseline {
+        name: "qwertz",
+        layout_string: "qwert zuiopüß\nasdfg hjklö⇘\nyxcvb nm,.ä",
+    },
+];
+
+/// The built-in baseline of that name.
+pub fn builtin_baseline(name: &str) -> Option<&'static BuiltinBaseline> {
+    BUILTIN_BASELINES
+        .iter()
+        .find(|baseline| baseline.name == name)
+}
 
-pub trait LayoutT {
-    fn from_args(options: &KloOptions) -> Self;
+/// The built-in base layout of that name.
+pub fn builtin_base_layout(name: &str) -> Option<&'static BuiltinBaseLayout> {
+    BUILTIN_BASE_LAYOUTS
+        .iter()
+        .find(|builtin| builtin.name == name)
+}
+
+/**
+The first key and the number of keys of the rows of a layout string on a
+blueprint with room for the Neo letter rows, starting from row 1. The bottom
+row starts at key 2, right of the extra ISO key `⇚`.
+*/
+const LAYOUT_STRING_ROWS: [(usize, usize); 3] = [(1, 12), (1, 11), (2, 10)];
+
+/// Reading, changing and printing blueprints.
+pub trait BlueprintHelpers {
+    /// The base layout of --base-layout with `layout` (a layout string) merged into layer 0.
+    #[cfg(feature = "cli")]
+    fn from_args(options: &KloOptions, layout: &str) -> Result<Self, LayoutError>
+    where
+        Self: Sized;
+    /// A blueprint json, like the contents of a base_layout.json.
+    fn from_json(json: &str) -> Result<Self, LayoutError>
+    where
+        Self: Sized;
+    /// This blueprint with `layout` (a layout string) merged into layer 0, if `check_layout_string` finds no problem.
+    fn with_layout_string(self, layout: &str, alphabet: &str) -> Result<Self, LayoutError>
+    where
+        Self: Sized;
+    /// A layout given as a layout string, a file with a layout string or a blueprint json file.
+    #[cfg(feature = "cli")]
+    fn from_layout_arg(options: &KloOptions, arg: &str) -> Result<Self, LayoutError>
+    where
+        Self: Sized;
+    /// Put `new_key` on a layer of a key.
     fn set_key(&mut self, row: usize, key: usize, layer: usize, new_key: String);
-    fn get_base_layout(path: &Option<String>) -> Self;
+    /// Exchange the characters of two keys on one layer.
+    fn swap_keys(&mut self, first: KeyPos, second: KeyPos, layer: usize);
+    /// The base layout at `path`, a built-in one of that name or the default one.
+    fn try_get_base_layout(path: &Option<String>) -> Result<Self, LayoutError>
+    where
+        Self: Sized;
+    /// Like `try_get_base_layout`, without timing it for --profile.
+    fn read_base_layout(path: &Option<String>) -> Result<Self, LayoutError>
+    where
+        Self: Sized;
+    /**
+    Find every character of a layout string that has no layer 0 slot to be
+    merged into, and every character outside of the alphabet that isn't the
+    character the base layout already has on its key. Those only anchor the
+    layout string to the base layout, so special keys like `⇘` and punctuation
+    stay on the keys whose costs were made for them.
+    */
+    fn check_layout_string(&self, layout: &str, alphabet: &str) -> Vec<String>;
+    /// Put the characters of a layout string on layer 0, row by row.
     fn merge_layout_string(&mut self, layout: &str);
+    /// Layer 0 in the format of --starting-layout.
+    fn layout_string(&self) -> String;
+    /// The layout string of any layer. Keys without a printable character on that layer are spaces.
+    fn layer_string(&self, layer: usize) -> String;
+    /// The number of layers of the key with the most layers.
+    fn layer_count(&self) -> usize;
+    /// Every layer selected with --optimize-layers must exist in the blueprint.
+    fn check_layers(&self, layers: &[usize]) -> Result<(), String>;
+    /**
+    Randomly rearrange the characters of the alphabet among their keys on one
+    layer. Without an alphabet every single printable character of the layer
+    moves, but named keys like `Entf` and control characters stay.
+    */
+    fn shuffle_layer(&mut self, layer: usize, alphabet: Option<&str>, rng: &mut dyn RngCore);
+    /// Log every layer 0 row at debug level.
     fn debug_print(&self);
-    fn get_randomized_variant(&self, alphabet: String, switches: u128) -> Self;
+    /// A copy with `switches` random swaps of layer 0 characters of the alphabet.
+    fn get_randomized_variant(
+        &self,
+        alphabet: String,
+        switches: u128,
+        rng: &mut dyn RngCore,
+    ) -> Self;
+    /// Do `switches` swaps of two random characters of the alphabet on one layer, chosen like in `shuffle_layer`.
+    fn swap_random_keys(
+        &mut self,
+        layer: usize,
+        alphabet: Option<&str>,
+        switches: u128,
+        rng: &mut dyn RngCore,
+    );
+    /// The keys whose character on `layer` moves with `shuffle_layer` and `swap_random_keys`.
+    fn movable_keys(&self, layer: usize, alphabet: Option<&str>) -> Vec<(usize, usize)>;
+    /**
+    Swap the layer 0 characters `new_key` and `old_key`, so `new_key` is on
+    the key of `old_key` and no character gets lost or doubled. Warns and
+    changes nothing if either of them isn't on layer 0.
+    */
     fn set_new_key(&mut self, new_key: String, old_key: String);
-    fn get_key_pos(&mut self, needle: String) -> (usize, usize);
+    /// The row and key of a layer 0 character, none if it isn't on the layout.
+    fn get_key_pos(&self, needle: &str) -> Option<(usize, usize)>;
 }
 
-impl LayoutT for Layout {
-    fn from_args(options: &KloOptions) -> Self {
-        let mut layout = Self::get_base_layout(&options.base_layout);
-        layout.debug_print();
-        layout.merge_layout_string(options.starting_layout.as_ref());
-        layout
+impl BlueprintHelpers for Blueprint {
+    #[cfg(feature = "cli")]
+    fn from_args(options: &KloOptions, layout: &str) -> Result<Self, LayoutError> {
+        let blueprint = Self::try_get_base_layout(&options.base_layout)?;
+        blueprint.debug_print();
+        blueprint.with_layout_string(layout, &options.alphabet)
+    }
+
+    fn from_json(json: &str) -> Result<Self, LayoutError> {
+        parse_blueprint(json).map_err(|e| LayoutError(format!("Invalid blueprint: {}", e)))
+    }
+
+    fn with_layout_string(mut self, layout: &str, alphabet: &str) -> Result<Self, LayoutError> {
+        let problems = self.check_layout_string(layout, alphabet);
+        if !problems.is_empty() {
+            return Err(LayoutError(problems.join("\n")));
+        }
+        self.merge_layout_string(layout);
+        Ok(self)
+    }
+
+    #[cfg(feature = "cli")]
+    fn from_layout_arg(options: &KloOptions, arg: &str) -> Result<Self, LayoutError> {
+        if !Path::new(arg).is_file() {
+            return Self::from_args(options, arg);
+        }
+        let contents = read_to_string(arg)
+            .map_err(|e| LayoutError(format!("Unable to read {}: {}", arg, e)))?;
+        if contents.trim_start().starts_with('[') {
+            parse_blueprint(&contents)
+                .map_err(|e| LayoutError(format!("Invalid blueprint {}: {}", arg, e)))
+        } else {
+            Self::from_args(options, contents.trim_end_matches(&['\r', '\n'][..]))
+                .map_err(|e| LayoutError(format!("{}: {}", arg, e)))
+        }
     }
 
     fn set_key(&mut self, row: usize, key: usize, layer: usize, new_key: String) {
         self[row][key][layer] = new_key;
     }
 
-    fn get_base_layout(path: &Option<String>) -> Self {
+    fn swap_keys(
+        &mut self,
+        (first_row, first_key): KeyPos,
+        (second_row, second_key): KeyPos,
+        layer: usize,
+    ) {
+        if (first_row, first_key) == (second_row, second_key) {
+            return;
+        }
+        let first = std::mem::take(&mut self[first_row][first_key][layer]);
+        let second = std::mem::replace(&mut self[second_row][second_key][layer], first);
+        self[first_row][first_key][layer] = second;
+    }
+
+    fn try_get_base_layout(path: &Option<String>) -> Result<Self, LayoutError> {
+        profile::time("base layout load", || Self::read_base_layout(path))
+    }
+
+    fn read_base_layout(path: &Option<String>) -> Result<Self, LayoutError> {
         debug!("Reading base layout");
-        let default_json = include_str!("../default_base_layout.json");
         let json = match path {
+            // A file of the same name wins, so existing setups keep working.
+            Some(name) if !Path::new(name).is_file() && builtin_base_layout(name).is_some() => {
+                debug!("Using the built-in base layout {}.", name);
+                builtin_base_layout(name).unwrap().json.to_string()
+            }
             Some(path) => {
                 debug!("Reading json from argument with path {}.", path);
-                read_to_string(path).unwrap()
+                read_to_string(path)
+                    .map_err(|e| LayoutError(format!("Unable to read {}: {}", path, e)))?
             }
             None => {
                 debug!("Assigning default layout (NEO)");
-                default_json.to_string()
+                BUILTIN_BASE_LAYOUTS[0].json.to_string()
             }
         };
 
-        serde_json::from_str(&json).unwrap()
+        parse_blueprint(&json).map_err(|e| LayoutError(format!("Invalid base layout: {}", e)))
     }
 
-    fn merge_layout_string(&mut self, layout: &str) {
-        let clean_lines = layout.replace(" ", "");
-        let lines = clean_lines.split('\n');
+    fn check_layout_string(&self, layout: &str, alphabet: &str) -> Vec<String> {
+        let mut problems = vec![];
+        let shape = LayoutStringShape::of(self);
 
-        for (idx, line) in lines.enumerate() {
-            let chars = line.chars();
+        for (idx, line) in layout_string_lines(layout).iter().enumerate() {
+            let row = shape.row(idx);
+            let keys = match self.get(row) {
+                Some(keys) => keys,
+                None if line.is_empty() => continue,
+                None => {
+                    problems.push(format!(
+                        "Layout row {} doesn't exist in the base layout",
+                        row
+                    ));
+                    continue;
+                }
+            };
 
-            for (idy, char) in chars.enumerate() {
-                self.set_key(idx + 1, idy + 1, 0, char.into());
+            let mut missing = 0;
+            for (key, char) in shape.keys(idx, line) {
+                match keys.get(key).and_then(|key| key.first()) {
+                    None => missing += 1,
+                    Some(base) if !alphabet.contains(char) && *base != char.to_string() => {
+                        problems.push(format!(
+                            "Layout row {} key {} has {:?}, which isn't in the alphabet, but the base layout has {:?} there",
+                            row,
+                            key,
+                            char,
+                            base
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            if missing > 0 {
+                let length = line.chars().count();
+                problems.push(format!(
+                    "Layout row {} has {} characters, but row {} of the base layout only has room for {} starting at key {}",
+                    row,
+                    length,
+                    row,
+                    length - missing,
+                    shape.first_key(idx)
+                ));
             }
         }
+        problems
+    }
+
+    fn merge_layout_string(&mut self, layout: &str) {
+        let shape = LayoutStringShape::of(self);
+        for (idx, line) in layout_string_lines(layout).iter().enumerate() {
+            for (key, char) in shape.keys(idx, line) {
+                self.set_key(shape.row(idx), key, 0, char.into());
+            }
+        }
+    }
+
+    fn layout_string(&self) -> String {
+        self.layer_string(0)
+    }
+
+    fn layer_string(&self, layer: usize) -> String {
+        let shape = LayoutStringShape::of(self);
+        let lines: Vec<String> = shape
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(idx, &(first_key, length))| {
+                // Missing rows and keys of a smaller base layout are shown as spaces.
+                let row = self.get(shape.row(idx)).map_or(&[][..], Vec::as_slice);
+                let keys: Vec<String> = (first_key..first_key + length)
+                    .map(|key| {
+                        row.get(key)
+                            .and_then(|key| key.get(layer))
+                            .filter(|char| !char.is_empty() && !char.chars().any(char::is_control))
+                            .cloned()
+                            .unwrap_or_else(|| " ".to_string())
+                    })
+                    .collect();
+                let (left, right) =
+                    keys.split_at(shape.left_keys.unwrap_or(length / 2).min(length));
+                format!("{} {}", left.concat(), right.concat())
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    fn layer_count(&self) -> usize {
+        self.iter().flatten().map(Vec::len).max().unwrap_or(0)
+    }
+
+    fn check_layers(&self, layers: &[usize]) -> Result<(), String> {
+        let count = self.layer_count();
+        match layers.iter().find(|&&layer| layer >= count) {
+            Some(layer) => Err(format!(
+                "--optimize-layers selects layer {}, but the base layout only has {} layers (0 to {})",
+                layer,
+                count,
+                count.saturating_sub(1)
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn shuffle_layer(&mut self, layer: usize, alphabet: Option<&str>, rng: &mut dyn RngCore) {
+        let positions = movable_keys(self, layer, alphabet);
+        let mut chars: Vec<String> = positions
+            .iter()
+            .map(|&(row, key)| self[row][key][layer].clone())
+            .collect();
+        chars.shuffle(rng);
+        #[cfg(debug_assertions)]
+        let before = sorted_layer(self, layer);
+        for ((row, key), char) in positions.into_iter().zip(chars) {
+            self.set_key(row, key, layer, char);
+        }
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            before,
+            sorted_layer(self, layer),
+            "shuffling may only rearrange the characters of layer {}",
+            layer
+        );
     }
 
     fn debug_print(&self) {
         for row in self {
             let mut keys = "".to_string();
             for key in row {
-                keys = keys + &key.first().unwrap_or(&" ".to_string());
+                keys = keys + key.first().unwrap_or(&" ".to_string());
             }
             debug!("{}", keys);
         }
     }
 
-    fn get_randomized_variant(&self, alphabet: String, steps: u128) -> Self {
-        debug!("Creating a new randomized variant with {} steps.", steps);
+    fn get_randomized_variant(
+        &self,
+        alphabet: String,
+        switches: u128,
+        rng: &mut dyn RngCore,
+    ) -> Self {
+        debug!(
+            "Creating a new randomized variant with {} switches.",
+            switches
+        );
         let mut layout = self.clone();
+        layout.swap_random_keys(0, Some(&alphabet), switches, rng);
+        self.debug_print();
+        layout.debug_print();
+
+        layout
+    }
+
+    fn swap_random_keys(
+        &mut self,
+        layer: usize,
+        alphabet: Option<&str>,
+        switches: u128,
+        rng: &mut dyn RngCore,
+    ) {
+        let positions = movable_keys(self, layer, alphabet);
+        if positions.len() < 2 {
+            return;
+        }
+        #[cfg(debug_assertions)]
+        let before = sorted_layer(self, layer);
+        for _ in 0..switches {
+            let pair: Vec<&(usize, usize)> = positions.choose_multiple(rng, 2).collect();
+            self.swap_keys(*pair[0], *pair[1], layer);
+        }
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            before,
+            sorted_layer(self, layer),
+            "swapping may only rearrange the characters of layer {}",
+            layer
+        );
+    }
+
+    fn movable_keys(&self, layer: usize, alphabet: Option<&str>) -> Vec<(usize, usize)> {
+        movable_keys(self, layer, alphabet)
+    }
+
+    fn set_new_key(&mut self, new_key: String, old_key: String) {
+        match (self.get_key_pos(&old_key), self.get_key_pos(&new_key)) {
+            (Some(old_pos), Some(new_pos)) => self.swap_keys(old_pos, new_pos, 0),
+            (None, _) => warn!(
+                "Can't put {:?} in the place of {:?}, which isn't on the layout.",
+                new_key, old_key
+            ),
+            (_, None) => warn!(
+                "Can't put {:?} in the place of {:?}, it isn't on the layout to swap with.",
+                new_key, old_key
+            ),
+        }
+    }
 
-        let mut old_alphabet = vec![];
-        let mut new_alphabet = vec![];
-        alphabet.chars().for_each(|c| {
-            old_alphabet.push(c.to_string());
-            new_alphabet.push(c.to_string());
+    fn get_key_pos(&self, needle: &str) -> Option<(usize, usize)> {
+        let pos = self.iter().enumerate().find_map(|(row_idx, row)| {
+            row.iter()
+                .position(|key| key.first().map(String::as_str) == Some(needle))
+                .map(|key_idx| (row_idx, key_idx))
         });
-        new_alphabet.shuffle(&mut thread_rng());
+        debug!("Found key {} at {:?}", needle, pos);
+        pos
+    }
+}
 
-        for (idx, new_char) in new_alphabet.iter().enumerate() {
-            let old_char = &old_alphabet[idx];
-            layout.set_new_key(old_char.clone(), new_char.clone());
+/**
+Whether a character that exists more than once is rather typed at `pos` than
+at `existing`: at the cheapest position, with the cost of the layer's
+modifiers. Of equally cheap ones the lowest layer wins.
+*/
+fn is_cheaper_position(pos: &Pos, existing: Option<&Pos>, key_costs: &KeyCosts) -> bool {
+    match existing {
+        Some(existing) => {
+            let cost = key_costs.position_cost(pos);
+            let existing_cost = key_costs.position_cost(existing);
+            cost < existing_cost || (cost == existing_cost && pos.layer < existing.layer)
         }
+        None => true,
+    }
+}
 
-        debug!("{:?}", old_alphabet);
-        debug!("{:?}", new_alphabet);
-        self.debug_print();
-        layout.debug_print();
+/// The rows of a layout string without spaces. CRLF line endings and empty lines at the end are fine.
+fn layout_string_lines(layout: &str) -> Vec<String> {
+    let mut lines: Vec<String> = layout
+        .replace(' ', "")
+        .lines()
+        .map(str::to_string)
+        .collect();
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines
+}
 
-        layout
+/**
+How the lines of a layout string map to the keys of a blueprint. A blueprint
+with room for the Neo letter rows has the rows of `LAYOUT_STRING_ROWS`, so the
+layout strings of the Neo keyboard work on every base layout made for it. Any
+other blueprint, like an ortholinear 4x12 one, has a line for each of its rows
+with every key of the row, so the layout string leaves none of its keys out.
+*/
+struct LayoutStringShape {
+    /// The blueprint row of the first line.
+    first_row: usize,
+    /// The first key and the number of keys of each line.
+    rows: Vec<(usize, usize)>,
+    /// The keys left of the space of each line, half of its keys if none.
+    left_keys: Option<usize>,
+}
+
+impl LayoutStringShape {
+    fn of(blueprint: &Blueprint) -> Self {
+        let has_neo_rows =
+            LAYOUT_STRING_ROWS
+                .iter()
+                .enumerate()
+                .all(|(idx, &(first_key, length))| {
+                    blueprint
+                        .get(idx + 1)
+                        .is_some_and(|keys| keys.len() >= first_key + length)
+                });
+        if has_neo_rows {
+            LayoutStringShape {
+                first_row: 1,
+                rows: LAYOUT_STRING_ROWS.to_vec(),
+                left_keys: Some(5),
+            }
+        } else {
+            LayoutStringShape {
+                first_row: 0,
+                rows: blueprint.iter().map(|keys| (0, keys.len())).collect(),
+                left_keys: None,
+            }
+        }
     }
 
-    fn set_new_key(&mut self, new_key: String, old_key: String) {
-        let (row, key) = self.get_key_pos(old_key);
-        self.set_key(row, key, 0, new_key);
+    /// The blueprint row of line `idx` (counted from 0).
+    fn row(&self, idx: usize) -> usize {
+        self.first_row + idx
+    }
+
+    /// The key the first character of line `idx` goes on. Lines after the last row start like the first one.
+    fn first_key(&self, idx: usize) -> usize {
+        self.rows
+            .get(idx)
+            .or_else(|| self.rows.first())
+            .map_or(0, |&(first_key, _)| first_key)
+    }
+
+    /// The keys the characters of line `idx` of a layout string without spaces go on.
+    fn keys<'a>(&self, idx: usize, line: &'a str) -> impl Iterator<Item = (usize, char)> + 'a {
+        let first_key = self.first_key(idx);
+        line.chars()
+            .enumerate()
+            .map(move |(idy, char)| (first_key + idy, char))
+    }
+}
+
+/**
+The keys whose character on `layer` is a single character of the alphabet.
+Without an alphabet every single printable character counts, but named keys
+like `Entf` and control characters don't.
+*/
+fn movable_keys(
+    blueprint: &Blueprint,
+    layer: usize,
+    alphabet: Option<&str>,
+) -> Vec<(usize, usize)> {
+    blueprint
+        .iter()
+        .enumerate()
+        .flat_map(|(row_idx, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, key)| {
+                    key.get(layer).is_some_and(|char| {
+                        let mut chars = char.chars();
+                        match (chars.next(), chars.next(), alphabet) {
+                            (Some(single), None, Some(alphabet)) => alphabet.contains(single),
+                            (Some(single), None, None) => !single.is_control(),
+                            _ => false,
+                        }
+                    })
+                })
+                .map(move |(key_idx, _)| (row_idx, key_idx))
+        })
+        .collect()
+}
+
+/// The characters of a layer, sorted, to check that a layer was only rearranged.
+#[cfg(debug_assertions)]
+fn sorted_layer(blueprint: &Blueprint, layer: usize) -> Vec<String> {
+    let mut chars: Vec<String> = blueprint
+        .iter()
+        .flatten()
+        .filter_map(|key| key.get(layer).cloned())
+        .collect();
+    chars.sort();
+    chars
+}
+
+/// The name and layout of a --baselines entry: a file, or a built-in baseline if there is no such file, or a layout string.
+pub fn baseline_arg(arg: &str) -> (String, String) {
+    match builtin_baseline_arg(arg) {
+        Some(baseline) => (
+            baseline.name.to_string(),
+            baseline.layout_string.to_string(),
+        ),
+        None => (layout_arg_name(arg), arg.to_string()),
+    }
+}
+
+/// The built-in baseline a --baselines entry stands for, none if it is a file or a layout string.
+pub fn builtin_baseline_arg(arg: &str) -> Option<&'static BuiltinBaseline> {
+    builtin_baseline(arg).filter(|_| !Path::new(arg).is_file())
+}
+
+/// A short name for a layout argument: the file name or the first row of the layout string.
+pub fn layout_arg_name(arg: &str) -> String {
+    let path = Path::new(arg);
+    if path.is_file() {
+        if let Some(stem) = path.file_stem() {
+            return stem.to_string_lossy().to_string();
+        }
     }
+    arg.lines().next().unwrap_or_default().trim().to_string()
+}
 
-    fn get_key_pos(&mut self, needle: String) -> (usize, usize) {
-        let mut row_index = 0;
-        let mut key_index = 0;
+/**
+The characters of `layer` that move with `shuffle_layer` and the given
+alphabet and are on another key of that layer in `after` than in `before`.
+*/
+pub fn moved_characters(
+    before: &Blueprint,
+    after: &Blueprint,
+    layer: usize,
+    alphabet: Option<&str>,
+) -> Vec<char> {
+    let position = |blueprint: &Blueprint, char: &str| {
+        blueprint.iter().enumerate().find_map(|(row_idx, row)| {
+            row.iter()
+                .position(|key| key.get(layer).map(String::as_str) == Some(char))
+                .map(|key_idx| (row_idx, key_idx))
+        })
+    };
+    movable_keys(before, layer, alphabet)
+        .into_iter()
+        .map(|(row, key)| &before[row][key][layer])
+        .filter(|char| position(before, char) != position(after, char))
+        .filter_map(|char| char.chars().next())
+        .collect()
+}
 
-        for (inr, row) in self.iter().enumerate() {
-            for (ink, key) in row.iter().enumerate() {
-                let default_key = "not_set".to_string();
-                let key = key.first().unwrap_or(&default_key);
-                if key == &needle {
-                    row_index = inr;
-                    key_index = ink;
+/// A blueprint json whose rows, keys and layers all fit a `Pos`.
+fn parse_blueprint(json: &str) -> Result<Blueprint, String> {
+    let blueprint: Blueprint = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let too_large = blueprint.len() > MAX_BLUEPRINT_DIMENSION
+        || blueprint.iter().any(|row| {
+            row.len() > MAX_BLUEPRINT_DIMENSION
+                || row.iter().any(|key| key.len() > MAX_BLUEPRINT_DIMENSION)
+        });
+    if too_large {
+        return Err(format!(
+            "a blueprint has at most {} rows, keys per row and layers per key",
+            MAX_BLUEPRINT_DIMENSION
+        ));
+    }
+    Ok(blueprint)
+}
+
+/// A blueprint together with a lookup table from characters to their positions.
+#[derive(Clone)]
+pub struct Layout {
+    /// The keyboard.
+    pub blueprint: Blueprint,
+    /// The position every character is typed at, indexed by the id the interner gave it.
+    pub positions: Vec<Option<Pos>>,
+    /// The interner of the ngrams the layout is scored with.
+    pub interner: Arc<Interner>,
+    /// The key costs that chose the position of the characters that are on several keys.
+    pub key_costs: Arc<KeyCosts>,
+}
+
+impl Layout {
+    /**
+    Index the characters of a blueprint that occur in the ngrams of `interner`.
+    A character on several keys is typed at the cheapest of them by
+    `key_costs`, which should be the ones of the model that scores the layout.
+    */
+    pub fn from_blueprint(
+        blueprint: Blueprint,
+        interner: &Arc<Interner>,
+        key_costs: &Arc<KeyCosts>,
+    ) -> Self {
+        let mut positions: Vec<Option<Pos>> = vec![None; interner.len()];
+
+        for (row_idx, row) in blueprint.iter().enumerate() {
+            for (key_idx, key) in row.iter().enumerate() {
+                for (layer_idx, char) in key.iter().enumerate() {
+                    let id = match interner.id(char) {
+                        Some(id) => usize::from(id),
+                        None => continue,
+                    };
+                    let pos = Pos::new(row_idx, key_idx, layer_idx);
+                    if is_cheaper_position(&pos, positions[id].as_ref(), key_costs) {
+                        positions[id] = Some(pos);
+                    }
                 }
             }
         }
 
-        debug!("Found key {} in {} x {}", needle, row_index, key_index);
+        Layout {
+            blueprint,
+            positions,
+            interner: Arc::clone(interner),
+            key_costs: Arc::clone(key_costs),
+        }
+    }
+
+    /**
+    Swap the characters of two keys on one layer and move them in the lookup
+    table, without indexing the whole blueprint again. Swapping the same keys
+    again undoes it.
+    */
+    pub fn swap_keys(&mut self, first: KeyPos, second: KeyPos, layer: usize) {
+        self.blueprint.swap_keys(first, second, layer);
+        let chars = [first, second].map(|(row, key)| self.blueprint[row][key][layer].clone());
+        for char in chars {
+            if let Some(id) = self.interner.id(&char) {
+                self.positions[usize::from(id)] = self.cheapest_position(&char);
+            }
+        }
+    }
+
+    /// The cheapest position of a character of the blueprint, like `from_blueprint` chooses it.
+    fn cheapest_position(&self, char: &str) -> Option<Pos> {
+        let mut cheapest = None;
+        for (row_idx, row) in self.blueprint.iter().enumerate() {
+            for (key_idx, key) in row.iter().enumerate() {
+                for (layer_idx, _) in key.iter().enumerate().filter(|(_, other)| *other == char) {
+                    let pos = Pos::new(row_idx, key_idx, layer_idx);
+                    if is_cheaper_position(&pos, cheapest.as_ref(), &self.key_costs) {
+                        cheapest = Some(pos);
+                    }
+                }
+            }
+        }
+        cheapest
+    }
+
+    /// A layout given like [`BlueprintHelpers::from_layout_arg`] accepts it, with the key costs of the options.
+    #[cfg(feature = "cli")]
+    pub fn from_layout_arg(
+        options: &KloOptions,
+        arg: &str,
+        interner: &Arc<Interner>,
+    ) -> Result<Self, LayoutError> {
+        Blueprint::from_layout_arg(options, arg).map(|blueprint| {
+            Self::from_blueprint(blueprint, interner, &options.cost_weights.key_costs)
+        })
+    }
+
+    /// The position `char` is typed at. Characters that don't occur in the ngrams have none.
+    pub fn position(&self, char: &str) -> Option<Pos> {
+        self.interner
+            .id(char)
+            .and_then(|id| self.positions[usize::from(id)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// Every character of every layer of a blueprint, sorted.
+    fn characters(blueprint: &Blueprint) -> Vec<String> {
+        let mut chars: Vec<String> = blueprint.iter().flatten().flatten().cloned().collect();
+        chars.sort();
+        chars
+    }
+
+    fn neo_blueprint() -> Blueprint {
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+        blueprint
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn from_args_puts_the_layout_string_on_the_base_layout() {
+        use clap::Clap;
+
+        let options = KloOptions::try_parse_from(["klo", "evolve"]).unwrap();
+        let layout = "jmuaz kdflvbß\ncriey ptsnh⇘\nxäüoö wg,.q";
+        let blueprint = Blueprint::from_args(&options, layout).unwrap();
+        assert_eq!(blueprint[1][1][0], "j");
+        assert_eq!(blueprint.layout_string(), layout);
+    }
+
+    #[test]
+    fn the_layout_string_of_another_shape_has_every_key() {
+        let blueprint: Blueprint = (0..4u8)
+            .map(|row| {
+                (0..12u8)
+                    .map(|key| vec![char::from(b'A' + 12 * row + key).to_string()])
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            blueprint.layout_string(),
+            "ABCDEF GHIJKL\nMNOPQR STUVWX\nYZ[\\]^ _`abcd\nefghij klmnop"
+        );
+
+        let mut swapped = blueprint.clone();
+        swapped.swap_keys((0, 0), (3, 11), 0);
+        swapped.swap_keys((1, 0), (2, 11), 0);
+        let mut merged = blueprint.clone();
+        assert!(merged
+            .check_layout_string(&swapped.layout_string(), "ApMd")
+            .is_empty());
+        merged.merge_layout_string(&swapped.layout_string());
+        assert_eq!(merged, swapped);
+    }
+
+    #[test]
+    fn the_layout_string_of_a_neo_keyboard_has_the_neo_rows() {
+        let blueprint = neo_blueprint();
+        let neo = builtin_baseline("neo").unwrap().layout_string;
+        assert_eq!(blueprint.layout_string(), neo);
+
+        // A key more in every row stays out of the layout string.
+        let mut wider = blueprint.clone();
+        for row in &mut wider {
+            row.push(vec!["x".to_string()]);
+        }
+        assert_eq!(wider.layout_string(), neo);
+    }
+
+    #[test]
+    fn get_key_pos_finds_only_characters_on_layer_0() {
+        let blueprint = neo_blueprint();
+        assert_eq!(blueprint[0][0][..2], ["^", "ˇ"]);
+        assert_eq!(blueprint.get_key_pos("^"), Some((0, 0)));
+        let (row, key) = blueprint.get_key_pos("e").unwrap();
+        assert_eq!(blueprint[row][key][0], "e");
+        assert_eq!(blueprint.get_key_pos("ˇ"), None);
+        assert_eq!(blueprint.get_key_pos("☃"), None);
+        assert_eq!(blueprint.get_key_pos(""), None);
+    }
+
+    #[test]
+    fn set_new_key_swaps_the_two_characters() {
+        let mut blueprint = neo_blueprint();
+        let (e, n) = (blueprint.get_key_pos("e"), blueprint.get_key_pos("n"));
+        blueprint.set_new_key("e".to_string(), "n".to_string());
+        assert_eq!(blueprint.get_key_pos("e"), n);
+        assert_eq!(blueprint.get_key_pos("n"), e);
+    }
+
+    #[test]
+    fn swapping_a_key_with_itself_keeps_its_character() {
+        let mut blueprint = neo_blueprint();
+        let before = blueprint.clone();
+        blueprint.set_new_key("e".to_string(), "e".to_string());
+        assert_eq!(blueprint, before);
+    }
+
+    #[test]
+    fn set_new_key_with_a_character_off_the_layout_changes_nothing() {
+        let mut blueprint = neo_blueprint();
+        let before = blueprint.clone();
+        blueprint.set_new_key("☃".to_string(), "e".to_string());
+        blueprint.set_new_key("e".to_string(), "☃".to_string());
+        assert_eq!(blueprint, before);
+    }
+
+    #[test]
+    fn a_too_long_row_reports_the_room_of_the_base_layout_row() {
+        let blueprint = Blueprint::read_base_layout(&None).unwrap();
+        let room = blueprint[1][1..]
+            .iter()
+            .filter(|key| !key.is_empty())
+            .count();
+        let line = "a".repeat(room + 3);
+        let problems = blueprint.check_layout_string(&line, "a");
+        assert_eq!(
+            problems,
+            vec![format!(
+                "Layout row 1 has {} characters, but row 1 of the base layout only has room for {} starting at key 1",
+                room + 3,
+                room
+            )]
+        );
+    }
+
+    #[test]
+    fn a_randomized_variant_with_one_switch_moves_two_characters() {
+        let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+        let original = neo_blueprint();
+        for seed in 0..16 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let variant = original.get_randomized_variant(alphabet.to_string(), 1, &mut rng);
+            let moved = moved_characters(&original, &variant, 0, Some(alphabet));
+            assert_eq!(moved.len(), 2, "seed {}: {:?}", seed, moved);
+        }
+    }
+
+    #[test]
+    fn a_randomized_variant_puts_every_alphabet_character_on_one_key_of_layer_0() {
+        let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+        let original = neo_blueprint();
+        let movable = original.movable_keys(0, Some(alphabet));
+        assert_eq!(movable.len(), alphabet.chars().count());
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let variant = original.get_randomized_variant(alphabet.to_string(), 100, &mut rng);
+            for char in alphabet.chars().map(String::from) {
+                let keys = movable
+                    .iter()
+                    .filter(|&&(row, key)| variant[row][key][0] == char)
+                    .count();
+                assert_eq!(keys, 1, "seed {}: {:?}", seed, char);
+            }
+            for (row, keys) in original.iter().enumerate() {
+                for (key, layers) in keys.iter().enumerate() {
+                    let skip = usize::from(movable.contains(&(row, key)));
+                    assert_eq!(
+                        variant[row][key][skip..],
+                        layers[skip..],
+                        "seed {}: row {} key {}",
+                        seed,
+                        row,
+                        key
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mutations_only_rearrange_the_characters() {
+        let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+        let original = neo_blueprint();
+        let expected = characters(&original);
+        let letters: Vec<char> = alphabet.chars().collect();
+        for seed in 0..64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut blueprint = original.clone();
+            for _ in 0..32 {
+                match rng.gen_range(0..4) {
+                    0 => {
+                        blueprint =
+                            blueprint.get_randomized_variant(alphabet.to_string(), 3, &mut rng)
+                    }
+                    1 => blueprint.swap_random_keys(rng.gen_range(0..3), None, 2, &mut rng),
+                    2 => blueprint.shuffle_layer(rng.gen_range(0..3), Some(alphabet), &mut rng),
+                    _ => {
+                        let new_key = letters[rng.gen_range(0..letters.len())];
+                        let old_key = letters[rng.gen_range(0..letters.len())];
+                        blueprint.set_new_key(new_key.to_string(), old_key.to_string());
+                    }
+                }
+                assert_eq!(characters(&blueprint), expected, "seed {}", seed);
+            }
+        }
+    }
+
+    /// The cheapest position of every key of a blueprint, the lower layer on a tie, keyed by string.
+    fn cheapest_positions(blueprint: &Blueprint, key_costs: &KeyCosts) -> HashMap<String, Pos> {
+        let mut positions: HashMap<String, Pos> = HashMap::new();
+        for (row, keys) in blueprint.iter().enumerate() {
+            for (key, layers) in keys.iter().enumerate() {
+                for (layer, char) in layers.iter().enumerate() {
+                    let pos = Pos::new(row, key, layer);
+                    let cheaper = positions.get(char).is_none_or(|existing| {
+                        let (cost, existing_cost) = (
+                            key_costs.position_cost(&pos),
+                            key_costs.position_cost(existing),
+                        );
+                        cost < existing_cost
+                            || (cost == existing_cost && pos.layer < existing.layer)
+                    });
+                    if cheaper {
+                        positions.insert(char.clone(), pos);
+                    }
+                }
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn the_position_table_agrees_with_a_map_of_the_blueprint() {
+        let chars = ["a", "b", "c", "d", "e", "f", "⇧", "space"];
+        let mut interner = Interner::default();
+        for char in "abcdefg".chars() {
+            interner.intern(char).unwrap();
+        }
+        let interner = Arc::new(interner);
+        let key_costs = Arc::new(KeyCosts::default());
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..100 {
+            let layers = rng.gen_range(1..4);
+            let blueprint: Blueprint = (0..rng.gen_range(1..6))
+                .map(|_| {
+                    (0..rng.gen_range(1..15))
+                        .map(|_| {
+                            (0..layers)
+                                .map(|_| chars[rng.gen_range(0..chars.len())].to_string())
+                                .collect()
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut layout = Layout::from_blueprint(blueprint, &interner, &key_costs);
+
+            for _ in 0..10 {
+                let oracle = cheapest_positions(&layout.blueprint, &key_costs);
+                for char in "abcdefg".chars().map(String::from) {
+                    assert_eq!(
+                        layout.position(&char),
+                        oracle.get(&char).copied(),
+                        "{}",
+                        char
+                    );
+                }
+                assert_eq!(layout.position("space"), None);
+
+                let key = |rng: &mut StdRng| {
+                    let row = rng.gen_range(0..layout.blueprint.len());
+                    (row, rng.gen_range(0..layout.blueprint[row].len()))
+                };
+                let (first, second) = (key(&mut rng), key(&mut rng));
+                layout.swap_keys(first, second, rng.gen_range(0..layers));
+            }
+        }
+    }
+
+    /// A random blueprint with every character of a random alphabet on one key of layer 0, and its alphabet.
+    fn random_blueprint(rng: &mut StdRng) -> (Blueprint, String) {
+        let letters: Vec<char> = "abcdefghijklmnopqrstuvwxyzäöüß,.".chars().collect();
+        let others: Vec<char> = "⇘⇧⌫1234567890".chars().collect();
+        let (rows, keys, layers) = (
+            rng.gen_range(1..6),
+            rng.gen_range(2..15),
+            rng.gen_range(1..4),
+        );
+        let count = rng.gen_range(2..=letters.len().min(rows * keys));
+        let mut alphabet: Vec<char> = letters.choose_multiple(rng, count).copied().collect();
+        let mut layer_0: Vec<char> = alphabet.clone();
+        while layer_0.len() < rows * keys {
+            layer_0.push(*others.choose(rng).unwrap());
+        }
+        layer_0.shuffle(rng);
+        let all: Vec<char> = letters.iter().chain(&others).copied().collect();
+        let blueprint = layer_0
+            .chunks(keys)
+            .map(|row| {
+                row.iter()
+                    .map(|&char| {
+                        let higher = (1..layers).map(|_| all.choose(rng).unwrap().to_string());
+                        std::iter::once(char.to_string()).chain(higher).collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        alphabet.sort_unstable();
+        (blueprint, alphabet.into_iter().collect())
+    }
+
+    /// The row and key of every alphabet character on layer 0.
+    fn alphabet_keys(blueprint: &Blueprint, alphabet: &str) -> HashMap<char, KeyPos> {
+        let mut keys = HashMap::new();
+        for (row, row_keys) in blueprint.iter().enumerate() {
+            for (key, layers) in row_keys.iter().enumerate() {
+                let char = layers[0].chars().next().unwrap();
+                if alphabet.contains(char) {
+                    assert!(
+                        keys.insert(char, (row, key)).is_none(),
+                        "{:?} is on layer 0 twice",
+                        char
+                    );
+                }
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn swaps_merges_and_shuffles_keep_the_alphabet_and_the_position_table() {
+        let mut interner = Interner::default();
+        for char in "abcdefghijklmnopqrstuvwxyzäöüß,.⇘⇧⌫1234567890".chars() {
+            interner.intern(char).unwrap();
+        }
+        let interner = Arc::new(interner);
+        let key_costs = Arc::new(KeyCosts::default());
+        let mut rng = StdRng::seed_from_u64(453);
+        for _ in 0..100 {
+            let (blueprint, alphabet) = random_blueprint(&mut rng);
+            let mut layout = Layout::from_blueprint(blueprint, &interner, &key_costs);
+            for step in 0..20 {
+                let before = layout.blueprint.clone();
+                let movable = before.movable_keys(0, Some(&alphabet));
+                let char_at = |(row, key): KeyPos| before[row][key][0].chars().next().unwrap();
+                let mut touched: Vec<char> = match rng.gen_range(0..4) {
+                    0 => {
+                        let pair: Vec<KeyPos> =
+                            movable.choose_multiple(&mut rng, 2).copied().collect();
+                        layout.swap_keys(pair[0], pair[1], 0);
+                        pair.into_iter().map(char_at).collect()
+                    }
+                    1 => {
+                        // Any two keys, which may be the same, on any layer.
+                        let mut key = || {
+                            let row = rng.gen_range(0..before.len());
+                            (row, rng.gen_range(0..before[row].len()))
+                        };
+                        let (first, second) = (key(), key());
+                        let layer = rng.gen_range(0..before[0][0].len());
+                        layout.swap_keys(first, second, layer);
+                        [first, second]
+                            .iter()
+                            .copied()
+                            .filter(|_| layer == 0 && first != second)
+                            .map(char_at)
+                            .filter(|&char| alphabet.contains(char))
+                            .collect()
+                    }
+                    2 => {
+                        // The alphabet characters of the layout string, in another order.
+                        let old = before.layout_string();
+                        let old_chars: Vec<char> = old.chars().collect();
+                        let slots: Vec<usize> = (0..old_chars.len())
+                            .filter(|&idx| alphabet.contains(old_chars[idx]))
+                            .collect();
+                        let mut new_chars = old_chars.clone();
+                        let mut shuffled: Vec<char> =
+                            slots.iter().map(|&idx| old_chars[idx]).collect();
+                        shuffled.shuffle(&mut rng);
+                        for (&idx, &char) in slots.iter().zip(&shuffled) {
+                            new_chars[idx] = char;
+                        }
+                        let mut blueprint = before.clone();
+                        blueprint.merge_layout_string(&new_chars.iter().collect::<String>());
+                        layout = Layout::from_blueprint(blueprint, &interner, &key_costs);
+                        slots
+                            .into_iter()
+                            .filter(|&idx| old_chars[idx] != new_chars[idx])
+                            .map(|idx| old_chars[idx])
+                            .collect()
+                    }
+                    _ => {
+                        let mut blueprint = before.clone();
+                        blueprint.shuffle_layer(0, Some(&alphabet), &mut rng);
+                        layout = Layout::from_blueprint(blueprint, &interner, &key_costs);
+                        let (old, new) = (
+                            alphabet_keys(&before, &alphabet),
+                            alphabet_keys(&layout.blueprint, &alphabet),
+                        );
+                        alphabet
+                            .chars()
+                            .filter(|char| old[char] != new[char])
+                            .collect()
+                    }
+                };
+
+                let keys = alphabet_keys(&layout.blueprint, &alphabet);
+                let mut on_layer_0: Vec<char> = keys.keys().copied().collect();
+                on_layer_0.sort_unstable();
+                assert_eq!(
+                    on_layer_0.into_iter().collect::<String>(),
+                    alphabet,
+                    "step {}",
+                    step
+                );
+
+                let fresh = Layout::from_blueprint(layout.blueprint.clone(), &interner, &key_costs);
+                assert_eq!(layout.positions, fresh.positions, "step {}", step);
+
+                let mut moved = moved_characters(&before, &layout.blueprint, 0, Some(&alphabet));
+                moved.sort_unstable();
+                touched.sort_unstable();
+                touched.dedup();
+                assert_eq!(moved, touched, "step {}: {:?}", step, layout.blueprint);
+            }
+        }
+    }
+
+    #[test]
+    fn a_duplicate_is_typed_where_it_is_cheapest_with_the_cost_of_its_layer() {
+        let mut interner = Interner::default();
+        for char in "xy".chars() {
+            interner.intern(char).unwrap();
+        }
+        let interner = Arc::new(interner);
+        let key_costs = Arc::new(KeyCosts {
+            keys: vec![vec![3.0, 20.0, 18.0]],
+            ..KeyCosts::default()
+        });
+        // `x` is on layer 4 of the easiest key (3 + 27) and on layer 0 of a harder one (20).
+        // `y` costs 3 + 15 on layer 1 of the easiest key and 18 on layer 0 of the last one.
+        let blueprint: Blueprint = vec![vec![
+            ["", "y", "", "", "x"]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+            vec!["x".to_string()],
+            vec!["y".to_string()],
+        ]];
+        let mut layout = Layout::from_blueprint(blueprint, &interner, &key_costs);
+        assert_eq!(layout.position("x"), Some(Pos::new(0, 1, 0)));
+        assert_eq!(layout.position("y"), Some(Pos::new(0, 2, 0)));
+
+        // On layer 0 of the easiest key, `x` is typed there.
+        layout.swap_keys((0, 0), (0, 1), 0);
+        assert_eq!(layout.position("x"), Some(Pos::new(0, 0, 0)));
+        layout.swap_keys((0, 0), (0, 1), 0);
+        assert_eq!(layout.position("x"), Some(Pos::new(0, 1, 0)));
+        assert_eq!(layout.position("y"), Some(Pos::new(0, 2, 0)));
+    }
+
+    #[test]
+    fn pos_helpers_look_at_the_key_and_the_layer() {
+        let pos = Pos::new(1, 4, 2);
+        assert_eq!(pos.key(), (1, 4));
+        assert_eq!(pos.with_layer(0), Pos::new(1, 4, 0));
+        assert!(pos.same_key(&pos.with_layer(5)));
+        assert!(!pos.same_key(&Pos::new(4, 1, 2)));
+        assert_eq!(pos.row_distance(&Pos::new(3, 0, 0)), 2);
+        assert_eq!(Pos::new(3, 0, 0).row_distance(&pos), 2);
+        assert_eq!(pos.to_string(), "row 1 key 4 layer 2");
+    }
+
+    #[test]
+    fn positions_are_ordered_by_row_key_and_layer() {
+        let mut positions = vec![Pos::new(1, 0, 0), Pos::new(0, 2, 1), Pos::new(0, 2, 0)];
+        positions.sort();
+        assert_eq!(
+            positions,
+            [Pos::new(0, 2, 0), Pos::new(0, 2, 1), Pos::new(1, 0, 0)]
+        );
+        let json = serde_json::to_string(&Pos::new(2, 3, 4)).unwrap();
+        assert_eq!(json, r#"{"row":2,"col":3,"layer":4}"#);
+        assert_eq!(
+            serde_json::from_str::<Pos>(&json).unwrap(),
+            Pos::new(2, 3, 4)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "MAX_BLUEPRINT_DIMENSION")]
+    fn a_slot_outside_of_the_blueprint_limits_is_a_bug() {
+        Pos::new(MAX_BLUEPRINT_DIMENSION, 0, 0);
+    }
+
+    #[test]
+    fn the_default_starting_layout_keeps_the_special_keys_of_the_neo_base_layout() {
+        let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+        let base = Blueprint::read_base_layout(&None).unwrap();
+        let layout = "bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q";
+        let blueprint = base.clone().with_layout_string(layout, alphabet).unwrap();
+        assert_eq!(blueprint.layout_string(), layout);
+        assert_eq!(blueprint.get_key_pos("⇘"), base.get_key_pos("⇘"));
+    }
+
+    #[test]
+    fn a_character_outside_of_the_alphabet_must_match_the_base_layout() {
+        let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+        let base = Blueprint::read_base_layout(&None).unwrap();
+        // `⇘` and `h` swapped.
+        let layout = "bmuaz kdflvjß\ncriey ptsn⇘h\nxäüoö wg,.q";
+        let (row, key) = base.get_key_pos("⇘").unwrap();
+        let error = base
+            .clone()
+            .with_layout_string(layout, alphabet)
+            .unwrap_err();
+        assert_eq!(
+            error.0,
+            format!(
+                "Layout row 2 key {} has '⇘', which isn't in the alphabet, but the base layout has {:?} there",
+                key - 1,
+                base[row][key - 1][0]
+            )
+        );
 
-        (row_index, key_index)
+        // Without `,` in the alphabet it anchors the layout string where the base layout has it.
+        let without_comma = alphabet.replace(',', "");
+        assert!(base
+            .clone()
+            .with_layout_string("bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q", &without_comma)
+            .is_ok());
+        let problems =
+            base.check_layout_string("bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö w,g.q", &without_comma);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("has ','"), "{:?}", problems);
     }
 }
@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+use crate::layout::{Blueprint, BlueprintHelpers};
+
+/**
+How far the layout string of a result is from the layout the evolution
+started with: the keys that got another character and the characters that
+stayed on their key, the anchors of someone relearning the layout step by step.
+*/
+#[derive(Debug, Serialize)]
+pub struct LayoutDiff {
+    /// The number of keys of the layout string with another character than in the starting layout.
+    pub moved_keys: usize,
+    /// The characters on the same key as in the starting layout, in the order of the layout string.
+    pub anchors: Vec<String>,
+    /// Every row of the result, with the columns whose character moved.
+    #[serde(skip)]
+    rows: Vec<(String, Vec<bool>)>,
+}
+
+impl LayoutDiff {
+    /// Compare layer 0 of `result` to `start` key by key.
+    pub fn new(start: &Blueprint, result: &Blueprint) -> Self {
+        let mut diff = LayoutDiff {
+            moved_keys: 0,
+            anchors: vec![],
+            rows: vec![],
+        };
+        let start = start.layout_string();
+        let result = result.layout_string();
+        for (start_row, result_row) in start.lines().zip(result.lines()) {
+            let mut moved = vec![];
+            for (start_char, result_char) in start_row.chars().zip(result_row.chars()) {
+                let same = start_char == result_char;
+                if !same {
+                    diff.moved_keys += 1;
+                } else if !result_char.is_whitespace() {
+                    diff.anchors.push(result_char.to_string());
+                }
+                moved.push(!same);
+            }
+            diff.rows.push((result_row.to_string(), moved));
+        }
+        diff
+    }
+
+    /**
+    The rows of the result, each followed by a line with a `^` under every
+    moved key, unless none of its keys moved. `width` is the number of columns
+    a character is shown in, so the marks stay aligned when the characters are
+    rendered as ASCII.
+    */
+    pub fn marked_lines(&self, width: &dyn Fn(char) -> usize) -> Vec<String> {
+        let mut lines = vec![];
+        for (row, moved) in &self.rows {
+            lines.push(row.clone());
+            if !moved.contains(&true) {
+                continue;
+            }
+            let marks: String = row
+                .chars()
+                .zip(moved)
+                .map(|(char, &moved)| {
+                    let mark = if moved { "^" } else { " " };
+                    format!("{:<width$}", mark, width = width(char))
+                })
+                .collect();
+            lines.push(marks.trim_end().to_string());
+        }
+        lines
+    }
+}
@@ -0,0 +1,245 @@
+/*!
+The keyboard layout optimizer behind the `klo` command line tool.
+
+Load the ngrams of your texts with [`ngrams::NGrams`], describe a layout with a
+[`layout::Blueprint`], score it with [`scoring::cost_breakdown`] and evolve a
+better one with [`optimizer::evolve_a_layout`]. The options of every step are
+the [`klo_options::KloOptions`] of the command line, so a program embedding klo
+configures it the same way a klo.toml does. To evolve layouts for another
+metric, implement [`scoring::CostModel`] and pass it to
+[`optimizer::evolve_with_model`].
+
+A program that only scores layouts can leave out the command line with
+`default-features = false, features = ["scoring"]`: ngrams, layouts,
+geometries and the cost stay, the options and the evolution need the `cli`
+feature. `parallel`, `text-corpus` and `exporters` add rayon, counting text
+corpora and `klo export`.
+*/
+#![deny(missing_docs)]
+
+#[cfg(feature = "cli")]
+mod alphabet;
+#[cfg(feature = "cli")]
+mod analyze;
+/// Counters of the anneal levels of an evolution.
+pub mod anneal_stats;
+#[cfg(feature = "cli")]
+mod best_stream;
+#[cfg(feature = "cli")]
+mod checkpoint;
+#[cfg(feature = "cli")]
+mod completions;
+/// Layering klo.toml, `KLO_` environment variables and the command line.
+#[cfg(feature = "cli")]
+pub mod config_file;
+#[cfg(feature = "cli")]
+mod config_init;
+#[cfg(feature = "cli")]
+mod constraints;
+/// The best cost of evolution runs at logarithmically spaced steps, for plotting.
+pub mod convergence;
+#[cfg(feature = "cli")]
+mod corpus_matrix;
+#[cfg(feature = "cli")]
+mod dry_run;
+/// The errors of klo.
+pub mod error;
+/// The exit codes of klo.
+pub mod exit_code;
+#[cfg(feature = "cli")]
+mod explain;
+#[cfg(all(feature = "cli", feature = "exporters"))]
+mod export;
+/// Which fingers type which keys.
+pub mod geometry;
+#[cfg(feature = "cli")]
+mod heatmap;
+#[cfg(feature = "cli")]
+mod html;
+#[cfg(feature = "cli")]
+mod interactive;
+/// Compact ids for the characters of the ngrams.
+pub mod interner;
+#[cfg(feature = "cli")]
+mod interrupt;
+/// The key cost table of --key-costs.
+pub mod key_costs;
+/// The options of klo and their validation.
+#[cfg(feature = "cli")]
+pub mod klo_options;
+/// Blueprints, layout strings and the built-in layouts.
+pub mod layout;
+#[cfg(feature = "cli")]
+mod layout_diff;
+#[cfg(feature = "cli")]
+mod listing;
+/// What the log lines of the current thread belong to.
+pub mod log_context;
+#[cfg(feature = "cli")]
+mod markdown;
+#[cfg(feature = "cli")]
+mod ngram_tools;
+/// Loading and mixing the ngrams of texts.
+pub mod ngrams;
+/// Evolving layouts.
+#[cfg(feature = "cli")]
+pub mod optimizer;
+/// Rendering console output as Unicode or ASCII.
+pub mod output_profile;
+#[cfg(all(feature = "cli", feature = "exporters"))]
+mod png;
+/// The timing table of --profile.
+pub mod profile;
+/// The progress events of an evolution and the channel they are sent over.
+#[cfg(feature = "cli")]
+pub mod progress;
+#[cfg(feature = "cli")]
+mod report;
+/// What a result was made with, for the header of every file klo writes one to.
+#[cfg(feature = "cli")]
+pub mod repro;
+/// The spread of the final costs of the runs of an evolution.
+pub mod run_summary;
+#[cfg(feature = "cli")]
+mod runs_csv;
+/// The cost of a layout.
+pub mod scoring;
+#[cfg(feature = "cli")]
+mod simulate;
+/// Finger loads, same finger bigrams and other statistics of a layout.
+pub mod statistics;
+/// The best layouts of an evolution.
+pub mod top_layouts;
+/// The weights of the cost components.
+pub mod weights;
+
+#[cfg(feature = "cli")]
+use std::sync::Arc;
+
+#[cfg(feature = "cli")]
+use log::{debug, error, warn};
+
+#[cfg(feature = "cli")]
+use crate::{
+    config_file::ValueSources,
+    error::KloError,
+    interrupt::InterruptWatcher,
+    key_costs::KeyCosts,
+    klo_options::{Command, KloOptions},
+    weights::CostWeights,
+};
+
+/**
+Run the subcommand of `options`, like the klo binary does after parsing the
+command line with its config layers into `options` and `sources` and setting
+up the logger. `legacy_invocation` tells that klo was called without a
+subcommand, which is deprecated.
+*/
+#[cfg(feature = "cli")]
+pub fn run(
+    options: KloOptions,
+    sources: &ValueSources,
+    legacy_invocation: bool,
+) -> Result<(), KloError> {
+    // A pool of its own instead of the global one, which can only be built
+    // once, so every call gets the --threads it asks for. 0 makes rayon pick
+    // the number of cores itself.
+    #[cfg(feature = "parallel")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.threads)
+            .build()
+            .map_err(|e| format!("Unable to start the thread pool: {}", e))?;
+        pool.install(|| {
+            let _subcommand = log_context::enter_subcommand();
+            debug!(
+                "Using {} threads for parallel work.",
+                rayon::current_num_threads()
+            );
+            run_subcommand(options, sources, legacy_invocation)
+        })
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        debug!("Built without the parallel feature, --threads is ignored.");
+        run_subcommand(options, sources, legacy_invocation)
+    }
+}
+
+/// `run` in the thread pool of the --threads.
+#[cfg(feature = "cli")]
+fn run_subcommand(
+    mut options: KloOptions,
+    sources: &ValueSources,
+    legacy_invocation: bool,
+) -> Result<(), KloError> {
+    if legacy_invocation {
+        warn!("Running klo without a subcommand is deprecated and will stop working in the next release. Use `klo evolve` instead.");
+    }
+
+    options.alphabet = alphabet::expand_alphabet(&options.alphabet).map_err(KloError::Config)?;
+    options.cost_weights =
+        CostWeights::load(options.weights.as_deref(), &options.weight).map_err(KloError::Config)?;
+    options.cost_weights.hand_target = options.hand_target;
+    options.cost_weights.missing_char_cost = options.missing_char_cost;
+    debug!("Cost weights: {:?}", options.cost_weights.to_map());
+    if let Err(errors) = options.validate() {
+        for error in &errors {
+            error!("{}", error);
+        }
+        return Err(KloError::Options(errors));
+    }
+    let key_costs = match &options.key_costs {
+        Some(path) => KeyCosts::load(path).map_err(KloError::Config)?,
+        None => KeyCosts::default(),
+    };
+    options.cost_weights.key_costs = Arc::new(KeyCosts {
+        off_table_cost: options.off_table_cost,
+        ..key_costs
+    });
+
+    match options.command.take() {
+        Some(Command::Evolve(mut evolve)) => {
+            evolve.derive_effective_options();
+            debug!(
+                "Effective configuration:\n{}",
+                config_file::effective_config(&options, Some(&evolve), sources)
+            );
+            if evolve.dry_run {
+                dry_run::dry_run(&options, &evolve)
+            } else {
+                let (progress, consumer) = progress::report_to_stdout(evolve.progress_format);
+                let interrupts = InterruptWatcher::start(progress.stop_handle());
+                let result = optimizer::evolve_a_layout(&options, &evolve, Some(&progress));
+                let interrupted = interrupts.finish();
+                // The final result is printed after every event of the runs.
+                drop(progress);
+                consumer
+                    .join()
+                    .expect("printing the progress doesn't panic");
+                let result = result?;
+                report::report_evolution(&options, &evolve, &result)?;
+                let stats = &result.stats;
+                if interrupted && stats.completed_runs < stats.runs {
+                    return Err(KloError::Interrupted {
+                        completed_runs: stats.completed_runs,
+                        runs: stats.runs,
+                    });
+                }
+                Ok(())
+            }
+        }
+        Some(Command::Analyze(analyze)) => analyze::analyze_layout(&options, &analyze),
+        Some(Command::Compare(compare)) => analyze::compare_layouts(&options, &compare),
+        Some(Command::Ngrams(ngrams)) => ngram_tools::run(&options, &ngrams),
+        #[cfg(feature = "exporters")]
+        Some(Command::Export(export)) => export::export_layout(&options, &export),
+        Some(Command::Completions(completions)) => {
+            completions::print_completions(completions.shell);
+            Ok(())
+        }
+        Some(Command::List(list)) => listing::print_list(&options, list.topic),
+        Some(Command::Config(config)) => config_init::run(&config),
+        None => unreachable!("the legacy fallback always selects a subcommand"),
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    error::KloError,
+    exit_code::ExitCode,
+    geometry::{Geometry, KeyPos},
+    klo_options::{KloOptions, ListTopic},
+    layout::{Blueprint, BlueprintHelpers, BUILTIN_BASE_LAYOUTS},
+    output_profile,
+};
+
+/// Print the requested list to stdout. None of them needs ngram data.
+pub fn print_list(options: &KloOptions, topic: ListTopic) -> Result<(), KloError> {
+    let list = match topic {
+        ListTopic::BaseLayouts => base_layouts(),
+        ListTopic::Positions => positions(&selected_geometry(options)?),
+        ListTopic::Fingers => fingers(&selected_geometry(options)?),
+        ListTopic::ExitCodes => exit_codes(),
+    };
+    print!("{}", output_profile::render(&list));
+    Ok(())
+}
+
+/// The geometry an evolution with these options would use.
+fn selected_geometry(options: &KloOptions) -> Result<Geometry, KloError> {
+    let blueprint = Blueprint::try_get_base_layout(&options.base_layout)?;
+    Ok(Geometry::from_options(options, &blueprint)?)
+}
+
+fn base_layouts() -> String {
+    let width = BUILTIN_BASE_LAYOUTS
+        .iter()
+        .map(|builtin| builtin.name.len())
+        .max()
+        .unwrap_or(0);
+    BUILTIN_BASE_LAYOUTS
+        .iter()
+        .enumerate()
+        .map(|(index, builtin)| {
+            let default = if index == 0 { " (default)" } else { "" };
+            format!(
+                "{:width$}  {}{}\n",
+                builtin.name,
+                builtin.description,
+                default,
+                width = width
+            )
+        })
+        .collect()
+}
+
+/// One line per row of the blueprint, with every column as wide as its longest name.
+fn positions(geometry: &Geometry) -> String {
+    // Keys shared by several fingers (like the thumb key) show all their names.
+    let mut names: BTreeMap<KeyPos, String> = BTreeMap::new();
+    for (name, pos) in geometry.position_names() {
+        names
+            .entry(pos)
+            .and_modify(|names| *names = format!("{}/{}", names, name))
+            .or_insert(name);
+    }
+    let rows = names.keys().map(|(row, _)| row + 1).max().unwrap_or(0);
+    let columns = names.keys().map(|(_, key)| key + 1).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|key| {
+            (0..rows)
+                .filter_map(|row| names.get(&(row, key)))
+                .map(String::len)
+                .max()
+                .unwrap_or(1)
+        })
+        .collect();
+
+    let mut grid = format!("Positions of the {} geometry:\n", geometry.name);
+    for row in 0..rows {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(key, &width)| {
+                let name = names.get(&(row, key)).map_or("-", String::as_str);
+                format!("{:width$}", name, width = width)
+            })
+            .collect();
+        grid.push_str(cells.join(" | ").trim_end());
+        grid.push('\n');
+    }
+    grid
+}
+
+fn fingers(geometry: &Geometry) -> String {
+    // The names come finger by finger, in the order of their positions.
+    let mut position_names = geometry.position_names().into_iter();
+    geometry
+        .fingers
+        .iter()
+        .map(|finger| {
+            let positions: Vec<String> = position_names
+                .by_ref()
+                .take(finger.positions.len())
+                .map(|(name, pos)| format!("{} {:?}", name, pos))
+                .collect();
+            format!("{}: {}\n", finger.name, positions.join(", "))
+        })
+        .collect()
+}
+
+fn exit_codes() -> String {
+    ExitCode::ALL
+        .iter()
+        .map(|code| format!("{:>3}  {}\n", code.code(), code.description()))
+        .collect()
+}
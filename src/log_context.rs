@@ -0,0 +1,87 @@
+use std::cell::Cell;
+
+thread_local! {
+    static RUN: Cell<Option<u128>> = const { Cell::new(None) };
+    static SUBCOMMAND: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the log lines of the current thread with a run until it is dropped.
+pub struct RunScope {
+    previous: Option<u128>,
+}
+
+impl Drop for RunScope {
+    fn drop(&mut self) {
+        RUN.with(|run| run.set(self.previous));
+    }
+}
+
+/// Mark the log lines of the current thread with `run` until the returned scope is dropped.
+pub fn enter_run(run: u128) -> RunScope {
+    RunScope {
+        previous: RUN.with(|current| current.replace(Some(run))),
+    }
+}
+
+/// The run the current thread is working on, if any.
+pub fn current_run() -> Option<u128> {
+    RUN.with(Cell::get)
+}
+
+/// Marks the current thread as the one running the subcommand until it is dropped.
+pub struct SubcommandScope {
+    previous: bool,
+}
+
+impl Drop for SubcommandScope {
+    fn drop(&mut self) {
+        SUBCOMMAND.with(|subcommand| subcommand.set(self.previous));
+    }
+}
+
+/**
+Mark the current thread as the one running the subcommand until the returned
+scope is dropped. `klo::run` runs it on a thread of its pool, whose log lines
+only name the thread while it works on a run.
+*/
+pub fn enter_subcommand() -> SubcommandScope {
+    SubcommandScope {
+        previous: SUBCOMMAND.with(|subcommand| subcommand.replace(true)),
+    }
+}
+
+/// Whether the current thread is the one running the subcommand.
+pub fn runs_subcommand() -> bool {
+    SUBCOMMAND.with(Cell::get)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn a_run_scope_marks_its_thread_until_it_is_dropped() {
+        assert_eq!(current_run(), None);
+        let outer = enter_run(1);
+        {
+            let _inner = enter_run(2);
+            assert_eq!(current_run(), Some(2));
+            assert_eq!(thread::spawn(current_run).join().unwrap(), None);
+        }
+        assert_eq!(current_run(), Some(1));
+        drop(outer);
+        assert_eq!(current_run(), None);
+    }
+
+    #[test]
+    fn a_subcommand_scope_marks_its_thread_until_it_is_dropped() {
+        assert!(!runs_subcommand());
+        let scope = enter_subcommand();
+        assert!(runs_subcommand());
+        assert!(!thread::spawn(runs_subcommand).join().unwrap());
+        drop(scope);
+        assert!(!runs_subcommand());
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    cmp::max,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use simple_logger::SimpleLogger;
+
+use klo::{
+    log_context,
+    output_profile::{self, OutputProfile},
+};
+
+/// How many rotated logs are kept next to the log file, as `<log file>.1` (the newest) and up.
+const KEPT_ROTATED_LOGS: usize = 5;
+
+/// Sends every log record to the console and, if configured, to a log file with its own level.
+pub struct Dispatcher {
+    console: SimpleLogger,
+    file: Option<(LevelFilter, Mutex<LogFile>)>,
+}
+
+impl Dispatcher {
+    /**
+    Install the dispatcher as the global logger. The log file, if any, receives
+    the records up to `file_level` and is rotated once it grows beyond
+    `rotate_mb` megabytes.
+    */
+    pub fn init(
+        console_level: LevelFilter,
+        log_file: Option<&str>,
+        file_level: LevelFilter,
+        rotate_mb: Option<u64>,
+    ) -> Result<(), String> {
+        // Rotating at 0 bytes would rotate away the old logs line by line before --log-rotate-mb is validated.
+        let rotate_at = rotate_mb
+            .filter(|&mb| mb > 0)
+            .map(|mb| mb.saturating_mul(1024 * 1024));
+        let file = match log_file {
+            Some(path) => Some((
+                file_level,
+                Mutex::new(
+                    LogFile::open(path, rotate_at)
+                        .map_err(|e| format!("Unable to open log file {}: {}", path, e))?,
+                ),
+            )),
+            None => None,
+        };
+
+        let max_level = match &file {
+            Some((file_level, _)) => max(console_level, *file_level),
+            None => console_level,
+        };
+        let dispatcher = Dispatcher {
+            console: SimpleLogger::new().with_level(console_level),
+            file,
+        };
+
+        log::set_boxed_logger(Box::new(dispatcher)).map_err(|e: SetLoggerError| e.to_string())?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for Dispatcher {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let file_enabled = matches!(&self.file, Some((level, _)) if metadata.level() <= *level);
+        file_enabled || self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        match output_profile::current() {
+            OutputProfile::Unicode => self.console.log(record),
+            OutputProfile::Ascii => {
+                let message = record.args().to_string();
+                self.console.log(
+                    &Record::builder()
+                        .args(format_args!("{}", output_profile::render(&message)))
+                        .metadata(record.metadata().clone())
+                        .module_path(record.module_path())
+                        .file(record.file())
+                        .line(record.line())
+                        .build(),
+                );
+            }
+        }
+
+        if let Some((level, file)) = &self.file {
+            if record.level() > *level {
+                return;
+            }
+            let line = format_line(
+                &Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string(),
+                record.level(),
+                log_context::current_run(),
+                current_thread_index(),
+                record.target(),
+                &record.args().to_string(),
+            );
+            // A failing log file must not take the optimization down with it.
+            let _ = file.lock().unwrap().write_line(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((_, file)) = &self.file {
+            let _ = file.lock().unwrap().file.flush();
+        }
+    }
+}
+
+/// The rayon worker the current thread is, if any, leaving out the one running the subcommand outside of runs.
+fn current_thread_index() -> Option<usize> {
+    #[cfg(feature = "parallel")]
+    return rayon::current_thread_index()
+        .filter(|_| !log_context::runs_subcommand() || log_context::current_run().is_some());
+    #[cfg(not(feature = "parallel"))]
+    None
+}
+
+/**
+A line of the log file: the ISO-8601 timestamp, the level, the run and the
+worker thread if the record comes from one, the module and the message.
+*/
+fn format_line(
+    timestamp: &str,
+    level: Level,
+    run: Option<u128>,
+    thread: Option<usize>,
+    target: &str,
+    message: &str,
+) -> String {
+    let mut line = format!("{} {:<5} ", timestamp, level);
+    if let Some(run) = run {
+        line.push_str(&format!("[run {}] ", run));
+    }
+    if let Some(thread) = thread {
+        line.push_str(&format!("[thread {}] ", thread));
+    }
+    line.push_str(&format!("[{}] {}", target, message));
+    line
+}
+
+/// The log file, which is rotated once it would grow beyond `rotate_at` bytes.
+struct LogFile {
+    path: String,
+    file: File,
+    size: u64,
+    rotate_at: Option<u64>,
+}
+
+impl LogFile {
+    fn open(path: &str, rotate_at: Option<u64>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            path: path.to_string(),
+            file,
+            size,
+            rotate_at,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self
+            .rotate_at
+            .is_some_and(|limit| self.size > 0 && self.size + length > limit)
+        {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += length;
+        Ok(())
+    }
+
+    /// Move every rotated log up by one, dropping the oldest, and start over with an empty file.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let rotated = |index: usize| format!("{}.{}", self.path, index);
+        for index in (1..KEPT_ROTATED_LOGS).rev() {
+            if Path::new(&rotated(index)).exists() {
+                fs::rename(rotated(index), rotated(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn a_log_line_has_the_run_and_the_thread_only_if_there_are_any() {
+        let timestamp = "2021-09-20T18:30:00.000+02:00";
+        assert_eq!(
+            format_line(timestamp, Level::Info, None, None, "klo", "Starting"),
+            "2021-09-20T18:30:00.000+02:00 INFO  [klo] Starting"
+        );
+        assert_eq!(
+            format_line(
+                timestamp,
+                Level::Debug,
+                Some(12),
+                Some(3),
+                "klo::optimizer",
+                "New best"
+            ),
+            "2021-09-20T18:30:00.000+02:00 DEBUG [run 12] [thread 3] [klo::optimizer] New best"
+        );
+        assert_eq!(
+            format_line(timestamp, Level::Warn, None, Some(0), "klo", "Slow"),
+            "2021-09-20T18:30:00.000+02:00 WARN  [thread 0] [klo] Slow"
+        );
+    }
+
+    #[test]
+    fn the_log_file_is_rotated_before_it_grows_beyond_the_limit() {
+        let dir = env::temp_dir().join(format!("klo-log-rotation-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("klo.log").to_string_lossy().to_string();
+        let rotated = |index: usize| format!("{}.{}", path, index);
+
+        // Every line takes 10 bytes with its newline, so two lines fit into 25 bytes.
+        let mut log = LogFile::open(&path, Some(25)).unwrap();
+        for line in 0..2 * (KEPT_ROTATED_LOGS + 2) {
+            log.write_line(&format!("line {:04}", line)).unwrap();
+        }
+        drop(log);
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!(
+                "line {:04}\nline {:04}\n",
+                2 * KEPT_ROTATED_LOGS + 2,
+                2 * KEPT_ROTATED_LOGS + 3
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(rotated(1)).unwrap(),
+            format!(
+                "line {:04}\nline {:04}\n",
+                2 * KEPT_ROTATED_LOGS,
+                2 * KEPT_ROTATED_LOGS + 1
+            )
+        );
+        assert!(Path::new(&rotated(KEPT_ROTATED_LOGS)).exists());
+        assert!(!Path::new(&rotated(KEPT_ROTATED_LOGS + 1)).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_reopened_log_file_counts_its_old_size() {
+        let dir = env::temp_dir().join(format!("klo-log-reopen-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("klo.log").to_string_lossy().to_string();
+        fs::write(&path, "an old line\n").unwrap();
+
+        let mut log = LogFile::open(&path, Some(20)).unwrap();
+        log.write_line("line 0001").unwrap();
+        drop(log);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line 0001\n");
+        assert_eq!(
+            fs::read_to_string(format!("{}.1", path)).unwrap(),
+            "an old line\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,34 +1,74 @@
-use clap::Clap;
-use klo_options::KloOptions;
-use log::{debug, LevelFilter};
-use simple_logger::SimpleLogger;
+use klo::{
+    config_file::{self, ValueSource},
+    error::KloError,
+    exit_code::ExitCode,
+    klo_options::KloOptions,
+    output_profile::{self, OutputProfile},
+    profile,
+};
+use log::{debug, error, LevelFilter};
+use logger::Dispatcher;
+use std::time::Instant;
 
-mod check_neo;
-mod klo_options;
-mod layout;
-mod ngrams;
+mod logger;
 
 fn main() {
-    let mut options = KloOptions::parse();
-    options.post_parse_checks();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let code = ExitCode::InternalError;
+        eprintln!("Exit code {}: {}", code.code(), code.description());
+    }));
 
-    if options.quiet {
-        SimpleLogger::new()
-            .with_level(LevelFilter::Warn)
-            .init()
-            .unwrap();
+    let code = match run() {
+        Ok(()) => ExitCode::Success,
+        Err(error) => {
+            let code = error.exit_code();
+            // Before the logger is set up, errors are printed directly.
+            if log::max_level() == LevelFilter::Off {
+                eprintln!("{}", error);
+                eprintln!("Exit code {}: {}", code.code(), code.description());
+            } else {
+                error!("{}", error);
+                error!("Exit code {}: {}", code.code(), code.description());
+            }
+            code
+        }
+    };
+    profile::log_table();
+    std::process::exit(code.code());
+}
+
+fn run() -> Result<(), KloError> {
+    let start = Instant::now();
+    let (mut options, matches, legacy_invocation) = KloOptions::parse_with_legacy_fallback();
+    let sources =
+        config_file::apply_config_layers(&mut options, &matches).map_err(KloError::Config)?;
+    if options.profile {
+        profile::enable();
+        profile::record("config parse", start.elapsed());
+    }
+
+    output_profile::init(OutputProfile::detect(
+        options.ascii,
+        !matches!(sources.get("ascii"), ValueSource::Default),
+    ));
+    let console_level = if options.quiet {
+        LevelFilter::Warn
     } else if options.verbose {
-        SimpleLogger::new()
-            .with_level(LevelFilter::Trace)
-            .init()
-            .unwrap();
-        debug!("Verbose mode is on - going to talk to you a lot.")
+        LevelFilter::Trace
     } else {
-        SimpleLogger::new()
-            .with_level(LevelFilter::Info)
-            .init()
-            .unwrap();
+        LevelFilter::Info
+    };
+    Dispatcher::init(
+        console_level,
+        options.log_file.as_deref(),
+        options.log_level.to_level_filter(),
+        options.log_rotate_mb,
+    )?;
+    if options.verbose {
+        debug!("Verbose mode is on - going to talk to you a lot.");
     }
 
-    check_neo::evolve_a_layout(&options);
+    klo::run(options, &sources, legacy_invocation)
 }
@@ -0,0 +1,331 @@
+use std::fmt::Write;
+
+use crate::{
+    report::{JsonCost, JsonResult},
+    statistics::LayoutStatistics,
+};
+
+/**
+The markdown report of `--output-format markdown`: the layout in a fenced code
+block, the cost, the statistics and the comparisons as tables and the command
+of the run in a code span. It is rendered from the same `JsonResult` as the
+json, so both always show the same numbers.
+*/
+pub fn render(result: &JsonResult) -> String {
+    let mut markdown = result.reproduction.xml_comment();
+    writeln!(markdown, "# klo result\n").unwrap();
+    writeln!(markdown, "The layout, as `--starting-layout` accepts it:\n").unwrap();
+    writeln!(markdown, "{}\n", code_block(&result.layout.layout_string)).unwrap();
+    for (layer, layer_string) in result.layout.layers.iter().filter(|(&layer, _)| layer != 0) {
+        writeln!(markdown, "Layer {}:\n", layer).unwrap();
+        writeln!(markdown, "{}\n", code_block(layer_string)).unwrap();
+    }
+    if !result.fixed.is_empty() {
+        let fixed: Vec<String> = result
+            .fixed
+            .iter()
+            .map(|(char, position)| format!("{} on {}", code_span(char), position))
+            .collect();
+        writeln!(markdown, "Fixed: {}\n", fixed.join(", ")).unwrap();
+    }
+
+    if result.diff.moved_keys == 0 {
+        writeln!(markdown, "No key moved from the starting layout.\n").unwrap();
+    } else {
+        writeln!(
+            markdown,
+            "{} keys moved from the starting layout, marked with `^`:\n",
+            result.diff.moved_keys
+        )
+        .unwrap();
+        let lines = result.diff.marked_lines(&|_| 1);
+        writeln!(markdown, "{}\n", code_block(&lines.join("\n"))).unwrap();
+        let anchors: Vec<String> = result
+            .diff
+            .anchors
+            .iter()
+            .map(|char| code_span(char))
+            .collect();
+        writeln!(
+            markdown,
+            "{} characters stayed on their key: {}\n",
+            anchors.len(),
+            anchors.join(" ")
+        )
+        .unwrap();
+    }
+
+    writeln!(markdown, "## Cost\n").unwrap();
+    writeln!(
+        markdown,
+        "Scored with the {} geometry.\n",
+        cell(result.geometry)
+    )
+    .unwrap();
+    markdown.push_str(&table(
+        &["Component", "Weight", "Cost"],
+        &[Align::Left, Align::Right, Align::Right],
+        &result
+            .cost_rows()
+            .into_iter()
+            .map(|(component, weight, cost)| {
+                vec![
+                    component.to_string(),
+                    format!("{}", weight),
+                    format!("{:.2}", cost),
+                ]
+            })
+            .chain(std::iter::once(vec![
+                "**total**".to_string(),
+                String::new(),
+                format!("**{:.2}**", result.cost.total),
+            ]))
+            .collect::<Vec<_>>(),
+    ));
+
+    writeln!(markdown, "\n## Statistics\n").unwrap();
+    markdown.push_str(&statistics(result));
+
+    if result.top_layouts.len() > 1 {
+        writeln!(markdown, "\n## Top layouts\n").unwrap();
+        for (rank, top_layout) in result.top_layouts.iter().enumerate() {
+            writeln!(
+                markdown,
+                "#{} from run {} with a total cost of {:.2}:\n",
+                rank + 1,
+                top_layout.run,
+                top_layout.cost.total
+            )
+            .unwrap();
+            writeln!(markdown, "{}\n", code_block(&top_layout.layout_string)).unwrap();
+        }
+    }
+
+    if !result.comparisons.is_empty() {
+        writeln!(markdown, "\n## Comparison\n").unwrap();
+        let result_row =
+            comparison_row("**result**", &result.cost, String::new(), result.statistics);
+        let rows: Vec<Vec<String>> = std::iter::once(result_row)
+            .chain(result.comparisons.iter().map(|comparison| {
+                comparison_row(
+                    &cell(&comparison.name),
+                    &comparison.cost,
+                    format!("{:+.2}%", comparison.delta_percent),
+                    comparison.statistics,
+                )
+            }))
+            .collect();
+        markdown.push_str(&table(
+            &["Layout", "Total cost", "Difference", "Same finger bigrams"],
+            &[Align::Left, Align::Right, Align::Right, Align::Right],
+            &rows,
+        ));
+    }
+
+    if !result.notes.is_empty() {
+        writeln!(markdown, "\n## Notes\n").unwrap();
+        for note in result.notes {
+            writeln!(
+                markdown,
+                "- {} is {} instead of {}: {}",
+                code_span(note.field),
+                code_span(&note.effective),
+                code_span(&note.requested),
+                note.reason
+            )
+            .unwrap();
+        }
+    }
+
+    writeln!(markdown, "\n## Reproduce\n").unwrap();
+    writeln!(markdown, "{}", code_span(&result.command)).unwrap();
+    markdown
+}
+
+/// The finger loads, the shares of the bigrams, the layers and the worst bigrams as tables.
+fn statistics(result: &JsonResult) -> String {
+    let statistics = result.statistics;
+    let mut markdown = table(
+        &["Finger", "Keystrokes"],
+        &[Align::Left, Align::Right],
+        &statistics
+            .finger_loads
+            .iter()
+            .map(|load| vec![cell(&load.finger), percent(load.share, 1)])
+            .collect::<Vec<_>>(),
+    );
+
+    let shares: Vec<Vec<String>> = result
+        .share_rows()
+        .into_iter()
+        .map(|(name, share)| vec![name.to_string(), share])
+        .collect();
+    markdown.push('\n');
+    markdown.push_str(&table(
+        &["Statistic", "Share"],
+        &[Align::Left, Align::Right],
+        &shares,
+    ));
+    markdown.push('\n');
+    markdown.push_str(&table(
+        &["Layer", "Keystrokes", "Most frequent characters"],
+        &[Align::Left, Align::Right, Align::Left],
+        &statistics
+            .layer_loads
+            .iter()
+            .map(|load| {
+                let characters: Vec<String> = load
+                    .characters
+                    .iter()
+                    .map(|character| {
+                        format!(
+                            "{} {}",
+                            code_span(&character.char).replace('|', "\\|"),
+                            percent(character.share, 2)
+                        )
+                    })
+                    .collect();
+                vec![
+                    load.layer.to_string(),
+                    percent(load.share, 1),
+                    characters.join(", "),
+                ]
+            })
+            .collect::<Vec<_>>(),
+    ));
+
+    if !statistics.worst_bigrams.is_empty() {
+        writeln!(
+            markdown,
+            "\nMost frequent same finger bigrams and scissors:\n"
+        )
+        .unwrap();
+        markdown.push_str(&table(
+            &["Bigram", "Kind", "Bigrams"],
+            &[Align::Left, Align::Left, Align::Right],
+            &statistics
+                .worst_bigrams
+                .iter()
+                .map(|worst| {
+                    // A pipe ends the cell even in a code span.
+                    vec![
+                        code_span(&worst.bigram).replace('|', "\\|"),
+                        worst.kind.label().to_string(),
+                        percent(worst.share, 3),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ));
+    }
+
+    if !result.missing_characters.is_empty() {
+        writeln!(markdown, "\nCharacters on no key of the layout:\n").unwrap();
+        markdown.push_str(&table(
+            &["Character", "Keystrokes"],
+            &[Align::Left, Align::Right],
+            &result
+                .missing_characters
+                .iter()
+                .map(|missing| {
+                    vec![
+                        code_span(&missing.char).replace('|', "\\|"),
+                        percent(missing.share, 3),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ));
+    }
+    markdown
+}
+
+/// A row of the comparison table.
+fn comparison_row(
+    name: &str,
+    cost: &JsonCost,
+    difference: String,
+    statistics: &LayoutStatistics,
+) -> Vec<String> {
+    vec![
+        name.to_string(),
+        format!("{:.2}", cost.total),
+        difference,
+        percent(statistics.same_finger_bigrams, 2),
+    ]
+}
+
+/// How the cells of a table column are aligned.
+#[derive(Clone, Copy)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A markdown table. The cells are already escaped.
+pub fn table(header: &[&str], align: &[Align], rows: &[Vec<String>]) -> String {
+    let line = |cells: &[String]| format!("| {} |\n", cells.join(" | "));
+    let mut markdown = line(
+        &header
+            .iter()
+            .map(|&name| name.to_string())
+            .collect::<Vec<_>>(),
+    );
+    markdown.push_str(&line(
+        &align
+            .iter()
+            .map(|align| match align {
+                Align::Left => "---".to_string(),
+                Align::Right => "---:".to_string(),
+            })
+            .collect::<Vec<_>>(),
+    ));
+    for row in rows {
+        markdown.push_str(&line(row));
+    }
+    markdown
+}
+
+fn percent(share: f64, decimals: usize) -> String {
+    format!("{:.*}%", decimals, share * 100.0)
+}
+
+/// Text in a table cell, with the characters markdown would interpret escaped.
+pub fn cell(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if "\\|`*_[]<>#".contains(char) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+/// The length of the longest run of backticks in `text`.
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|char| char != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Text in a code span, with a fence longer than any backticks in it.
+fn code_span(text: &str) -> String {
+    let fence = "`".repeat(longest_backtick_run(text) + 1);
+    let padding = if text.starts_with('`') || text.ends_with('`') || text.starts_with(' ') {
+        " "
+    } else {
+        ""
+    };
+    format!(
+        "{fence}{padding}{}{padding}{fence}",
+        text,
+        fence = fence,
+        padding = padding
+    )
+}
+
+/// Text in a fenced code block, with a fence longer than any backticks in it.
+fn code_block(text: &str) -> String {
+    let fence = "`".repeat((longest_backtick_run(text) + 1).max(3));
+    format!("{}text\n{}\n{}", fence, text.trim_end_matches('\n'), fence)
+}
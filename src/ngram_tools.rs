@@ -0,0 +1,49 @@
+use log::info;
+
+use crate::{
+    error::KloError,
+    klo_options::{KloOptions, NgramsCommand, NgramsOptions},
+    ngrams::NGrams,
+    output_profile,
+};
+
+/// Run one of the `klo ngrams` subcommands.
+pub fn run(options: &KloOptions, ngrams_options: &NgramsOptions) -> Result<(), KloError> {
+    match &ngrams_options.command {
+        #[cfg(feature = "text-corpus")]
+        NgramsCommand::Generate(generate) => {
+            let ngram_data = NGrams::from_text_file(&generate.text)?;
+            ngram_data.write_pregenerated(&generate.output_dir, None)?;
+            info!(
+                "Wrote the ngrams of {} to {}",
+                generate.text, generate.output_dir
+            );
+        }
+        NgramsCommand::Top(top) => {
+            let mut ngram_data = NGrams::from_options(options)?;
+            ngram_data.sort_by_frequency();
+            let names = ["letters", "bigrams", "trigrams"];
+            for (name, ngrams) in names.iter().zip(ngram_data.texts()) {
+                println!("Top {} {}:", top.count, name);
+                for (ngram, weight) in ngrams.iter().take(top.count) {
+                    let line = format!("{:>16.2} {:?}", weight, ngram);
+                    println!("{}", output_profile::render(&line));
+                }
+            }
+        }
+        NgramsCommand::Export(export) => {
+            let ngram_data = NGrams::from_options(options)?;
+            ngram_data.write_pregenerated(&export.output_dir, Some(export.keystrokes))?;
+            let source = if options.corpus.is_empty() {
+                options.ngrams_config.clone()
+            } else {
+                options.corpus.join(", ")
+            };
+            info!(
+                "Wrote the mixed ngrams of {} to {}",
+                source, export.output_dir
+            );
+        }
+    }
+    Ok(())
+}
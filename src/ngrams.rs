@@ -1,22 +1,39 @@
+#[cfg(feature = "text-corpus")]
 use lazy_static::lazy_static;
 use log::{debug, warn};
-use rayon::iter::ParallelIterator;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+#[cfg(feature = "text-corpus")]
 use regex::Regex;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{fs, vec};
 
+#[cfg(feature = "cli")]
+use crate::klo_options::KloOptions;
+use crate::{
+    error::{KloError, NgramsError},
+    interner::Interner,
+    profile,
+};
+
 /**
 NGrams contains ngrams from various sources in raw and weighted
 form and can export them to the simple (1gramme.txt, 2gramme.txt,
 3gramme.txt) form with a given number of total keystrokes.
 */
 pub struct NGrams {
-    pub letters: Vec<(String, f64)>,
-    pub bigrams: Vec<(String, f64)>,
-    pub trigrams: Vec<(String, f64)>,
+    /// Single characters and their weighted frequency.
+    pub letters: Vec<(u16, f64)>,
+    /// Pairs of characters and their weighted frequency.
+    pub bigrams: Vec<([u16; 2], f64)>,
+    /// Triples of characters and their weighted frequency.
+    pub trigrams: Vec<([u16; 3], f64)>,
+    /// The characters behind the ids of the ngrams.
+    pub interner: Arc<Interner>,
 }
 
 struct RawNGrams {
@@ -34,9 +51,49 @@ struct NormalizedNGrams {
 }
 
 impl NGrams {
-    pub fn from_config(path: &str) -> Result<NGrams, String> {
+    /// Load the ngrams of the --corpus files if any are given, otherwise those of --ngrams-config.
+    #[cfg(feature = "cli")]
+    pub fn from_options(options: &KloOptions) -> Result<NGrams, NgramsError> {
+        if options.corpus.is_empty() {
+            Self::from_config(&options.ngrams_config, options.ngrams_paths_from_cwd)
+        } else {
+            let config = corpus_config(&options.corpus).map_err(NgramsError)?;
+            Self::from_config_str(&config, "--corpus", Path::new("")).map_err(NgramsError)
+        }
+    }
+
+    /// Like `check_config` for the ngrams `from_options` would load.
+    #[cfg(feature = "cli")]
+    pub fn check_options(options: &KloOptions) -> Vec<String> {
+        if options.corpus.is_empty() {
+            return Self::check_config(&options.ngrams_config, options.ngrams_paths_from_cwd);
+        }
+        match corpus_config(&options.corpus) {
+            Ok(config) => Self::check_config_str(&config, "--corpus", Path::new("")),
+            Err(problem) => vec![problem],
+        }
+    }
+
+    /**
+    Load and mix the sources of the ngrams config at `path`. Its relative paths
+    are relative to the directory of the config, or to the working directory
+    with `paths_from_cwd`.
+    */
+    pub fn from_config(path: &str, paths_from_cwd: bool) -> Result<NGrams, NgramsError> {
         debug!("Trying to open ngrams config file {}", path);
-        let config = fs::read_to_string(path).expect("Unable to open given ngrams config file.");
+        let config = fs::read_to_string(path).map_err(|e| {
+            NgramsError(format!("Unable to open the ngrams config {}: {}", path, e))
+        })?;
+        Self::from_config_str(&config, path, &config_base_dir(path, paths_from_cwd))
+            .map_err(NgramsError)
+    }
+
+    /// Parse the contents of an ngrams config. `source` names it in error messages, relative paths are relative to `base`.
+    fn from_config_str(config: &str, source: &str, base: &Path) -> Result<NGrams, String> {
+        let problems = Self::check_config_str(config, source, base);
+        if !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
 
         /*
         Originally python could parse v0.0 ngrams, but they are not part of the repo anymore
@@ -46,27 +103,184 @@ impl NGrams {
         // Remove comment lines
         let lines: Vec<&str> = config
             .lines()
-            .filter(|line| !line.starts_with("#"))
+            .filter(|line| !line.starts_with('#'))
             .collect();
 
-        let raw_vec: Vec<_> = lines
-            .into_par_iter()
-            .map(|line| Self::work_ngrams_config_line(line))
-            .collect();
+        #[cfg(feature = "parallel")]
+        let lines = lines.into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let lines = lines.into_iter();
+        let raw_vec = lines
+            .map(|line| Self::work_ngrams_config_line(line, base))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        profile::time("ngram merge", || {
+            let normalized: Vec<_> = raw_vec
+                .iter()
+                .flatten()
+                .map(Self::normalize_ngrams)
+                .collect();
 
-        let normalized: Vec<_> = raw_vec
+            Self::collect_normalized_ngrams(normalized)
+        })
+    }
+
+    /// Count the raw ngrams of a single text file.
+    #[cfg(feature = "text-corpus")]
+    pub fn from_text_file(path: &str) -> Result<NGrams, NgramsError> {
+        let raw = Self::parse_text_ngrams(1.0, path).map_err(NgramsError)?;
+        Self::intern(raw.letters, raw.bigrams, raw.trigrams).map_err(NgramsError)
+    }
+
+    /// Like `from_text_file` for a text that is already in memory, so it works without a file system.
+    #[cfg(feature = "text-corpus")]
+    pub fn from_text(text: &str) -> Result<NGrams, NgramsError> {
+        let raw = Self::count_text_ngrams(1.0, text.as_bytes());
+        Self::intern(raw.letters, raw.bigrams, raw.trigrams).map_err(NgramsError)
+    }
+
+    /**
+    The ngrams of the contents of pregenerated letter, bigram and trigram files
+    (like `klo ngrams generate` writes them) that are already in memory. The
+    counts are used as they are.
+    */
+    pub fn from_pregenerated(
+        letters: &str,
+        bigrams: &str,
+        trigrams: &str,
+    ) -> Result<NGrams, NgramsError> {
+        let parse =
+            |contents, source| Self::parse_pregenerated(contents, source).map_err(NgramsError);
+        Self::intern(
+            parse(letters, "the letters")?,
+            parse(bigrams, "the bigrams")?,
+            parse(trigrams, "the trigrams")?,
+        )
+        .map_err(NgramsError)
+    }
+
+    /**
+    Give every character of the ngrams an id. Ngrams that don't have the
+    length of their kind, which only pregenerated files can contain, are
+    skipped with a warning.
+    */
+    fn intern(
+        letters: Vec<(String, f64)>,
+        bigrams: Vec<(String, f64)>,
+        trigrams: Vec<(String, f64)>,
+    ) -> Result<Self, String> {
+        let mut interner = Interner::default();
+        let mut skipped = 0;
+        let mut intern_kind = |ngrams: Vec<(String, f64)>, length: usize| {
+            let mut interned = Vec::with_capacity(ngrams.len());
+            for (ngram, weight) in ngrams {
+                if ngram.chars().count() != length {
+                    skipped += 1;
+                    continue;
+                }
+                let ids = ngram
+                    .chars()
+                    .map(|char| interner.intern(char))
+                    .collect::<Result<Vec<u16>, String>>()?;
+                interned.push((ids, weight));
+            }
+            Ok::<_, String>(interned)
+        };
+        let letters = intern_kind(letters, 1)?
             .into_iter()
-            .filter(|raw| raw.is_some())
-            .map(|raw| {
-                let raw = raw.as_ref().unwrap();
-                Self::normalize_ngrams(raw)
-            })
+            .map(|(ids, weight)| (ids[0], weight))
+            .collect();
+        let bigrams = intern_kind(bigrams, 2)?
+            .into_iter()
+            .map(|(ids, weight)| ([ids[0], ids[1]], weight))
+            .collect();
+        let trigrams = intern_kind(trigrams, 3)?
+            .into_iter()
+            .map(|(ids, weight)| ([ids[0], ids[1], ids[2]], weight))
             .collect();
+        if skipped > 0 {
+            warn!(
+                "Skipped {} ngrams that don't have the length of their kind.",
+                skipped
+            );
+        }
+
+        Ok(NGrams {
+            letters,
+            bigrams,
+            trigrams,
+            interner: Arc::new(interner),
+        })
+    }
+
+    /// The letters, bigrams and trigrams as strings.
+    pub fn texts(&self) -> [Vec<(String, f64)>; 3] {
+        let text = |ids: &[u16]| self.interner.text(ids);
+        [
+            self.letters
+                .iter()
+                .map(|(id, weight)| (text(&[*id]), *weight))
+                .collect(),
+            self.bigrams
+                .iter()
+                .map(|(ids, weight)| (text(ids), *weight))
+                .collect(),
+            self.trigrams
+                .iter()
+                .map(|(ids, weight)| (text(ids), *weight))
+                .collect(),
+        ]
+    }
 
-        Ok(Self::collect_normalized_ngrams(normalized))
+    /// Sort the ngrams of each kind from most to least frequent.
+    pub fn sort_by_frequency(&mut self) {
+        let interner = &self.interner;
+        let by_frequency = |a: &f64, b: &f64| b.total_cmp(a);
+        self.letters.sort_by(|a, b| {
+            by_frequency(&a.1, &b.1).then_with(|| interner.char(a.0).cmp(&interner.char(b.0)))
+        });
+        self.bigrams.sort_by(|a, b| {
+            by_frequency(&a.1, &b.1).then_with(|| interner.text(&a.0).cmp(&interner.text(&b.0)))
+        });
+        self.trigrams.sort_by(|a, b| {
+            by_frequency(&a.1, &b.1).then_with(|| interner.text(&a.0).cmp(&interner.text(&b.0)))
+        });
+    }
+
+    /**
+    Write the ngrams to 1gramme.txt, 2gramme.txt and 3gramme.txt in `dir`. If
+    `keystrokes` is given, the counts of each kind are scaled to add up to it.
+    Ngrams containing line breaks can't be represented in the line based format
+    and are skipped.
+    */
+    pub fn write_pregenerated(&self, dir: &str, keystrokes: Option<f64>) -> Result<(), KloError> {
+        fs::create_dir_all(dir).map_err(|e| KloError::io("create", dir, e))?;
+
+        let filenames = ["1gramme.txt", "2gramme.txt", "3gramme.txt"];
+        for (filename, mut sorted) in filenames.iter().zip(self.texts()) {
+            sorted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            let scale = match keystrokes {
+                Some(keystrokes) => keystrokes / sorted.iter().fold(0.0, Self::fold_ngrams),
+                None => 1.0,
+            };
+
+            let path = Path::new(dir).join(filename);
+            debug!("Writing {} ngrams to {}", sorted.len(), path.display());
+            let mut file =
+                File::create(&path).map_err(|e| KloError::io("create", path.display(), e))?;
+            for (ngram, count) in sorted {
+                if ngram.contains(&['\n', '\r'][..]) {
+                    continue;
+                }
+                writeln!(file, "{} {}", (count * scale).round(), ngram)
+                    .map_err(|e| KloError::io("write", path.display(), e))?;
+            }
+        }
+        Ok(())
     }
 
-    fn collect_normalized_ngrams(normalized: Vec<NormalizedNGrams>) -> Self {
+    fn collect_normalized_ngrams(normalized: Vec<NormalizedNGrams>) -> Result<Self, String> {
         let mut letter_weight = HashMap::new();
         let mut bigram_weight = HashMap::new();
         let mut trigram_weight = HashMap::new();
@@ -85,26 +299,11 @@ impl NGrams {
             }
         }
 
-        let mut letters = vec![];
-        letter_weight.into_iter().for_each(|(letter, num)| {
-            letters.push((letter, num));
-        });
-
-        let mut bigrams = vec![];
-        bigram_weight.into_iter().for_each(|(bigram, num)| {
-            bigrams.push((bigram, num));
-        });
-
-        let mut trigrams = vec![];
-        trigram_weight.into_iter().for_each(|(trigram, num)| {
-            trigrams.push((trigram, num));
-        });
-
-        NGrams {
-            letters,
-            bigrams,
-            trigrams,
-        }
+        Self::intern(
+            sorted_by_frequency(letter_weight),
+            sorted_by_frequency(bigram_weight),
+            sorted_by_frequency(trigram_weight),
+        )
     }
 
     fn normalize_ngrams(ngrams: &RawNGrams) -> NormalizedNGrams {
@@ -116,19 +315,19 @@ impl NGrams {
         let normalized_letters: Vec<_> = ngrams
             .letters
             .iter()
-            .map(|(letter, number)| (letter.clone(), *number as f64 / total as f64))
+            .map(|(letter, number)| (letter.clone(), *number / total))
             .collect();
 
         let normalized_bigrams: Vec<_> = ngrams
             .bigrams
             .iter()
-            .map(|(bigram, number)| (bigram.clone(), *number as f64 / total as f64))
+            .map(|(bigram, number)| (bigram.clone(), *number / total))
             .collect();
 
         let normalized_trigrams: Vec<_> = ngrams
             .trigrams
             .iter()
-            .map(|(trigram, number)| (trigram.clone(), *number as f64 / total as f64))
+            .map(|(trigram, number)| (trigram.clone(), *number / total))
             .collect();
 
         NormalizedNGrams {
@@ -143,32 +342,95 @@ impl NGrams {
         sum + ngram.1
     }
 
-    fn work_ngrams_config_line(line: &str) -> Option<RawNGrams> {
-        let line_array = line.split(" ");
-        let parts: Vec<&str> = line_array
-            .filter(|part| part.to_string() != "".to_string())
-            .collect();
+    /**
+    Check an ngrams config without parsing the corpora: every line must be
+    well-formed and every referenced file must exist. Only the first line of
+    each file is read. Returns every problem found.
+    */
+    pub fn check_config(path: &str, paths_from_cwd: bool) -> Vec<String> {
+        match fs::read_to_string(path) {
+            Ok(config) => {
+                Self::check_config_str(&config, path, &config_base_dir(path, paths_from_cwd))
+            }
+            Err(e) => vec![format!("Unable to open ngrams config {}: {}", path, e)],
+        }
+    }
+
+    fn check_config_str(config: &str, source: &str, base: &Path) -> Vec<String> {
+        let mut problems = vec![];
+        for (idx, line) in config.lines().enumerate() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let location = format!("{} line {}", source, idx + 1);
+            let config_line = match ConfigLine::parse(line, base) {
+                Ok(config_line) => config_line,
+                Err(problem) => {
+                    problems.push(format!("{}: {}", location, problem));
+                    continue;
+                }
+            };
+
+            for data_path in &config_line.paths {
+                let first_line = File::open(data_path).and_then(|f| {
+                    let mut first_line = vec![];
+                    BufReader::new(f).read_until(b'\n', &mut first_line)?;
+                    Ok(first_line)
+                });
+                match first_line {
+                    Err(e) => {
+                        problems.push(format!("{}: unable to open {}: {}", location, data_path, e))
+                    }
+                    Ok(first_line) if config_line.datatype == "pregenerated" => {
+                        let first_line =
+                            String::from_utf8_lossy(&first_line).replace('\u{feff}', "");
+                        let count = first_line.split(' ').find(|part| !part.is_empty());
+                        if !first_line.is_empty()
+                            && count.is_none_or(|count| count.parse::<f64>().is_err())
+                        {
+                            problems.push(format!(
+                                "{}: {} doesn't start with `count ngram`",
+                                location, data_path
+                            ));
+                        }
+                    }
+                    Ok(_) => {}
+                }
+            }
+        }
+        problems
+    }
 
-        let weight = parts[0].parse::<f64>().unwrap();
-        let datatype = parts[1];
-        let datapath = parts[2];
+    /// Load the ngrams of a config line. The lines are checked by `check_config_str` before.
+    fn work_ngrams_config_line(line: &str, base: &Path) -> Result<Option<RawNGrams>, String> {
+        // Like `check_config_str`, which skips lines of only tabs and other whitespace as well.
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        let ConfigLine {
+            weight,
+            datatype,
+            paths,
+        } = ConfigLine::parse(line, base).expect("check_config_str rejects invalid lines");
 
         debug!(
-            "Read config line => weight: {} ---- type: {} ---- path: {} ",
-            weight, datatype, datapath
+            "Read config line => weight: {} ---- type: {} ---- paths: {:?} ",
+            weight, datatype, paths
         );
 
+        let phase = format!("corpus {}", paths.join(";"));
+        #[cfg(feature = "text-corpus")]
         if datatype == "text" {
-            Some(Self::parse_text_ngrams(weight, datapath))
-        } else if datatype == "pregenerated" {
-            let paths: Vec<&str> = datapath.split(";").collect();
-            Some(Self::parse_pregenerated_ngrams(
-                weight, paths[0], paths[1], paths[2],
-            ))
+            return profile::time(&phase, || Self::parse_text_ngrams(weight, &paths[0])).map(Some);
+        }
+        if datatype == "pregenerated" {
+            profile::time(&phase, || {
+                Self::parse_pregenerated_ngrams(weight, &paths[0], &paths[1], &paths[2])
+            })
+            .map(Some)
         } else {
             warn!("Unsupported data type {}", datatype);
-            //todo!("Implement error for unsupported data types.");
-            None
+            Ok(None)
         }
     }
 
@@ -177,52 +439,68 @@ impl NGrams {
         letters_path: &str,
         bigrams_path: &str,
         trigrams_path: &str,
-    ) -> RawNGrams {
-        let letters = Self::read_pregenerated_file(letters_path.to_string());
-        let bigrams = Self::read_pregenerated_file(bigrams_path.to_string());
-        let trigrams = Self::read_pregenerated_file(trigrams_path.to_string());
-        RawNGrams {
+    ) -> Result<RawNGrams, String> {
+        Ok(RawNGrams {
             weight,
-            letters,
-            bigrams,
-            trigrams,
-        }
+            letters: Self::read_pregenerated_file(letters_path)?,
+            bigrams: Self::read_pregenerated_file(bigrams_path)?,
+            trigrams: Self::read_pregenerated_file(trigrams_path)?,
+        })
     }
 
-    fn read_pregenerated_file(path: String) -> Vec<(String, f64)> {
-        let contents = fs::read_to_string(path).unwrap();
+    fn read_pregenerated_file(path: &str) -> Result<Vec<(String, f64)>, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        Self::parse_pregenerated(&contents, path)
+    }
 
+    /**
+    Parse the lines of a pregenerated file. A line is the count, optionally
+    after spaces (like the output of `uniq -c`), a single space and then the
+    ngram verbatim up to the end of the line, so `12  x` is ` x`, `12 x ` is
+    `x ` and `12   ` is two spaces. A line with nothing after the separator is
+    the space unigram whose own space was stripped, like `12345 `. Empty lines
+    are skipped, a byte order mark and the `\r` of Windows line ends are
+    ignored. `source` names the file in error messages.
+    */
+    fn parse_pregenerated(contents: &str, source: &str) -> Result<Vec<(String, f64)>, String> {
         let mut data = vec![];
 
-        for line in contents.lines() {
-            let cleaned_line = line.replace("\u{feff}", "");
-            let line_array = cleaned_line.split(" ");
-            let parts: Vec<&str> = line_array
-                .filter(|part| part.to_string() != "".to_string())
-                .collect();
-
-            let mut letters = parts.last().unwrap().to_string();
-
-            if line.chars().last().unwrap() == ' ' {
-                if letters == parts.first().unwrap().to_string() {
-                    letters = line.chars().last().unwrap().to_string();
-                } else {
-                    letters += &line.chars().last().unwrap().to_string();
-                }
-            }
-
-            if parts.len() == 2 || (parts.len() == 1 && line.chars().last().unwrap() == ' ') {
-                let weight = parts.first().unwrap();
-                let number = weight.parse::<f64>().unwrap();
-                data.push((letters, number))
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim_start_matches('\u{feff}');
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.trim().is_empty() {
+                continue;
             }
+            let (count, ngram) = line
+                .trim_start_matches(' ')
+                .split_once(' ')
+                .ok_or_else(|| {
+                    format!(
+                        "{} line {}: expected `count ngram`, found {:?}",
+                        source,
+                        idx + 1,
+                        line
+                    )
+                })?;
+            let count = count
+                .parse::<f64>()
+                .map_err(|_| format!("{} line {}: invalid count {:?}", source, idx + 1, count))?;
+            let ngram = if ngram.is_empty() { " " } else { ngram };
+            data.push((ngram.to_string(), count));
         }
-        data
+        Ok(data)
     }
 
-    fn parse_text_ngrams(weight: f64, path: &str) -> RawNGrams {
-        let f = File::open(path).unwrap();
-        let mut reader = BufReader::new(f);
+    #[cfg(feature = "text-corpus")]
+    fn parse_text_ngrams(weight: f64, path: &str) -> Result<RawNGrams, String> {
+        let f = File::open(path).map_err(|e| format!("Unable to open {}: {}", path, e))?;
+        Ok(Self::count_text_ngrams(weight, BufReader::new(f)))
+    }
+
+    /// Count the ngrams of a text line by line.
+    #[cfg(feature = "text-corpus")]
+    fn count_text_ngrams(weight: f64, mut reader: impl BufRead) -> RawNGrams {
         let mut buf = vec![];
 
         let mut letters: HashMap<String, f64> = HashMap::new();
@@ -232,7 +510,7 @@ impl NGrams {
         let mut bigram_char = None;
         let mut trigram_char = None;
 
-        while let Ok(_) = reader.read_until(b'\n', &mut buf) {
+        while reader.read_until(b'\n', &mut buf).is_ok() {
             if buf.is_empty() {
                 break;
             }
@@ -251,14 +529,15 @@ impl NGrams {
                     *letters.entry(letter.to_string()).or_insert(0.0) += 1.0;
                 }
 
+                // `bigram_char` is the previous character and `trigram_char` the one before it.
                 if let Some(bigram_char) = bigram_char {
                     *bigrams
-                        .entry(format!("{}{}", letter, bigram_char))
+                        .entry(format!("{}{}", bigram_char, letter))
                         .or_insert(0.0) += 1.0;
 
                     if let Some(trigram_char) = trigram_char {
                         *trigrams
-                            .entry(format!("{}{}{}", letter, bigram_char, trigram_char))
+                            .entry(format!("{}{}{}", trigram_char, bigram_char, letter))
                             .or_insert(0.0) += 1.0;
                     }
 
@@ -271,47 +550,322 @@ impl NGrams {
             buf.clear();
         }
 
-        let mut trigrams_drained: Vec<_> = trigrams.iter().collect();
-        let trigrams_final_length = trigrams_drained.len().saturating_sub(1);
-        trigrams_drained.truncate(trigrams_final_length);
-        trigrams_drained.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap());
-        trigrams_drained.reverse();
+        RawNGrams {
+            weight,
+            letters: sorted_by_frequency(letters),
+            bigrams: sorted_by_frequency(bigrams),
+            trigrams: sorted_by_frequency(trigrams),
+        }
+    }
+}
 
-        let mut letters_vec = vec![];
+/// A line of an ngrams config: `weight type path`, where `pregenerated` has the paths of its three files.
+struct ConfigLine {
+    weight: f64,
+    datatype: String,
+    paths: Vec<String>,
+}
 
-        for (letter, count) in letters.iter() {
-            letters_vec.push((letter.clone(), count.clone()))
+impl ConfigLine {
+    /**
+    Parse a line that is neither a comment nor blank. Its fields are separated
+    by whitespace, a field in double or single quotes can contain whitespace.
+    The three paths of `pregenerated` are three fields or, like klo always
+    read them, one field separated by `;`. Backslashes in paths become
+    slashes, and relative paths are relative to `base`.
+    */
+    fn parse(line: &str, base: &Path) -> Result<Self, String> {
+        let fields = config_fields(line)?;
+        if fields.len() < 3 {
+            return Err(format!("expected `weight type path`, found {:?}", line));
         }
-        letters_vec.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-        letters_vec.reverse();
-
-        let mut bigrams_vec = vec![];
-        let bigrams_final_length = bigrams.len().saturating_sub(2);
-        bigrams_vec.sort_by(|a: &(String, f64), b: &(String, f64)| a.1.partial_cmp(&b.1).unwrap());
-        bigrams_vec.reverse();
+        let weight = fields[0]
+            .parse::<f64>()
+            .map_err(|_| format!("invalid weight {:?}", fields[0]))?;
+        let paths: Vec<&str> = match fields[1].as_str() {
+            #[cfg(feature = "text-corpus")]
+            "text" if fields.len() == 3 => vec![&fields[2]],
+            #[cfg(feature = "text-corpus")]
+            "text" => {
+                return Err(format!(
+                    "text needs one path, found {} (quote paths with spaces)",
+                    fields.len() - 2
+                ))
+            }
+            #[cfg(not(feature = "text-corpus"))]
+            "text" => {
+                return Err(format!(
+                    "klo is built without the text-corpus feature, use pregenerated ngrams instead of {}",
+                    fields[2]
+                ))
+            }
+            "pregenerated" => {
+                let paths: Vec<&str> = match &fields[2..] {
+                    [joined] => joined.split(';').collect(),
+                    paths => paths.iter().map(String::as_str).collect(),
+                };
+                if paths.len() != 3 {
+                    return Err(format!(
+                        "pregenerated needs three paths, as three fields or separated by `;`, found {}",
+                        paths.len()
+                    ));
+                }
+                paths
+            }
+            datatype => return Err(format!("unsupported data type {}", datatype)),
+        };
+        Ok(ConfigLine {
+            weight,
+            datatype: fields[1].clone(),
+            paths: paths
+                .into_iter()
+                .map(|path| config_path(path, base))
+                .collect(),
+        })
+    }
+}
 
-        for (bigram, count) in bigrams.iter() {
-            bigrams_vec.push((bigram.clone(), count.clone()))
+/// The whitespace separated fields of an ngrams config line, without the quotes of quoted ones.
+fn config_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(&first) = chars.peek() {
+        if first.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut field = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(char) if char == first => break,
+                    Some(char) => field.push(char),
+                    None => return Err(format!("unterminated {} in {:?}", first, line)),
+                }
+            }
+        } else {
+            while let Some(char) = chars.next_if(|char| !char.is_whitespace()) {
+                field.push(char);
+            }
         }
+        fields.push(field);
+    }
+    Ok(fields)
+}
 
-        // Remove the last two bigrams because they just get filled up with the same char.
-        bigrams_vec.truncate(bigrams_final_length);
+/// A path of an ngrams config with slashes instead of backslashes, relative to `base` unless it is absolute.
+fn config_path(path: &str, base: &Path) -> String {
+    let path = path.replace('\\', "/");
+    let mut chars = path.chars();
+    // `C:/...` is absolute on Windows, but not to `Path` on other systems.
+    let has_drive = matches!(
+        (chars.next(), chars.next()),
+        (Some(drive), Some(':')) if drive.is_ascii_alphabetic()
+    );
+    if has_drive || Path::new(&path).is_absolute() {
+        return path;
+    }
+    base.join(path).to_string_lossy().into_owned()
+}
 
-        let mut trigrams_vec = vec![];
-        let trigrams_final_length = trigrams_drained.len().saturating_sub(1);
+/// The directory the relative paths of the ngrams config at `path` are relative to.
+fn config_base_dir(path: &str, paths_from_cwd: bool) -> PathBuf {
+    match Path::new(path).parent() {
+        Some(dir) if !paths_from_cwd => dir.to_path_buf(),
+        _ => PathBuf::new(),
+    }
+}
 
-        for (trigram, count) in trigrams.iter() {
-            trigrams_vec.push((trigram.clone(), count.clone()));
-        }
+/**
+The counted ngrams from most to least frequent, ties in the order of the
+ngrams. The order of a `HashMap` changes from run to run; summing in this one
+instead makes the weights and costs bit-identical between runs.
+*/
+fn sorted_by_frequency(counts: HashMap<String, f64>) -> Vec<(String, f64)> {
+    let mut sorted: Vec<_> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sorted
+}
 
-        // Remove the last trigram because it just gets filled up with the same char.
-        trigrams_vec.truncate(trigrams_final_length);
+#[cfg(feature = "cli")]
+/**
+Build the ngrams config equivalent to the --corpus arguments: one `weight text
+path` line per corpus. A corpus is a path with an optional `:weight` suffix.
+*/
+fn corpus_config(corpora: &[String]) -> Result<String, String> {
+    let mut config = String::new();
+    for corpus in corpora {
+        let (path, weight) = match corpus.rsplit_once(':') {
+            Some((path, weight)) if weight.parse::<f64>().is_ok() => {
+                (path, weight.parse().unwrap())
+            }
+            _ => (corpus.as_str(), 1.0),
+        };
+        // The config format separates its fields with whitespace, unless they are quoted.
+        let quote = match (path.contains('"'), path.contains('\'')) {
+            (false, _) => '"',
+            (true, false) => '\'',
+            (true, true) => {
+                return Err(format!(
+                    "--corpus {:?}: paths with both kinds of quotes aren't supported, use --ngrams-config instead",
+                    corpus
+                ))
+            }
+        };
+        config.push_str(&format!("{} text {}{}{}\n", weight, quote, path, quote));
+    }
+    Ok(config)
+}
 
-        RawNGrams {
-            weight,
-            letters: letters_vec,
-            bigrams: bigrams_vec,
-            trigrams: trigrams_vec,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pregenerated_lines_are_a_count_a_space_and_the_ngram() {
+        let cases = [
+            ("12 ab", "ab", 12.0),
+            ("12  x", " x", 12.0),
+            ("12 x ", "x ", 12.0),
+            ("12   ", "  ", 12.0),
+            ("12345 ", " ", 12345.0),
+            ("     7 ab", "ab", 7.0),
+            ("\u{feff}3 ab\r", "ab", 3.0),
+            ("0.5 ß", "ß", 0.5),
+        ];
+        for (line, ngram, count) in cases {
+            assert_eq!(
+                NGrams::parse_pregenerated(line, "test").unwrap(),
+                vec![(ngram.to_string(), count)],
+                "{:?}",
+                line
+            );
         }
+        assert_eq!(
+            NGrams::parse_pregenerated("\n  \n", "test").unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn config_lines_take_quoted_and_separate_paths_relative_to_the_config() {
+        let base = Path::new("configs");
+        let line = ConfigLine::parse(
+            "2.5\tpregenerated  a.txt 'my b.txt' \"C:\\My Texts\\c.txt\"",
+            base,
+        )
+        .unwrap();
+        assert_eq!(line.weight, 2.5);
+        assert_eq!(line.datatype, "pregenerated");
+        assert_eq!(
+            line.paths,
+            ["configs/a.txt", "configs/my b.txt", "C:/My Texts/c.txt"]
+        );
+
+        let line = ConfigLine::parse("1 pregenerated a.txt;sub\\b.txt;/abs/c.txt", base).unwrap();
+        assert_eq!(
+            line.paths,
+            ["configs/a.txt", "configs/sub/b.txt", "/abs/c.txt"]
+        );
+
+        let line = ConfigLine::parse("1 pregenerated a.txt;b.txt;c.txt", Path::new("")).unwrap();
+        assert_eq!(line.paths, ["a.txt", "b.txt", "c.txt"]);
+    }
+
+    #[cfg(feature = "text-corpus")]
+    #[test]
+    fn a_text_line_takes_one_quoted_windows_path() {
+        let line =
+            ConfigLine::parse(r#"1 text "my texts\corpus.txt""#, Path::new("configs")).unwrap();
+        assert_eq!(line.paths, ["configs/my texts/corpus.txt"]);
+        let error = ConfigLine::parse(r"1 text my texts\corpus.txt", Path::new(""))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            "text needs one path, found 2 (quote paths with spaces)"
+        );
+    }
+
+    #[test]
+    fn malformed_config_lines_say_what_is_wrong() {
+        let error = |line| ConfigLine::parse(line, Path::new("")).err().unwrap();
+        assert_eq!(
+            error("1 pregenerated \"a.txt b.txt c.txt"),
+            "unterminated \" in \"1 pregenerated \\\"a.txt b.txt c.txt\""
+        );
+        assert_eq!(
+            error("1 pregenerated a.txt;b.txt"),
+            "pregenerated needs three paths, as three fields or separated by `;`, found 2"
+        );
+        assert_eq!(error("x pregenerated a;b;c"), "invalid weight \"x\"");
+        assert_eq!(error("1 binary a.txt"), "unsupported data type binary");
+        assert_eq!(
+            error("1 pregenerated"),
+            "expected `weight type path`, found \"1 pregenerated\""
+        );
+    }
+
+    #[test]
+    fn malformed_pregenerated_lines_name_the_file_and_line() {
+        let error = NGrams::parse_pregenerated("1 ab\nab", "2gramme.txt").unwrap_err();
+        assert_eq!(
+            error,
+            "2gramme.txt line 2: expected `count ngram`, found \"ab\""
+        );
+        let error = NGrams::parse_pregenerated("x ab", "2gramme.txt").unwrap_err();
+        assert_eq!(error, "2gramme.txt line 1: invalid count \"x\"");
+    }
+
+    #[test]
+    fn exported_ngrams_with_spaces_are_read_back_verbatim() {
+        let ngrams =
+            NGrams::from_pregenerated("5 a\n4  \n", "3  x\n2 x \n1   \n1 ab\n", "1 a b\n").unwrap();
+        let dir = std::env::temp_dir().join("klo_ngrams_spaces_round_trip");
+        ngrams
+            .write_pregenerated(dir.to_str().unwrap(), None)
+            .unwrap();
+        let read = |name: &str| {
+            let path = dir.join(name);
+            let mut ngrams = NGrams::read_pregenerated_file(path.to_str().unwrap()).unwrap();
+            ngrams.sort_by(|a, b| a.0.cmp(&b.0));
+            ngrams
+        };
+        let owned = |ngrams: &[(&str, f64)]| -> Vec<(String, f64)> {
+            ngrams
+                .iter()
+                .map(|&(ngram, count)| (ngram.to_string(), count))
+                .collect()
+        };
+
+        assert_eq!(read("1gramme.txt"), owned(&[(" ", 4.0), ("a", 5.0)]));
+        assert_eq!(
+            read("2gramme.txt"),
+            owned(&[("  ", 1.0), (" x", 3.0), ("ab", 1.0), ("x ", 2.0)])
+        );
+        assert_eq!(read("3gramme.txt"), owned(&[("a b", 1.0)]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "text-corpus")]
+    #[test]
+    fn text_ngrams_keep_the_order_they_are_typed_in() {
+        let raw = NGrams::count_text_ngrams(1.0, "abcabcabc".as_bytes());
+        let count = |ngrams: &[(String, f64)], ngram: &str| {
+            ngrams
+                .iter()
+                .find(|(text, _)| text == ngram)
+                .map_or(0.0, |(_, count)| *count)
+        };
+
+        assert_eq!(count(&raw.bigrams, "ab"), 3.0);
+        assert_eq!(count(&raw.bigrams, "bc"), 3.0);
+        assert_eq!(count(&raw.bigrams, "ca"), 2.0);
+        assert_eq!(count(&raw.bigrams, "ba"), 0.0);
+        assert_eq!(count(&raw.bigrams, "cb"), 0.0);
+        assert_eq!(count(&raw.trigrams, "abc"), 3.0);
+        assert_eq!(count(&raw.trigrams, "bca"), 2.0);
+        assert_eq!(count(&raw.trigrams, "cba"), 0.0);
     }
 }
@@ -0,0 +1,1065 @@
+use crate::layout::BlueprintHelpers;
+use log::{debug, error, info, warn};
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, RngCore, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::{
+    convert::TryInto,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    alphabet,
+    anneal_stats::{self, AnnealLevel, AnnealStats},
+    best_stream::BestStream,
+    checkpoint::{Checkpoint, CheckpointLayout, Checkpointer, CHECKPOINT_SCHEMA_VERSION},
+    config_file::config_hash,
+    constraints::Constraints,
+    convergence::{ConvergencePoint, ConvergenceSampler},
+    error::{KloError, LayoutError},
+    geometry::{Geometry, KeyPos},
+    klo_options::{EvolveOptions, KloOptions},
+    layout::{Blueprint, Layout},
+    log_context,
+    ngrams::NGrams,
+    profile,
+    progress::{ProgressEvent, ProgressSender},
+    repro::ReproHeader,
+    run_summary::RunSummary,
+    runs_csv::RunsCsv,
+    scoring::{cost_model, CostBreakdown, CostModel},
+    statistics::LayoutStatistics,
+    top_layouts::{RankedLayout, TopLayouts},
+};
+
+/// The relative cost increase that the first anneal level accepts with a probability of 1/e.
+const ANNEAL_TEMPERATURE: f64 = 0.02;
+
+/**
+Everything an evolution found. The evolution itself only logs its progress and
+sends it to the progress channel, if given; reporting the result is up to the
+caller, like `klo evolve` does with its text and json output.
+*/
+pub struct OptimizationResult {
+    /// The --top-n layouts of all runs, best first. There is at least one.
+    pub best: TopLayouts,
+    /// What the evolution did.
+    pub stats: RunStats,
+    /// The spread of the final costs of the runs evolved now, none if every run was resumed.
+    pub run_summary: Option<RunSummary>,
+    /// The best cost of every evolved run at logarithmically spaced steps. Resumed runs have none.
+    pub convergence: Vec<ConvergencePoint>,
+    /// The counters of every anneal level, summed over the evolved runs. Empty if no run annealed.
+    pub anneal: Vec<AnnealLevel>,
+    /// What the evolution ran with, including its options.
+    pub reproduction: ReproHeader,
+    /// How long the evolution took, including loading the ngrams.
+    pub duration: Duration,
+    /// The ngrams the layouts were scored with.
+    pub ngrams: NGrams,
+    /// The geometry of the base layout.
+    pub geometry: Geometry,
+    /// The layout every run started from, with the fixed keys but before prerandomizing.
+    pub starting_layout: Blueprint,
+    /// The pinned and fixed characters, to mark them in the report.
+    pub(crate) constraints: Constraints,
+}
+
+impl OptimizationResult {
+    /// The best layout of all runs.
+    pub fn best(&self) -> &RankedLayout {
+        self.best
+            .best()
+            .expect("--num-layouts is at least 1 and a resumed checkpoint has layouts, so there is a result")
+    }
+}
+
+/// The numbers of an evolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunStats {
+    /// The runs the evolution was started with, see --num-layouts.
+    pub runs: u128,
+    /// The runs restored from the --resume checkpoint instead of being evolved again.
+    pub resumed_runs: u128,
+    /// The runs that finished, including the resumed ones. Fewer than `runs` if the evolution was asked to stop.
+    pub completed_runs: u128,
+    /// The steps of every run.
+    pub steps: u128,
+}
+
+/**
+Evolve a layout by selecting the fittest of random mutations step by step,
+scored with the `EffortModel` of the --weights. The progress events go to
+`progress`, whose receiver can also stop the evolution early, see
+[`progress::channel`](crate::progress::channel).
+*/
+pub fn evolve_a_layout(
+    options: &KloOptions,
+    evolve: &EvolveOptions,
+    progress: Option<&ProgressSender>,
+) -> Result<OptimizationResult, KloError> {
+    evolve_with_model(
+        options,
+        evolve,
+        &|geometry| cost_model(options, geometry),
+        progress,
+    )
+}
+
+/**
+Like `evolve_a_layout`, but the layouts are scored with the model `model_for`
+builds for the geometry of the base layout. This is how a cost model klo
+doesn't know itself takes part in an evolution.
+*/
+pub fn evolve_with_model<'a>(
+    options: &KloOptions,
+    evolve: &EvolveOptions,
+    model_for: &dyn Fn(Geometry) -> Box<dyn CostModel + 'a>,
+    progress: Option<&ProgressSender>,
+) -> Result<OptimizationResult, KloError> {
+    let emit = |event| {
+        if let Some(progress) = progress {
+            progress.send(event);
+        }
+    };
+    let start = Instant::now();
+    emit(ProgressEvent::PhaseChanged {
+        phase: "loading".to_string(),
+    });
+    let mut ngram_data = NGrams::from_options(options)?;
+
+    if evolve.limit_ngrams > 0 {
+        ngram_data
+            .letters
+            .truncate(evolve.limit_ngrams.try_into().unwrap());
+        ngram_data
+            .bigrams
+            .truncate(evolve.limit_ngrams.try_into().unwrap());
+        ngram_data
+            .trigrams
+            .truncate(evolve.limit_ngrams.try_into().unwrap());
+    }
+
+    let mut blueprint = Blueprint::from_args(options, &evolve.starting_layout)?;
+    let geometry = Geometry::from_options(options, &blueprint)?;
+    debug!("Using the {} geometry.", geometry.name);
+    let model = model_for(geometry.clone());
+    debug!("Scoring with the {} model.", model.name());
+    blueprint
+        .check_layers(&options.optimize_layers)
+        .map_err(KloError::Config)?;
+    let full_alphabet = alphabet::covered_alphabet(
+        &blueprint,
+        &options.alphabet,
+        &geometry,
+        options.allow_partial_alphabet,
+    )
+    .map_err(KloError::Config)?;
+    let constraints = Constraints::from_options(evolve, &blueprint, &geometry, &full_alphabet)
+        .map_err(|problems| {
+            for problem in &problems {
+                error!("{}", problem);
+            }
+            KloError::Config(format!(
+                "Found {} problems with the constraints.",
+                problems.len()
+            ))
+        })?;
+    constraints.apply_fixed(&mut blueprint);
+    if !constraints.pinned.is_empty() {
+        debug!("Pinned characters: {:?}", constraints.pinned);
+    }
+    let alphabet = constraints.mutable_alphabet(&full_alphabet);
+
+    let config_hash = config_hash(options, evolve);
+    let mut reproduction = ReproHeader::new(options, Some(evolve));
+    let seed = match evolve.seed {
+        Some(seed) => seed,
+        None => {
+            let seed = thread_rng().gen();
+            info!(
+                "Seeding the runs with {}, --seed {} repeats them.",
+                seed, seed
+            );
+            seed
+        }
+    };
+    reproduction.seed = Some(seed);
+    let mut checkpointer = evolve
+        .checkpoint
+        .as_ref()
+        .or(evolve.resume.as_ref())
+        .map(|path| {
+            Checkpointer::new(
+                path,
+                evolve.checkpoint_interval,
+                evolve.keep_checkpoint_backup,
+            )
+        });
+    let runs_csv = evolve
+        .runs_csv
+        .as_ref()
+        .map(|path| RunsCsv::open(path, &config_hash, seed, &reproduction))
+        .transpose()?;
+    let top_layers = options
+        .optimize_layers
+        .iter()
+        .map(|&layer| match layer {
+            0 => (0, Some(full_alphabet.clone())),
+            _ => (layer, options.layer_alphabet(layer)),
+        })
+        .collect();
+    let mut top_layouts = TopLayouts::new(evolve.top_n, evolve.min_diff, top_layers);
+    let (completed_runs, runs) = match &evolve.resume {
+        Some(path) => resume(
+            path,
+            options,
+            evolve,
+            &config_hash,
+            &blueprint,
+            |blueprint| {
+                let layout =
+                    Layout::from_blueprint(blueprint, &ngram_data.interner, &model.key_costs());
+                let cost = model.score(&layout, &ngram_data);
+                (layout, cost)
+            },
+            &mut top_layouts,
+        )?,
+        None => (0, evolve.num_layouts),
+    };
+    let best_stream = evolve
+        .best_stream
+        .as_ref()
+        .map(|path| {
+            let resumed_best = top_layouts
+                .best()
+                .map_or(f64::INFINITY, |best| best.cost.total());
+            BestStream::open(path, resumed_best)
+        })
+        .transpose()?;
+    emit(ProgressEvent::PhaseChanged {
+        phase: "evolving".to_string(),
+    });
+    let mut finished_runs = completed_runs;
+    let mut convergence = vec![];
+    let mut anneal = vec![];
+    let mut finals = vec![];
+    let context = RunContext {
+        options,
+        evolve,
+        blueprint: &blueprint,
+        alphabet: &alphabet,
+        constraints: &constraints,
+        geometry: &geometry,
+        ngrams: &ngram_data,
+        model: model.as_ref(),
+        best_stream: best_stream.as_ref(),
+        progress,
+        runs,
+        seed,
+    };
+    let pending: Vec<u128> = (completed_runs + 1..=runs).collect();
+    // One run per thread at a time. The runs of a batch are finished in their
+    // order, so the kept layouts don't depend on which thread was faster.
+    #[cfg(feature = "parallel")]
+    let batch_size = rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    let batch_size = 1;
+    for batch in pending.chunks(batch_size) {
+        #[cfg(feature = "parallel")]
+        let batch = batch.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let batch = batch.iter();
+        let finished: Vec<_> = batch.map(|&run| evolve_run(&context, run)).collect();
+        for finished_run in finished {
+            let FinishedRun {
+                run,
+                layout,
+                cost,
+                convergence: run_convergence,
+                anneal: run_anneal,
+            } = finished_run?;
+            convergence.extend(run_convergence);
+            anneal_stats::add_run(&mut anneal, &run_anneal);
+            if let Some(runs_csv) = &runs_csv {
+                let statistics = LayoutStatistics::new(&layout, &ngram_data, &geometry);
+                runs_csv.run_finished(run, &layout, &cost, &statistics)?;
+            }
+            let fingerprint = fingerprint(options, &layout);
+            finals.push((cost.total(), fingerprint.clone()));
+            top_layouts.offer(RankedLayout {
+                run,
+                layout,
+                cost,
+                fingerprint,
+            });
+            if let Some(checkpointer) = &mut checkpointer {
+                checkpointer.run_finished(&checkpoint(run, runs, &config_hash, &top_layouts))?;
+            }
+            finished_runs = run;
+        }
+
+        if finished_runs < runs && progress.is_some_and(ProgressSender::stop_requested) {
+            info!(
+                "Stopping after {} of {} runs as requested.",
+                finished_runs, runs
+            );
+            break;
+        }
+    }
+    if let Some(checkpointer) = &mut checkpointer {
+        checkpointer.finish(&checkpoint(finished_runs, runs, &config_hash, &top_layouts))?;
+    }
+    Ok(OptimizationResult {
+        best: top_layouts,
+        stats: RunStats {
+            runs,
+            resumed_runs: completed_runs,
+            completed_runs: finished_runs,
+            steps: evolve.steps,
+        },
+        run_summary: RunSummary::new(&finals),
+        convergence,
+        anneal,
+        reproduction,
+        duration: start.elapsed(),
+        ngrams: ngram_data,
+        geometry,
+        starting_layout: blueprint,
+        constraints,
+    })
+}
+
+/**
+A copy of the blueprint with `switches` swaps of two characters of one of the
+--optimize-layers. On layer 0 only the characters of the mutable `alphabet`
+move, and the hand locks are restored afterwards.
+*/
+fn mutate(
+    blueprint: &Blueprint,
+    options: &KloOptions,
+    alphabet: &str,
+    constraints: &Constraints,
+    geometry: &Geometry,
+    switches: u128,
+    rng: &mut dyn RngCore,
+) -> Blueprint {
+    let mut candidate = blueprint.clone();
+    let layer = *options
+        .optimize_layers
+        .choose(rng)
+        .expect("--optimize-layers is never empty");
+    if layer == 0 {
+        candidate.swap_random_keys(0, Some(alphabet), switches, rng);
+        constraints.apply_hand_locks(&mut candidate, geometry, alphabet);
+    } else {
+        candidate.swap_random_keys(
+            layer,
+            options.layer_alphabet(layer).as_deref(),
+            switches,
+            rng,
+        );
+    }
+    candidate
+}
+
+/// What every run of an evolution reads.
+struct RunContext<'a> {
+    options: &'a KloOptions,
+    evolve: &'a EvolveOptions,
+    /// The base layout with the fixed keys, before prerandomizing.
+    blueprint: &'a Blueprint,
+    /// The characters of layer 0 the evolution may move.
+    alphabet: &'a str,
+    constraints: &'a Constraints,
+    geometry: &'a Geometry,
+    ngrams: &'a NGrams,
+    model: &'a dyn CostModel,
+    best_stream: Option<&'a BestStream>,
+    progress: Option<&'a ProgressSender>,
+    /// The number of runs of the evolution.
+    runs: u128,
+    /// The seed every run derives its own from.
+    seed: u64,
+}
+
+/// The best layout of a run and what the run did.
+struct FinishedRun {
+    run: u128,
+    layout: Layout,
+    cost: CostBreakdown,
+    convergence: Vec<ConvergencePoint>,
+    anneal: Vec<AnnealLevel>,
+}
+
+/**
+Evolve one layout from a prerandomized copy of the base layout. Runs only
+share what they read and the --best-stream, so they can run on any thread.
+*/
+fn evolve_run(context: &RunContext, run: u128) -> Result<FinishedRun, KloError> {
+    let RunContext {
+        options,
+        evolve,
+        blueprint,
+        alphabet,
+        constraints,
+        geometry,
+        ngrams,
+        model,
+        best_stream,
+        progress,
+        runs,
+        seed,
+    } = *context;
+    let emit = |event| {
+        if let Some(progress) = progress {
+            progress.send(event);
+        }
+    };
+    let _run_scope = log_context::enter_run(run);
+    let run_start = Instant::now();
+    let mut rng = StdRng::seed_from_u64(run_seed(seed, run));
+    let mut blueprint = blueprint.clone();
+    if evolve.prerandomize > 0 {
+        debug!("Doing {} prerandomization switches.", evolve.prerandomize);
+        blueprint.debug_print();
+        if options.optimize_layers.contains(&0) {
+            blueprint = blueprint.get_randomized_variant(
+                alphabet.to_string(),
+                evolve.prerandomize,
+                &mut rng,
+            );
+        }
+        for &layer in options.optimize_layers.iter().filter(|&&layer| layer != 0) {
+            blueprint.swap_random_keys(
+                layer,
+                options.layer_alphabet(layer).as_deref(),
+                evolve.prerandomize,
+                &mut rng,
+            );
+        }
+    }
+    constraints.apply_hand_locks(&mut blueprint, geometry, alphabet);
+
+    emit(ProgressEvent::RunStarted {
+        run,
+        runs,
+        steps: evolve.steps,
+    });
+    let mut layout = Layout::from_blueprint(blueprint, &ngrams.interner, &model.key_costs());
+    let mut cost = model.score(&layout, ngrams);
+    // Annealing may leave the best layout of the run for more expensive ones.
+    let mut best = (layout.clone(), cost);
+    let mut sampler = ConvergenceSampler::new(run, cost.total());
+    let mut anneal_stats = AnnealStats::new(evolve.anneal);
+    emit(ProgressEvent::StepProgress {
+        run,
+        step: 0,
+        steps: evolve.steps,
+        cost: cost.total(),
+    });
+    emit(ProgressEvent::NewBestLayout {
+        run,
+        step: 0,
+        layout: layout.blueprint.layout_string(),
+        cost: cost.total(),
+    });
+    if let Some(best_stream) = best_stream {
+        best_stream.offer(run, 0, cost.total(), &layout.blueprint.layout_string())?;
+    }
+    let swappable = swappable_keys(&layout.blueprint, options, alphabet);
+    for step in 1..=evolve.steps {
+        let level = anneal_level(evolve, step);
+        let accepted = if evolve.controlled && level.is_none() {
+            match best_swap(
+                &mut layout,
+                &swappable,
+                model,
+                ngrams,
+                constraints,
+                geometry,
+                cost.total(),
+            ) {
+                Some(swapped) => {
+                    cost = swapped;
+                    true
+                }
+                None => {
+                    debug!(
+                        "No single swap improves the layout after {} steps, it is a local minimum.",
+                        step - 1
+                    );
+                    break;
+                }
+            }
+        } else {
+            let switches = level.map_or(1, |level| evolve.anneal + 1 - level as u128);
+            let candidate = mutate(
+                &layout.blueprint,
+                options,
+                alphabet,
+                constraints,
+                geometry,
+                switches,
+                &mut rng,
+            );
+            let candidate = Layout::from_blueprint(candidate, &ngrams.interner, &layout.key_costs);
+            let candidate_cost = model.score(&candidate, ngrams);
+            let accepted = if candidate_cost.total() < cost.total() {
+                if let Some(level) = level {
+                    anneal_stats.candidate(level);
+                    anneal_stats.accepted(level);
+                }
+                true
+            } else if let Some(level) = level {
+                anneal_stats.candidate(level);
+                let increase = (candidate_cost.total() - cost.total()) / cost.total();
+                let uphill = candidate_cost.total() > cost.total()
+                    && rng.gen::<f64>() < uphill_probability(evolve.anneal, level, increase);
+                if uphill {
+                    anneal_stats.uphill_accepted(level, cost.total());
+                }
+                uphill
+            } else {
+                false
+            };
+            if accepted {
+                layout = candidate;
+                cost = candidate_cost;
+            }
+            accepted
+        };
+        if accepted && cost.total() < best.1.total() {
+            best = (layout.clone(), cost);
+            emit(ProgressEvent::NewBestLayout {
+                run,
+                step,
+                layout: layout.blueprint.layout_string(),
+                cost: cost.total(),
+            });
+            if let Some(best_stream) = best_stream {
+                best_stream.offer(run, step, cost.total(), &layout.blueprint.layout_string())?;
+            }
+        }
+        sampler.record(step, best.1.total());
+        emit(ProgressEvent::StepProgress {
+            run,
+            step,
+            steps: evolve.steps,
+            cost: best.1.total(),
+        });
+    }
+    let (mut layout, mut cost) = best;
+    // Without steps the run scores the prerandomized layout as it is.
+    if evolve.controlled_tail && evolve.steps > 0 {
+        let cost_before = cost.total();
+        let mut swaps = 0;
+        while let Some(swapped) = best_swap(
+            &mut layout,
+            &swappable,
+            model,
+            ngrams,
+            constraints,
+            geometry,
+            cost.total(),
+        ) {
+            cost = swapped;
+            swaps += 1;
+        }
+        if swaps > 0 {
+            emit(ProgressEvent::NewBestLayout {
+                run,
+                step: evolve.steps,
+                layout: layout.blueprint.layout_string(),
+                cost: cost.total(),
+            });
+            if let Some(best_stream) = best_stream {
+                best_stream.offer(
+                    run,
+                    evolve.steps,
+                    cost.total(),
+                    &layout.blueprint.layout_string(),
+                )?;
+            }
+        }
+        info!(
+            "The controlled tail of run {} did {} swaps, which lowered its cost by {}",
+            run,
+            swaps,
+            cost_before - cost.total()
+        );
+    }
+    let levels = anneal_stats.finish(cost.total());
+    for level in &levels {
+        debug!(
+            "Anneal level with {} switches: {} of {} candidates accepted, {} rejected, {} of them uphill",
+            level.switches,
+            level.accepted,
+            level.candidates,
+            level.candidates - level.accepted,
+            level.uphill_accepted
+        );
+    }
+    debug!("Evolved layout:");
+    layout.blueprint.debug_print();
+    info!("Run {} ended with a total cost of {}", run, cost.total());
+    emit(ProgressEvent::RunFinished {
+        run,
+        layout: layout.blueprint.layout_string(),
+        cost: cost.total(),
+    });
+    profile::record("evolution run", run_start.elapsed());
+    Ok(FinishedRun {
+        run,
+        layout,
+        cost,
+        convergence: sampler.finish(evolve.steps),
+        anneal: levels,
+    })
+}
+
+/**
+The keys of every --optimize-layers layer whose characters the controlled
+steps swap: the keys of the mutable `alphabet` on layer 0 and the ones of the
+--layer-alphabet on the others. Swapping their characters keeps them the same
+for a whole run.
+*/
+fn swappable_keys(
+    blueprint: &Blueprint,
+    options: &KloOptions,
+    alphabet: &str,
+) -> Vec<(usize, Vec<KeyPos>)> {
+    options
+        .optimize_layers
+        .iter()
+        .map(|&layer| {
+            let keys = match layer {
+                0 => blueprint.movable_keys(0, Some(alphabet)),
+                _ => blueprint.movable_keys(layer, options.layer_alphabet(layer).as_deref()),
+            };
+            (layer, keys)
+        })
+        .collect()
+}
+
+/**
+Do the swap of two `swappable` keys that makes the layout cheapest and return
+its cost, or leave the layout as it is if no swap gets it below `cost`. Swaps
+that would move a hand locked character to the other hand are skipped. Every
+swap is scored in place and undone again, so the layout is never indexed anew.
+*/
+fn best_swap(
+    layout: &mut Layout,
+    swappable: &[(usize, Vec<KeyPos>)],
+    model: &dyn CostModel,
+    ngrams: &NGrams,
+    constraints: &Constraints,
+    geometry: &Geometry,
+    cost: f64,
+) -> Option<CostBreakdown> {
+    let mut best: Option<(usize, KeyPos, KeyPos, CostBreakdown)> = None;
+    for (layer, keys) in swappable {
+        for (index, &first) in keys.iter().enumerate() {
+            for &second in &keys[index + 1..] {
+                if *layer == 0
+                    && !constraints.allows_swap(&layout.blueprint, geometry, first, second)
+                {
+                    continue;
+                }
+                layout.swap_keys(first, second, *layer);
+                let swapped = model.score(layout, ngrams);
+                layout.swap_keys(first, second, *layer);
+                let best_cost = best.as_ref().map_or(cost, |(.., best)| best.total());
+                if swapped.total() < best_cost {
+                    best = Some((*layer, first, second, swapped));
+                }
+            }
+        }
+    }
+    let (layer, first, second, swapped) = best?;
+    layout.swap_keys(first, second, layer);
+    Some(swapped)
+}
+
+/// The seed of one run, so every run gets the same random numbers no matter which thread evolves it.
+fn run_seed(seed: u64, run: u128) -> u64 {
+    // Spread the run numbers over all bits, so the runs of neighbouring seeds differ.
+    seed ^ (run as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// The anneal level of a step counted from 1, none after the first `anneal * anneal_step` steps.
+fn anneal_level(evolve: &EvolveOptions, step: u128) -> Option<usize> {
+    if step > evolve.anneal * evolve.anneal_step {
+        return None;
+    }
+    Some(((step - 1) / evolve.anneal_step) as usize)
+}
+
+/**
+The probability that an anneal level accepts a candidate whose cost is higher
+by `increase`, relative to the cost of the layout before it. The temperature
+falls from `ANNEAL_TEMPERATURE` on the first level towards 0 after the last.
+*/
+fn uphill_probability(anneal: u128, level: usize, increase: f64) -> f64 {
+    let temperature = ANNEAL_TEMPERATURE * (anneal - level as u128) as f64 / anneal as f64;
+    (-increase / temperature).exp()
+}
+
+/**
+What tells two layouts apart: every key of the optimized layers. The layer
+strings would do for the Neo keyboard, but they leave out keys a custom base
+layout may have alphabet characters on.
+*/
+fn fingerprint(options: &KloOptions, layout: &Layout) -> String {
+    let layers: Vec<Vec<Vec<&str>>> = options
+        .optimize_layers
+        .iter()
+        .map(|&layer| {
+            layout
+                .blueprint
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|key| key.get(layer).map_or("", String::as_str))
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+    format!("{:?}", layers)
+}
+
+/**
+Restore the kept layouts of the --resume checkpoint and return the number of
+completed runs and the number of runs the evolution was started with. Warns
+(or fails with --resume-strict) if the checkpoint was written with other
+options. The runs are independent, so the evolution simply continues with the
+next one.
+*/
+fn resume(
+    path: &str,
+    options: &KloOptions,
+    evolve: &EvolveOptions,
+    config_hash: &str,
+    blueprint: &Blueprint,
+    score: impl Fn(Blueprint) -> (Layout, CostBreakdown),
+    top_layouts: &mut TopLayouts,
+) -> Result<(u128, u128), KloError> {
+    let saved = Checkpoint::read(path).map_err(KloError::Config)?;
+    if saved.config_hash != config_hash {
+        let message = format!(
+            "{} was written with other options than the current ones",
+            path
+        );
+        if evolve.resume_strict {
+            return Err(KloError::Config(message));
+        }
+        warn!("{}, resuming anyway", message);
+    }
+    if saved.completed_runs > 0 && saved.top_layouts.is_empty() {
+        return Err(KloError::Config(format!(
+            "{} holds no layouts to resume from",
+            path
+        )));
+    }
+
+    let shape = |blueprint: &Blueprint| blueprint.iter().map(Vec::len).collect::<Vec<_>>();
+    for saved_layout in saved.top_layouts {
+        if shape(&saved_layout.blueprint) != shape(blueprint) {
+            return Err(KloError::Layout(LayoutError(format!(
+                    "The layouts in {} have rows of {:?} keys, but the base layout has rows of {:?} keys",
+                    path,
+                    shape(&saved_layout.blueprint),
+                    shape(blueprint)
+            ))));
+        }
+        let (layout, cost) = score(saved_layout.blueprint);
+        top_layouts.offer(RankedLayout {
+            run: saved_layout.run,
+            cost,
+            fingerprint: fingerprint(options, &layout),
+            layout,
+        });
+    }
+    info!(
+        "Resuming after {} of {} runs from {}",
+        saved.completed_runs, saved.runs, path
+    );
+    Ok((saved.completed_runs, saved.runs))
+}
+
+/// The checkpoint after `completed_runs` of `runs` runs, with the kept layouts so far.
+fn checkpoint(
+    completed_runs: u128,
+    runs: u128,
+    config_hash: &str,
+    top_layouts: &TopLayouts,
+) -> Checkpoint {
+    let best = top_layouts.best();
+    Checkpoint {
+        schema_version: CHECKPOINT_SCHEMA_VERSION,
+        completed_runs,
+        runs,
+        best_layout: best
+            .map(|best| best.layout.blueprint.layout_string())
+            .unwrap_or_default(),
+        best_cost: best.map_or(0.0, |best| best.cost.total()),
+        config_hash: config_hash.to_string(),
+        top_layouts: top_layouts
+            .kept()
+            .iter()
+            .map(|ranked| CheckpointLayout {
+                run: ranked.run,
+                blueprint: ranked.layout.blueprint.clone(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(all(test, feature = "text-corpus"))]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use clap::Clap;
+
+    use super::*;
+    use crate::{interner::Interner, klo_options::Command, progress};
+
+    /// The options of `klo --corpus <fixture corpus> evolve <args>`, prepared like `klo::run` does.
+    fn options(args: &[&str]) -> (KloOptions, EvolveOptions) {
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/corpus.txt");
+        let mut all = vec!["klo", "--corpus", corpus, "evolve", "--baselines", "none"];
+        all.extend(args);
+        let mut options = KloOptions::try_parse_from(all).unwrap();
+        options.alphabet = alphabet::expand_alphabet(&options.alphabet).unwrap();
+        options.validate().unwrap();
+        match options.command.take() {
+            Some(Command::Evolve(mut evolve)) => {
+                evolve.derive_effective_options();
+                (options, evolve)
+            }
+            _ => unreachable!("the arguments select evolve"),
+        }
+    }
+
+    /// Costs the row and key of `q` on layer 0 and nothing else, and counts its calls.
+    struct QInTheCorner<'a> {
+        scores: &'a AtomicUsize,
+    }
+
+    impl CostModel for QInTheCorner<'_> {
+        fn score(&self, layout: &Layout, _ngrams: &NGrams) -> CostBreakdown {
+            self.scores.fetch_add(1, Ordering::Relaxed);
+            let (row, key) = layout.blueprint.get_key_pos("q").unwrap();
+            CostBreakdown {
+                key_position: (100 * row + key) as f64,
+                hand_balance: 0.0,
+                same_finger: 0.0,
+                same_hand: 0.0,
+                left_hand_share: 0.5,
+            }
+        }
+
+        fn name(&self) -> &str {
+            "q in the corner"
+        }
+    }
+
+    #[test]
+    fn the_evolution_keeps_what_the_model_scores_best() {
+        let (options, evolve) = options(&["-n", "2", "--steps", "50", "--seed", "4"]);
+        let scores = AtomicUsize::new(0);
+        let result = evolve_with_model(
+            &options,
+            &evolve,
+            &|_| Box::new(QInTheCorner { scores: &scores }),
+            None,
+        )
+        .unwrap();
+        assert!(scores.load(Ordering::Relaxed) > 2 * 50);
+
+        let model = QInTheCorner { scores: &scores };
+        let best = result.best();
+        assert_eq!(best.cost, model.score(&best.layout, &result.ngrams));
+        let start = Layout::from_blueprint(
+            result.starting_layout.clone(),
+            &result.ngrams.interner,
+            &model.key_costs(),
+        );
+        assert!(best.cost.total() < model.score(&start, &result.ngrams).total());
+        for kept in result.best.kept() {
+            assert_eq!(kept.cost, model.score(&kept.layout, &result.ngrams));
+        }
+    }
+
+    #[test]
+    fn an_evolution_returns_its_layouts_and_numbers() {
+        let (options, evolve) = options(&["-n", "3", "--steps", "30", "--seed", "2"]);
+        let result = evolve_a_layout(&options, &evolve, None).unwrap();
+
+        assert_eq!(
+            result.stats,
+            RunStats {
+                runs: 3,
+                resumed_runs: 0,
+                completed_runs: 3,
+                steps: 30,
+            }
+        );
+        assert_eq!(result.reproduction.seed, Some(2));
+        assert_eq!(result.run_summary.as_ref().unwrap().runs, 3);
+        let costs: Vec<f64> = result
+            .best
+            .kept()
+            .iter()
+            .map(|kept| kept.cost.total())
+            .collect();
+        assert!(
+            costs.windows(2).all(|pair| pair[0] <= pair[1]),
+            "{:?}",
+            costs
+        );
+        assert_eq!(result.best().cost.total(), costs[0]);
+        let model = cost_model(&options, result.geometry.clone());
+        assert_eq!(
+            result.best().cost,
+            model.score(&result.best().layout, &result.ngrams)
+        );
+    }
+
+    #[test]
+    fn a_stopped_evolution_returns_the_runs_so_far() {
+        let (options, evolve) = options(&["-n", "100000", "--steps", "10", "--seed", "2"]);
+        let (sender, receiver) = progress::channel();
+        receiver.stop_handle().request_stop();
+        let result = evolve_a_layout(&options, &evolve, Some(&sender)).unwrap();
+
+        assert_eq!(result.stats.runs, 100_000);
+        let completed = result.stats.completed_runs;
+        assert!((1..100_000).contains(&completed), "{} runs", completed);
+        assert_eq!(result.run_summary.as_ref().unwrap().runs as u128, completed);
+        assert!(result.best().cost.total().is_finite());
+    }
+
+    #[test]
+    fn a_seeded_evolution_sends_the_events_of_its_phases_and_runs() {
+        let (options, evolve) = options(&["-n", "2", "--steps", "40", "--seed", "3"]);
+        let (sender, receiver) = progress::channel();
+        let result = evolve_a_layout(&options, &evolve, Some(&sender)).unwrap();
+        drop(sender);
+        let events: Vec<ProgressEvent> = std::iter::from_fn(|| receiver.recv()).collect();
+
+        let phase = |phase: &str| ProgressEvent::PhaseChanged {
+            phase: phase.to_string(),
+        };
+        assert_eq!(events[..2], [phase("loading"), phase("evolving")]);
+        let mut finished = vec![];
+        for run in 1..=2 {
+            let of_run: Vec<&ProgressEvent> = events
+                .iter()
+                .filter(|event| match event {
+                    ProgressEvent::RunStarted { run: of, .. }
+                    | ProgressEvent::StepProgress { run: of, .. }
+                    | ProgressEvent::NewBestLayout { run: of, .. }
+                    | ProgressEvent::RunFinished { run: of, .. } => *of == run,
+                    _ => false,
+                })
+                .collect();
+            assert_eq!(
+                of_run[0],
+                &ProgressEvent::RunStarted {
+                    run,
+                    runs: 2,
+                    steps: 40,
+                }
+            );
+            let bests: Vec<(&String, f64)> = of_run
+                .iter()
+                .filter_map(|event| match event {
+                    ProgressEvent::NewBestLayout { layout, cost, .. } => Some((layout, *cost)),
+                    _ => None,
+                })
+                .collect();
+            assert!(
+                bests.windows(2).all(|pair| pair[1].1 < pair[0].1),
+                "{:?}",
+                bests
+            );
+            match of_run[of_run.len() - 1] {
+                ProgressEvent::RunFinished { layout, cost, .. } => {
+                    assert_eq!((layout, *cost), bests[bests.len() - 1]);
+                    finished.push(*cost);
+                }
+                event => panic!("run {} ended with {:?}", run, event),
+            }
+        }
+        let phases = events
+            .iter()
+            .filter(|event| matches!(event, ProgressEvent::PhaseChanged { .. }))
+            .count();
+        assert_eq!(phases, 2);
+        let best = finished.iter().copied().fold(f64::INFINITY, f64::min);
+        assert_eq!(result.best().cost.total(), best);
+    }
+
+    #[test]
+    fn layouts_that_differ_outside_of_the_layout_string_have_other_fingerprints() {
+        let (options, _) = options(&[]);
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint[1].push(vec!["x".to_string()]);
+        let mut other = blueprint.clone();
+        other[1][14] = vec!["y".to_string()];
+        assert_eq!(blueprint.layout_string(), other.layout_string());
+
+        let layout = |blueprint: &Blueprint| {
+            Layout::from_blueprint(
+                blueprint.clone(),
+                &Arc::new(Interner::default()),
+                &options.cost_weights.key_costs,
+            )
+        };
+        assert_ne!(
+            fingerprint(&options, &layout(&blueprint)),
+            fingerprint(&options, &layout(&other))
+        );
+        assert_eq!(
+            fingerprint(&options, &layout(&blueprint)),
+            fingerprint(&options, &layout(&blueprint))
+        );
+    }
+
+    #[test]
+    fn the_anneal_counters_of_a_seeded_evolution_add_up() {
+        let (options, evolve) = options(&[
+            "-n",
+            "2",
+            "--steps",
+            "400",
+            "--seed",
+            "6",
+            "--anneal",
+            "3",
+            "--anneal-step",
+            "40",
+        ]);
+        let result = evolve_a_layout(&options, &evolve, None).unwrap();
+
+        let switches: Vec<u128> = result.anneal.iter().map(|level| level.switches).collect();
+        assert_eq!(switches, [4, 3, 2]);
+        for level in &result.anneal {
+            assert!(level.candidates > 0, "{:?}", level);
+            assert!(level.accepted <= level.candidates, "{:?}", level);
+            assert!(level.uphill_accepted <= level.accepted, "{:?}", level);
+            assert!(level.vindicated <= level.uphill_accepted, "{:?}", level);
+        }
+        let uphill: u64 = result
+            .anneal
+            .iter()
+            .map(|level| level.uphill_accepted)
+            .sum();
+        assert!(uphill > 0);
+    }
+}
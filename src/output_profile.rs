@@ -0,0 +1,143 @@
+use std::{
+    borrow::Cow,
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// How text that is shown on the console is rendered. Files are always written as UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputProfile {
+    /// Show the text as it is.
+    Unicode,
+    /// Transliterate (`ä` → `ae`, `⇧` → `Shift`) or escape (`\u{2603}`) everything that isn't ASCII.
+    Ascii,
+}
+
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// The ASCII spelling of characters that have a common one, including the Neo modifier symbols.
+const TRANSLITERATIONS: &[(char, &str)] = &[
+    ('ä', "ae"),
+    ('ö', "oe"),
+    ('ü', "ue"),
+    ('Ä', "Ae"),
+    ('Ö', "Oe"),
+    ('Ü', "Ue"),
+    ('ß', "ss"),
+    ('ẞ', "SS"),
+    ('é', "e"),
+    ('è', "e"),
+    ('ê', "e"),
+    ('à', "a"),
+    ('ç', "c"),
+    ('ñ', "n"),
+    ('€', "EUR"),
+    ('£', "GBP"),
+    ('´', "'"),
+    ('‑', "-"),
+    ('–', "-"),
+    ('—', "-"),
+    ('…', "..."),
+    ('·', "."),
+    ('×', "x"),
+    ('→', "->"),
+    ('⇧', "Shift"),
+    ('⇥', "Tab"),
+    ('⇩', "M3"),
+    ('⇘', "M3"),
+    ('⇚', "M4"),
+    ('⇗', "M4"),
+    ('←', "Backspace"),
+    ('⌫', "Backspace"),
+    ('⏎', "Enter"),
+    ('↵', "Enter"),
+    ('─', "-"),
+    ('━', "-"),
+    ('│', "|"),
+    ('┃', "|"),
+    ('┌', "+"),
+    ('┐', "+"),
+    ('└', "+"),
+    ('┘', "+"),
+    ('├', "+"),
+    ('┤', "+"),
+    ('┬', "+"),
+    ('┴', "+"),
+    ('┼', "+"),
+];
+
+impl OutputProfile {
+    /**
+    The profile for --ascii. Without an explicit choice, the classic Windows
+    console (anything but Windows Terminal) gets the ASCII profile, because its
+    default code page can't show Unicode.
+    */
+    pub fn detect(ascii: bool, is_explicit: bool) -> Self {
+        let legacy_console = cfg!(windows) && env::var_os("WT_SESSION").is_none();
+        if ascii || (!is_explicit && legacy_console) {
+            OutputProfile::Ascii
+        } else {
+            OutputProfile::Unicode
+        }
+    }
+
+    /// The text as it is shown with this profile.
+    pub fn render(self, text: &str) -> Cow<'_, str> {
+        if self == OutputProfile::Unicode || text.is_ascii() {
+            return Cow::Borrowed(text);
+        }
+        let mut rendered = String::with_capacity(text.len());
+        for char in text.chars() {
+            if char.is_ascii() {
+                rendered.push(char);
+            } else if let Some((_, ascii)) = TRANSLITERATIONS.iter().find(|(c, _)| *c == char) {
+                rendered.push_str(ascii);
+            } else {
+                rendered.push_str(&char.escape_unicode().to_string());
+            }
+        }
+        Cow::Owned(rendered)
+    }
+
+    /// Escape a json document so it only contains ASCII. The escaped json means the same.
+    pub fn render_json(self, json: &str) -> Cow<'_, str> {
+        if self == OutputProfile::Unicode || json.is_ascii() {
+            return Cow::Borrowed(json);
+        }
+        let mut rendered = String::with_capacity(json.len());
+        for char in json.chars() {
+            if char.is_ascii() {
+                rendered.push(char);
+            } else {
+                for unit in char.encode_utf16(&mut [0; 2]) {
+                    rendered.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+        Cow::Owned(rendered)
+    }
+}
+
+/// Select the profile for everything that is rendered from now on.
+pub fn init(profile: OutputProfile) {
+    ASCII.store(profile == OutputProfile::Ascii, Ordering::Relaxed);
+}
+
+/// The profile set with `init`, Unicode before that.
+pub fn current() -> OutputProfile {
+    if ASCII.load(Ordering::Relaxed) {
+        OutputProfile::Ascii
+    } else {
+        OutputProfile::Unicode
+    }
+}
+
+/// Render console text with the current profile.
+pub fn render(text: &str) -> Cow<'_, str> {
+    current().render(text)
+}
+
+/// Render a json document for the console with the current profile.
+pub fn render_json(json: &str) -> Cow<'_, str> {
+    current().render_json(json)
+}
@@ -0,0 +1,311 @@
+use std::convert::TryFrom;
+
+use crate::heatmap::Heatmap;
+
+/// The color of the background.
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+
+/// The color of the key borders.
+const BORDER: [u8; 3] = [136, 136, 136];
+
+/// The color of the characters.
+const INK: [u8; 3] = [34, 34, 34];
+
+/// The height of the legend below the keys, in keys of the drawing.
+const LEGEND_HEIGHT: f64 = 0.6;
+
+/// The width and height of a glyph of the bitmap font, in font pixels.
+const GLYPH_SIZE: (usize, usize) = (5, 7);
+
+/**
+The 5x7 bitmap font the characters are drawn with: every row of a glyph is a
+byte whose 5 lowest bits are its pixels, the highest bit on the left.
+Characters without a glyph are drawn as a box.
+*/
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; 7])] = &[
+    ('a', [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111]),
+    ('b', [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110]),
+    ('c', [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('d', [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111]),
+    ('e', [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110]),
+    ('f', [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000]),
+    ('g', [0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110]),
+    ('h', [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001]),
+    ('i', [0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('j', [0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('k', [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010]),
+    ('l', [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('m', [0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001]),
+    ('n', [0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001]),
+    ('o', [0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('p', [0b00000, 0b00000, 0b11110, 0b10001, 0b11110, 0b10000, 0b10000]),
+    ('q', [0b00000, 0b00000, 0b01101, 0b10011, 0b01111, 0b00001, 0b00001]),
+    ('r', [0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000]),
+    ('s', [0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110]),
+    ('t', [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110]),
+    ('u', [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101]),
+    ('v', [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('w', [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010]),
+    ('x', [0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001]),
+    ('y', [0b00000, 0b00000, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110]),
+    ('z', [0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('ä', [0b01010, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111]),
+    ('ö', [0b01010, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('ü', [0b01010, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101]),
+    ('ß', [0b01110, 0b10001, 0b10001, 0b10110, 0b10001, 0b10001, 0b10110]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (';', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('\'', [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+];
+
+/// The glyph of a character, falling back to its lower case.
+fn glyph(char: char) -> Option<&'static [u8; 7]> {
+    let find = |wanted: char| {
+        GLYPHS
+            .iter()
+            .find(|(glyph_char, _)| *glyph_char == wanted)
+            .map(|(_, rows)| rows)
+    };
+    find(char).or_else(|| char.to_lowercase().next().and_then(find))
+}
+
+/// An RGB image being drawn.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&BACKGROUND);
+        }
+        Canvas {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Fill a rectangle, clipped to the canvas.
+    fn fill(&mut self, (x, y): (usize, usize), (width, height): (usize, usize), color: [u8; 3]) {
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                let index = (row * self.width + column) * 3;
+                self.pixels[index..index + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// A rectangle with a border of one pixel.
+    fn rect(&mut self, at: (usize, usize), size: (usize, usize), fill: [u8; 3]) {
+        self.fill(at, size, BORDER);
+        if size.0 > 2 && size.1 > 2 {
+            self.fill((at.0 + 1, at.1 + 1), (size.0 - 2, size.1 - 2), fill);
+        }
+    }
+
+    /// Draw text with the bitmap font, every font pixel `scale` pixels wide, centered on `center`.
+    fn text(&mut self, text: &str, center: (usize, usize), scale: usize) {
+        let (glyph_width, glyph_height) = GLYPH_SIZE;
+        let advance = (glyph_width + 1) * scale;
+        let width = text.chars().count() * advance - scale;
+        let left = center.0.saturating_sub(width / 2);
+        let top = center.1.saturating_sub(glyph_height * scale / 2);
+        for (index, char) in text.chars().enumerate() {
+            let x = left + index * advance;
+            match glyph(char) {
+                Some(rows) => {
+                    for (row, bits) in rows.iter().enumerate() {
+                        for column in 0..glyph_width {
+                            if bits & (1 << (glyph_width - 1 - column)) != 0 {
+                                self.fill(
+                                    (x + column * scale, top + row * scale),
+                                    (scale, scale),
+                                    INK,
+                                );
+                            }
+                        }
+                    }
+                }
+                None => {
+                    let size = (glyph_width * scale, glyph_height * scale);
+                    self.fill((x, top), size, INK);
+                    self.fill(
+                        (x + scale, top + scale),
+                        (size.0 - 2 * scale, size.1 - 2 * scale),
+                        BACKGROUND,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/**
+Draw layer 0 of a heatmap as a png image that is `width` pixels wide, with the
+same key grid and colors as the svg and a legend of the colors below the keys.
+Key names of more than one character are left out. The `comment` is stored in
+the file as its `Comment` text.
+*/
+pub fn heatmap_png(heatmap: &Heatmap, width: u32, comment: &str) -> Vec<u8> {
+    let (drawing_width, drawing_height) = heatmap.size();
+    let scale = f64::from(width) / drawing_width;
+    let key_height = drawing_height / heatmap.rows.max(1) as f64;
+    let legend_height = (key_height * LEGEND_HEIGHT * scale).round() as usize;
+    let width = width as usize;
+    let keys_height = (drawing_height * scale).round() as usize;
+    let mut canvas = Canvas::new(width, keys_height + legend_height);
+
+    let pixel = |value: f64| (value * scale).round() as usize;
+    let mut font_scale = 1;
+    for key in &heatmap.keys {
+        let (x, y, size) = heatmap.key_rect(key);
+        let share = key.shares.first().copied().unwrap_or(0.0);
+        canvas.rect(
+            (pixel(x), pixel(y)),
+            (pixel(size), pixel(size)),
+            heatmap.color(share),
+        );
+        font_scale = (pixel(size) / 2 / GLYPH_SIZE.1).max(1);
+        let label = key.labels.first().map_or("", String::as_str);
+        if label.chars().count() == 1 && !label.trim().is_empty() {
+            canvas.text(
+                label,
+                (pixel(x + size / 2.0), pixel(y + size / 2.0)),
+                font_scale,
+            );
+        }
+    }
+
+    // The legend: a gradient over half of the width from no to the most keystrokes.
+    let legend_scale = (font_scale / 2).max(1);
+    let bar = (width / 4, keys_height + legend_height / 4);
+    let bar_size = (width / 2, (legend_height / 2).max(1));
+    for column in 0..bar_size.0 {
+        let share = heatmap.max_share * column as f64 / bar_size.0 as f64;
+        canvas.fill(
+            (bar.0 + column, bar.1),
+            (1, bar_size.1),
+            heatmap.color(share),
+        );
+    }
+    let label_y = bar.1 + bar_size.1 / 2;
+    let gap = (GLYPH_SIZE.0 + 1) * legend_scale * 3;
+    canvas.text("0%", (bar.0.saturating_sub(gap), label_y), legend_scale);
+    canvas.text(
+        &format!("{:.1}%", heatmap.max_share * 100.0),
+        (bar.0 + bar_size.0 + gap, label_y),
+        legend_scale,
+    );
+
+    encode(&canvas, comment)
+}
+
+/// Encode a canvas as an 8 bit RGB png, with the image data in uncompressed deflate blocks and the comment as text.
+fn encode(canvas: &Canvas, comment: &str) -> Vec<u8> {
+    let row_bytes = canvas.width * 3;
+    let mut raw = Vec::with_capacity((row_bytes + 1) * canvas.height);
+    for row in canvas.pixels.chunks(row_bytes) {
+        // Filter type 0: the row as it is.
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(usize::from(u16::MAX)).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let length = u16::try_from(block.len()).expect("blocks are at most u16::MAX bytes long");
+        zlib.push(if blocks.peek().is_none() { 1 } else { 0 });
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = vec![];
+    header.extend_from_slice(&(canvas.width as u32).to_be_bytes());
+    header.extend_from_slice(&(canvas.height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, no filters beyond type 0, no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    chunk(&mut png, b"IHDR", &header);
+    // An uncompressed international text, which unlike tEXt may be UTF-8: the
+    // keyword, no compression, no language and no translated keyword.
+    let mut text = b"Comment\0\0\0\0\0".to_vec();
+    text.extend_from_slice(comment.as_bytes());
+    chunk(&mut png, b"iTXt", &text);
+    chunk(&mut png, b"IDAT", &zlib);
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc_data = kind.to_vec();
+    crc_data.extend_from_slice(data);
+    png.extend_from_slice(&crc32(&crc_data).to_be_bytes());
+}
+
+/// The CRC-32 of the png chunks (polynomial 0xedb88320).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The Adler-32 checksum of the zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_checksums_match_their_check_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+    }
+}
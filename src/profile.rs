@@ -0,0 +1,100 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use log::info;
+use serde::Serialize;
+
+/// The time spent in one phase of the run, summed over all its calls.
+#[derive(Serialize, Clone, Debug)]
+pub struct PhaseTiming {
+    /// The name of the phase, like `scoring unigrams`.
+    pub phase: String,
+    /// How often the phase ran.
+    pub calls: u64,
+    /// The time of all calls together.
+    pub seconds: f64,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref PHASES: Mutex<Vec<PhaseTiming>> = Mutex::new(vec![]);
+}
+
+/// Start collecting timings. Without --profile, `time` only checks the flag.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether --profile is on.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Run `f` and add the time it took to `phase`.
+pub fn time<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    record(phase, start.elapsed());
+    result
+}
+
+/// Add a duration to `phase`, for phases that aren't a single call.
+pub fn record(phase: &str, duration: Duration) {
+    if !is_enabled() {
+        return;
+    }
+    let mut phases = PHASES.lock().unwrap();
+    match phases.iter_mut().find(|timing| timing.phase == phase) {
+        Some(timing) => {
+            timing.calls += 1;
+            timing.seconds += duration.as_secs_f64();
+        }
+        None => phases.push(PhaseTiming {
+            phase: phase.to_string(),
+            calls: 1,
+            seconds: duration.as_secs_f64(),
+        }),
+    }
+}
+
+/// Every phase so far, in the order it first ran. None without --profile.
+pub fn timings() -> Option<Vec<PhaseTiming>> {
+    if is_enabled() {
+        Some(PHASES.lock().unwrap().clone())
+    } else {
+        None
+    }
+}
+
+/// Log the table of all phases.
+pub fn log_table() {
+    let timings = match timings() {
+        Some(timings) => timings,
+        None => return,
+    };
+    let width = timings
+        .iter()
+        .map(|timing| timing.phase.chars().count())
+        .max()
+        .unwrap_or(0);
+    info!("Profile:");
+    for timing in &timings {
+        info!(
+            "    {:width$}  {:>7} calls  {:>10.3} ms",
+            timing.phase,
+            timing.calls,
+            timing.seconds * 1000.0,
+            width = width
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    klo_options::ProgressFormat, output_profile, report::JsonCost, scoring::CostBreakdown,
+};
+
+/// The version of the progress events. Bump it whenever a field is removed or changes its meaning.
+pub const PROGRESS_SCHEMA_VERSION: u32 = 1;
+
+/// Step progress events are dropped if the last one was sent less than this long ago.
+const STEP_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/**
+A progress event, written as one json object per line with `--progress-format jsonl`.
+
+Schema version 1, every object has `schema_version` (always 1) and `event`:
+- `phase-changed`: `phase`, `loading` (the ngrams, the base layout and the constraints) or `evolving`
+- `run-started`: `run` (counting from 1), `runs`, `steps`
+- `step-progress`: `run`, `step`, `steps`, `cost` of the current layout; at most four per second
+- `new-best-layout`: `run`, `step`, `layout` (a layout string) and its `cost`
+- `run-finished`: `run`, the `layout` the run ended with and its `cost`
+- `final-result`: the best `layout` of all runs and its `cost` components, like the json result
+*/
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+#[allow(missing_docs)] // The variants and their fields are described above.
+pub enum ProgressEvent {
+    PhaseChanged {
+        phase: String,
+    },
+    RunStarted {
+        run: u128,
+        runs: u128,
+        steps: u128,
+    },
+    StepProgress {
+        run: u128,
+        step: u128,
+        steps: u128,
+        cost: f64,
+    },
+    NewBestLayout {
+        run: u128,
+        step: u128,
+        layout: String,
+        cost: f64,
+    },
+    RunFinished {
+        run: u128,
+        layout: String,
+        cost: f64,
+    },
+    FinalResult {
+        layout: String,
+        #[serde(serialize_with = "serialize_cost")]
+        cost: CostBreakdown,
+    },
+}
+
+/// The cost of the final result like the json result has it.
+fn serialize_cost<S: Serializer>(cost: &CostBreakdown, serializer: S) -> Result<S::Ok, S::Error> {
+    JsonCost::from_breakdown(cost).serialize(serializer)
+}
+
+#[derive(Serialize)]
+struct ProgressLine<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    event: &'a ProgressEvent,
+}
+
+/**
+The two ends of the progress of an evolution: the optimizer sends its events
+with the sender, a frontend like the progress output of `klo evolve` receives
+them on another thread and can ask the evolution to stop.
+*/
+pub fn channel() -> (ProgressSender, ProgressReceiver) {
+    let (events, receiver) = mpsc::channel();
+    let stop = StopHandle(Arc::new(AtomicBool::new(false)));
+    (
+        ProgressSender {
+            events,
+            stop: stop.clone(),
+            last_step_progress: Mutex::new(None),
+        },
+        ProgressReceiver {
+            events: receiver,
+            stop,
+        },
+    )
+}
+
+/// The end of the progress channel the optimizer sends its events with.
+pub struct ProgressSender {
+    events: Sender<ProgressEvent>,
+    stop: StopHandle,
+    last_step_progress: Mutex<Option<Instant>>,
+}
+
+impl ProgressSender {
+    /**
+    Send an event without waiting for the receiver, so a slow frontend never
+    holds up the evolution. Step progress is dropped if the last one was sent
+    less than 250 ms ago, and events are dropped once nobody receives them.
+    */
+    pub fn send(&self, event: ProgressEvent) {
+        if let ProgressEvent::StepProgress { .. } = event {
+            let mut last = self.last_step_progress.lock().unwrap();
+            if last.is_some_and(|last| last.elapsed() < STEP_PROGRESS_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        let _ = self.events.send(event);
+    }
+
+    /// A handle to ask the evolution to stop from this end, like on an interrupt.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Whether the receiving end asked the evolution to stop.
+    pub fn stop_requested(&self) -> bool {
+        self.stop.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The end of the progress channel a frontend receives the events on.
+pub struct ProgressReceiver {
+    events: Receiver<ProgressEvent>,
+    stop: StopHandle,
+}
+
+impl ProgressReceiver {
+    /// Wait for the next event. None after the last one, once the evolution is done.
+    pub fn recv(&self) -> Option<ProgressEvent> {
+        self.events.recv().ok()
+    }
+
+    /// A handle to ask the evolution to stop from any thread.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+}
+
+/**
+Asks an evolution to stop. It finishes the current run and ends with the
+runs so far, so the result and the checkpoint are still complete.
+*/
+#[derive(Clone, Debug)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    /// Ask the evolution to stop after the current run.
+    pub fn request_stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/**
+Print the events of a new progress channel to stdout in the selected format,
+on a thread that ends when the sender is dropped.
+*/
+pub(crate) fn report_to_stdout(format: ProgressFormat) -> (ProgressSender, JoinHandle<()>) {
+    let (sender, receiver) = channel();
+    let reporter = ProgressReporter { format };
+    let consumer = thread::spawn(move || {
+        while let Some(event) = receiver.recv() {
+            reporter.emit(&event);
+        }
+    });
+    (sender, consumer)
+}
+
+/// Prints progress events to stdout in the selected format.
+pub(crate) struct ProgressReporter {
+    format: ProgressFormat,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(format: ProgressFormat) -> Self {
+        ProgressReporter { format }
+    }
+
+    pub(crate) fn emit(&self, event: &ProgressEvent) {
+        // The human readable progress is part of the log.
+        if self.format == ProgressFormat::Text {
+            return;
+        }
+
+        let line = serde_json::to_string(&ProgressLine {
+            schema_version: PROGRESS_SCHEMA_VERSION,
+            event,
+        })
+        .unwrap();
+        // Locking stdout keeps lines of parallel runs from interleaving.
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ =
+            writeln!(stdout, "{}", output_profile::render_json(&line)).and_then(|_| stdout.flush());
+    }
+}
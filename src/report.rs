@@ -0,0 +1,926 @@
+use std::{cell::OnceCell, collections::BTreeMap, fmt::Write, fs, io, time::Instant};
+
+use chrono::Local;
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::{
+    anneal_stats::AnnealLevel,
+    constraints::Constraints,
+    convergence::{self, ConvergencePoint},
+    error::{KloError, LayoutError},
+    explain,
+    geometry::Geometry,
+    heatmap::Heatmap,
+    html, interactive,
+    klo_options::{EvolveOptions, KloOptions, OptionNote, OutputFormat},
+    layout::{
+        baseline_arg, builtin_baseline_arg, layout_arg_name, Blueprint, BlueprintHelpers, Layout,
+    },
+    layout_diff::LayoutDiff,
+    markdown,
+    ngrams::NGrams,
+    optimizer::OptimizationResult,
+    output_profile,
+    profile::{self, PhaseTiming},
+    progress::{ProgressEvent, ProgressReporter},
+    repro::ReproHeader,
+    run_summary::RunSummary,
+    scoring::{cost_model, CostBreakdown, CostModel},
+    statistics::{
+        high_layer_characters, missing_characters, CharacterShare, LayoutStatistics,
+        MissingCharacter,
+    },
+    top_layouts::TopLayouts,
+    weights::CostWeights,
+};
+#[cfg(feature = "exporters")]
+use crate::{
+    export,
+    klo_options::{export_arg, ExportFormat},
+};
+
+/// The version of the json result document. Bump it whenever a field is removed or changes its meaning.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/**
+The result of a run, as written with `--output-format json`. The markdown and
+html reports of the other formats are rendered from the same document.
+
+Schema version 1:
+- `schema_version`: always 1
+- `command`: the command line of the run, quoted for a POSIX shell
+- `effective_options`: every option the run used, keyed like in klo.toml
+- `reproduction`: what else the result was made with: the klo `version`, `git_describe`
+  of the checkout it was built from (or null), the `seed` of the runs (--seed or the one
+  chosen without it), the `ngrams_config` (null with --corpus) with the FNV-1a `ngrams_config_hash` of its contents (or null) and
+  the FNV-1a `weights_hash` of the weights, see `ReproHeader`
+- `layout.layout_string`: the layout in the format `--starting-layout` accepts
+- `layout.layers`: the layout strings of the --optimize-layers, keyed by layer
+- `layout.blueprint`: the full blueprint with all layers, like base_layout.json
+- `geometry`: the name (or path) of the geometry the layout was scored with
+- `cost`: the weighted cost components and their `total`
+- `top_layouts`: the --top-n layouts, best first, with their `run`, `layout_string` and `cost`
+- `top_layout_differences`: for every pair of `top_layouts`, the number of characters
+  of the --optimize-layers on other keys
+- `weights`: the effective weight of every cost component
+- `hand_split`: the `left` share of keystrokes and the `target` of --hand-target (or null)
+- `statistics`: the `finger_loads` (`finger` and `share` of the keystrokes), the
+  `left_hand_share`, the shares of `same_finger_bigrams`, `hand_alternation`, `rolls` and
+  `scissors` of the bigrams, the `home_row` share of the keystrokes (or null), the `worst_bigrams` with
+  their `bigram`, `kind` (`same-finger` or `scissor`) and `share`, and the `layer_loads`
+  with the `layer`, its `share` and its most frequent `characters` (`char` and `share`)
+  for layers above 0, see `LayoutStatistics`
+- `missing_characters`: the characters of the ngrams on no key of the layout with at
+  least --missing-char-threshold of the keystrokes, with their `char` and `share`
+- `diff`: the layout string against the one the runs started from, with fixed keys but
+  before prerandomizing: the number of `moved_keys` with another character and the
+  `anchors`, the characters on the same key, see `LayoutDiff`
+- `run_summary`: the final costs of the runs evolved now (null if all were resumed): their
+  number of `runs`, the `best`, `mean`, `median` and `standard_deviation`, the number of
+  `distinct_layouts`, the `runs_on_best` layout and the `histogram` bins with `from`, `to`
+  and `runs`
+- `convergence`: the best cost so far of every evolved run at the steps 1, 2, 5, 10, 20,
+  50, … and its last step, with their `run`, `step` and `best_cost`
+- `anneal`: for every anneal level of the evolved runs (empty without annealing), the keys
+  it `switches` per candidate, the `candidates` it scored, how many of them it `accepted`,
+  the `uphill_accepted` moves to a higher cost and how many of those were `vindicated` by the run ending below the
+  cost before the move
+- `comparisons`: the --compare-with layouts and the --baselines with their `name`, `layout_string`,
+  `cost`, `delta_percent`, the difference of their total cost to the result, and `statistics`
+- `notes`: the options klo adjusted, with their `field`, the `requested` and the
+  `effective` value and the `reason`
+- `profile`: with --profile, the `phase`s of the run so far with their `calls` and `seconds`
+- `pinned`: the characters the evolution wasn't allowed to move
+- `fixed`: the characters placed by --fix, mapped to their position names
+*/
+#[derive(Serialize)]
+pub struct JsonResult<'a> {
+    pub schema_version: u32,
+    pub command: String,
+    pub effective_options: toml::Value,
+    pub reproduction: &'a ReproHeader,
+    pub layout: JsonLayout<'a>,
+    pub geometry: &'a str,
+    pub cost: JsonCost,
+    pub top_layouts: Vec<JsonTopLayout>,
+    pub top_layout_differences: Vec<Vec<usize>>,
+    pub weights: BTreeMap<String, f64>,
+    pub comparisons: Vec<JsonComparison<'a>>,
+    pub hand_split: JsonHandSplit,
+    pub statistics: &'a LayoutStatistics,
+    pub missing_characters: Vec<MissingCharacter>,
+    pub diff: LayoutDiff,
+    pub run_summary: Option<&'a RunSummary>,
+    pub convergence: &'a [ConvergencePoint],
+    pub anneal: &'a [AnnealLevel],
+    pub notes: &'a [OptionNote],
+    pub profile: Option<Vec<PhaseTiming>>,
+    pub pinned: Vec<String>,
+    pub fixed: BTreeMap<String, String>,
+}
+
+impl JsonResult<'_> {
+    /// The name, the weight and the weighted cost of every cost component, for the tables of the reports.
+    pub fn cost_rows(&self) -> Vec<(&'static str, f64, f64)> {
+        [
+            ("key_position", self.cost.key_position),
+            ("hand_balance", self.cost.hand_balance),
+            ("same_finger", self.cost.same_finger),
+            ("same_hand", self.cost.same_hand),
+        ]
+        .iter()
+        .map(|&(component, cost)| {
+            let weight = self.weights.get(component).copied().unwrap_or(0.0);
+            (component, weight, cost)
+        })
+        .collect()
+    }
+
+    /// The hand split and the shares of the statistics, formatted as percentages, for the tables of the reports.
+    pub fn share_rows(&self) -> Vec<(&'static str, String)> {
+        let statistics = self.statistics;
+        let percent = |share: f64, decimals: usize| format!("{:.*}%", decimals, share * 100.0);
+        let mut rows = vec![
+            ("Left hand", percent(statistics.left_hand_share, 1)),
+            ("Right hand", percent(1.0 - statistics.left_hand_share, 1)),
+        ];
+        if let Some(target) = self.hand_split.target {
+            rows.push(("Left hand target", percent(target, 1)));
+        }
+        if let Some(home_row) = statistics.home_row {
+            rows.push(("Home row", percent(home_row, 1)));
+        }
+        rows.extend(vec![
+            (
+                "Same finger bigrams",
+                percent(statistics.same_finger_bigrams, 2),
+            ),
+            ("Hand alternation", percent(statistics.hand_alternation, 2)),
+            ("Rolls", percent(statistics.rolls, 2)),
+            ("Scissors", percent(statistics.scissors, 2)),
+        ]);
+        rows
+    }
+}
+
+#[derive(Serialize)]
+pub struct JsonLayout<'a> {
+    pub layout_string: String,
+    pub layers: BTreeMap<usize, String>,
+    pub blueprint: &'a Blueprint,
+}
+
+#[derive(Serialize)]
+pub struct JsonCost {
+    pub key_position: f64,
+    pub hand_balance: f64,
+    pub same_finger: f64,
+    pub same_hand: f64,
+    pub total: f64,
+}
+
+#[derive(Serialize)]
+pub struct JsonTopLayout {
+    pub run: u128,
+    pub layout_string: String,
+    pub cost: JsonCost,
+}
+
+#[derive(Serialize)]
+pub struct JsonHandSplit {
+    pub left: f64,
+    pub target: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct JsonComparison<'a> {
+    pub name: String,
+    pub layout_string: String,
+    pub cost: JsonCost,
+    pub delta_percent: f64,
+    pub statistics: &'a LayoutStatistics,
+}
+
+/// A layout that is scored against the result of an evolution.
+pub struct Comparison {
+    pub name: String,
+    pub layout_string: String,
+    pub cost: CostBreakdown,
+    pub statistics: LayoutStatistics,
+}
+
+impl Comparison {
+    /// How much more (or less, if negative) the layout costs than `best`, in percent.
+    pub fn delta_percent(&self, best: &CostBreakdown) -> f64 {
+        (self.cost.total() - best.total()) / best.total() * 100.0
+    }
+}
+
+/// The --compare-with layouts and the --baselines, scored the first time a report needs them.
+pub struct Comparisons<'a> {
+    /**
+    The name and the layout argument (or built-in layout string) of every
+    layout, and whether it is a built-in baseline. The built-in baselines
+    are made for the Neo keyboard, so they are skipped with a warning if they
+    don't fit the base layout.
+    */
+    layouts: Vec<(String, String, bool)>,
+    options: &'a KloOptions,
+    ngrams: &'a NGrams,
+    model: &'a dyn CostModel,
+    geometry: &'a Geometry,
+    scored: OnceCell<Vec<Comparison>>,
+}
+
+impl<'a> Comparisons<'a> {
+    pub fn new(
+        options: &'a KloOptions,
+        evolve: &EvolveOptions,
+        ngrams: &'a NGrams,
+        model: &'a dyn CostModel,
+        geometry: &'a Geometry,
+    ) -> Self {
+        let compare_with = evolve
+            .compare_with
+            .iter()
+            .map(|arg| (layout_arg_name(arg), arg.clone(), false));
+        let baselines = evolve
+            .baselines
+            .iter()
+            .filter(|&arg| arg != "none")
+            .map(|arg| {
+                let (name, layout) = baseline_arg(arg);
+                (name, layout, builtin_baseline_arg(arg).is_some())
+            });
+        Comparisons {
+            layouts: compare_with.chain(baselines).collect(),
+            options,
+            ngrams,
+            model,
+            geometry,
+            scored: OnceCell::new(),
+        }
+    }
+
+    /// Score the layouts on the first call and return the same scores afterwards.
+    pub fn get(&self) -> Result<&[Comparison], LayoutError> {
+        if let Some(scored) = self.scored.get() {
+            return Ok(scored);
+        }
+        let mut scored = vec![];
+        for (name, arg, builtin) in &self.layouts {
+            let compared = match Layout::from_layout_arg(self.options, arg, &self.ngrams.interner) {
+                Ok(compared) => compared,
+                Err(e) if *builtin => {
+                    warn!(
+                        "Not comparing with the baseline {}, which doesn't fit the base layout: {}",
+                        name, e
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    return Err(LayoutError(format!(
+                        "Unable to compare with {}: {}",
+                        name, e
+                    )))
+                }
+            };
+            scored.push(Comparison {
+                name: name.clone(),
+                layout_string: compared.blueprint.layout_string(),
+                cost: self.model.score(&compared, self.ngrams),
+                statistics: LayoutStatistics::new(&compared, self.ngrams, self.geometry),
+            });
+        }
+        Ok(self.scored.get_or_init(|| scored))
+    }
+}
+
+/**
+Report the result of `klo evolve`: log the layouts, the hand split and the
+comparisons, emit the final progress event, write the json result with
+--output-format json and start the --interactive session.
+*/
+pub fn report_evolution(
+    options: &KloOptions,
+    evolve: &EvolveOptions,
+    result: &OptimizationResult,
+) -> Result<(), KloError> {
+    let best = result.best();
+    let (layout, cost) = (&best.layout, best.cost);
+    let model = cost_model(options, result.geometry.clone());
+
+    let report_start = Instant::now();
+    let comparisons = Comparisons::new(
+        options,
+        evolve,
+        &result.ngrams,
+        model.as_ref(),
+        &result.geometry,
+    );
+    let statistics = LayoutStatistics::new(layout, &result.ngrams, &result.geometry);
+
+    log_layout(layout, &result.constraints, &options.optimize_layers);
+    log_starting_layout(layout, &result.constraints);
+    warn_unless_round_trip(options, layout);
+    log_layout_diff(&LayoutDiff::new(&result.starting_layout, &layout.blueprint));
+    log_top_layouts(&result.best);
+    if let Some(summary) = &result.run_summary {
+        log_run_summary(summary);
+    }
+    if options.verbose {
+        log_anneal_levels(&result.anneal);
+    }
+    log_hand_split(&cost, &options.cost_weights);
+    log_missing_characters(
+        &missing_characters(layout, &result.ngrams, options.missing_char_threshold),
+        &options.cost_weights,
+    );
+    log_statistics(&statistics);
+    log_high_layer_characters(&high_layer_characters(
+        layout,
+        &result.ngrams,
+        options.high_layer_threshold,
+    ));
+    if evolve.explain {
+        explain::log_explanation(
+            layout,
+            &result.ngrams,
+            &result.geometry,
+            model.as_ref(),
+            &cost,
+        );
+    }
+    log_comparisons(&cost, comparisons.get()?);
+    ProgressReporter::new(evolve.progress_format).emit(&ProgressEvent::FinalResult {
+        layout: layout.blueprint.layout_string(),
+        cost,
+    });
+    if let Some(path) = &evolve.output_layout_string {
+        fs::write(path, layout.blueprint.layout_string() + "\n")
+            .map_err(|e| KloError::io("write", path, e))?;
+        info!("Wrote the layout string to {}", path);
+    }
+    #[cfg(feature = "exporters")]
+    for arg in &evolve.export {
+        let export = export_arg(arg).expect("--export is validated");
+        if export.0 == ExportFormat::Html {
+            // Written with the result document, like --output-format html.
+            continue;
+        }
+        export::export_result(
+            options,
+            export,
+            &layout.blueprint,
+            &result.ngrams,
+            &result.reproduction,
+        )?;
+    }
+    if let Some(path) = &evolve.convergence_csv {
+        let csv = result.reproduction.comment("# ") + &convergence::to_csv(&result.convergence);
+        fs::write(path, csv).map_err(|e| KloError::io("write", path, e))?;
+        info!(
+            "Wrote the convergence of {} runs to {}",
+            result.stats.completed_runs - result.stats.resumed_runs,
+            path
+        );
+    }
+    write_result(options, evolve, result, &statistics, comparisons.get()?)?;
+    profile::record("report", report_start.elapsed());
+    if evolve.interactive {
+        interactive::run_session(
+            io::stdin().lock(),
+            io::stdout(),
+            layout.blueprint.clone(),
+            &result.ngrams,
+            model.as_ref(),
+        )?;
+    }
+    Ok(())
+}
+
+impl<'a> JsonLayout<'a> {
+    /// The layout strings of a blueprint and of its --optimize-layers.
+    pub fn new(options: &KloOptions, blueprint: &'a Blueprint) -> Self {
+        JsonLayout {
+            layout_string: blueprint.layout_string(),
+            layers: options
+                .optimize_layers
+                .iter()
+                .map(|&layer| (layer, blueprint.layer_string(layer)))
+                .collect(),
+            blueprint,
+        }
+    }
+}
+
+impl JsonCost {
+    pub fn from_breakdown(cost: &CostBreakdown) -> Self {
+        JsonCost {
+            key_position: cost.key_position,
+            hand_balance: cost.hand_balance,
+            same_finger: cost.same_finger,
+            same_hand: cost.same_hand,
+            total: cost.total(),
+        }
+    }
+}
+
+/// Log the layout string at info level, with fixed characters in brackets, and the other optimized layers.
+pub fn log_layout(layout: &Layout, constraints: &Constraints, layers: &[usize]) {
+    if constraints.fixed.is_empty() {
+        info!("Layout, as --starting-layout accepts it:");
+    } else {
+        info!("Layout (fixed keys in brackets):");
+    }
+
+    let marked: String = layout
+        .blueprint
+        .layout_string()
+        .chars()
+        .map(|char| {
+            if constraints.is_fixed(&char.to_string()) {
+                format!("[{}]", char)
+            } else {
+                char.to_string()
+            }
+        })
+        .collect();
+    for line in marked.lines() {
+        info!("    {}", line);
+    }
+
+    for &layer in layers.iter().filter(|&&layer| layer != 0) {
+        info!("Layer {}:", layer);
+        for line in layout.blueprint.layer_string(layer).lines() {
+            info!("    {}", line);
+        }
+    }
+}
+
+/**
+With fixed keys, log the layout once more exactly as --starting-layout
+accepts it, because `log_layout` marks them.
+*/
+fn log_starting_layout(layout: &Layout, constraints: &Constraints) {
+    if constraints.fixed.is_empty() {
+        return;
+    }
+    info!("As --starting-layout:");
+    for line in layout.blueprint.layout_string().lines() {
+        info!("    {}", line);
+    }
+}
+
+/**
+Warn if --starting-layout with the layout string of `layout` gives another
+layer 0, which happens when the evolution moved characters to keys outside the
+rows of the layout string.
+*/
+fn warn_unless_round_trip(options: &KloOptions, layout: &Layout) {
+    let layer_0 = |blueprint: &Blueprint| -> Vec<Vec<Option<String>>> {
+        blueprint
+            .iter()
+            .map(|row| row.iter().map(|key| key.first().cloned()).collect())
+            .collect()
+    };
+    let same = Blueprint::from_args(options, &layout.blueprint.layout_string())
+        .is_ok_and(|parsed| layer_0(&parsed) == layer_0(&layout.blueprint));
+    if !same {
+        warn!("--starting-layout with this layout string doesn't give the same layout, because some characters are on keys outside of its rows. The blueprint of --output-format json has every key.");
+    }
+}
+
+/// Log the layout with the keys that moved from the starting layout marked, and the anchors that didn't.
+fn log_layout_diff(diff: &LayoutDiff) {
+    if diff.moved_keys == 0 {
+        info!("No key moved from the starting layout.");
+        return;
+    }
+    info!(
+        "{} keys moved from the starting layout (marked with ^):",
+        diff.moved_keys
+    );
+    for line in diff.marked_lines(&|char| output_profile::render(&char.to_string()).chars().count())
+    {
+        info!("    {}", line);
+    }
+    if diff.anchors.is_empty() {
+        info!("No character stayed on its key.");
+    } else {
+        info!(
+            "{} characters stayed on their key: {}",
+            diff.anchors.len(),
+            diff.anchors.join(" ")
+        );
+    }
+}
+
+/// Log the --top-n layouts after the best one, and how many characters they differ in.
+pub fn log_top_layouts(top_layouts: &TopLayouts) {
+    let kept = top_layouts.kept();
+    if kept.len() < 2 {
+        return;
+    }
+    for (rank, ranked) in kept.iter().enumerate().skip(1) {
+        info!(
+            "#{} from run {} with a total cost of {:.2}:",
+            rank + 1,
+            ranked.run,
+            ranked.cost.total()
+        );
+        for line in ranked.layout.blueprint.layout_string().lines() {
+            info!("    {}", line);
+        }
+    }
+    info!("Characters on other keys between the top layouts:");
+    for (rank, differences) in top_layouts.pairwise_differences().iter().enumerate() {
+        let cells: Vec<String> = differences
+            .iter()
+            .map(|difference| format!("{:>3}", difference))
+            .collect();
+        info!("    #{:<3}{}", rank + 1, cells.join(""));
+    }
+}
+
+/// Log how the final costs of the runs are spread, with a histogram.
+pub fn log_run_summary(summary: &RunSummary) {
+    info!(
+        "Final costs of {} runs: best {:.2}, mean {:.2}, median {:.2}, standard deviation {:.3}",
+        summary.runs, summary.best, summary.mean, summary.median, summary.standard_deviation
+    );
+    info!(
+        "Distinct layouts: {}, runs that ended on the best one: {}",
+        summary.distinct_layouts, summary.runs_on_best
+    );
+    for line in summary.histogram_lines() {
+        info!("    {}", line);
+    }
+}
+
+/// Log how often every anneal level accepted a worse layout and how often that paid off.
+pub fn log_anneal_levels(levels: &[AnnealLevel]) {
+    if levels.is_empty() {
+        return;
+    }
+    let share = |count: u64, of: u64| {
+        if of > 0 {
+            count as f64 / of as f64 * 100.0
+        } else {
+            0.0
+        }
+    };
+    info!("Anneal levels:");
+    for level in levels {
+        info!(
+            "    {:>2} switches: {} candidates, {} accepted, {} uphill accepted ({:.1}%), {} of them vindicated ({:.1}%)",
+            level.switches,
+            level.candidates,
+            level.accepted,
+            level.uphill_accepted,
+            share(level.uphill_accepted, level.candidates),
+            level.vindicated,
+            share(level.vindicated, level.uphill_accepted)
+        );
+    }
+}
+
+/// Log the share of the left hand, and the target if there is one.
+pub fn log_hand_split(cost: &CostBreakdown, weights: &CostWeights) {
+    match weights.hand_target {
+        Some(target) => info!(
+            "Left hand share: {:.1}% (target {:.1}%)",
+            cost.left_hand_share * 100.0,
+            target * 100.0
+        ),
+        None => info!("Left hand share: {:.1}%", cost.left_hand_share * 100.0),
+    }
+}
+
+/// Warn about the frequent characters on layer 2 or higher, see `high_layer_characters`.
+pub fn log_high_layer_characters(high: &[(usize, CharacterShare)]) {
+    for (layer, character) in high {
+        warn!(
+            "{:?} is on layer {} with {:.2}% of the keystrokes",
+            character.char,
+            layer,
+            character.share * 100.0
+        );
+    }
+}
+
+/// Warn about the characters of the ngrams the layout can't type, see `missing_characters`.
+pub fn log_missing_characters(missing: &[MissingCharacter], weights: &CostWeights) {
+    if missing.is_empty() {
+        return;
+    }
+    let share: f64 = missing.iter().map(|missing| missing.share).sum();
+    match weights.missing_char_cost {
+        Some(cost) => warn!(
+            "{} characters with {:.2}% of the keystrokes are on no key of the layout and cost {} per keystroke:",
+            missing.len(),
+            share * 100.0,
+            cost
+        ),
+        None => warn!(
+            "{} characters with {:.2}% of the keystrokes are on no key of the layout, so they cost nothing (see --missing-char-cost):",
+            missing.len(),
+            share * 100.0
+        ),
+    }
+    for missing in missing {
+        warn!("    {:?} {:.3}%", missing.char, missing.share * 100.0);
+    }
+}
+
+/// Log the finger loads, the bigram shares and the worst bigrams of a layout.
+pub fn log_statistics(statistics: &LayoutStatistics) {
+    info!("Finger loads:");
+    for load in &statistics.finger_loads {
+        info!("    {:<10} {:>5.1}%", load.finger, load.share * 100.0);
+    }
+    info!(
+        "Hand split: {:.1}% left, {:.1}% right",
+        statistics.left_hand_share * 100.0,
+        (1.0 - statistics.left_hand_share) * 100.0
+    );
+    if let Some(home_row) = statistics.home_row {
+        info!("Home row: {:.1}% of the keystrokes", home_row * 100.0);
+    }
+    info!(
+        "Same finger bigrams: {:.2}%, hand alternation: {:.2}%, rolls: {:.2}%, scissors: {:.2}%",
+        statistics.same_finger_bigrams * 100.0,
+        statistics.hand_alternation * 100.0,
+        statistics.rolls * 100.0,
+        statistics.scissors * 100.0
+    );
+    info!("Layers:");
+    for load in &statistics.layer_loads {
+        let characters: Vec<String> = load
+            .characters
+            .iter()
+            .map(|character| format!("{:?} {:.2}%", character.char, character.share * 100.0))
+            .collect();
+        if characters.is_empty() {
+            info!("    {} {:>5.1}%", load.layer, load.share * 100.0);
+        } else {
+            info!(
+                "    {} {:>5.1}%: {}",
+                load.layer,
+                load.share * 100.0,
+                characters.join(", ")
+            );
+        }
+    }
+    if statistics.worst_bigrams.is_empty() {
+        return;
+    }
+    info!("Most frequent same finger bigrams and scissors:");
+    for worst in &statistics.worst_bigrams {
+        info!(
+            "    {:?} {:<11} {:.3}%",
+            worst.bigram,
+            worst.kind.label(),
+            worst.share * 100.0
+        );
+    }
+}
+
+/// Log the comparison layouts with their cost relative to the result.
+pub fn log_comparisons(best: &CostBreakdown, comparisons: &[Comparison]) {
+    if comparisons.is_empty() {
+        return;
+    }
+    info!(
+        "Compared to the result with a total cost of {:.2}:",
+        best.total()
+    );
+    for comparison in comparisons {
+        info!(
+            "    {}: {:.2} ({:+.2}%)",
+            comparison.name,
+            comparison.cost.total(),
+            comparison.delta_percent(best)
+        );
+    }
+}
+
+/// Write the result of an evolution to the output file in the --output-format and to the html --export files (and the json to stdout if requested).
+fn write_result(
+    options: &KloOptions,
+    evolve: &EvolveOptions,
+    optimization: &OptimizationResult,
+    statistics: &LayoutStatistics,
+    comparisons: &[Comparison],
+) -> Result<(), String> {
+    let result = result_document(options, evolve, optimization, statistics, comparisons);
+    let contents = match evolve.output_format {
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(&result)
+                .map_err(|e| format!("Unable to serialize the result: {}", e))?
+                + "\n"
+        }
+        OutputFormat::Markdown => markdown::render(&result),
+        OutputFormat::Html => html_report(&result, optimization),
+        OutputFormat::Text => text(&result),
+    };
+    #[cfg(feature = "exporters")]
+    for (format, path) in evolve.export.iter().filter_map(|arg| export_arg(arg)) {
+        if format == ExportFormat::Html {
+            debug!("Writing {:?} export to {}", format, path);
+            fs::write(path, html_report(&result, optimization))
+                .map_err(|e| format!("Unable to write {}: {}", path, e))?;
+            info!("Exported the layout to {}", path);
+        }
+    }
+    let path = &evolve.filename;
+    if evolve.output_format == OutputFormat::Text {
+        debug!("Appending the result to {}", path);
+        append_result(path, &contents).map_err(|e| format!("Unable to write {}: {}", path, e))?;
+        info!("Appended the result to {}", path);
+    } else {
+        debug!("Writing the result to {}", path);
+        fs::write(path, contents).map_err(|e| format!("Unable to write {}: {}", path, e))?;
+        info!("Wrote the result to {}", path);
+    }
+
+    if evolve.json_stdout {
+        let json = serde_json::to_string(&result).unwrap();
+        println!("{}", output_profile::render_json(&json));
+    }
+    Ok(())
+}
+
+/// The html report of an evolution, with the heatmap of its best layout.
+fn html_report(result: &JsonResult, optimization: &OptimizationResult) -> String {
+    html::render(
+        result,
+        &Heatmap::new(
+            &optimization.best().layout,
+            &optimization.ngrams,
+            &optimization.geometry,
+        ),
+    )
+}
+
+/**
+Append a text result to the file at `path`, after a separator line with the
+local time, so the results of several evolutions pile up in one file like in
+the Python optimizer. A blank line keeps it apart from the result before.
+*/
+fn append_result(path: &str, contents: &str) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let gap = if file.metadata()?.len() > 0 { "\n" } else { "" };
+    let separator = format!("{}# ======== {} ========\n", gap, Local::now().to_rfc3339());
+    io::Write::write_all(&mut file, (separator + contents).as_bytes())
+}
+
+/// The result file of `--output-format text`: the reproduction header and the adjusted options as `#` lines, the layout string and its cost.
+fn text(result: &JsonResult) -> String {
+    let mut text = result.reproduction.comment("# ");
+    for note in result.notes {
+        writeln!(text, "# note: {}", note).unwrap();
+    }
+    writeln!(text, "{}\n", result.layout.layout_string).unwrap();
+    writeln!(text, "total cost: {}", result.cost.total).unwrap();
+    writeln!(text, "key position: {}", result.cost.key_position).unwrap();
+    writeln!(text, "hand balance: {}", result.cost.hand_balance).unwrap();
+    writeln!(text, "same finger: {}", result.cost.same_finger).unwrap();
+    writeln!(text, "same hand: {}", result.cost.same_hand).unwrap();
+    text
+}
+
+/// The result document of an evolution, which every --output-format is written from.
+fn result_document<'a>(
+    options: &KloOptions,
+    evolve: &'a EvolveOptions,
+    optimization: &'a OptimizationResult,
+    statistics: &'a LayoutStatistics,
+    comparisons: &'a [Comparison],
+) -> JsonResult<'a> {
+    let best = optimization.best();
+    let (layout, cost) = (&best.layout, &best.cost);
+    let (geometry, constraints, top_layouts) = (
+        &optimization.geometry,
+        &optimization.constraints,
+        &optimization.best,
+    );
+    JsonResult {
+        schema_version: JSON_SCHEMA_VERSION,
+        command: command_line(),
+        effective_options: optimization.reproduction.options.clone(),
+        reproduction: &optimization.reproduction,
+        layout: JsonLayout::new(options, &layout.blueprint),
+        geometry: &geometry.name,
+        cost: JsonCost::from_breakdown(cost),
+        top_layouts: top_layouts
+            .kept()
+            .iter()
+            .map(|ranked| JsonTopLayout {
+                run: ranked.run,
+                layout_string: ranked.layout.blueprint.layout_string(),
+                cost: JsonCost::from_breakdown(&ranked.cost),
+            })
+            .collect(),
+        top_layout_differences: top_layouts.pairwise_differences(),
+        weights: options.cost_weights.to_map(),
+        hand_split: JsonHandSplit {
+            left: cost.left_hand_share,
+            target: options.cost_weights.hand_target,
+        },
+        statistics,
+        missing_characters: missing_characters(
+            layout,
+            &optimization.ngrams,
+            options.missing_char_threshold,
+        ),
+        diff: LayoutDiff::new(&optimization.starting_layout, &layout.blueprint),
+        run_summary: optimization.run_summary.as_ref(),
+        convergence: &optimization.convergence,
+        anneal: &optimization.anneal,
+        comparisons: comparisons
+            .iter()
+            .map(|comparison| JsonComparison {
+                name: comparison.name.clone(),
+                layout_string: comparison.layout_string.clone(),
+                cost: JsonCost::from_breakdown(&comparison.cost),
+                delta_percent: comparison.delta_percent(cost),
+                statistics: &comparison.statistics,
+            })
+            .collect(),
+        notes: &evolve.notes,
+        profile: profile::timings(),
+        pinned: constraints.pinned.iter().cloned().collect(),
+        fixed: constraints
+            .fixed
+            .iter()
+            .map(|fixed_key| (fixed_key.char.clone(), fixed_key.position.clone()))
+            .collect(),
+    }
+}
+
+/**
+The result document of a single layout that wasn't evolved, for the html of
+`klo export`: it has no runs, no comparisons and no key moved.
+*/
+#[cfg(feature = "exporters")]
+pub fn layout_document<'a>(
+    options: &KloOptions,
+    reproduction: &'a ReproHeader,
+    layout: &'a Layout,
+    ngrams: &NGrams,
+    geometry: &'a Geometry,
+    statistics: &'a LayoutStatistics,
+) -> JsonResult<'a> {
+    let cost = cost_model(options, geometry.clone()).score(layout, ngrams);
+    JsonResult {
+        schema_version: JSON_SCHEMA_VERSION,
+        command: command_line(),
+        effective_options: reproduction.options.clone(),
+        reproduction,
+        layout: JsonLayout::new(options, &layout.blueprint),
+        geometry: &geometry.name,
+        cost: JsonCost::from_breakdown(&cost),
+        top_layouts: vec![],
+        top_layout_differences: vec![],
+        weights: options.cost_weights.to_map(),
+        hand_split: JsonHandSplit {
+            left: cost.left_hand_share,
+            target: options.cost_weights.hand_target,
+        },
+        statistics,
+        missing_characters: missing_characters(layout, ngrams, options.missing_char_threshold),
+        diff: LayoutDiff::new(&layout.blueprint, &layout.blueprint),
+        run_summary: None,
+        convergence: &[],
+        anneal: &[],
+        comparisons: vec![],
+        notes: &[],
+        profile: profile::timings(),
+        pinned: vec![],
+        fixed: BTreeMap::new(),
+    }
+}
+
+/// The command line of this run as `klo` with its arguments, quoted for a POSIX shell.
+fn command_line() -> String {
+    let quote = |arg: String| {
+        let plain = !arg.is_empty()
+            && arg
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || "-_./=:,+@%".contains(char));
+        if plain {
+            arg
+        } else {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    };
+    std::iter::once("klo".to_string())
+        .chain(std::env::args().skip(1).map(quote))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,114 @@
+use std::fs;
+
+use serde::Serialize;
+
+use crate::{
+    config_file::{effective_config_value, fnv1a},
+    klo_options::{EvolveOptions, KloOptions},
+};
+
+/**
+What a result was made with, so a file found on disk later can be reproduced,
+or at least explained: the version of klo, the options, the seed and hashes of
+the ngrams config and the weights. Every file klo writes a result to starts
+with it, as comment lines in csv files, an xml comment in svg, html and
+markdown, a text chunk in png and the `reproduction` field in json. The
+--runs-csv file, which several evolutions share, keeps it in a file of its
+own, see `RunsCsv`.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct ReproHeader {
+    /// The version of the crate.
+    pub version: &'static str,
+    /// `git describe` of the checkout klo was built from, none if it wasn't built from one.
+    pub git_describe: Option<&'static str>,
+    /// The seed of the runs of an evolution, none for other results.
+    pub seed: Option<u64>,
+    /// The --ngrams-config the ngrams were loaded from, none if they were counted from --corpus texts.
+    pub ngrams_config: Option<String>,
+    /// The FNV-1a hash of the contents of the ngrams config (not of the files it lists), none if it can't be read.
+    pub ngrams_config_hash: Option<String>,
+    /// The FNV-1a hash of the effective weights of the cost components.
+    pub weights_hash: String,
+    /// The effective options, keyed like in klo.toml. The json result has them as `effective_options`.
+    #[serde(skip)]
+    pub options: toml::Value,
+}
+
+impl ReproHeader {
+    /// The header of a result of the given options, with the evolve options if it is one of `klo evolve`.
+    pub fn new(options: &KloOptions, evolve: Option<&EvolveOptions>) -> Self {
+        let ngrams_config = if options.corpus.is_empty() {
+            Some(options.ngrams_config.clone())
+        } else {
+            None
+        };
+        let ngrams_config_hash = ngrams_config
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| fnv1a(&contents));
+        let weights: String = options
+            .cost_weights
+            .to_map()
+            .iter()
+            .map(|(path, weight)| format!("{} = {}\n", path, weight))
+            .collect();
+        ReproHeader {
+            version: env!("CARGO_PKG_VERSION"),
+            git_describe: option_env!("KLO_GIT_DESCRIBE"),
+            seed: evolve.and_then(|evolve| evolve.seed),
+            ngrams_config,
+            ngrams_config_hash,
+            weights_hash: fnv1a(&weights),
+            options: effective_config_value(options, evolve),
+        }
+    }
+
+    /// The header as lines of text, ending with the options as klo.toml.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![match self.git_describe {
+            Some(describe) => format!("klo {} ({})", self.version, describe),
+            None => format!("klo {}", self.version),
+        }];
+        lines.push(match self.seed {
+            Some(seed) => format!("seed: {}", seed),
+            None => "seed: none".to_string(),
+        });
+        lines.push(match (&self.ngrams_config, &self.ngrams_config_hash) {
+            (Some(path), Some(hash)) => format!("ngrams config: {} (fnv1a {})", path, hash),
+            (Some(path), None) => format!("ngrams config: {} (unreadable)", path),
+            (None, _) => {
+                "ngrams config: none, the ngrams were counted from the corpus texts".to_string()
+            }
+        });
+        lines.push(format!("weights: fnv1a {}", self.weights_hash));
+        lines.push("options:".to_string());
+        lines.extend(
+            self.options
+                .to_string()
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| format!("    {}", line)),
+        );
+        lines
+    }
+
+    /// The header as lines starting with `prefix`, like `# ` for csv.
+    pub fn comment(&self, prefix: &str) -> String {
+        self.lines()
+            .iter()
+            .map(|line| format!("{}{}\n", prefix, line))
+            .collect()
+    }
+
+    /// The header as an xml comment, which html and markdown hide as well.
+    pub fn xml_comment(&self) -> String {
+        // `--` ends a comment early in html and is invalid in xml comments.
+        let lines: Vec<String> = self
+            .lines()
+            .iter()
+            .map(|line| line.replace("--", "- -"))
+            .collect();
+        format!("<!--\n{}\n-->\n", lines.join("\n"))
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+/// The number of bars of `RunSummary::histogram`.
+const HISTOGRAM_BINS: usize = 10;
+
+/// The longest bar of `RunSummary::histogram_lines`, in characters.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/**
+How the final layouts of the runs of an evolution are spread, to judge whether
+there were enough runs: if most runs end on the best layout, more won't find a
+better one.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// The number of runs the summary is of.
+    pub runs: usize,
+    /// The lowest final cost.
+    pub best: f64,
+    /// The mean final cost.
+    pub mean: f64,
+    /// The median final cost.
+    pub median: f64,
+    /// The standard deviation of the final costs.
+    pub standard_deviation: f64,
+    /// The number of different final layouts, by the strings of their optimized layers.
+    pub distinct_layouts: usize,
+    /// The number of runs that ended on the layout with the lowest cost.
+    pub runs_on_best: usize,
+    /// The final costs in equally wide bins from the best to the worst, best first.
+    pub histogram: Vec<HistogramBin>,
+}
+
+/// The runs with a final cost from `from` to `to`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HistogramBin {
+    /// The lowest cost of the bin.
+    pub from: f64,
+    /// The highest cost of the bin, included only in the last one.
+    pub to: f64,
+    /// The number of runs with a final cost in the bin.
+    pub runs: usize,
+}
+
+impl RunSummary {
+    /// The summary of the final cost and the fingerprint of every run, none without runs.
+    pub fn new(finals: &[(f64, String)]) -> Option<Self> {
+        if finals.is_empty() {
+            return None;
+        }
+        let mut costs: Vec<f64> = finals.iter().map(|(cost, _)| *cost).collect();
+        costs.sort_by(|a, b| a.total_cmp(b));
+        let runs = costs.len();
+        let (best, worst) = (costs[0], costs[runs - 1]);
+        let mean = costs.iter().sum::<f64>() / runs as f64;
+        let median = if runs.is_multiple_of(2) {
+            (costs[runs / 2 - 1] + costs[runs / 2]) / 2.0
+        } else {
+            costs[runs / 2]
+        };
+        let variance = costs.iter().map(|cost| (cost - mean).powi(2)).sum::<f64>() / runs as f64;
+
+        let mut layouts: HashMap<&str, usize> = HashMap::new();
+        for (_, fingerprint) in finals {
+            *layouts.entry(fingerprint).or_default() += 1;
+        }
+        let best_fingerprint = finals
+            .iter()
+            .find(|(cost, _)| *cost == best)
+            .map(|(_, fingerprint)| fingerprint.as_str())
+            .expect("the best cost is one of the costs");
+
+        let bins = if worst > best { HISTOGRAM_BINS } else { 1 };
+        let width = (worst - best) / bins as f64;
+        let mut histogram: Vec<HistogramBin> = (0..bins)
+            .map(|bin| HistogramBin {
+                from: best + width * bin as f64,
+                to: if bin + 1 == bins {
+                    worst
+                } else {
+                    best + width * (bin + 1) as f64
+                },
+                runs: 0,
+            })
+            .collect();
+        for cost in &costs {
+            let bin = if width > 0.0 {
+                (((cost - best) / width) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            histogram[bin].runs += 1;
+        }
+
+        Some(RunSummary {
+            runs,
+            best,
+            mean,
+            median,
+            standard_deviation: variance.sqrt(),
+            distinct_layouts: layouts.len(),
+            runs_on_best: layouts[best_fingerprint],
+            histogram,
+        })
+    }
+
+    /// The histogram as lines of bars of `#`, the longest one 40 characters long.
+    pub fn histogram_lines(&self) -> Vec<String> {
+        let most = self
+            .histogram
+            .iter()
+            .map(|bin| bin.runs)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let decimals = self.label_decimals();
+        self.histogram
+            .iter()
+            .map(|bin| {
+                // Every bin with runs gets at least one character.
+                let length = (bin.runs * HISTOGRAM_WIDTH).div_ceil(most);
+                format!(
+                    "{:>8.decimals$} - {:<8.decimals$} {:<width$} {}",
+                    bin.from,
+                    bin.to,
+                    "#".repeat(length),
+                    bin.runs,
+                    decimals = decimals,
+                    width = HISTOGRAM_WIDTH
+                )
+            })
+            .collect()
+    }
+
+    /**
+    The decimals of the bounds of `histogram_lines`: at least 2, and enough that
+    the bounds of neighbouring bins differ, since the bins of runs that end on
+    almost the same cost are narrower than a hundredth.
+    */
+    fn label_decimals(&self) -> usize {
+        let width = self.histogram[0].to - self.histogram[0].from;
+        if width > 0.0 {
+            (-width.log10()).ceil().clamp(2.0, 15.0) as usize
+        } else {
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_nan_cost_sorts_last_instead_of_panicking() {
+        let finals = [
+            (f64::NAN, "c".to_string()),
+            (2.0, "b".to_string()),
+            (1.0, "a".to_string()),
+        ];
+        let summary = RunSummary::new(&finals).unwrap();
+        assert_eq!(summary.best, 1.0);
+        assert_eq!(summary.median, 2.0);
+        assert_eq!(
+            summary.histogram.iter().map(|bin| bin.runs).sum::<usize>(),
+            3
+        );
+    }
+
+    #[test]
+    fn narrow_bins_get_enough_decimals_to_tell_their_bounds_apart() {
+        let finals = [(1.9787, "a".to_string()), (1.9827, "b".to_string())];
+        let lines = RunSummary::new(&finals).unwrap().histogram_lines();
+        let bounds: Vec<&str> = lines
+            .iter()
+            .map(|line| line.split(" - ").next().unwrap().trim())
+            .collect();
+        assert_eq!(bounds[..3], ["1.9787", "1.9791", "1.9795"]);
+        assert!(lines[9].contains(" - 1.9827 "), "{}", lines[9]);
+
+        let finals = [(1.0, "a".to_string()), (21.0, "b".to_string())];
+        let lines = RunSummary::new(&finals).unwrap().histogram_lines();
+        assert!(lines[1].starts_with("    3.00 - 5.00 "), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+};
+
+use log::debug;
+
+use crate::{
+    layout::{BlueprintHelpers, Layout},
+    repro::ReproHeader,
+    scoring::CostBreakdown,
+    statistics::LayoutStatistics,
+};
+
+/// The columns of the --runs-csv file.
+const HEADER: &str = "config_hash,klo_version,seed,run,total,key_position,hand_balance,same_finger,same_hand,left_hand_share,same_finger_bigrams,layout_string";
+
+/**
+The --runs-csv file, which gets a row for every finished run. Separate
+evolutions can append to the same file: only the one that creates it writes
+the header, and every row is appended with a single write, so the rows of
+evolutions running at the same time don't interleave.
+
+`config_hash` tells the rows of evolutions with different options apart. A
+header at the top of the file would only describe the first evolution, so
+the reproduction header of every `config_hash` goes to the `.configs` file
+next to it instead, and every row has the version of klo and the seed.
+*/
+pub struct RunsCsv {
+    path: String,
+    config_hash: String,
+    seed: u64,
+}
+
+impl RunsCsv {
+    /**
+    Create the file with the header, unless it already exists. The header is
+    written with the handle that created the file, so an evolution that opens
+    the file at the same time never writes it a second time. The reproduction
+    header is added to the `.configs` file unless it has one for
+    `config_hash` already.
+    */
+    pub fn open(
+        path: &str,
+        config_hash: &str,
+        seed: u64,
+        reproduction: &ReproHeader,
+    ) -> Result<Self, String> {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                debug!("Writing the header of {}", path);
+                file.write_all(format!("{}\n", HEADER).as_bytes())
+                    .map_err(|e| format!("Unable to write {}: {}", path, e))?;
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(format!("Unable to create {}: {}", path, e)),
+        }
+        add_configuration(&configs_path(path), config_hash, reproduction)?;
+        Ok(RunsCsv {
+            path: path.to_string(),
+            config_hash: config_hash.to_string(),
+            seed,
+        })
+    }
+
+    /// Append the row of a finished run.
+    pub fn run_finished(
+        &self,
+        run: u128,
+        layout: &Layout,
+        cost: &CostBreakdown,
+        statistics: &LayoutStatistics,
+    ) -> Result<(), String> {
+        self.append(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            self.config_hash,
+            env!("CARGO_PKG_VERSION"),
+            self.seed,
+            run,
+            cost.total(),
+            cost.key_position,
+            cost.hand_balance,
+            cost.same_finger,
+            cost.same_hand,
+            cost.left_hand_share,
+            statistics.same_finger_bigrams,
+            quote(&layout.blueprint.layout_string())
+        ))
+    }
+
+    fn append(&self, contents: &str) -> Result<(), String> {
+        OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| format!("Unable to write {}: {}", self.path, e))
+    }
+}
+
+/// The file with the reproduction header of every `config_hash` of the rows of a --runs-csv file.
+fn configs_path(path: &str) -> String {
+    format!("{}.configs", path)
+}
+
+/**
+Append the reproduction header of `config_hash` to the configs file, after a
+`config_hash:` line, unless it has that line already. Evolutions with the
+same options that start at the same time may both add it, which is harmless.
+*/
+fn add_configuration(
+    path: &str,
+    config_hash: &str,
+    reproduction: &ReproHeader,
+) -> Result<(), String> {
+    let marker = format!("config_hash: {}", config_hash);
+    match fs::read_to_string(path) {
+        Ok(configs) if configs.lines().any(|line| line == marker) => return Ok(()),
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Unable to read {}: {}", path, e)),
+    }
+    debug!("Adding the configuration {} to {}", config_hash, path);
+    let entry = format!("{}\n{}\n", marker, reproduction.comment(""));
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(entry.as_bytes()))
+        .map_err(|e| format!("Unable to write {}: {}", path, e))
+}
+
+/// A csv field in quotes, with the quotes in it doubled.
+pub fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+#[cfg(feature = "cli")]
+use crate::klo_options::KloOptions;
+use crate::{
+    geometry::{Geometry, Hand},
+    key_costs::KeyCosts,
+    layout::{Layout, Pos},
+    ngrams::NGrams,
+    profile,
+    weights::CostWeights,
+};
+
+/// The cost of a layout against a set of ngrams, split into its components. Every component is already weighted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostBreakdown {
+    /// The summed effort of hitting every key, weighted by letter frequency.
+    pub key_position: f64,
+    /// How far the left hand share is off --hand-target.
+    pub hand_balance: f64,
+    /// The bigrams typed with the same finger on two different keys, weighted by the rows between the keys.
+    pub same_finger: f64,
+    /// The bigrams typed by one hand instead of alternating hands.
+    pub same_hand: f64,
+    /// The share of keystrokes typed by the left hand, ignoring keys both hands reach.
+    pub left_hand_share: f64,
+}
+
+impl CostBreakdown {
+    /// The sum of the weighted cost components.
+    pub fn total(&self) -> f64 {
+        self.key_position + self.hand_balance + self.same_finger + self.same_hand
+    }
+}
+
+/// Like `KeyCosts::position_cost`, with the --missing-char-cost for a character on no key (none without it).
+fn letter_position_cost(pos: Option<Pos>, weights: &CostWeights) -> Option<f64> {
+    match pos {
+        Some(pos) => Some(weights.key_costs.position_cost(&pos)),
+        None => weights.missing_char_cost,
+    }
+}
+
+/**
+A way to score layouts against ngrams. The evolution, analyze, compare and the
+interactive session only see this trait, so a library user can evolve layouts
+for another metric by implementing it. The runs of an evolution share the model
+across threads.
+*/
+pub trait CostModel: Sync {
+    /// The cost of a layout against the ngrams. Lower is better.
+    fn score(&self, layout: &Layout, ngrams: &NGrams) -> CostBreakdown;
+    /// The name of the model, like `effort`.
+    fn name(&self) -> &str;
+    /**
+    The share of single ngrams in the cost of `score`, for explaining a cost
+    in reports. It is never called during an evolution, so it may be slow.
+    Models that can't tell return none.
+    */
+    fn attributions(&self, _layout: &Layout, _ngrams: &NGrams) -> Vec<Attribution> {
+        vec![]
+    }
+    /**
+    The key costs that decide which of several keys with the same character
+    types it, see [`Layout::from_blueprint`]. The built-in table by default.
+    */
+    fn key_costs(&self) -> Arc<KeyCosts> {
+        Arc::new(KeyCosts::default())
+    }
+}
+
+/// The part of a cost component one ngram is responsible for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribution {
+    /// The ids of the characters of the ngram.
+    pub ngram: Vec<u16>,
+    /// The cost component, named like in the weights, e.g. `key_position`.
+    pub component: &'static str,
+    /// The weighted frequency of the ngram.
+    pub weight: f64,
+    /// The weighted cost.
+    pub cost: f64,
+}
+
+/// The default model: the effort of hitting every key, plus the hand balance if there is a --hand-target.
+pub struct EffortModel<'a> {
+    /// The weights of the cost components.
+    pub weights: &'a CostWeights,
+    /// The geometry that decides the hand of every key.
+    pub geometry: Geometry,
+}
+
+impl CostModel for EffortModel<'_> {
+    fn score(&self, layout: &Layout, ngrams: &NGrams) -> CostBreakdown {
+        cost_breakdown(layout, ngrams, self.weights, &self.geometry)
+    }
+
+    fn name(&self) -> &str {
+        "effort"
+    }
+
+    fn key_costs(&self) -> Arc<KeyCosts> {
+        Arc::clone(&self.weights.key_costs)
+    }
+
+    /// The key position cost of every letter and the cost of every same finger and same hand bigram. The hand balance belongs to no single ngram.
+    fn attributions(&self, layout: &Layout, ngrams: &NGrams) -> Vec<Attribution> {
+        let letters = ngrams.letters.iter().filter_map(|&(letter, weight)| {
+            letter_position_cost(layout.positions[usize::from(letter)], self.weights).map(|cost| {
+                Attribution {
+                    ngram: vec![letter],
+                    component: "key_position",
+                    weight,
+                    cost: self.weights.key_position * weight * cost,
+                }
+            })
+        });
+        let same_finger = ngrams.bigrams.iter().filter_map(|&(bigram, weight)| {
+            same_finger_penalty(layout, bigram, &self.geometry).map(|penalty| Attribution {
+                ngram: bigram.to_vec(),
+                component: "same_finger",
+                weight,
+                cost: self.weights.same_finger * weight * penalty,
+            })
+        });
+        let same_hand = ngrams
+            .bigrams
+            .iter()
+            .filter(|&&(bigram, _)| is_same_hand(layout, bigram, &self.geometry))
+            .map(|&(bigram, weight)| Attribution {
+                ngram: bigram.to_vec(),
+                component: "same_hand",
+                weight,
+                cost: self.weights.same_hand * weight,
+            });
+        letters.chain(same_finger).chain(same_hand).collect()
+    }
+}
+
+/// The model the command line scores layouts with, the `EffortModel` of the --weights.
+#[cfg(feature = "cli")]
+pub fn cost_model(options: &KloOptions, geometry: Geometry) -> Box<dyn CostModel + '_> {
+    Box::new(EffortModel {
+        weights: &options.cost_weights,
+        geometry,
+    })
+}
+
+/// Score a layout against the given ngrams, with the hands of the geometry.
+pub fn cost_breakdown(
+    layout: &Layout,
+    ngrams: &NGrams,
+    weights: &CostWeights,
+    geometry: &Geometry,
+) -> CostBreakdown {
+    let (key_position, hand_balance, left_hand_share) = profile::time("scoring unigrams", || {
+        let left_hand_share = left_hand_share(layout, &ngrams.letters, geometry);
+        let hand_balance = match weights.hand_target {
+            Some(target) => weights.hand_balance * (left_hand_share - target).abs() * 100.0,
+            None => 0.0,
+        };
+        (
+            weights.key_position * key_position_cost(layout, &ngrams.letters, weights),
+            hand_balance,
+            left_hand_share,
+        )
+    });
+    let (same_finger, same_hand) = profile::time("scoring bigrams", || {
+        let (mut same_finger, mut same_hand) = (0.0, 0.0);
+        for &(bigram, weight) in &ngrams.bigrams {
+            if let Some(penalty) = same_finger_penalty(layout, bigram, geometry) {
+                same_finger += weight * penalty;
+            }
+            if is_same_hand(layout, bigram, geometry) {
+                same_hand += weight;
+            }
+        }
+        (
+            weights.same_finger * same_finger,
+            weights.same_hand * same_hand,
+        )
+    });
+    CostBreakdown {
+        key_position,
+        hand_balance,
+        same_finger,
+        same_hand,
+        left_hand_share,
+    }
+}
+
+fn left_hand_share(layout: &Layout, letters: &[(u16, f64)], geometry: &Geometry) -> f64 {
+    let mut left = 0.0;
+    let mut both = 0.0;
+    for &(letter, weight) in letters {
+        let hand =
+            layout.positions[usize::from(letter)].and_then(|pos| geometry.hand_of(pos.key()));
+        match hand {
+            Some(Hand::Left) => {
+                left += weight;
+                both += weight;
+            }
+            Some(Hand::Right) => both += weight,
+            None => {}
+        }
+    }
+    if both > 0.0 {
+        left / both
+    } else {
+        0.5
+    }
+}
+
+/// Sum the position cost of every letter, skipping letters the layout can't type.
+fn key_position_cost(layout: &Layout, letters: &[(u16, f64)], weights: &CostWeights) -> f64 {
+    letters
+        .iter()
+        .filter_map(|&(letter, weight)| {
+            letter_position_cost(layout.positions[usize::from(letter)], weights)
+                .map(|cost| weight * cost)
+        })
+        .sum()
+}
+
+/**
+The penalty of a bigram whose characters are on two different keys of the same
+finger: 1, and 1 more for every row between the keys. Repeating a key, keys
+that several fingers type and characters on no key have none.
+*/
+fn same_finger_penalty(
+    layout: &Layout,
+    [first, second]: [u16; 2],
+    geometry: &Geometry,
+) -> Option<f64> {
+    let first = layout.positions[usize::from(first)]?;
+    let second = layout.positions[usize::from(second)]?;
+    if first.same_key(&second) {
+        return None;
+    }
+    let finger = geometry.finger_of(first.key())?;
+    if geometry.finger_of(second.key()) != Some(finger) {
+        return None;
+    }
+    Some(1.0 + f64::from(first.row_distance(&second)))
+}
+
+/// Whether both characters of a bigram are typed by the same hand. Keys both hands reach and characters on no key are skipped.
+fn is_same_hand(layout: &Layout, [first, second]: [u16; 2], geometry: &Geometry) -> bool {
+    let hand =
+        |id: u16| layout.positions[usize::from(id)].and_then(|pos| geometry.hand_of(pos.key()));
+    match (hand(first), hand(second)) {
+        (Some(first), Some(second)) => first == second,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Blueprint, BlueprintHelpers};
+
+    /// `a` and `A` on the first key (costs 2, layer 1 adds 10), `b` on the second (cost 5) and `c` on no key.
+    fn tiny_layout(ngrams: &NGrams, weights: &CostWeights) -> Layout {
+        let blueprint = Blueprint::from_json(r#"[[["a", "A"], ["b"]]]"#).unwrap();
+        Layout::from_blueprint(blueprint, &ngrams.interner, &weights.key_costs)
+    }
+
+    fn tiny_weights(missing_char_cost: Option<f64>) -> CostWeights {
+        CostWeights {
+            missing_char_cost,
+            key_costs: Arc::new(KeyCosts {
+                keys: vec![vec![2.0, 5.0]],
+                layers: vec![0.0, 10.0],
+                ..KeyCosts::default()
+            }),
+            ..CostWeights::default()
+        }
+    }
+
+    #[test]
+    fn the_key_position_cost_sums_the_letter_frequencies_times_the_position_costs() {
+        let ngrams = NGrams::from_pregenerated("3 a\n1 b\n2 A\n4 c\n", "", "").unwrap();
+        let geometry = Geometry::neo();
+        for (missing_char_cost, expected) in [(None, 35.0), (Some(7.0), 63.0)] {
+            let weights = tiny_weights(missing_char_cost);
+            let layout = tiny_layout(&ngrams, &weights);
+            let breakdown = cost_breakdown(&layout, &ngrams, &weights, &geometry);
+            // 3 * 2 + 1 * 5 + 2 * (2 + 10), and 4 * 7 for the missing `c`.
+            assert_eq!(breakdown.key_position, expected);
+        }
+    }
+
+    /**
+    The key position cost and the hand share of the baselines on the fixture
+    corpus, once with the char ids and once by looking up the characters as
+    strings like klo did before the ids.
+    */
+    #[cfg(all(feature = "cli", feature = "text-corpus"))]
+    #[test]
+    fn the_char_ids_score_like_string_keys() {
+        use std::collections::HashMap;
+
+        use clap::Clap;
+
+        use crate::layout::builtin_baseline;
+
+        let options = KloOptions::try_parse_from(["klo", "evolve"]).unwrap();
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/corpus.txt");
+        let ngrams = NGrams::from_text_file(corpus).unwrap();
+        let weights = CostWeights {
+            hand_target: Some(0.5),
+            ..CostWeights::default()
+        };
+        let geometry = Geometry::neo();
+        for baseline in ["neo", "bone", "qwertz"] {
+            let layout_string = builtin_baseline(baseline).unwrap().layout_string;
+            let layout =
+                Layout::from_layout_arg(&options, layout_string, &ngrams.interner).unwrap();
+
+            let mut positions: HashMap<String, Pos> = HashMap::new();
+            for (row, keys) in layout.blueprint.iter().enumerate() {
+                for (key, layers) in keys.iter().enumerate() {
+                    for (layer, char) in layers.iter().enumerate() {
+                        let pos = Pos::new(row, key, layer);
+                        let cost = weights.key_costs.position_cost(&pos);
+                        let cheaper = positions.get(char).is_none_or(|existing| {
+                            cost < weights.key_costs.position_cost(existing)
+                        });
+                        if cheaper {
+                            positions.insert(char.clone(), pos);
+                        }
+                    }
+                }
+            }
+            let (mut key_position, mut left, mut both) = (0.0, 0.0, 0.0);
+            for &(letter, weight) in &ngrams.letters {
+                let pos = positions.get(&ngrams.interner.text(&[letter])).copied();
+                if let Some(cost) = letter_position_cost(pos, &weights) {
+                    key_position += weight * cost;
+                }
+                match pos.and_then(|pos| geometry.hand_of(pos.key())) {
+                    Some(Hand::Left) => {
+                        left += weight;
+                        both += weight;
+                    }
+                    Some(Hand::Right) => both += weight,
+                    None => {}
+                }
+            }
+
+            let breakdown = cost_breakdown(&layout, &ngrams, &weights, &geometry);
+            assert_eq!(breakdown.key_position, key_position, "{}", baseline);
+            assert_eq!(breakdown.left_hand_share, left / both, "{}", baseline);
+        }
+    }
+}
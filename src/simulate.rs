@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use log::info;
+
+use crate::{
+    explain::PositionNames,
+    geometry::Geometry,
+    interner::Interner,
+    key_costs::KeyCosts,
+    layout::{Blueprint, Layout, Pos},
+    statistics::{KeyFingers, Transition},
+};
+
+/// The transitions of a sentence, for its summary.
+#[derive(Default)]
+struct Summary {
+    typed: usize,
+    missing: usize,
+    same_key: usize,
+    same_finger: usize,
+    rolls: usize,
+    scissors: usize,
+    alternations: usize,
+    /// Transitions from or to a key several fingers type.
+    unclassified: usize,
+}
+
+impl Summary {
+    fn count(&mut self, transition: Option<Transition>) {
+        match transition {
+            Some(Transition::SameKey) => self.same_key += 1,
+            Some(Transition::SameFinger) => self.same_finger += 1,
+            Some(Transition::Roll { scissor }) => {
+                self.rolls += 1;
+                if scissor {
+                    self.scissors += 1;
+                }
+            }
+            Some(Transition::Alternation) => self.alternations += 1,
+            None => self.unclassified += 1,
+        }
+    }
+
+    fn log(&self, sentence: usize) {
+        let transitions = self.same_key + self.same_finger + self.rolls + self.alternations;
+        let share = |count: usize| {
+            if transitions > 0 {
+                count as f64 / transitions as f64 * 100.0
+            } else {
+                0.0
+            }
+        };
+        info!(
+            "Sentence {}: {} characters typed, {} missing; {:.1}% alternations, {:.1}% rolls, {:.1}% scissors, {:.1}% same finger, {:.1}% same key of {} transitions ({} with a key of several fingers)",
+            sentence,
+            self.typed,
+            self.missing,
+            share(self.alternations),
+            share(self.rolls),
+            share(self.scissors),
+            share(self.same_finger),
+            share(self.same_key),
+            transitions,
+            self.unclassified
+        );
+    }
+}
+
+/**
+Log how a sample text is typed on a layout, sentence by sentence: the position,
+finger and hand of every character and how it is reached from the previous
+one, using the same classification as `LayoutStatistics`. Characters on higher
+layers count for their key, without the modifier.
+*/
+pub fn log_simulation(
+    blueprint: &Blueprint,
+    geometry: &Geometry,
+    key_costs: &Arc<KeyCosts>,
+    sample: &str,
+) -> Result<(), String> {
+    let mut interner = Interner::default();
+    for char in sample.chars() {
+        interner.intern(char)?;
+    }
+    let layout = Layout::from_blueprint(blueprint.clone(), &Arc::new(interner), key_costs);
+    let fingers = KeyFingers::new(geometry);
+    let names = PositionNames::new(geometry);
+
+    for (index, sentence) in sentences(sample).iter().enumerate() {
+        info!("Typing {:?}:", sentence);
+        let (lines, summary) = type_sentence(&layout, geometry, &fingers, &names, sentence);
+        for line in lines {
+            info!("    {}", line);
+        }
+        summary.log(index + 1);
+    }
+    Ok(())
+}
+
+/// The line of every character of a sentence typed on `layout`, and the summary of its transitions.
+fn type_sentence(
+    layout: &Layout,
+    geometry: &Geometry,
+    fingers: &KeyFingers,
+    names: &PositionNames,
+    sentence: &str,
+) -> (Vec<String>, Summary) {
+    let mut lines = vec![];
+    let mut summary = Summary::default();
+    let mut previous: Option<Pos> = None;
+    for char in sentence.chars() {
+        let pos = match layout.position(&char.to_string()) {
+            Some(pos) => pos,
+            None => {
+                lines.push(format!("{:?} is not on the layout", char));
+                summary.missing += 1;
+                previous = None;
+                continue;
+            }
+        };
+        summary.typed += 1;
+
+        let key_fingers: Vec<&str> = fingers
+            .all(&pos)
+            .iter()
+            .map(|&finger| geometry.fingers[finger].name.as_str())
+            .collect();
+        let hand = match fingers.only(&pos) {
+            Some(finger) => format!("{} hand", finger.hand()),
+            None if key_fingers.is_empty() => "no finger".to_string(),
+            None => "either hand".to_string(),
+        };
+        let transition = previous.map(|previous| {
+            let transition = Transition::of(fingers, &previous, &pos);
+            summary.count(transition);
+            match transition {
+                Some(Transition::SameKey) => ", same key",
+                Some(Transition::SameFinger) => ", same finger",
+                Some(Transition::Roll { scissor: true }) => ", roll, scissor",
+                Some(Transition::Roll { scissor: false }) => ", roll",
+                Some(Transition::Alternation) => ", alternation",
+                None => "",
+            }
+        });
+        lines.push(format!(
+            "{:?} {} ({}, {}){}",
+            char,
+            names.name(&pos),
+            key_fingers.join("/"),
+            hand,
+            transition.unwrap_or("")
+        ));
+        previous = Some(pos);
+    }
+    (lines, summary)
+}
+
+/// The sentences of a text, each ending after a `.`, `!` or `?`, without the whitespace between them.
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = vec![];
+    let mut start = 0;
+    for (index, char) in text.char_indices() {
+        if matches!(char, '.' | '!' | '?') {
+            sentences.push(&text[start..index + char.len_utf8()]);
+            start = index + char.len_utf8();
+        }
+    }
+    sentences.push(&text[start..]);
+    sentences
+        .into_iter()
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+#[cfg(all(test, feature = "text-corpus"))]
+mod tests {
+    use super::*;
+    use crate::{
+        layout::{builtin_baseline, BlueprintHelpers},
+        ngrams::NGrams,
+        statistics::LayoutStatistics,
+    };
+
+    #[test]
+    fn the_transitions_of_a_sentence_add_up_to_its_statistics() {
+        let sentence = "zwölf boxkämpfer jagen 😀 viktor quer über den großen sylter deich";
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+        let geometry = Geometry::neo();
+        let key_costs = Arc::new(KeyCosts::default());
+
+        let mut interner = Interner::default();
+        for char in sentence.chars() {
+            interner.intern(char).unwrap();
+        }
+        let layout = Layout::from_blueprint(blueprint.clone(), &Arc::new(interner), &key_costs);
+        let fingers = KeyFingers::new(&geometry);
+        let names = PositionNames::new(&geometry);
+        let (lines, summary) = type_sentence(&layout, &geometry, &fingers, &names, sentence);
+        assert_eq!(lines.len(), sentence.chars().count());
+        assert_eq!(summary.missing, 1);
+        assert_eq!(lines[23], "'😀' is not on the layout");
+
+        let ngrams = NGrams::from_text(sentence).unwrap();
+        let layout = Layout::from_blueprint(blueprint, &ngrams.interner, &key_costs);
+        let statistics = LayoutStatistics::new(&layout, &ngrams, &geometry);
+        // Every pair of typed characters is a bigram of the statistics, also the ones of keys
+        // several fingers type, like the space of both thumbs.
+        let pairs = (summary.same_key
+            + summary.same_finger
+            + summary.rolls
+            + summary.alternations
+            + summary.unclassified) as f64;
+        assert_eq!(pairs as usize, summary.typed - 2);
+        for (count, share) in [
+            (summary.same_finger, statistics.same_finger_bigrams),
+            (summary.rolls, statistics.rolls),
+            (summary.scissors, statistics.scissors),
+            (summary.alternations, statistics.hand_alternation),
+        ] {
+            assert!(count > 0);
+            assert!(
+                (count as f64 / pairs - share).abs() < 1e-12,
+                "{} {}",
+                count,
+                share
+            );
+        }
+        assert!(summary.unclassified > 0);
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    geometry::{Finger, Geometry, Hand, KeyPos},
+    layout::{Layout, Pos},
+    ngrams::NGrams,
+};
+
+/// The number of bigrams in `LayoutStatistics::worst_bigrams`.
+const WORST_BIGRAMS: usize = 10;
+
+/// The number of characters of every layer in `LayerLoad::characters`.
+const LAYER_CHARACTERS: usize = 10;
+
+/**
+What typing the ngrams on a layout is like, independent of the cost model. The
+shares are of the keystrokes (letters) or bigrams the layout can type; a
+character on a higher layer counts for its key, without the modifier.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutStatistics {
+    /// The share of the keystrokes of every finger of the geometry. Keys of several fingers count for each of them in equal parts.
+    pub finger_loads: Vec<FingerLoad>,
+    /// The share of the keystrokes of the left hand, of those typed by one hand.
+    pub left_hand_share: f64,
+    /// The share of the bigrams typed with the same finger on two different keys.
+    pub same_finger_bigrams: f64,
+    /// The share of the bigrams typed with one hand after the other.
+    pub hand_alternation: f64,
+    /// The share of the bigrams typed with two fingers of the same hand.
+    pub rolls: f64,
+    /// The share of the bigrams typed with two neighbouring fingers of the same hand, two or more rows apart.
+    pub scissors: f64,
+    /// The share of the keystrokes on the row named `home`, if the geometry has one.
+    pub home_row: Option<f64>,
+    /// The most frequent same finger bigrams and scissors, most frequent first.
+    pub worst_bigrams: Vec<WorstBigram>,
+    /// The share of the keystrokes of every layer with keystrokes, from layer 0 up.
+    pub layer_loads: Vec<LayerLoad>,
+}
+
+/// The share of the keystrokes of a layer.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayerLoad {
+    /// The layer, 0 without a modifier.
+    pub layer: usize,
+    /// Its share of the keystrokes.
+    pub share: f64,
+    /// For the layers with a modifier, its most frequent characters with their share of all keystrokes.
+    pub characters: Vec<CharacterShare>,
+}
+
+/// A character and its share of the keystrokes.
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterShare {
+    /// The character.
+    pub char: String,
+    /// Its share of the keystrokes.
+    pub share: f64,
+}
+
+/// The share of the keystrokes of a finger.
+#[derive(Debug, Clone, Serialize)]
+pub struct FingerLoad {
+    /// The name of the finger, like `L_index`.
+    pub finger: String,
+    /// Its share of the keystrokes.
+    pub share: f64,
+}
+
+/// A character of the ngrams that is on no key of the layout.
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingCharacter {
+    /// The character.
+    pub char: String,
+    /// Its share of all keystrokes, including those of other missing characters.
+    pub share: f64,
+}
+
+/**
+The characters of the ngrams that are on no key of any layer of the layout and
+make up at least `threshold` of the keystrokes, most frequent first. Their cost
+is 0 unless there is a --missing-char-cost, so a layout can look better than
+it is when a corpus is full of them.
+*/
+pub fn missing_characters(
+    layout: &Layout,
+    ngrams: &NGrams,
+    threshold: f64,
+) -> Vec<MissingCharacter> {
+    let keystrokes: f64 = ngrams.letters.iter().map(|(_, weight)| weight).sum();
+    if keystrokes <= 0.0 {
+        return vec![];
+    }
+    let mut missing: Vec<MissingCharacter> = ngrams
+        .letters
+        .iter()
+        .filter(|&&(letter, _)| layout.positions[usize::from(letter)].is_none())
+        .map(|&(letter, weight)| MissingCharacter {
+            char: ngrams.interner.text(&[letter]),
+            share: weight / keystrokes,
+        })
+        .filter(|missing| missing.share >= threshold)
+        .collect();
+    missing.sort_by(|a, b| {
+        b.share
+            .total_cmp(&a.share)
+            .then_with(|| a.char.cmp(&b.char))
+    });
+    missing
+}
+
+/**
+The characters on layer 2 or higher that make up at least `threshold` of the
+keystrokes, with their layer, most frequent first. Those layers need a modifier
+that is harder to hold than the shift of layer 1.
+*/
+pub fn high_layer_characters(
+    layout: &Layout,
+    ngrams: &NGrams,
+    threshold: f64,
+) -> Vec<(usize, CharacterShare)> {
+    let keystrokes: f64 = ngrams
+        .letters
+        .iter()
+        .filter(|&&(letter, _)| layout.positions[usize::from(letter)].is_some())
+        .map(|(_, weight)| weight)
+        .sum();
+    if keystrokes <= 0.0 {
+        return vec![];
+    }
+    let mut high: Vec<(usize, CharacterShare)> = ngrams
+        .letters
+        .iter()
+        .filter_map(|&(letter, weight)| {
+            let pos = layout.positions[usize::from(letter)]?;
+            Some((
+                usize::from(pos.layer),
+                CharacterShare {
+                    char: ngrams.interner.text(&[letter]),
+                    share: weight / keystrokes,
+                },
+            ))
+        })
+        .filter(|(layer, character)| *layer >= 2 && character.share >= threshold)
+        .collect();
+    high.sort_by(|a, b| b.1.share.total_cmp(&a.1.share));
+    high
+}
+
+/// A bigram that is hard to type, and how much it adds to its kind.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorstBigram {
+    /// The two characters.
+    pub bigram: String,
+    /// What makes it hard to type.
+    pub kind: BigramKind,
+    /// Its share of all bigrams.
+    pub share: f64,
+}
+
+/// The kinds of bigrams `LayoutStatistics::worst_bigrams` lists.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BigramKind {
+    /// Both characters are typed with the same finger.
+    SameFinger,
+    /// Neighbouring fingers stretch over two or more rows.
+    Scissor,
+}
+
+impl BigramKind {
+    /// The name of the kind in reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            BigramKind::SameFinger => "same finger",
+            BigramKind::Scissor => "scissor",
+        }
+    }
+}
+
+/// The fingers of every key of a geometry.
+pub(crate) struct KeyFingers<'a> {
+    geometry: &'a Geometry,
+    fingers_of: HashMap<KeyPos, Vec<usize>>,
+}
+
+impl<'a> KeyFingers<'a> {
+    pub fn new(geometry: &'a Geometry) -> Self {
+        let mut fingers_of: HashMap<KeyPos, Vec<usize>> = HashMap::new();
+        for (index, finger) in geometry.fingers.iter().enumerate() {
+            for &pos in &finger.positions {
+                fingers_of.entry(pos).or_default().push(index);
+            }
+        }
+        KeyFingers {
+            geometry,
+            fingers_of,
+        }
+    }
+
+    /// The indices of the fingers that type a key, into the fingers of the geometry.
+    pub fn all(&self, pos: &Pos) -> &[usize] {
+        self.fingers_of.get(&pos.key()).map_or(&[], Vec::as_slice)
+    }
+
+    /// The one finger that types a key, if only one does.
+    pub fn only(&self, pos: &Pos) -> Option<&'a Finger> {
+        match self.all(pos) {
+            &[finger] => Some(&self.geometry.fingers[finger]),
+            _ => None,
+        }
+    }
+}
+
+/// How the second of two keys typed one after the other is reached from the first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Transition {
+    /// The same key again.
+    SameKey,
+    /// Another key with the same finger.
+    SameFinger,
+    /// Another finger of the same hand. A scissor if the fingers are neighbours and the keys two or more rows apart.
+    Roll { scissor: bool },
+    /// The other hand.
+    Alternation,
+}
+
+impl Transition {
+    /**
+    Classify two keys typed one after the other. Keys that several fingers
+    type, like a thumb key both hands reach, have no transition.
+    */
+    pub fn of(fingers: &KeyFingers, first: &Pos, second: &Pos) -> Option<Self> {
+        let (first_finger, second_finger) = (fingers.only(first)?, fingers.only(second)?);
+        Some(if first_finger.name == second_finger.name {
+            if first.same_key(second) {
+                Transition::SameKey
+            } else {
+                Transition::SameFinger
+            }
+        } else if first_finger.hand() == second_finger.hand() {
+            let neighbours = first_finger.home_column.abs_diff(second_finger.home_column) == 1;
+            Transition::Roll {
+                scissor: neighbours && first.row_distance(second) >= 2,
+            }
+        } else {
+            Transition::Alternation
+        })
+    }
+}
+
+impl LayoutStatistics {
+    /// The statistics of a layout typing the ngrams, with the fingers of the geometry.
+    pub fn new(layout: &Layout, ngrams: &NGrams, geometry: &Geometry) -> Self {
+        let fingers = KeyFingers::new(geometry);
+        let home_row = geometry.row_names.iter().position(|name| name == "home");
+
+        let mut loads = vec![0.0; geometry.fingers.len()];
+        let (mut keystrokes, mut left, mut one_hand, mut home) = (0.0, 0.0, 0.0, 0.0);
+        let mut layers: Vec<(f64, Vec<(u16, f64)>)> = vec![];
+        for &(letter, weight) in &ngrams.letters {
+            let pos = match layout.positions[usize::from(letter)] {
+                Some(pos) => pos,
+                None => continue,
+            };
+            keystrokes += weight;
+            let layer = usize::from(pos.layer);
+            if layers.len() <= layer {
+                layers.resize(layer + 1, (0.0, vec![]));
+            }
+            layers[layer].0 += weight;
+            if layer > 0 {
+                layers[layer].1.push((letter, weight));
+            }
+            let key_fingers = fingers.all(&pos);
+            for &finger in key_fingers {
+                loads[finger] += weight / key_fingers.len() as f64;
+            }
+            if let Some(hand) = geometry.hand_of(pos.key()) {
+                one_hand += weight;
+                if hand == Hand::Left {
+                    left += weight;
+                }
+            }
+            if home_row == Some(usize::from(pos.row)) {
+                home += weight;
+            }
+        }
+
+        let (mut bigrams, mut same_finger, mut alternation) = (0.0, 0.0, 0.0);
+        let (mut rolls, mut scissors) = (0.0, 0.0);
+        let mut worst = vec![];
+        for &([first, second], weight) in &ngrams.bigrams {
+            let (first_pos, second_pos) = match (
+                layout.positions[usize::from(first)],
+                layout.positions[usize::from(second)],
+            ) {
+                (Some(first), Some(second)) => (first, second),
+                _ => continue,
+            };
+            bigrams += weight;
+            let kind = match Transition::of(&fingers, &first_pos, &second_pos) {
+                Some(Transition::SameFinger) => {
+                    same_finger += weight;
+                    BigramKind::SameFinger
+                }
+                Some(Transition::Roll { scissor }) => {
+                    rolls += weight;
+                    if !scissor {
+                        continue;
+                    }
+                    scissors += weight;
+                    BigramKind::Scissor
+                }
+                Some(Transition::Alternation) => {
+                    alternation += weight;
+                    continue;
+                }
+                _ => continue,
+            };
+            worst.push(WorstBigram {
+                bigram: ngrams.interner.text(&[first, second]),
+                kind,
+                share: weight,
+            });
+        }
+
+        let share = |part: f64, whole: f64| if whole > 0.0 { part / whole } else { 0.0 };
+        worst.sort_by(|a, b| {
+            b.share
+                .total_cmp(&a.share)
+                .then_with(|| a.bigram.cmp(&b.bigram))
+        });
+        worst.truncate(WORST_BIGRAMS);
+        for bigram in &mut worst {
+            bigram.share = share(bigram.share, bigrams);
+        }
+        LayoutStatistics {
+            finger_loads: geometry
+                .fingers
+                .iter()
+                .zip(loads)
+                .map(|(finger, load)| FingerLoad {
+                    finger: finger.name.clone(),
+                    share: share(load, keystrokes),
+                })
+                .collect(),
+            left_hand_share: if one_hand > 0.0 { left / one_hand } else { 0.5 },
+            same_finger_bigrams: share(same_finger, bigrams),
+            hand_alternation: share(alternation, bigrams),
+            rolls: share(rolls, bigrams),
+            scissors: share(scissors, bigrams),
+            home_row: home_row.map(|_| share(home, keystrokes)),
+            worst_bigrams: worst,
+            layer_loads: layers
+                .into_iter()
+                .enumerate()
+                .filter(|(_, (weight, _))| *weight > 0.0)
+                .map(|(layer, (weight, mut characters))| {
+                    characters.sort_by(|a, b| b.1.total_cmp(&a.1));
+                    LayerLoad {
+                        layer,
+                        share: share(weight, keystrokes),
+                        characters: characters
+                            .into_iter()
+                            .take(LAYER_CHARACTERS)
+                            .map(|(letter, weight)| CharacterShare {
+                                char: ngrams.interner.text(&[letter]),
+                                share: share(weight, keystrokes),
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    layout::{moved_characters, Layout},
+    scoring::CostBreakdown,
+};
+
+/// The result of a run that is offered to the `TopLayouts`.
+pub struct RankedLayout {
+    /// The run that found the layout, counting from 1.
+    pub run: u128,
+    /// The layout.
+    pub layout: Layout,
+    /// Its cost.
+    pub cost: CostBreakdown,
+    /// Every key of the optimized layers. Equal fingerprints mean equal layouts.
+    pub fingerprint: String,
+}
+
+/**
+The best --top-n layouts of all runs. With --min-diff, every kept layout has at
+least that many characters of the optimized layers on other keys than every
+other kept layout. A candidate that is closer to kept layouts replaces them,
+but only if it is strictly better than all of them (usually there is just the
+closest one).
+*/
+pub struct TopLayouts {
+    n: usize,
+    min_diff: usize,
+    layers: Vec<(usize, Option<String>)>,
+    kept: Vec<RankedLayout>,
+}
+
+impl TopLayouts {
+    /**
+    Keep the best `n` layouts that differ in at least `min_diff` characters
+    of `layers`, the optimized layers with the alphabet of their characters
+    that move (see `moved_characters`).
+    */
+    pub fn new(n: usize, min_diff: usize, layers: Vec<(usize, Option<String>)>) -> Self {
+        TopLayouts {
+            n,
+            min_diff,
+            layers,
+            kept: vec![],
+        }
+    }
+
+    /// Keep `candidate` if it is among the best layouts and different enough from the others.
+    pub fn offer(&mut self, candidate: RankedLayout) {
+        if self
+            .kept
+            .iter()
+            .any(|kept| kept.fingerprint == candidate.fingerprint)
+        {
+            return;
+        }
+
+        let too_close: Vec<bool> = self
+            .kept
+            .iter()
+            .map(|kept| self.difference(kept, &candidate) < self.min_diff)
+            .collect();
+        let is_better = self
+            .kept
+            .iter()
+            .zip(&too_close)
+            .filter(|(_, &too_close)| too_close)
+            .all(|(kept, _)| candidate.cost.total() < kept.cost.total());
+        if !is_better {
+            return;
+        }
+        let mut too_close = too_close.into_iter();
+        self.kept.retain(|_| !too_close.next().unwrap());
+        self.kept.push(candidate);
+        self.kept
+            .sort_by(|a, b| a.cost.total().total_cmp(&b.cost.total()));
+        self.kept.truncate(self.n);
+    }
+
+    /// The kept layouts, best first.
+    pub fn kept(&self) -> &[RankedLayout] {
+        &self.kept
+    }
+
+    /// The best layout, if any was offered.
+    pub fn best(&self) -> Option<&RankedLayout> {
+        self.kept.first()
+    }
+
+    /// The number of characters of the optimized layers on other keys, for every pair of kept layouts.
+    pub fn pairwise_differences(&self) -> Vec<Vec<usize>> {
+        self.kept
+            .iter()
+            .map(|a| self.kept.iter().map(|b| self.difference(a, b)).collect())
+            .collect()
+    }
+
+    fn difference(&self, a: &RankedLayout, b: &RankedLayout) -> usize {
+        self.layers
+            .iter()
+            .map(|(layer, alphabet)| {
+                moved_characters(
+                    &a.layout.blueprint,
+                    &b.layout.blueprint,
+                    *layer,
+                    alphabet.as_deref(),
+                )
+                .len()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        interner::Interner,
+        key_costs::KeyCosts,
+        layout::{builtin_baseline, Blueprint, BlueprintHelpers},
+    };
+
+    fn ranked(run: u128, blueprint: Blueprint, cost: f64) -> RankedLayout {
+        let fingerprint = format!(
+            "{}\n\n{}",
+            blueprint.layer_string(0),
+            blueprint.layer_string(2)
+        );
+        let layout = Layout::from_blueprint(
+            blueprint,
+            &Arc::new(Interner::default()),
+            &Arc::new(KeyCosts::default()),
+        );
+        RankedLayout {
+            run,
+            layout,
+            cost: CostBreakdown {
+                key_position: cost,
+                hand_balance: 0.0,
+                same_finger: 0.0,
+                same_hand: 0.0,
+                left_hand_share: 0.5,
+            },
+            fingerprint,
+        }
+    }
+
+    /// The neo baseline, and a copy of it with `…` and `_` and `<` and `>` of layer 2 swapped.
+    fn layer_2_variants() -> (Blueprint, Blueprint) {
+        let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+        blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+        let mut variant = blueprint.clone();
+        variant.swap_keys((1, 1), (1, 2), 2);
+        variant.swap_keys((1, 7), (1, 8), 2);
+        assert_eq!(
+            variant.layer_string(2).lines().next(),
+            Some("_…[]^ !><=&@ſ")
+        );
+        (blueprint, variant)
+    }
+
+    #[test]
+    fn min_diff_counts_the_characters_of_every_optimized_layer() {
+        let (blueprint, variant) = layer_2_variants();
+        let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+        let layers = vec![(0, Some(alphabet.to_string())), (2, None)];
+        let mut top = TopLayouts::new(5, 4, layers);
+        top.offer(ranked(1, blueprint, 2.0));
+        top.offer(ranked(2, variant, 1.0 + 2.0));
+
+        assert_eq!(top.kept().len(), 2);
+        assert_eq!(top.pairwise_differences(), vec![vec![0, 4], vec![4, 0]]);
+    }
+
+    #[test]
+    fn layouts_closer_than_min_diff_keep_the_better_one() {
+        let (blueprint, variant) = layer_2_variants();
+        let layers = vec![(0, None), (2, None)];
+        let mut top = TopLayouts::new(5, 5, layers);
+        top.offer(ranked(1, blueprint, 2.0));
+        top.offer(ranked(2, variant.clone(), 3.0));
+        assert_eq!(top.kept().len(), 1);
+        assert_eq!(top.best().unwrap().run, 1);
+
+        top.offer(ranked(3, variant, 1.0));
+        assert_eq!(top.kept().len(), 1);
+        assert_eq!(top.best().unwrap().run, 3);
+    }
+}
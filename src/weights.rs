@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, fs, sync::Arc};
+
+use toml::Value;
+
+use crate::key_costs::KeyCosts;
+
+/// How much each cost component counts towards the total cost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostWeights {
+    /// The cost per keystroke on a hard to reach key.
+    pub key_position: f64,
+    /// The cost per percentage point that the left hand share is off --hand-target.
+    pub hand_balance: f64,
+    /// The cost per same finger bigram on two different keys, and as much again for every row the finger moves.
+    pub same_finger: f64,
+    /// The cost per bigram typed by one hand, which `same_finger` only adds to.
+    pub same_hand: f64,
+    /// The desired share of keystrokes typed by the left hand. No balance cost without it.
+    pub hand_target: Option<f64>,
+    /// The effort per keystroke of a character that is on no key, see --missing-char-cost. Missing characters cost nothing without it.
+    pub missing_char_cost: Option<f64>,
+    /// The effort of every key before weighting, see --key-costs and --off-table-cost.
+    pub key_costs: Arc<KeyCosts>,
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        CostWeights {
+            key_position: 1.0,
+            hand_balance: 10.0,
+            same_finger: 5.0,
+            same_hand: 1.0,
+            hand_target: None,
+            missing_char_cost: None,
+            key_costs: Arc::new(KeyCosts::default()),
+        }
+    }
+}
+
+/// Map the dotted paths of the weights file and --weight to the fields of `CostWeights`.
+macro_rules! weight_paths {
+    ($($path:literal => $($field:ident).+),* $(,)?) => {
+        impl CostWeights {
+            /// Every valid path, in the order of the struct.
+            pub const PATHS: &'static [&'static str] = &[$($path),*];
+
+            fn get_mut(&mut self, path: &str) -> Option<&mut f64> {
+                match path {
+                    $($path => Some(&mut self.$($field).+),)*
+                    _ => None,
+                }
+            }
+
+            /// The weights keyed by their paths, for echoing them into results.
+            pub fn to_map(&self) -> BTreeMap<String, f64> {
+                let mut map = BTreeMap::new();
+                $(map.insert($path.to_string(), self.$($field).+);)*
+                map
+            }
+        }
+    };
+}
+
+weight_paths!(
+    "key_position" => key_position,
+    "hand_balance" => hand_balance,
+    "same_finger" => same_finger,
+    "same_hand" => same_hand,
+);
+
+impl CostWeights {
+    /**
+    Load the weights from a weights.toml (tables nest like the dotted paths)
+    and apply the `path=value` overrides on top of it. Weights that are
+    given nowhere keep their defaults.
+    */
+    pub fn load(file: Option<&str>, overrides: &[String]) -> Result<Self, String> {
+        let mut weights = CostWeights::default();
+
+        if let Some(path) = file {
+            let contents =
+                fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+            let table: Value = toml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+            let mut flat = vec![];
+            flatten("", table, &mut flat);
+            for (key, value) in flat {
+                let value = match value {
+                    Value::Float(value) => value,
+                    Value::Integer(value) => value as f64,
+                    other => {
+                        return Err(format!(
+                            "{}: {} must be a number, found {}",
+                            path, key, other
+                        ))
+                    }
+                };
+                weights
+                    .set(&key, value)
+                    .map_err(|e| format!("{}: {}", path, e))?;
+            }
+        }
+
+        for assignment in overrides {
+            let (key, value) = assignment.split_once('=').ok_or_else(|| {
+                format!("Invalid --weight {:?}, expected `path=value`", assignment)
+            })?;
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid --weight {:?}: {}", assignment, e))?;
+            weights.set(key.trim(), value)?;
+        }
+        Ok(weights)
+    }
+
+    fn set(&mut self, path: &str, value: f64) -> Result<(), String> {
+        if !value.is_finite() {
+            return Err(format!(
+                "Weight {} must be a finite number, found {}",
+                path, value
+            ));
+        }
+        match self.get_mut(path) {
+            Some(weight) => {
+                *weight = value;
+                Ok(())
+            }
+            None => Err(format!(
+                "Unknown weight {}. Valid weights are {}.",
+                path,
+                Self::PATHS.join(", ")
+            )),
+        }
+    }
+}
+
+fn flatten(prefix: &str, value: Value, flat: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, value, flat);
+            }
+        }
+        value => flat.push((prefix.to_string(), value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_the_defaults() {
+        let weights = CostWeights::load(None, &["same_finger=2.5".to_string()]).unwrap();
+        assert_eq!(weights.same_finger, 2.5);
+        assert_eq!(weights.key_position, CostWeights::default().key_position);
+    }
+
+    #[test]
+    fn non_finite_overrides_are_rejected() {
+        for value in &["inf", "-inf", "NaN"] {
+            let assignment = format!("key_position={}", value);
+            let error = CostWeights::load(None, &[assignment]).unwrap_err();
+            assert!(error.contains("finite"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn non_finite_weights_files_are_rejected() {
+        let path = std::env::temp_dir().join("klo_weights_non_finite.toml");
+        fs::write(&path, "same_hand = nan\n").unwrap();
+        let result = CostWeights::load(path.to_str(), &[]);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.unwrap_err().contains("finite"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Alphabets with a character twice, in --alphabet or on the layout.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+#[test]
+fn a_duplicated_alphabet_character_is_an_error_naming_it() {
+    let dir = work_dir("a_duplicated_alphabet_character_is_an_error_naming_it");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "--alphabet",
+            "abcdefghijklmnopqrstuvwxyzäöüße",
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "10",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    let log = stderr(&output);
+    assert!(
+        log.contains("--alphabet contains 'e' more than once"),
+        "{}",
+        log
+    );
+    assert!(!log.contains("panicked"), "{}", log);
+    assert!(!dir.join("output.txt").exists());
+}
+
+#[test]
+fn a_starting_layout_with_an_alphabet_character_twice_is_an_error_naming_its_keys() {
+    let dir =
+        work_dir("a_starting_layout_with_an_alphabet_character_twice_is_an_error_naming_its_keys");
+    // The default starting layout with a second `j` instead of the `ß`.
+    let layout = "lvxcw khgfqyj\nuiaeo snrtd⇘\nüöäpz bm,.j";
+    for dry_run in [false, true] {
+        let mut args = vec![
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "10",
+            "--starting-layout",
+            layout,
+        ];
+        if dry_run {
+            args.push("--dry-run");
+        }
+        let output = klo_with_corpus(&dir, &args);
+        assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+        assert!(stderr(&output).contains(
+            "The alphabet has characters on more than one key of layer 0 of the layout: 'j' on [(1, 12), (3, 11)]."
+        ), "{}", stderr(&output));
+    }
+}
@@ -0,0 +1,68 @@
+//! The json lines of --best-stream.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+#[test]
+fn every_line_is_a_cheaper_layout_than_the_ones_before() {
+    let dir = work_dir("every_line_is_a_cheaper_layout_than_the_ones_before");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "--threads",
+            "2",
+            "evolve",
+            "-n",
+            "4",
+            "--steps",
+            "100",
+            "--seed",
+            "8",
+            "--best-stream",
+            "best.jsonl",
+            "--output-format",
+            "json",
+            "-o",
+            "result.json",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    let stream = fs::read_to_string(dir.join("best.jsonl")).unwrap();
+    let lines: Vec<Value> = stream
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(lines.len() > 1, "{}", stream);
+    for line in &lines {
+        assert!(
+            line["timestamp"].as_str().unwrap().contains('T'),
+            "{}",
+            line
+        );
+        assert!((1..=4).contains(&line["run"].as_u64().unwrap()), "{}", line);
+        assert!(line["step"].as_u64().unwrap() <= 100, "{}", line);
+        assert_eq!(line["layout"].as_str().unwrap().lines().count(), 3);
+    }
+    let costs: Vec<f64> = lines
+        .iter()
+        .map(|line| line["cost"].as_f64().unwrap())
+        .collect();
+    assert!(
+        costs.windows(2).all(|pair| pair[1] < pair[0]),
+        "{:?}",
+        costs
+    );
+
+    let result: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    let last = &lines[lines.len() - 1];
+    assert_eq!(last["cost"], result["cost"]["total"]);
+    assert_eq!(last["layout"], result["layout"]["layout_string"]);
+}
@@ -0,0 +1,170 @@
+//! What the integration tests share: running the klo binary in a directory of its own.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// A file of `tests/fixtures`.
+pub fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// An empty working directory for a test, so klo finds no klo.toml and writes its files there.
+pub fn work_dir(test: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run klo in `dir` without the `KLO_` environment variables of the shell.
+pub fn klo(dir: &Path, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_klo"));
+    command.current_dir(dir).args(args);
+    for (name, _) in env::vars() {
+        if name.starts_with("KLO_") {
+            command.env_remove(name);
+        }
+    }
+    command.output().unwrap()
+}
+
+/// Like `klo`, with the fixture corpus as the ngrams.
+pub fn klo_with_corpus(dir: &Path, args: &[&str]) -> Output {
+    let corpus = fixture("corpus.txt");
+    let mut all = args.to_vec();
+    all.extend(["--corpus", corpus.as_str()]);
+    klo(dir, &all)
+}
+
+/// The log of a run, which klo writes to stderr.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+/// The exit code of a run, none if it was killed by a signal.
+pub fn exit_code(output: &Output) -> Option<i32> {
+    output.status.code()
+}
+
+/**
+Compare `actual` with the golden file `tests/snapshots/<name>`. With
+`UPDATE_SNAPSHOTS=1` in the environment the file is written instead, to
+review the changes with `git diff`.
+*/
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(name);
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_SNAPSHOTS=1)", path.display(), e));
+    if expected != actual {
+        let line = expected
+            .lines()
+            .zip(actual.lines())
+            .position(|(expected, actual)| expected != actual)
+            .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+        panic!(
+            "{} differs from line {} on (run with UPDATE_SNAPSHOTS=1 to accept the change):\n{}",
+            name,
+            line + 1,
+            actual
+        );
+    }
+}
+
+/**
+`output` of a run in `dir` with what changes from run to run replaced: the
+paths of `dir` and of the repository, timestamps, durations and the version
+and git checkout of klo.
+*/
+pub fn normalize(output: &str, dir: &Path) -> String {
+    let mut output = output
+        .replace(dir.to_string_lossy().as_ref(), "<dir>")
+        .replace(env!("CARGO_MANIFEST_DIR"), "<repo>")
+        .replace(
+            &format!("klo {}", env!("CARGO_PKG_VERSION")),
+            "klo <version>",
+        )
+        .replace(
+            &format!("\"{}\"", env!("CARGO_PKG_VERSION")),
+            "\"<version>\"",
+        );
+    if let Some(describe) = option_env!("KLO_GIT_DESCRIBE") {
+        output = output
+            .replace(&format!("({})", describe), "(<git>)")
+            .replace(&format!("\"{}\"", describe), "\"<git>\"");
+    }
+    let output = replace_timestamps(&output);
+    output
+        .split_inclusive(|char: char| char.is_whitespace() || char == '"' || char == ',')
+        .map(|token| {
+            let end = token
+                .trim_end_matches(|char: char| char.is_whitespace() || char == '"' || char == ',');
+            if is_duration(end) {
+                format!("<duration>{}", &token[end.len()..])
+            } else {
+                token.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Whether `token` is a duration like `Duration` prints it, e.g. `1.5s` or `20ms`.
+fn is_duration(token: &str) -> bool {
+    ["ns", "µs", "ms", "s"].iter().any(|unit| {
+        token.strip_suffix(unit).is_some_and(|number| {
+            number.starts_with(|char: char| char.is_ascii_digit()) && number.parse::<f64>().is_ok()
+        })
+    })
+}
+
+/// `text` with every timestamp like `2021-09-14 12:34:56,789` or `2021-09-14T12:34:56.789+00:00` replaced by `<time>`.
+fn replace_timestamps(text: &str) -> String {
+    const SHAPE: &str = "dddd-dd-dd dd:dd:dd";
+    let chars: Vec<char> = text.chars().collect();
+    let fits = |at: usize| {
+        SHAPE.chars().enumerate().all(|(idx, shape)| {
+            chars.get(at + idx).is_some_and(|&char| match shape {
+                'd' => char.is_ascii_digit(),
+                ' ' => char == ' ' || char == 'T',
+                _ => char == shape,
+            })
+        })
+    };
+    let mut replaced = String::new();
+    let mut at = 0;
+    while at < chars.len() {
+        if !fits(at) {
+            replaced.push(chars[at]);
+            at += 1;
+            continue;
+        }
+        at += SHAPE.len();
+        if matches!(chars.get(at), Some('.') | Some(',')) {
+            at += 1;
+            while chars.get(at).is_some_and(char::is_ascii_digit) {
+                at += 1;
+            }
+        }
+        match chars.get(at) {
+            Some('Z') => at += 1,
+            Some('+') | Some('-') if chars.get(at + 3) == Some(&':') => at += 6,
+            _ => {}
+        }
+        replaced.push_str("<time>");
+    }
+    replaced
+}
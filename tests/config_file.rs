@@ -0,0 +1,74 @@
+//! Options from klo.toml and the command line, and which of them wins.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The effective options an evolution without steps echoes into its output file, one `key = value` per line.
+fn evolve_options(dir: &Path, args: &[&str], evolve_args: &[&str]) -> Vec<String> {
+    let mut all = args.to_vec();
+    all.extend(["evolve", "-n", "1", "--steps", "0", "-o", "result.txt"]);
+    all.extend(evolve_args);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    fs::read_to_string(dir.join("result.txt"))
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("#     "))
+        .map(str::to_string)
+        .collect()
+}
+
+fn has(options: &[String], line: &str) -> bool {
+    options.iter().any(|option| option == line)
+}
+
+#[test]
+fn the_file_sets_the_options_it_has() {
+    let dir = work_dir("the_file_sets_the_options_it_has");
+    fs::write(
+        dir.join("klo.toml"),
+        "off_table_cost = 70.0\n\n[evolve]\nanneal = 3\n",
+    )
+    .unwrap();
+    let options = evolve_options(&dir, &[], &[]);
+    assert!(has(&options, "off_table_cost = 70.0"), "{:?}", options);
+    assert!(has(&options, "anneal = 3"), "{:?}", options);
+}
+
+#[test]
+fn the_command_line_sets_the_options_without_a_file() {
+    let dir = work_dir("the_command_line_sets_the_options_without_a_file");
+    let options = evolve_options(&dir, &["--off-table-cost", "60"], &["--anneal", "4"]);
+    assert!(has(&options, "off_table_cost = 60.0"), "{:?}", options);
+    assert!(has(&options, "anneal = 4"), "{:?}", options);
+}
+
+#[test]
+fn the_command_line_wins_over_a_config_file() {
+    let dir = work_dir("the_command_line_wins_over_a_config_file");
+    fs::write(
+        dir.join("other.toml"),
+        "off_table_cost = 70.0\n\n[evolve]\nanneal = 3\n",
+    )
+    .unwrap();
+    let options = evolve_options(&dir, &["--config", "other.toml"], &["--anneal", "4"]);
+    assert!(has(&options, "off_table_cost = 70.0"), "{:?}", options);
+    assert!(has(&options, "anneal = 4"), "{:?}", options);
+}
+
+#[test]
+fn unknown_keys_of_the_file_are_listed() {
+    let dir = work_dir("unknown_keys_of_the_file_are_listed");
+    fs::write(dir.join("klo.toml"), "bogus = 1\n\n[evolve]\nsteeps = 3\n").unwrap();
+    let output = klo_with_corpus(&dir, &["evolve", "-n", "1", "--steps", "0"]);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Unknown keys in klo.toml: bogus, evolve.steeps."),
+        "{}",
+        stderr(&output)
+    );
+}
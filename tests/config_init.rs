@@ -0,0 +1,58 @@
+//! `klo config init` and dry runs over the files it writes.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, fixture, klo, stderr, work_dir};
+
+#[test]
+fn a_dry_run_over_the_starter_files_only_misses_the_placeholder_corpus() {
+    let dir = work_dir("a_dry_run_over_the_starter_files_only_misses_the_placeholder_corpus");
+    let output = klo(
+        &dir,
+        &["config", "init", "--with-base-layout", "--with-geometry"],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    for name in [
+        "klo.toml",
+        "ngrams.config",
+        "weights.toml",
+        "base_layout.json",
+        "geometry.toml",
+    ] {
+        assert!(dir.join(name).is_file(), "{} is missing", name);
+    }
+
+    let output = klo(&dir, &["evolve", "--dry-run"]);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    let log = stderr(&output);
+    assert!(log.contains("The dry run found 1 problems."), "{}", log);
+    assert!(log.contains("unable to open my_corpus.txt"), "{}", log);
+
+    fs::copy(fixture("corpus.txt"), dir.join("my_corpus.txt")).unwrap();
+    let output = klo(&dir, &["evolve", "--dry-run"]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+}
+
+#[test]
+fn init_overwrites_files_only_with_force() {
+    let dir = work_dir("init_overwrites_files_only_with_force");
+    fs::write(dir.join("klo.toml"), "# mine\n").unwrap();
+
+    let output = klo(&dir, &["config", "init"]);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("pass --force to replace them"));
+    assert_eq!(
+        fs::read_to_string(dir.join("klo.toml")).unwrap(),
+        "# mine\n"
+    );
+
+    let output = klo(&dir, &["config", "init", "--force"]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert_ne!(
+        fs::read_to_string(dir.join("klo.toml")).unwrap(),
+        "# mine\n"
+    );
+}
@@ -0,0 +1,114 @@
+//! Pinned and fixed keys, which an evolution must not move.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The blueprint of a seeded evolution with `args`, from its json result.
+fn evolved_blueprint(dir: &Path, args: &[&str]) -> Value {
+    let mut all = vec!["evolve", "-n", "2", "--seed", "1"];
+    all.extend(args);
+    all.extend(["--output-format", "json", "-o", "result.json"]);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    result["layout"]["blueprint"].clone()
+}
+
+/// The row and key of `char` on layer 0 of a blueprint.
+fn position(blueprint: &Value, char: &str) -> Option<(usize, usize)> {
+    blueprint
+        .as_array()?
+        .iter()
+        .enumerate()
+        .find_map(|(row_idx, row)| {
+            row.as_array()?
+                .iter()
+                .position(|key| key[0] == char)
+                .map(|key_idx| (row_idx, key_idx))
+        })
+}
+
+#[test]
+fn pinned_keys_and_positions_never_move() {
+    let dir = work_dir("pinned_keys_and_positions_never_move");
+    let pins = ["--pin-keys", "xäöüß,.", "--pin-positions", "R_index_home"];
+    let mut unevolved = vec!["--steps", "0", "--prerandomize", "0"];
+    unevolved.extend(pins);
+    let start = evolved_blueprint(&dir, &unevolved);
+    let mut evolved = vec!["--steps", "200"];
+    evolved.extend(pins);
+    let end = evolved_blueprint(&dir, &evolved);
+
+    assert_ne!(start, end);
+    for char in ["x", "ä", "ö", "ü", "ß", ",", ".", "t"] {
+        assert_eq!(position(&start, char), position(&end, char), "{}", char);
+    }
+}
+
+#[test]
+fn fixed_characters_end_up_at_their_positions() {
+    let dir = work_dir("fixed_characters_end_up_at_their_positions");
+    let fixes = ["--fix", "n=L_index_home", "--fix", "e=R_index_home"];
+    let mut args = vec!["--steps", "200"];
+    args.extend(fixes);
+    let blueprint = evolved_blueprint(&dir, &args);
+
+    assert_eq!(position(&blueprint, "n"), Some((2, 4)));
+    assert_eq!(position(&blueprint, "e"), Some((2, 7)));
+}
+
+#[test]
+fn conflicting_fixes_are_reported_together() {
+    let dir = work_dir("conflicting_fixes_are_reported_together");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "evolve",
+            "--steps",
+            "0",
+            "--fix",
+            "n=L_index_home",
+            "--fix",
+            "e=L_index_home",
+            "--fix",
+            "a=R_index_home",
+            "--fix",
+            "a=R_middle_home",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    let log = stderr(&output);
+    assert!(
+        log.contains(r#"{"e", "n"} are all fixed at L_index_home"#),
+        "{}",
+        log
+    );
+    assert!(
+        log.contains(r#""a" is fixed at {"R_index_home", "R_middle_home"}"#),
+        "{}",
+        log
+    );
+    assert!(
+        log.contains("Found 2 problems with the constraints."),
+        "{}",
+        log
+    );
+}
+
+#[test]
+fn unknown_pins_are_rejected_at_startup() {
+    let dir = work_dir("unknown_pins_are_rejected_at_startup");
+    let output = klo_with_corpus(
+        &dir,
+        &["evolve", "--steps", "0", "--pin-positions", "Nowhere"],
+    );
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Unknown position Nowhere for the neo geometry"));
+}
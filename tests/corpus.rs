@@ -0,0 +1,81 @@
+//! Runs against text files given with --corpus instead of an ngrams config.
+#![cfg(all(feature = "cli", feature = "text-corpus"))]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, fixture, klo, stderr, work_dir};
+
+/// The total cost `klo analyze` logs for the default starting layout against `corpora`.
+fn analyzed_cost(dir: &Path, corpora: &[&str]) -> f64 {
+    let mut args = vec!["analyze"];
+    for corpus in corpora {
+        args.extend(["--corpus", corpus]);
+    }
+    args.push("");
+    let output = klo(dir, &args);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    let line = log
+        .lines()
+        .find(|line| line.contains("Total cost: "))
+        .unwrap();
+    line.split("Total cost: ").nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn an_evolution_runs_from_a_text_file_without_a_config() {
+    let dir = work_dir("an_evolution_runs_from_a_text_file_without_a_config");
+    let corpus = fixture("corpus.txt");
+    let output = klo(
+        &dir,
+        &[
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "50",
+            "--seed",
+            "1",
+            "--corpus",
+            &corpus,
+            "-o",
+            "result.txt",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(!dir.join("ngrams.config").exists());
+    let result = fs::read_to_string(dir.join("result.txt")).unwrap();
+    assert!(result.contains("total cost: "), "{}", result);
+}
+
+#[test]
+fn the_weight_of_a_corpus_changes_its_share() {
+    let dir = work_dir("the_weight_of_a_corpus_changes_its_share");
+    fs::write(dir.join("q.txt"), "qqq zzz xx qq\n").unwrap();
+    let corpus = fixture("corpus.txt");
+    let light = analyzed_cost(&dir, &[&format!("{}:1", corpus), "q.txt:1"]);
+    let heavy = analyzed_cost(&dir, &[&format!("{}:1", corpus), "q.txt:20"]);
+    assert!(heavy > light, "{} <= {}", heavy, light);
+}
+
+#[test]
+fn corpus_and_ngrams_config_are_exclusive() {
+    let dir = work_dir("corpus_and_ngrams_config_are_exclusive");
+    let corpus = fixture("corpus.txt");
+    let output = klo(
+        &dir,
+        &[
+            "analyze",
+            "--corpus",
+            &corpus,
+            "--ngrams-config",
+            "x.config",
+            "",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output)
+        .contains("--corpus and --ngrams-config (from the command line) can't be used together"));
+}
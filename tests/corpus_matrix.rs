@@ -0,0 +1,100 @@
+//! The matrix of `klo compare --compare-corpora` of two layouts on two tiny corpora.
+#![cfg(all(feature = "cli", feature = "text-corpus"))]
+
+mod common;
+
+use std::process::Output;
+
+use common::{exit_code, fixture, klo, stderr, work_dir};
+
+/**
+Compare the layouts of tests/fixtures/layouts on the corpora of
+tests/fixtures/corpora with the given weights: `a.txt` has only the letter
+`a`, `t.txt` only `t`. `a-on-home-row` is Neo with `t` moved to the bottom
+right pinky key, and `t-on-home-row` is Neo with `a` moved to the top left
+one, so each layout is the best one on the corpus of its home row letter.
+*/
+fn compare(test: &str, weights: (&str, &str), format: &str) -> Output {
+    let dir = work_dir(test);
+    let output = klo(
+        &dir,
+        &[
+            "compare",
+            &fixture("layouts/a-on-home-row.txt"),
+            &fixture("layouts/t-on-home-row.txt"),
+            "--compare-corpora",
+            &format!("{}:{}", fixture("corpora/a.config"), weights.0),
+            &format!("{}:{}", fixture("corpora/t.config"), weights.1),
+            "--output-format",
+            format,
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    output
+}
+
+/// The name, the normalized costs and the weighted average of every row of the csv matrix.
+fn csv_rows(output: &Output) -> Vec<(String, Vec<f64>)> {
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "\"Layout\",\"a (25%)\",\"t (75%)\",\"Weighted average\",\"Layout string\""
+    );
+    lines
+        .filter(|line| line.starts_with('"') && line.contains(','))
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            let name = fields[0].trim_matches('"').to_string();
+            (
+                name,
+                fields[1..4]
+                    .iter()
+                    .map(|field| field.parse().unwrap())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn the_matrix_normalizes_every_corpus_and_ranks_by_the_weighted_average() {
+    let output = compare(
+        "the_matrix_normalizes_every_corpus_and_ranks_by_the_weighted_average",
+        ("3", "1"),
+        "text",
+    );
+    let log = stderr(&output);
+    let matrix: Vec<&str> = log
+        .lines()
+        .filter_map(|line| line.split("[klo::corpus_matrix] ").nth(1))
+        .collect();
+    // 0.75 * 1.000 + 0.25 * 7.045 = 2.511 and 0.75 * 3.419 + 0.25 * 1.000 = 2.814.
+    assert_eq!(
+        matrix,
+        [
+            "Layout         a (75%)  t (25%)  Weighted average",
+            "a-on-home-row    1.000    7.045             2.511",
+            "t-on-home-row    3.419    1.000             2.814",
+        ]
+    );
+}
+
+#[test]
+fn other_weights_put_the_other_layout_first() {
+    let output = compare(
+        "other_weights_put_the_other_layout_first",
+        ("1", "3"),
+        "csv",
+    );
+    let rows = csv_rows(&output);
+    let names: Vec<&str> = rows.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["t-on-home-row", "a-on-home-row"]);
+    for (_, costs) in &rows {
+        let weighted = 0.25 * costs[0] + 0.75 * costs[1];
+        assert!((costs[2] - weighted).abs() < 0.001, "{:?}", costs);
+    }
+    // The best layout of every corpus has 1.
+    assert_eq!(rows[0].1[1], 1.0);
+    assert_eq!(rows[1].1[0], 1.0);
+}
@@ -0,0 +1,56 @@
+//! Scores that must not change between loads of a corpus or thread counts.
+#![cfg(all(feature = "cli", feature = "parallel", feature = "text-corpus"))]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use klo::{
+    geometry::Geometry,
+    layout::{builtin_baseline, Blueprint, BlueprintHelpers, Layout},
+    ngrams::NGrams,
+    scoring::cost_breakdown,
+    weights::CostWeights,
+};
+
+use common::{fixture, work_dir};
+
+/// An ngrams config that counts the fixture corpus twice with different weights, so merging sums over several corpora.
+fn write_config(dir: &Path) -> String {
+    let corpus = fixture("corpus.txt");
+    let config = format!("3 text {}\n1 text {}\n", corpus, corpus);
+    let path = dir.join("ngrams.config");
+    fs::write(&path, config).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// The bits of the total cost of the neo layout against the ngrams of `config`, loaded on `threads` threads.
+fn neo_cost_bits(config: &str, threads: usize) -> u64 {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let ngrams = pool.install(|| NGrams::from_config(config, false)).unwrap();
+
+    let mut blueprint = Blueprint::read_base_layout(&None).unwrap();
+    blueprint.merge_layout_string(builtin_baseline("neo").unwrap().layout_string);
+    let weights = CostWeights::default();
+    let layout = Layout::from_blueprint(blueprint, &ngrams.interner, &weights.key_costs);
+    cost_breakdown(&layout, &ngrams, &weights, &Geometry::neo())
+        .total()
+        .to_bits()
+}
+
+#[test]
+fn loading_the_same_corpus_twice_gives_the_same_cost() {
+    let dir = work_dir("loading_the_same_corpus_twice_gives_the_same_cost");
+    let config = write_config(&dir);
+    assert_eq!(neo_cost_bits(&config, 4), neo_cost_bits(&config, 4));
+}
+
+#[test]
+fn the_cost_is_the_same_on_1_and_8_threads() {
+    let dir = work_dir("the_cost_is_the_same_on_1_and_8_threads");
+    let config = write_config(&dir);
+    assert_eq!(neo_cost_bits(&config, 1), neo_cost_bits(&config, 8));
+}
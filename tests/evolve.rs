@@ -0,0 +1,143 @@
+//! Evolutions without steps, which only score their starting layout.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The default starting layout with `j` and `b` swapped.
+const STARTING_LAYOUT: &str = "jmuaz kdflvbß\ncriey ptsnh⇘\nxäüoö wg,.q";
+
+/// Evolve one run with `args` and return the json result.
+fn evolve_json(dir: &Path, args: &[&str]) -> serde_json::Value {
+    let mut all = vec!["evolve", "-n", "1", "--seed", "1"];
+    all.extend(args);
+    all.extend(["--output-format", "json", "-o", "result.json"]);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap()
+}
+
+/// The total cost `klo analyze` logs for a layout, rounded like it does.
+fn analyzed_cost(dir: &Path, layout: &str) -> String {
+    let output = klo_with_corpus(dir, &["analyze", layout]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    let line = log
+        .lines()
+        .find(|line| line.contains("Total cost: "))
+        .unwrap();
+    line.split("Total cost: ").nth(1).unwrap().to_string()
+}
+
+#[test]
+fn no_steps_and_no_prerandomization_keep_the_starting_layout_and_its_cost() {
+    let dir = work_dir("no_steps_and_no_prerandomization_keep_the_starting_layout_and_its_cost");
+    let result = evolve_json(
+        &dir,
+        &[
+            "--steps",
+            "0",
+            "--prerandomize",
+            "0",
+            "--starting-layout",
+            STARTING_LAYOUT,
+        ],
+    );
+
+    assert_eq!(result["layout"]["layout_string"], STARTING_LAYOUT);
+    let total = result["cost"]["total"].as_f64().unwrap();
+    assert_eq!(
+        format!("{:.2}", total),
+        analyzed_cost(&dir, STARTING_LAYOUT)
+    );
+}
+
+#[test]
+fn prerandomize_swaps_the_higher_optimized_layers_as_often_as_layer_0() {
+    let dir = work_dir("prerandomize_swaps_the_higher_optimized_layers_as_often_as_layer_0");
+    let layers = |prerandomize| {
+        let result = evolve_json(
+            &dir,
+            &[
+                "--optimize-layers",
+                "0,2",
+                "--steps",
+                "0",
+                "--prerandomize",
+                prerandomize,
+            ],
+        );
+        result["layout"]["layers"]["2"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    let (unchanged, swapped) = (layers("0"), layers("1"));
+
+    let moved = unchanged
+        .chars()
+        .zip(swapped.chars())
+        .filter(|(before, after)| before != after)
+        .count();
+    assert_eq!(moved, 2, "{}\n\n{}", unchanged, swapped);
+}
+
+#[test]
+fn no_steps_write_the_layout_and_its_cost_to_the_output_file() {
+    let dir = work_dir("no_steps_write_the_layout_and_its_cost_to_the_output_file");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "0",
+            "--prerandomize",
+            "0",
+            "--starting-layout",
+            STARTING_LAYOUT,
+            "-o",
+            "result.txt",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    let result = fs::read_to_string(dir.join("result.txt")).unwrap();
+    let text: Vec<&str> = result
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    assert_eq!(text[..3].join("\n"), STARTING_LAYOUT, "{}", result);
+    let total = text
+        .iter()
+        .find_map(|line| line.strip_prefix("total cost: "))
+        .expect("the result has a total cost");
+    let total: f64 = total.parse().unwrap();
+    assert!(total.is_finite() && total > 0.0);
+}
+
+#[test]
+fn different_starting_layouts_start_at_different_costs() {
+    let dir = work_dir("different_starting_layouts_start_at_different_costs");
+    let cost = |layout| {
+        let result = evolve_json(
+            &dir,
+            &[
+                "--steps",
+                "0",
+                "--prerandomize",
+                "0",
+                "--starting-layout",
+                layout,
+            ],
+        );
+        assert_eq!(result["layout"]["layout_string"], layout);
+        result["cost"]["total"].as_f64().unwrap()
+    };
+    let swapped_e_and_x = "bmuaz kdflvjß\ncrixy ptsnh⇘\neäüoö wg,.q";
+    assert_ne!(cost(STARTING_LAYOUT), cost(swapped_e_and_x));
+}
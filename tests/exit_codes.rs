@@ -0,0 +1,126 @@
+//! The exit codes of broken inputs and of an interrupted evolution.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{exit_code, fixture, klo, klo_with_corpus, stderr, work_dir};
+
+/// Run klo in a fresh directory and return its exit code, checking that it logged it.
+fn exit_code_of(test: &str, args: &[&str], with_corpus: bool) -> Option<i32> {
+    let dir = work_dir(test);
+    let output = if with_corpus {
+        klo_with_corpus(&dir, args)
+    } else {
+        klo(&dir, args)
+    };
+    let code = exit_code(&output);
+    if let Some(code) = code.filter(|&code| code != 0) {
+        let log = stderr(&output);
+        assert!(log.contains(&format!("Exit code {}:", code)), "{}", log);
+    }
+    code
+}
+
+#[test]
+fn a_broken_config_file_is_an_invalid_option() {
+    let config = fixture("broken_klo.toml");
+    let args = ["--config", config.as_str(), "analyze", ""];
+    assert_eq!(exit_code_of("broken_config", &args, true), Some(2));
+}
+
+#[test]
+fn an_out_of_range_option_is_an_invalid_option() {
+    let args = ["evolve", "--num-layouts", "0"];
+    assert_eq!(exit_code_of("out_of_range_option", &args, true), Some(2));
+}
+
+#[test]
+fn missing_ngram_files_are_an_ngrams_error() {
+    let config = fixture("broken_ngrams.config");
+    let args = ["--ngrams-config", config.as_str(), "analyze", ""];
+    assert_eq!(exit_code_of("missing_ngram_files", &args, false), Some(3));
+}
+
+#[test]
+fn a_broken_base_layout_is_a_layout_error() {
+    let base_layout = fixture("broken_base_layout.json");
+    let args = ["--base-layout", base_layout.as_str(), "analyze", ""];
+    assert_eq!(exit_code_of("broken_base_layout", &args, true), Some(4));
+}
+
+#[test]
+fn a_too_long_layout_string_is_a_layout_error() {
+    let args = ["analyze", "abcdefghijklmnopqrstuvwxyz"];
+    assert_eq!(exit_code_of("too_long_layout_string", &args, true), Some(4));
+}
+
+#[test]
+fn a_broken_geometry_file_lists_its_problems() {
+    let dir = work_dir("broken_geometry");
+    let geometry = fixture("broken_geometry.toml");
+    let output = klo_with_corpus(&dir, &["--geometry", geometry.as_str(), "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains(&format!(
+        "Invalid geometry {}: 0 row names for 1 rows, the finger name pinky doesn't start with L_ or R_, pinky has the position (0, 5), which isn't on the keyboard",
+        geometry
+    )));
+}
+
+#[cfg(feature = "exporters")]
+#[test]
+fn an_unwritable_output_file_is_a_failure() {
+    let dir = work_dir("unwritable_output_file");
+    let output = klo_with_corpus(&dir, &["export", "blueprint", "-o", "missing/layout.json"]);
+    assert_eq!(exit_code(&output), Some(1), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Unable to write missing/layout.json: "));
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_writes_the_runs_so_far_and_exits_with_5() {
+    use std::{
+        fs,
+        io::{BufRead, BufReader},
+        process::{Command, Stdio},
+    };
+
+    let dir = work_dir("sigint_writes_the_runs_so_far");
+    let corpus = fixture("corpus.txt");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_klo"))
+        .current_dir(&dir)
+        .args(["--threads", "1", "--corpus", corpus.as_str()])
+        .args(["evolve", "-n", "100000", "--steps", "200", "--seed", "1"])
+        .args(["--output-format", "json", "-o", "result.json"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Interrupt once the first run is done, so the handler is installed.
+    let mut log = BufReader::new(child.stderr.take().unwrap());
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        assert!(log.read_line(&mut line).unwrap() > 0, "{}", lines.join(""));
+        let run_ended = line.contains("Run 1 ended");
+        lines.push(line);
+        if run_ended {
+            break;
+        }
+    }
+    let pid = child.id().to_string();
+    let kill = Command::new("kill").args(["-INT", pid.as_str()]).status();
+    assert!(kill.unwrap().success());
+    for line in log.lines() {
+        lines.push(line.unwrap() + "\n");
+    }
+    let status = child.wait().unwrap();
+
+    let log = lines.join("");
+    assert_eq!(status.code(), Some(5), "{}", log);
+    assert!(log.contains("Exit code 5:"), "{}", log);
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    assert!(result["cost"]["total"].as_f64().is_some());
+    let runs = result["run_summary"]["runs"].as_u64().unwrap();
+    assert!((1..100_000).contains(&runs), "{} runs", runs);
+}
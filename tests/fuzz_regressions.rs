@@ -0,0 +1,223 @@
+//! Random and mutated input for the parsers of blueprints, layout strings and ngrams configs, and the inputs that made them panic.
+
+mod common;
+
+use std::{
+    fs,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    sync::Arc,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use klo::{
+    interner::Interner,
+    key_costs::KeyCosts,
+    layout::{Blueprint, BlueprintHelpers, Layout},
+    ngrams::NGrams,
+};
+
+use common::work_dir;
+
+const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+
+/// A file of `tests/fuzz-regressions`.
+fn regression(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fuzz-regressions")
+        .join(name)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Run `parse` on `input` and fail with the input if it panics instead of returning.
+fn no_panic<T>(input: &str, parse: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(parse)).unwrap_or_else(|_| panic!("panicked on {:?}", input))
+}
+
+/// `seed` with a few random bytes flipped, removed, repeated or replaced by json tokens.
+fn mutate(seed: &str, rng: &mut StdRng) -> String {
+    const TOKENS: [&str; 9] = [
+        "[",
+        "]",
+        "[]",
+        "\"\"",
+        ",",
+        "\"a\"",
+        "\"\\u0000\"",
+        "\"⇘\"",
+        "\n",
+    ];
+    let mut bytes = seed.as_bytes().to_vec();
+    for _ in 0..rng.gen_range(1..5) {
+        let at = rng.gen_range(0..=bytes.len());
+        match rng.gen_range(0..4) {
+            0 if at < bytes.len() => bytes[at] ^= 1 << rng.gen_range(0..8),
+            1 => {
+                let end = rng.gen_range(at..=bytes.len().min(at + 20));
+                bytes.drain(at..end);
+            }
+            2 => {
+                let end = rng.gen_range(at..=bytes.len().min(at + 20));
+                let repeated = bytes[at..end].to_vec();
+                bytes.splice(at..at, repeated);
+            }
+            _ => {
+                let token = TOKENS.choose(rng).unwrap().as_bytes();
+                bytes.splice(at..at, token.iter().copied());
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// A random layout string of letters, special keys, spaces and line breaks.
+fn random_layout_string(rng: &mut StdRng) -> String {
+    let chars: Vec<char> = "aeinrstß,.⇘x😀 \n\r\t\u{0}".chars().collect();
+    (0..rng.gen_range(0..60))
+        .map(|_| *chars.choose(rng).unwrap())
+        .collect()
+}
+
+/// Everything klo does with a blueprint it read: print, merge, index and score prerequisites.
+fn use_blueprint(blueprint: Blueprint, layout: &str) {
+    for layer in 0..blueprint.layer_count() {
+        blueprint.layer_string(layer);
+    }
+    let _ = blueprint.check_layers(&[0, 1]);
+    let interner = Arc::new(Interner::default());
+    Layout::from_blueprint(blueprint.clone(), &interner, &Arc::new(KeyCosts::default()));
+    if let Ok(merged) = blueprint.with_layout_string(layout, ALPHABET) {
+        merged.layout_string();
+    }
+}
+
+#[test]
+fn mutated_blueprints_are_read_or_rejected() {
+    let seeds = [
+        fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/default_base_layout.json"
+        ))
+        .unwrap(),
+        fs::read_to_string(regression("short_rows.json")).unwrap(),
+    ];
+    let mut rng = StdRng::seed_from_u64(459);
+    for _ in 0..300 {
+        let json = mutate(seeds.choose(&mut rng).unwrap(), &mut rng);
+        let layout = random_layout_string(&mut rng);
+        no_panic(&json, || {
+            if let Ok(blueprint) = Blueprint::from_json(&json) {
+                use_blueprint(blueprint, &layout);
+            }
+        });
+    }
+}
+
+#[test]
+fn random_layout_strings_are_merged_or_rejected() {
+    let bases = [
+        Blueprint::read_base_layout(&None).unwrap(),
+        Blueprint::from_json(&fs::read_to_string(regression("short_rows.json")).unwrap()).unwrap(),
+        vec![],
+    ];
+    let mut rng = StdRng::seed_from_u64(459);
+    for _ in 0..1000 {
+        let layout = random_layout_string(&mut rng);
+        let base = bases.choose(&mut rng).unwrap().clone();
+        no_panic(&layout, || use_blueprint(base, &layout));
+    }
+}
+
+#[test]
+fn a_blueprint_with_short_rows_has_a_layout_string() {
+    // `layer_string` used to index the three Neo letter rows of every blueprint.
+    let json = fs::read_to_string(regression("short_rows.json")).unwrap();
+    let blueprint = Blueprint::from_json(&json).unwrap();
+    assert_eq!(blueprint.layout_string(), "a b\n c");
+    let merged = blueprint
+        .clone()
+        .with_layout_string("ba\nc", ALPHABET)
+        .unwrap();
+    assert_eq!(merged[0][0], ["b"]);
+    assert!(blueprint.with_layout_string("ab\nc\nd", ALPHABET).is_err());
+}
+
+#[test]
+fn a_config_line_of_only_tabs_is_skipped() {
+    // The line passed the check, but loading it panicked.
+    let path = regression("tab_only_line.config");
+    assert_eq!(NGrams::check_config(&path, false), Vec::<String>::new());
+    let ngrams = NGrams::from_config(&path, false).unwrap();
+    assert_eq!(ngrams.letters.len(), 3);
+    assert_eq!(ngrams.trigrams.len(), 1);
+}
+
+#[test]
+fn random_ngrams_configs_are_loaded_or_rejected() {
+    let dir = work_dir("random_ngrams_configs_are_loaded_or_rejected");
+    for name in ["1-grams.txt", "2-grams.txt", "3-grams.txt"] {
+        fs::copy(regression(name), dir.join(name)).unwrap();
+    }
+    fs::write(dir.join("corpus.txt"), "Ein kleiner Text.\n").unwrap();
+    let weights = ["1", "0.5", "-1", "x", "1e400", "", "#1"];
+    let types = ["text", "pregenerated", "binary", ""];
+    let paths = [
+        "1-grams.txt;2-grams.txt;3-grams.txt",
+        "1-grams.txt 2-grams.txt '3-grams.txt'",
+        "corpus.txt",
+        "\"corpus.txt\"",
+        ".\\corpus.txt",
+        "'a b",
+        "a;b",
+        "missing.txt",
+        "",
+    ];
+    let separators = [" ", "\t", "  ", " \t "];
+    let mut rng = StdRng::seed_from_u64(459);
+    for _ in 0..300 {
+        let config: String = (0..rng.gen_range(0..4))
+            .map(|_| {
+                let line = match rng.gen_range(0..6) {
+                    0 => "\t\t".to_string(),
+                    1 => "# a comment".to_string(),
+                    2 => " # an indented comment".to_string(),
+                    _ => [&weights[..], &types[..], &paths[..]]
+                        .iter()
+                        .map(|fields| *fields.choose(&mut rng).unwrap())
+                        .collect::<Vec<_>>()
+                        .join(separators.choose(&mut rng).unwrap()),
+                };
+                line + ["\n", "\r\n"].choose(&mut rng).unwrap()
+            })
+            .collect();
+        let path = dir.join("ngrams.config");
+        fs::write(&path, &config).unwrap();
+        let path = path.to_string_lossy();
+        no_panic(&config, || {
+            NGrams::check_config(&path, false);
+            let _ = NGrams::from_config(&path, false);
+        });
+    }
+}
+
+#[test]
+fn random_pregenerated_lines_are_read_or_rejected() {
+    let parts = [
+        "1", "12", "0.5", "x", " ", "  ", "e", "ß", "😀", "\u{feff}", "\r", "\n", "\t",
+    ];
+    let mut rng = StdRng::seed_from_u64(459);
+    for _ in 0..1000 {
+        let mut file = || -> String {
+            (0..rng.gen_range(0..12))
+                .map(|_| *parts.choose(&mut rng).unwrap())
+                .collect()
+        };
+        let (letters, bigrams, trigrams) = (file(), file(), file());
+        let input = format!("{:?} {:?} {:?}", letters, bigrams, trigrams);
+        no_panic(&input, || {
+            let _ = NGrams::from_pregenerated(&letters, &bigrams, &trigrams);
+        });
+    }
+}
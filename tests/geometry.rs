@@ -0,0 +1,221 @@
+//! Choosing the geometry with --geometry or from the shape of the base layout.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// Write a base layout with rows of `shape` keys, each with a single character, to `name` in `dir`.
+fn write_base_layout(dir: &Path, name: &str, shape: &[usize]) {
+    let mut chars = "abcdefghijklmnopqrstuvwxyzäöüß,.-;:!?()[]{}<>=+*/".chars();
+    let blueprint: Vec<Vec<Vec<String>>> = shape
+        .iter()
+        .map(|&keys| {
+            (0..keys)
+                .map(|_| vec![chars.next().unwrap().to_string()])
+                .collect()
+        })
+        .collect();
+    fs::write(dir.join(name), serde_json::to_string(&blueprint).unwrap()).unwrap();
+}
+
+/// The geometry the json result of an evolution without steps names, and its log.
+fn geometry_of(dir: &Path, args: &[&str]) -> (String, String) {
+    let mut all = args.to_vec();
+    all.extend([
+        "evolve",
+        "-n",
+        "1",
+        "--steps",
+        "0",
+        "--starting-layout",
+        "",
+        "--baselines",
+        "none",
+        "--output-format",
+        "json",
+        "-o",
+        "result.json",
+    ]);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    (
+        result["geometry"].as_str().unwrap().to_string(),
+        stderr(&output),
+    )
+}
+
+#[test]
+fn an_explicit_geometry_is_used() {
+    let dir = work_dir("an_explicit_geometry_is_used");
+    write_base_layout(&dir, "split.json", &[12, 12, 12, 6]);
+    let (geometry, _) = geometry_of(
+        &dir,
+        &["--base-layout", "split.json", "--geometry", "split3x6+3"],
+    );
+    assert_eq!(geometry, "split3x6+3");
+}
+
+#[test]
+fn the_geometry_is_detected_from_the_shape_of_the_base_layout() {
+    let dir = work_dir("the_geometry_is_detected_from_the_shape_of_the_base_layout");
+    write_base_layout(&dir, "ortho.json", &[12, 12, 12, 12]);
+    let (geometry, _) = geometry_of(&dir, &["--base-layout", "ortho.json"]);
+    assert_eq!(geometry, "ortho4x12");
+}
+
+#[test]
+fn an_unknown_shape_falls_back_to_neo_with_a_warning() {
+    let dir = work_dir("an_unknown_shape_falls_back_to_neo_with_a_warning");
+    write_base_layout(&dir, "odd.json", &[12, 12, 12, 5]);
+    let (geometry, log) = geometry_of(&dir, &["--base-layout", "odd.json"]);
+    assert_eq!(geometry, "neo");
+    assert!(log.contains("No built-in geometry has rows of [12, 12, 12, 5] keys"));
+}
+
+#[test]
+fn a_geometry_of_another_shape_is_rejected_with_both_shapes() {
+    let dir = work_dir("a_geometry_of_another_shape_is_rejected_with_both_shapes");
+    let output = klo_with_corpus(&dir, &["--geometry", "ansi60", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains(
+        "The ansi60 geometry has rows of [14, 14, 13, 12, 8] keys, but the base layout has rows of [14, 14, 14, 13, 8] keys"
+    ));
+}
+
+/// The rows of the default base layout.
+fn default_base_layout() -> Vec<Vec<Vec<String>>> {
+    let json = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/default_base_layout.json"
+    ))
+    .unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn a_sixth_row_is_scored_with_the_off_table_cost_and_split_at_its_middle() {
+    let dir = work_dir("a_sixth_row_is_scored_with_the_off_table_cost_and_split_at_its_middle");
+    // The space moves from the thumb row to the right key of a sixth row, so the corpus types it.
+    let mut blueprint = default_base_layout();
+    let space = blueprint[4][3].clone();
+    blueprint[4][3].clear();
+    blueprint.push(vec![vec!["€".to_string()], space]);
+    fs::write(
+        dir.join("six.json"),
+        serde_json::to_string(&blueprint).unwrap(),
+    )
+    .unwrap();
+
+    let (geometry, log) = geometry_of(&dir, &["--base-layout", "six.json"]);
+    assert_eq!(geometry, "neo");
+    assert!(log.contains(
+        "Row 5 of the base layout has 2 keys, but the key cost table has costs for only 0. Its keys 0 to 1 cost 80"
+    ), "{}", log);
+    assert!(log.contains(
+        "Row 5 of the base layout isn't in the neo geometry, its keys are split between the pinkies at the middle of the row."
+    ), "{}", log);
+
+    let output = klo_with_corpus(&dir, &["--base-layout", "six.json", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    assert!(log.contains("Total cost: "), "{}", log);
+    assert!(!log.contains("on no key of the layout"), "{}", log);
+}
+
+#[test]
+fn a_three_row_blueprint_is_scored() {
+    let dir = work_dir("a_three_row_blueprint_is_scored");
+    let blueprint = default_base_layout()[1..4].to_vec();
+    fs::write(
+        dir.join("three.json"),
+        serde_json::to_string(&blueprint).unwrap(),
+    )
+    .unwrap();
+
+    let (geometry, log) = geometry_of(&dir, &["--base-layout", "three.json"]);
+    assert_eq!(geometry, "neo");
+    assert!(log.contains("No built-in geometry has rows of [14, 14, 13] keys"));
+
+    let output = klo_with_corpus(&dir, &["--base-layout", "three.json", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Total cost: "));
+}
+
+#[test]
+fn an_ortholinear_blueprint_evolves_and_skips_the_baselines_that_do_not_fit() {
+    let dir = work_dir("an_ortholinear_blueprint_evolves_and_skips_the_baselines_that_do_not_fit");
+    write_base_layout(&dir, "ortho.json", &[12, 12, 12, 12]);
+    geometry_of(&dir, &["--base-layout", "ortho.json"]);
+    let result = |name: &str| -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap()
+    };
+    let unchanged = result("result.json");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "--base-layout",
+            "ortho.json",
+            "evolve",
+            "-n",
+            "2",
+            "--steps",
+            "300",
+            "--seed",
+            "3",
+            "--starting-layout",
+            "",
+            "--prerandomize",
+            "50",
+            "--anneal",
+            "2",
+            "--output-format",
+            "json",
+            "-o",
+            "evolved.json",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    for baseline in ["neo", "bone", "qwertz"] {
+        assert!(
+            log.contains(&format!(
+                "Not comparing with the baseline {}, which doesn't fit the base layout",
+                baseline
+            )),
+            "{}",
+            log
+        );
+    }
+
+    let evolved = result("evolved.json");
+    assert_eq!(evolved["geometry"], "ortho4x12");
+    assert_ne!(evolved["layout"], unchanged["layout"]);
+    let total = evolved["cost"]["total"].as_f64().unwrap();
+    assert!(total < unchanged["cost"]["total"].as_f64().unwrap());
+
+    // The layout string has every key of the blueprint, so it gives the evolved layout again.
+    assert!(!log.contains("doesn't give the same layout"), "{}", log);
+    let layout = evolved["layout"]["layout_string"].as_str().unwrap();
+    assert_eq!(layout.lines().count(), 4, "{}", layout);
+    assert_eq!(
+        layout
+            .split_whitespace()
+            .collect::<String>()
+            .chars()
+            .count(),
+        48
+    );
+    let output = klo_with_corpus(&dir, &["--base-layout", "ortho.json", "analyze", layout]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains(&format!("Total cost: {:.2}\n", total)),
+        "{} {}",
+        total,
+        stderr(&output)
+    );
+}
@@ -0,0 +1,73 @@
+//! --hand-target and --hand-lock.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+#[test]
+fn the_result_has_the_target_and_the_achieved_hand_split() {
+    let dir = work_dir("the_result_has_the_target_and_the_achieved_hand_split");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "--hand-target",
+            "0.55",
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "20",
+            "--seed",
+            "1",
+            "--hand-lock",
+            "aeiou=right",
+            "--output-format",
+            "json",
+            "-o",
+            "result.json",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("(target 55.0%)"));
+
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    assert_eq!(result["hand_split"]["target"], 0.55);
+    let left = result["hand_split"]["left"].as_f64().unwrap();
+    assert!((0.0..=1.0).contains(&left));
+    assert_eq!(result["effective_options"]["hand_target"], 0.55);
+    assert_eq!(
+        result["effective_options"]["evolve"]["hand_lock"],
+        serde_json::json!(["aeiou=right"])
+    );
+}
+
+#[test]
+fn a_hand_target_outside_of_0_and_1_is_rejected() {
+    let dir = work_dir("a_hand_target_outside_of_0_and_1_is_rejected");
+    let output = klo_with_corpus(&dir, &["--hand-target", "1.5", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("--hand-target is 1.5, but it must be between 0 and 1"));
+}
+
+#[test]
+fn a_hand_lock_needs_alphabet_characters_and_a_hand() {
+    let dir = work_dir("a_hand_lock_needs_alphabet_characters_and_a_hand");
+    for (lock, error) in [
+        (
+            "é=right",
+            r#"Can't lock "é" to a hand, it is not in the alphabet"#,
+        ),
+        (
+            "aeiou=middle",
+            r#"Invalid hand lock "aeiou=middle": unknown hand "middle", expected left or right"#,
+        ),
+    ] {
+        let output = klo_with_corpus(&dir, &["evolve", "--steps", "0", "--hand-lock", lock]);
+        assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+        assert!(stderr(&output).contains(error), "{}", stderr(&output));
+    }
+}
@@ -0,0 +1,223 @@
+//! The html report of `--output-format html`, `--export html:<path>` and `klo export html`.
+#![cfg(all(feature = "cli", feature = "text-corpus", feature = "exporters"))]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// An element of an xml document, with the text of everything inside of it.
+#[derive(Debug)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> &str {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map_or_else(
+                || panic!("{} has no {}", self.name, name),
+                |(_, value)| value,
+            )
+    }
+}
+
+/// The text with the entities klo escapes replaced.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        unescaped.push_str(&rest[..at]);
+        let end = rest[at..].find(';').expect("an entity ends with ;") + at;
+        unescaped.push(match &rest[at..=end] {
+            "&amp;" => '&',
+            "&lt;" => '<',
+            "&gt;" => '>',
+            "&quot;" => '"',
+            "&#39;" => '\'',
+            entity => panic!("unknown entity {}", entity),
+        });
+        rest = &rest[end + 1..];
+    }
+    assert!(!rest.contains('>'), "unescaped > in {:?}", text);
+    unescaped + rest
+}
+
+/**
+Check that a document is well-formed xml after its doctype: comments without
+`--`, balanced tags with quoted attributes and only the escapes klo writes.
+Returns its elements in the order they start.
+*/
+fn parse(document: &str) -> Vec<Element> {
+    let mut rest = document
+        .strip_prefix("<!DOCTYPE html>\n")
+        .expect("a doctype");
+    let (mut elements, mut open): (Vec<Element>, Vec<usize>) = (vec![], vec![]);
+    while let Some(at) = rest.find('<') {
+        let text = unescape(&rest[..at]);
+        for &idx in &open {
+            elements[idx].text.push_str(&text);
+        }
+        rest = &rest[at..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").expect("a comment ends");
+            assert!(!comment[..end].contains("--"), "-- in a comment");
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = rest.find('>').expect("a tag ends");
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if let Some(name) = tag.strip_prefix('/') {
+            let idx = open
+                .pop()
+                .unwrap_or_else(|| panic!("</{}> closes nothing", name));
+            assert_eq!(elements[idx].name, name);
+            continue;
+        }
+        let (tag, empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let (name, mut attributes_text) = (&tag[..name_end], tag[name_end..].trim_start());
+        assert!(!name.is_empty() && name.chars().all(|char| char.is_ascii_alphanumeric()));
+        let mut attributes = vec![];
+        while !attributes_text.is_empty() {
+            let (attribute, value) = attributes_text
+                .split_once("=\"")
+                .unwrap_or_else(|| panic!("unquoted attribute in <{}>", tag));
+            let value_end = value.find('"').expect("an attribute value ends");
+            attributes.push((attribute.to_string(), unescape(&value[..value_end])));
+            attributes_text = value[value_end + 1..].trim_start();
+        }
+        elements.push(Element {
+            name: name.to_string(),
+            attributes,
+            text: String::new(),
+        });
+        if !empty {
+            open.push(elements.len() - 1);
+        }
+    }
+    assert!(open.is_empty(), "unclosed elements");
+    assert_eq!(rest.trim(), "");
+    assert_eq!(elements[0].name, "html");
+    elements
+}
+
+/// The characters of the keys of the first svg of the report, the keyboard of layer 0.
+fn layer_0_labels(elements: &[Element]) -> Vec<String> {
+    elements
+        .iter()
+        .skip_while(|element| element.name != "svg")
+        .skip(1)
+        .take_while(|element| element.name != "svg")
+        .filter(|element| element.name == "text")
+        .map(|element| element.text.clone())
+        .collect()
+}
+
+#[test]
+fn the_html_report_of_an_evolution_has_its_layout_and_convergence() {
+    let dir = work_dir("the_html_report_of_an_evolution_has_its_layout_and_convergence");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "--threads",
+            "1",
+            "evolve",
+            "-n",
+            "2",
+            "--steps",
+            "200",
+            "--seed",
+            "4",
+            "--output-format",
+            "html",
+            "-o",
+            "result.html",
+            "--export",
+            "html:export.html",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let report = fs::read_to_string(dir.join("result.html")).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("export.html")).unwrap(), report);
+
+    let elements = parse(&report);
+    let layout = &elements
+        .iter()
+        .find(|element| element.name == "pre")
+        .unwrap()
+        .text;
+    assert_eq!(layout.lines().count(), 3);
+    let labels = layer_0_labels(&elements);
+    for char in layout.chars().filter(|char| !char.is_whitespace()) {
+        assert!(
+            labels.contains(&char.to_string()),
+            "{:?} is on no key",
+            char
+        );
+    }
+    assert!(report.contains("keys moved from the starting layout"));
+
+    let runs: Vec<&Element> = elements
+        .iter()
+        .filter(|element| element.name == "polyline")
+        .collect();
+    assert_eq!(runs.len(), 2);
+    for (run, polyline) in runs.iter().enumerate() {
+        assert_eq!(polyline.text, format!("run {}", run + 1));
+        let xs: Vec<f64> = polyline
+            .attribute("points")
+            .split(' ')
+            .map(|point| point.split_once(',').unwrap().0.parse().unwrap())
+            .collect();
+        // The steps 1, 2, 5, 10, 20, 50, 100 and 200.
+        assert_eq!(xs.len(), 8);
+        assert!(xs.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", xs);
+    }
+}
+
+#[test]
+fn the_html_export_of_a_layout_has_its_keys() {
+    let dir = work_dir("the_html_export_of_a_layout_has_its_keys");
+    let layout = "xvlcw khgfqyß\nuiaeo snrtd⇘\nüöäpz bm,.j";
+    let output = klo_with_corpus(
+        &dir,
+        &["export", "html", "--layout", layout, "-o", "layout.html"],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let report = fs::read_to_string(dir.join("layout.html")).unwrap();
+
+    let elements = parse(&report);
+    assert_eq!(
+        elements
+            .iter()
+            .find(|element| element.name == "pre")
+            .unwrap()
+            .text,
+        layout
+    );
+    let labels = layer_0_labels(&elements);
+    for char in layout.chars().filter(|char| !char.is_whitespace()) {
+        assert!(
+            labels.contains(&char.to_string()),
+            "{:?} is on no key",
+            char
+        );
+    }
+    let layers = elements
+        .iter()
+        .filter(|element| element.name == "details")
+        .count();
+    assert!(layers >= 6, "a section for each of the 6 layers");
+    assert!(!report.contains("starting layout"));
+    assert!(!report.contains("<polyline"));
+}
@@ -0,0 +1,129 @@
+//! The json result document of `--output-format json`.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The arguments of a short evolution writing its json result to result.json.
+const ARGS: [&str; 13] = [
+    "evolve",
+    "-n",
+    "2",
+    "--steps",
+    "20",
+    "--seed",
+    "3",
+    "--baselines",
+    "neo",
+    "--output-format",
+    "json",
+    "-o",
+    "result.json",
+];
+
+#[test]
+fn the_json_result_has_the_documented_fields() {
+    let dir = work_dir("the_json_result_has_the_documented_fields");
+    let output = klo_with_corpus(&dir, &ARGS);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+
+    assert_eq!(result["schema_version"], 1);
+    assert_eq!(result["reproduction"]["seed"], 3);
+    assert_eq!(result["effective_options"]["evolve"]["steps"], 20);
+    let layout_string = result["layout"]["layout_string"].as_str().unwrap();
+    assert_eq!(layout_string.lines().count(), 3);
+    let blueprint = result["layout"]["blueprint"].as_array().unwrap();
+    assert!(!blueprint.is_empty());
+    let cost = &result["cost"];
+    let components: f64 = ["key_position", "hand_balance", "same_finger", "same_hand"]
+        .iter()
+        .map(|component| cost[component].as_f64().unwrap())
+        .sum();
+    assert!((cost["total"].as_f64().unwrap() - components).abs() < 1e-9);
+    assert_eq!(result["run_summary"]["runs"], 2);
+    assert_eq!(result["comparisons"][0]["name"], "neo");
+}
+
+#[test]
+fn json_stdout_prints_the_result_as_the_last_line() {
+    let dir = work_dir("json_stdout_prints_the_result_as_the_last_line");
+    let mut args = ARGS.to_vec();
+    args.push("--json-stdout");
+    let output = klo_with_corpus(&dir, &args);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let last: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    let file: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    assert_eq!(last["layout"], file["layout"]);
+    assert_eq!(last["cost"], file["cost"]);
+}
+
+#[test]
+fn adjusted_options_are_notes_of_the_result_files() {
+    let dir = work_dir("adjusted_options_are_notes_of_the_result_files");
+    let output = klo_with_corpus(&dir, &ARGS);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    assert_eq!(result["notes"][0]["field"], "anneal-step");
+    assert_eq!(result["notes"][0]["requested"], "1000");
+    assert_eq!(result["notes"][0]["effective"], "1");
+
+    // ARGS without the json output format and its file.
+    let text_args: Vec<&str> = ARGS[..ARGS.len() - 4]
+        .iter()
+        .copied()
+        .chain(["-o", "result.txt"])
+        .collect();
+    let output = klo_with_corpus(&dir, &text_args);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let text = fs::read_to_string(dir.join("result.txt")).unwrap();
+    assert!(
+        text.contains("# note: Using --anneal-step 1 instead of 1000: at most half of the 20 steps may be spent annealing\n"),
+        "{}",
+        text
+    );
+
+    let mut unadjusted = text_args;
+    unadjusted.extend(["--anneal", "0"]);
+    let output = klo_with_corpus(&dir, &unadjusted);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let text = fs::read_to_string(dir.join("result.txt")).unwrap();
+    let last_result = text.rsplit("# ========").next().unwrap();
+    assert!(!last_result.contains("# note:"), "{}", last_result);
+}
+
+#[test]
+fn the_run_summary_of_many_runs_adds_up() {
+    let dir = work_dir("the_run_summary_of_many_runs_adds_up");
+    let mut args = ARGS.to_vec();
+    args[2] = "8";
+    args[4] = "100";
+    let output = klo_with_corpus(&dir, &args);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+
+    let summary = &result["run_summary"];
+    let runs = summary["runs"].as_u64().unwrap();
+    assert_eq!(runs, 8);
+    let distinct = summary["distinct_layouts"].as_u64().unwrap();
+    assert!((1..=runs).contains(&distinct), "{}", summary);
+    let on_best = summary["runs_on_best"].as_u64().unwrap();
+    assert!((1..=runs).contains(&on_best), "{}", summary);
+    let histogram = summary["histogram"].as_array().unwrap();
+    let binned: u64 = histogram
+        .iter()
+        .map(|bin| bin["runs"].as_u64().unwrap())
+        .sum();
+    assert_eq!(binned, runs);
+    assert_eq!(histogram[0]["from"], summary["best"]);
+    assert_eq!(result["cost"]["total"], summary["best"]);
+}
@@ -0,0 +1,184 @@
+//! Scoring base layouts the built-in key cost table wasn't made for.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The default base layout.
+fn default_blueprint() -> Vec<Vec<Vec<String>>> {
+    let json = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/default_base_layout.json"
+    ))
+    .unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+/// The default base layout one key wider in every row, with the space on the extra key of the thumb row.
+fn wider_blueprint() -> Vec<Vec<Vec<String>>> {
+    let mut blueprint = default_blueprint();
+    for row in &mut blueprint {
+        row.push(vec![]);
+    }
+    let space = std::mem::take(&mut blueprint[4][3]);
+    blueprint[4][8] = space;
+    blueprint
+}
+
+/// The total cost analyze logs for the default layout on the wider.json base layout in `dir`, and the log.
+fn analyze(dir: &Path, args: &[&str]) -> (f64, String) {
+    let mut all = args.to_vec();
+    all.extend(["analyze", "--base-layout", "wider.json", ""]);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    let cost = log
+        .lines()
+        .find_map(|line| line.split("Total cost: ").nth(1))
+        .unwrap()
+        .parse()
+        .unwrap();
+    (cost, log)
+}
+
+#[test]
+fn a_blueprint_one_key_wider_in_every_row_costs_the_off_table_cost_there() {
+    let dir = work_dir("a_blueprint_one_key_wider_in_every_row_costs_the_off_table_cost_there");
+    let blueprint = wider_blueprint();
+    fs::write(
+        dir.join("wider.json"),
+        serde_json::to_string(&blueprint).unwrap(),
+    )
+    .unwrap();
+
+    let (default_cost, log) = analyze(&dir, &[]);
+    for (row, keys) in blueprint.iter().enumerate() {
+        let extra = keys.len() - 1;
+        assert!(log.contains(&format!(
+            "Row {} of the base layout has {} keys, but the key cost table has costs for only {}. Its key {} cost 80 (see --off-table-cost).",
+            row,
+            keys.len(),
+            extra,
+            extra
+        )), "{}", log);
+    }
+    let space_key = "Row 4 key 8 is outside of the key cost table, it costs 80.";
+    assert_eq!(log.matches(space_key).count(), 1, "{}", log);
+    assert!(!log.contains("on no key of the layout"), "{}", log);
+
+    let (cheap, _) = analyze(&dir, &["--off-table-cost", "10"]);
+    let (dear, _) = analyze(&dir, &["--off-table-cost", "200"]);
+    assert!(
+        cheap < default_cost && default_cost < dear,
+        "{} {} {}",
+        cheap,
+        default_cost,
+        dear
+    );
+}
+
+#[test]
+fn key_costs_of_another_shape_than_the_base_layout_are_rejected() {
+    let dir = work_dir("key_costs_of_another_shape_than_the_base_layout_are_rejected");
+    let blueprint = wider_blueprint();
+    let keys: Vec<Vec<f64>> = default_blueprint()
+        .iter()
+        .map(|row| vec![10.0; row.len()])
+        .collect();
+    fs::write(
+        dir.join("wider.json"),
+        serde_json::to_string(&blueprint).unwrap(),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("costs.json"),
+        serde_json::json!({ "keys": keys }).to_string(),
+    )
+    .unwrap();
+
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "analyze",
+            "--base-layout",
+            "wider.json",
+            "--key-costs",
+            "costs.json",
+            "",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    let log = stderr(&output);
+    assert!(
+        log.contains("The --key-costs don't fit the base layout"),
+        "{}",
+        log
+    );
+    for (row, keys) in blueprint.iter().enumerate() {
+        assert!(
+            log.contains(&format!(
+                "Row {} of the key costs has {} keys, but row {} of the base layout has {}",
+                row,
+                keys.len() - 1,
+                row,
+                keys.len()
+            )),
+            "{}",
+            log
+        );
+    }
+}
+
+/// The default base layout with `e` on a seventh layer of the first key of row 1, next to its own key.
+fn seven_layer_blueprint() -> Vec<Vec<Vec<String>>> {
+    let mut blueprint = default_blueprint();
+    let key = &mut blueprint[1][1];
+    key.resize(6, String::new());
+    key.push("e".to_string());
+    blueprint
+}
+
+#[test]
+fn a_seventh_layer_costs_the_off_table_cost() {
+    let dir = work_dir("a_seventh_layer_costs_the_off_table_cost");
+    let blueprint = serde_json::to_string(&seven_layer_blueprint()).unwrap();
+    fs::write(dir.join("seven.json"), blueprint).unwrap();
+
+    let output = klo_with_corpus(&dir, &["analyze", "--base-layout", "seven.json", ""]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Layer 6 is outside of the key cost table"));
+}
+
+#[test]
+fn key_costs_can_cover_a_seventh_layer() {
+    let dir = work_dir("key_costs_can_cover_a_seventh_layer");
+    let blueprint = seven_layer_blueprint();
+    let keys: Vec<Vec<f64>> = blueprint.iter().map(|row| vec![10.0; row.len()]).collect();
+    let costs = serde_json::json!({
+        "keys": keys,
+        "layers": [0.0, 15.0, 12.0, 10.0, 27.0, 22.0, 30.0],
+    });
+    fs::write(
+        dir.join("seven.json"),
+        serde_json::to_string(&blueprint).unwrap(),
+    )
+    .unwrap();
+    fs::write(dir.join("costs.json"), costs.to_string()).unwrap();
+
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "analyze",
+            "--base-layout",
+            "seven.json",
+            "--key-costs",
+            "costs.json",
+            "",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("outside of the key cost table"));
+}
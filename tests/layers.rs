@@ -0,0 +1,81 @@
+//! Evolutions of other layers than layer 0 with --optimize-layers.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The default starting layout.
+const STARTING_LAYOUT: &str = "bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q";
+
+/// The layout of a seeded layer 3 evolution with `steps`, from its json result.
+fn layer_3_evolution(dir: &Path, steps: &str) -> Value {
+    let output = klo_with_corpus(
+        dir,
+        &[
+            "--optimize-layers",
+            "3",
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            steps,
+            "--prerandomize",
+            "0",
+            "--seed",
+            "1",
+            "--output-format",
+            "json",
+            "-o",
+            "result.json",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    result["layout"].clone()
+}
+
+#[test]
+fn a_layer_3_evolution_leaves_layer_0_alone() {
+    let dir = work_dir("a_layer_3_evolution_leaves_layer_0_alone");
+    let start = layer_3_evolution(&dir, "0");
+    let end = layer_3_evolution(&dir, "50");
+
+    assert_eq!(end["layout_string"], STARTING_LAYOUT);
+    assert_ne!(start["layers"]["3"], end["layers"]["3"]);
+    let layer = |layout: &Value, layer: usize| -> Vec<Value> {
+        layout["blueprint"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|row| row.as_array().unwrap().iter())
+            .map(|key| key[layer].clone())
+            .collect()
+    };
+    for untouched in [0, 1, 2, 4, 5] {
+        assert_eq!(
+            layer(&start, untouched),
+            layer(&end, untouched),
+            "layer {}",
+            untouched
+        );
+    }
+}
+
+#[test]
+fn a_layer_the_base_layout_lacks_is_rejected() {
+    let dir = work_dir("a_layer_the_base_layout_lacks_is_rejected");
+    let output = klo_with_corpus(
+        &dir,
+        &["--optimize-layers", "0,9", "evolve", "--steps", "0"],
+    );
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains(
+        "--optimize-layers selects layer 9, but the base layout only has 6 layers (0 to 5)"
+    ));
+}
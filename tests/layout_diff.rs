@@ -0,0 +1,74 @@
+//! The keys a seeded evolution of a few steps moved, marked in the report and counted in the json result.
+#![cfg(all(feature = "cli", feature = "text-corpus"))]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+#[test]
+fn a_few_steps_mark_the_handful_of_keys_they_moved() {
+    let dir = work_dir("a_few_steps_mark_the_handful_of_keys_they_moved");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "--threads",
+            "1",
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "30",
+            "--prerandomize",
+            "0",
+            "--anneal",
+            "0",
+            "--controlled-tail",
+            "false",
+            "--seed",
+            "5",
+            "--output-format",
+            "json",
+            "-o",
+            "result.json",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    // The starting layout is the default one, "bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q".
+    let log = stderr(&output);
+    let report: Vec<&str> = log
+        .lines()
+        .map(|line| line.split("[klo::report] ").nth(1).unwrap_or_default())
+        .skip_while(|line| !line.contains("keys moved"))
+        .take(7)
+        .collect();
+    assert_eq!(
+        report,
+        [
+            "4 keys moved from the starting layout (marked with ^):",
+            "    bmuaz kdflvjß",
+            "    hriey wtsnc⇘",
+            "    ^     ^   ^",
+            "    xäüoö pg,.q",
+            "          ^",
+            "29 characters stayed on their key: b m u a z k d f l v j ß r i e y t s n ⇘ x ä ü o ö g , . q",
+        ]
+    );
+
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    assert_eq!(
+        result["layout"]["layout_string"],
+        "bmuaz kdflvjß\nhriey wtsnc⇘\nxäüoö pg,.q"
+    );
+    assert_eq!(result["diff"]["moved_keys"], 4);
+    let anchors: Vec<&str> = result["diff"]["anchors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|anchor| anchor.as_str().unwrap())
+        .collect();
+    assert_eq!(anchors.concat(), "bmuazkdflvjßrieytsn⇘xäüoög,.q");
+}
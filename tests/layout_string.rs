@@ -0,0 +1,72 @@
+//! The layout string `evolve --output-layout-string` writes, read back by `analyze` and `--starting-layout`.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// Evolve one run with `args` and return the json result.
+fn evolve_json(dir: &Path, args: &[&str]) -> serde_json::Value {
+    let mut all = vec!["evolve", "-n", "1", "--seed", "2"];
+    all.extend(args);
+    all.extend(["--output-format", "json", "-o", "result.json"]);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap()
+}
+
+/// The costs `klo analyze` logs for a layout, by the name of the json cost component.
+fn analyzed_costs(dir: &Path, layout: &str) -> Vec<(&'static str, String)> {
+    let output = klo_with_corpus(dir, &["analyze", layout]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    [
+        ("key_position", "Key position cost: "),
+        ("hand_balance", "Hand balance cost: "),
+        ("same_finger", "Same finger cost: "),
+        ("same_hand", "Same hand cost: "),
+        ("total", "Total cost: "),
+    ]
+    .iter()
+    .filter_map(|&(component, label)| {
+        let line = log.lines().find(|line| line.contains(label))?;
+        Some((component, line.split(label).nth(1).unwrap().to_string()))
+    })
+    .collect()
+}
+
+#[test]
+fn the_written_layout_string_has_the_cost_of_the_evolved_layout() {
+    let dir = work_dir("the_written_layout_string_has_the_cost_of_the_evolved_layout");
+    let result = evolve_json(
+        &dir,
+        &["--steps", "300", "--output-layout-string", "layout.txt"],
+    );
+    let written = fs::read_to_string(dir.join("layout.txt")).unwrap();
+    let layout = written.strip_suffix('\n').expect("a line break at the end");
+    assert_eq!(layout, result["layout"]["layout_string"]);
+
+    let costs = analyzed_costs(&dir, layout);
+    assert!(costs.len() >= 4, "{:?}", costs);
+    for (component, analyzed) in costs {
+        let evolved = result["cost"][component].as_f64().unwrap();
+        assert_eq!(format!("{:.2}", evolved), analyzed, "{}", component);
+    }
+
+    // Continuing from it without steps scores it exactly like the evolution did.
+    let continued = evolve_json(
+        &dir,
+        &[
+            "--steps",
+            "0",
+            "--prerandomize",
+            "0",
+            "--starting-layout",
+            layout,
+        ],
+    );
+    assert_eq!(continued["layout"]["layout_string"], layout);
+    assert_eq!(continued["cost"], result["cost"]);
+}
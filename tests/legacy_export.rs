@@ -0,0 +1,89 @@
+//! The text format of the Python evolve-keyboard-layout of `klo export legacy` and `--export legacy:<path>`.
+#![cfg(all(feature = "cli", feature = "text-corpus", feature = "exporters"))]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, fixture, klo_with_corpus, stderr, work_dir};
+
+const NEO: &str = "xvlcw khgfqyß\nuiaeo snrtd⇘\nüöäpz bm,.j";
+
+/**
+The lines of a legacy export after its reproduction header, which starts with
+`# klo` and ends before `# Evolved Layout`, the first line check_neo.py writes.
+*/
+fn without_header(export: &str) -> String {
+    assert!(export.starts_with("# klo "), "{}", export);
+    let start = export.find("# Evolved Layout\n").expect("the layout block");
+    export[start..].to_string()
+}
+
+/// A line with every number replaced by `<n>`, so lines of other layouts can be compared.
+fn shape(line: &str) -> String {
+    let mut shape = String::new();
+    let mut in_number = false;
+    for char in line.chars() {
+        let part_of_number = char.is_ascii_digit() || (in_number && char == '.');
+        if part_of_number && !in_number {
+            shape.push_str("<n>");
+        } else if !part_of_number {
+            shape.push(char);
+        }
+        in_number = part_of_number;
+    }
+    shape
+}
+
+#[test]
+fn the_legacy_export_of_neo_matches_the_sample() {
+    // tests/fixtures/check_neo.txt is the Neo layout with the fixture corpus in the
+    // format of check_neo.py, written by this exporter and checked to eval in Python.
+    let dir = work_dir("the_legacy_export_of_neo_matches_the_sample");
+    let output = klo_with_corpus(
+        &dir,
+        &["export", "legacy", "--layout", NEO, "-o", "neo.txt"],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let export = fs::read_to_string(dir.join("neo.txt")).unwrap();
+    assert_eq!(
+        without_header(&export),
+        fs::read_to_string(fixture("check_neo.txt")).unwrap()
+    );
+}
+
+#[test]
+fn an_evolution_exports_its_result_in_the_legacy_format() {
+    let dir = work_dir("an_evolution_exports_its_result_in_the_legacy_format");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "100",
+            "--seed",
+            "7",
+            "-o",
+            "result.txt",
+            "--export",
+            "legacy:legacy.txt",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let export = without_header(&fs::read_to_string(dir.join("legacy.txt")).unwrap());
+    let sample = fs::read_to_string(fixture("check_neo.txt")).unwrap();
+
+    let (lines, sample_lines): (Vec<&str>, Vec<&str>) =
+        (export.lines().collect(), sample.lines().collect());
+    assert_eq!(lines.len(), sample_lines.len());
+    // The layout block: the header line, the 3 rows and an empty line.
+    assert_eq!(lines[0], sample_lines[0]);
+    assert_eq!(lines[1..4].join("\n").chars().count(), NEO.chars().count());
+    assert_eq!(lines[4], "");
+    // The metric lines the Python scripts grep for.
+    for (line, sample_line) in lines[6..].iter().zip(&sample_lines[6..]) {
+        assert_eq!(shape(line), shape(sample_line));
+    }
+}
@@ -0,0 +1,32 @@
+//! Running klo from a program that embeds the library, like the binary does.
+#![cfg(all(feature = "cli", feature = "text-corpus"))]
+
+mod common;
+
+use clap::Clap;
+use klo::{config_file::ValueSources, klo_options::KloOptions};
+
+use common::fixture;
+
+/// The options of `klo --threads <threads> analyze` of the neo layout on the fixture corpus.
+fn analyze(threads: &str) -> KloOptions {
+    let corpus = fixture("corpus.txt");
+    KloOptions::try_parse_from([
+        "klo",
+        "--threads",
+        threads,
+        "--corpus",
+        &corpus,
+        "analyze",
+        "neo",
+    ])
+    .unwrap()
+}
+
+#[test]
+fn run_can_be_called_again_with_other_threads() {
+    for threads in ["1", "2", "1"] {
+        klo::run(analyze(threads), &ValueSources::default(), false)
+            .unwrap_or_else(|e| panic!("--threads {}: {}", threads, e));
+    }
+}
@@ -0,0 +1,36 @@
+//! The console log levels and --log-file.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo, klo_with_corpus, stderr, work_dir};
+
+#[test]
+fn a_quiet_console_still_writes_debug_lines_to_the_log_file() {
+    let dir = work_dir("a_quiet_console_still_writes_debug_lines_to_the_log_file");
+    let output = klo_with_corpus(&dir, &["--quiet", "--log-file", "klo.log", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    assert!(output.stdout.is_empty());
+    let console = stderr(&output);
+    assert!(
+        console
+            .lines()
+            .all(|line| !line.contains(" INFO ") && !line.contains(" DEBUG ")),
+        "{}",
+        console
+    );
+    let log = fs::read_to_string(dir.join("klo.log")).unwrap();
+    assert!(log.lines().any(|line| line.contains(" DEBUG ")), "{}", log);
+    assert!(log.lines().any(|line| line.contains(" INFO ")), "{}", log);
+}
+
+#[test]
+fn quiet_and_verbose_are_exclusive() {
+    let dir = work_dir("quiet_and_verbose_are_exclusive");
+    let output = klo(&dir, &["--quiet", "--verbose", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(2));
+    assert!(stderr(&output).contains("'--verbose' cannot be used with '--quiet'"));
+}
@@ -0,0 +1,93 @@
+//! The report of the corpus characters a layout can't type, and --missing-char-cost.
+#![cfg(all(feature = "cli", feature = "text-corpus"))]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, klo, stderr, work_dir};
+
+/// Write a corpus with `☃` (3 of 44 keystrokes) and `♥` (1), which no built-in layout has.
+fn write_corpus(dir: &Path) {
+    fs::write(
+        dir.join("corpus.txt"),
+        "Sie sagte ☃ hallo ☃ und dann ♥ so war das ☃.\n",
+    )
+    .unwrap();
+}
+
+/// The total cost analyze logs for the default layout, and the log.
+fn analyze(dir: &Path, args: &[&str]) -> (f64, String) {
+    let mut all = vec!["--corpus", "corpus.txt"];
+    all.extend(args);
+    all.extend(["analyze", ""]);
+    let output = klo(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    let cost = log
+        .lines()
+        .find_map(|line| line.split("Total cost: ").nth(1))
+        .unwrap()
+        .parse()
+        .unwrap();
+    (cost, log)
+}
+
+#[test]
+fn analyze_lists_the_characters_on_no_key_with_their_share() {
+    let dir = work_dir("analyze_lists_the_characters_on_no_key_with_their_share");
+    write_corpus(&dir);
+    let (_, log) = analyze(&dir, &[]);
+    assert!(log.contains(
+        "2 characters with 9.09% of the keystrokes are on no key of the layout, so they cost nothing (see --missing-char-cost):"
+    ), "{}", log);
+    let snowman = log.find(r#""☃" 6.818%"#).expect("the snowman is listed");
+    let heart = log.find(r#""♥" 2.273%"#).expect("the heart is listed");
+    assert!(snowman < heart, "the most frequent character comes first");
+
+    let (_, log) = analyze(&dir, &["--missing-char-threshold", "0.05"]);
+    assert!(log.contains(r#""☃" 6.818%"#), "{}", log);
+    assert!(!log.contains(r#""♥""#), "{}", log);
+}
+
+#[test]
+fn a_missing_char_cost_charges_the_characters_on_no_key() {
+    let dir = work_dir("a_missing_char_cost_charges_the_characters_on_no_key");
+    write_corpus(&dir);
+    let (free, _) = analyze(&dir, &[]);
+    let (charged, log) = analyze(&dir, &["--missing-char-cost", "10"]);
+    assert!(charged > free, "{} <= {}", charged, free);
+    assert!(log.contains("are on no key of the layout and cost 10 per keystroke:"));
+}
+
+#[test]
+fn the_json_result_has_the_missing_characters() {
+    let dir = work_dir("the_json_result_has_the_missing_characters");
+    write_corpus(&dir);
+    let output = klo(
+        &dir,
+        &[
+            "--corpus",
+            "corpus.txt",
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "5",
+            "--output-format",
+            "json",
+            "-o",
+            "result.json",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    let missing = result["missing_characters"].as_array().unwrap();
+    let chars: Vec<&str> = missing
+        .iter()
+        .map(|missing| missing["char"].as_str().unwrap())
+        .collect();
+    assert_eq!(chars, ["☃", "♥"]);
+    assert!((missing[0]["share"].as_f64().unwrap() - 3.0 / 44.0).abs() < 1e-9);
+}
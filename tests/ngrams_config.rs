@@ -0,0 +1,85 @@
+//! The paths of an ngrams config, relative to its directory or with --ngrams-paths-from-cwd.
+#![cfg(all(feature = "cli", feature = "text-corpus"))]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{exit_code, fixture, klo, stderr, work_dir};
+
+/// The total cost `klo analyze` logs for the default starting layout with `args`, and the log.
+fn analyze(dir: &Path, args: &[&str]) -> (f64, String) {
+    let mut all = args.to_vec();
+    all.extend(["analyze", ""]);
+    let output = klo(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    let cost = log
+        .lines()
+        .find_map(|line| line.split("Total cost: ").nth(1))
+        .unwrap()
+        .parse()
+        .unwrap();
+    (cost, log)
+}
+
+/// Write pregenerated ngrams of the words `ein` and `nie` next to a config in `configs/` of `dir`.
+fn write_pregenerated(dir: &Path) {
+    fs::create_dir_all(dir.join("configs")).unwrap();
+    fs::write(dir.join("configs/1-grams.txt"), "3 e\n3 i\n3 n\n").unwrap();
+    fs::write(dir.join("configs/2-grams.txt"), "1 ei\n2 in\n1 ni\n1 ie\n").unwrap();
+    fs::write(dir.join("configs/3-grams.txt"), "1 ein\n1 nie\n").unwrap();
+}
+
+#[test]
+fn the_paths_of_a_config_in_a_subdirectory_are_relative_to_it() {
+    let dir = work_dir("the_paths_of_a_config_in_a_subdirectory_are_relative_to_it");
+    write_pregenerated(&dir);
+    fs::write(
+        dir.join("configs/legacy.config"),
+        "1 pregenerated 1-grams.txt;2-grams.txt;3-grams.txt\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("configs/fields.config"),
+        "1\tpregenerated  '1-grams.txt' \"2-grams.txt\" .\\3-grams.txt\n",
+    )
+    .unwrap();
+
+    let (legacy, _) = analyze(&dir, &["--ngrams-config", "configs/legacy.config"]);
+    let (fields, _) = analyze(&dir, &["--ngrams-config", "configs/fields.config"]);
+    assert_eq!(legacy, fields);
+
+    let output = klo(
+        &dir,
+        &[
+            "--ngrams-config",
+            "configs/legacy.config",
+            "--ngrams-paths-from-cwd",
+            "analyze",
+            "",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("configs/legacy.config line 1: unable to open 1-grams.txt"));
+}
+
+#[test]
+fn a_quoted_windows_path_with_a_space_is_read() {
+    let dir = work_dir("a_quoted_windows_path_with_a_space_is_read");
+    fs::create_dir_all(dir.join("configs/my texts")).unwrap();
+    fs::copy(
+        fixture("corpus.txt"),
+        dir.join("configs/my texts/corpus.txt"),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("configs/windows.config"),
+        "1 text \"my texts\\corpus.txt\"\r\n",
+    )
+    .unwrap();
+
+    let (windows, _) = analyze(&dir, &["--ngrams-config", "configs/windows.config"]);
+    let (corpus, _) = analyze(&dir, &["--corpus", "configs/my texts/corpus.txt"]);
+    assert_eq!(windows, corpus);
+}
@@ -0,0 +1,124 @@
+//! The png heatmap of `klo export png` and `--export png:<path>`.
+#![cfg(all(feature = "cli", feature = "exporters"))]
+
+mod common;
+
+use std::{collections::HashSet, convert::TryInto, fs, path::Path};
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// A decoded png: its size, its RGB pixels and its comment.
+struct Png {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+    comment: String,
+}
+
+/// Decode the 8 bit RGB pngs klo writes, whose image data is in uncompressed deflate blocks.
+fn decode(path: &Path) -> Png {
+    let png = fs::read(path).unwrap();
+    assert_eq!(
+        png[..8],
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+    );
+    let (mut size, mut zlib, mut comment) = ((0, 0), vec![], String::new());
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+        match kind {
+            b"IHDR" => {
+                let dimension =
+                    |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+                size = (dimension(0) as usize, dimension(4) as usize);
+                assert_eq!(data[8..10], [8, 2], "8 bit RGB");
+            }
+            b"iTXt" => comment = String::from_utf8(data[12..].to_vec()).unwrap(),
+            b"IDAT" => zlib.extend_from_slice(data),
+            _ => {}
+        }
+        rest = &rest[12 + length..];
+    }
+
+    let mut raw = vec![];
+    let mut blocks = &zlib[2..];
+    loop {
+        let last = blocks[0] & 1 == 1;
+        assert_eq!(blocks[0] >> 1, 0, "uncompressed block");
+        let length = u16::from_le_bytes([blocks[1], blocks[2]]) as usize;
+        raw.extend_from_slice(&blocks[5..5 + length]);
+        blocks = &blocks[5 + length..];
+        if last {
+            break;
+        }
+    }
+
+    let (width, height) = size;
+    assert_eq!(raw.len(), (1 + 3 * width) * height);
+    let pixels = raw
+        .chunks(1 + 3 * width)
+        .flat_map(|row| {
+            assert_eq!(row[0], 0, "no filter");
+            row[1..]
+                .chunks(3)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        })
+        .collect();
+    Png {
+        width,
+        height,
+        pixels,
+        comment,
+    }
+}
+
+#[test]
+fn the_png_heatmap_has_the_width_and_the_colors_of_the_keys() {
+    let dir = work_dir("the_png_heatmap_has_the_width_and_the_colors_of_the_keys");
+    let mut heights = vec![];
+    for width in ["300", "600"] {
+        let output = klo_with_corpus(
+            &dir,
+            &["export", "png", "--png-width", width, "-o", "heatmap.png"],
+        );
+        assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+        let png = decode(&dir.join("heatmap.png"));
+        assert_eq!(png.width.to_string(), width);
+        assert_eq!(png.pixels.len(), png.width * png.height);
+        let colors: HashSet<[u8; 3]> = png.pixels.iter().copied().collect();
+        assert!(colors.len() > 10, "{} colors", colors.len());
+        assert!(png.comment.contains("klo"), "{}", png.comment);
+        heights.push(png.height);
+    }
+    // The height follows from the width.
+    assert!(
+        (heights[1] as i64 - 2 * heights[0] as i64).abs() <= 2,
+        "{:?}",
+        heights
+    );
+}
+
+#[test]
+fn an_evolution_exports_its_result_as_png() {
+    let dir = work_dir("an_evolution_exports_its_result_as_png");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "evolve",
+            "-n",
+            "1",
+            "--steps",
+            "10",
+            "-o",
+            "result.txt",
+            "--export",
+            "png:result.png",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let png = decode(&dir.join("result.png"));
+    assert_eq!(png.width, 800);
+    assert!(png.pixels.iter().any(|&pixel| pixel != png.pixels[0]));
+}
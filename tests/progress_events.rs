@@ -0,0 +1,55 @@
+//! The jsonl progress events of `--progress-format jsonl` on stdout.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use serde_json::Value;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+#[test]
+fn a_seeded_run_emits_only_progress_events_on_stdout() {
+    let dir = work_dir("a_seeded_run_emits_only_progress_events_on_stdout");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "evolve",
+            "-n",
+            "2",
+            "--steps",
+            "30",
+            "--seed",
+            "2",
+            "--progress-format",
+            "jsonl",
+            "-o",
+            "result.txt",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let events: Vec<Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", e, line)))
+        .collect();
+    assert!(events.iter().all(|event| event["schema_version"] == 1));
+    let names: Vec<&str> = events
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    let count = |name| names.iter().filter(|event| **event == name).count();
+    assert_eq!(count("run-started"), 2, "{:?}", names);
+    assert_eq!(count("run-finished"), 2, "{:?}", names);
+    assert!(count("new-best-layout") >= 2, "{:?}", names);
+    assert_eq!(names.last(), Some(&"final-result"));
+
+    let best = events
+        .iter()
+        .filter(|event| event["event"] == "run-finished")
+        .map(|event| event["cost"].as_f64().unwrap())
+        .fold(f64::INFINITY, f64::min);
+    let result = events.last().unwrap();
+    assert_eq!(result["cost"]["total"].as_f64(), Some(best));
+    assert_eq!(result["layout"].as_str().unwrap().lines().count(), 3);
+}
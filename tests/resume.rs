@@ -0,0 +1,151 @@
+//! Continuing a killed evolution from its checkpoint with --resume.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    env, fs,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use serde_json::Value;
+
+use common::{exit_code, fixture, klo_with_corpus, stderr, work_dir};
+
+/// A seeded evolution of 6 runs on one thread, so its runs finish one after the other.
+const ARGS: [&str; 13] = [
+    "--threads",
+    "1",
+    "evolve",
+    "-n",
+    "6",
+    "--steps",
+    "2000",
+    "--seed",
+    "5",
+    "--baselines",
+    "none",
+    "--output-format",
+    "json",
+];
+
+/// The json result in `name` in `dir`.
+fn result(dir: &Path, name: &str) -> Value {
+    serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap()
+}
+
+/// The completed runs of the checkpoint in `dir`, none while it is not written yet.
+fn completed_runs(dir: &Path) -> Option<u64> {
+    let checkpoint: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("checkpoint.json")).ok()?).ok()?;
+    checkpoint["completed_runs"].as_u64()
+}
+
+/// Start ARGS with a checkpoint after every run and kill klo once 3 runs are done.
+fn evolve_and_kill(dir: &Path) -> u64 {
+    let corpus = fixture("corpus.txt");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_klo"));
+    command
+        .current_dir(dir)
+        .args(ARGS)
+        .args(["-o", "killed.json", "--corpus", &corpus])
+        .args(["--checkpoint", "checkpoint.json"])
+        .args(["--checkpoint-interval", "1run"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    for (name, _) in env::vars() {
+        if name.starts_with("KLO_") {
+            command.env_remove(name);
+        }
+    }
+    let mut child = command.spawn().unwrap();
+    loop {
+        if completed_runs(dir).is_some_and(|runs| runs >= 3) {
+            child.kill().unwrap();
+            child.wait().unwrap();
+            break;
+        }
+        if child.try_wait().unwrap().is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    completed_runs(dir).unwrap()
+}
+
+#[test]
+fn a_resumed_evolution_ends_like_an_uninterrupted_one() {
+    let dir = work_dir("a_resumed_evolution_ends_like_an_uninterrupted_one");
+    let mut uninterrupted = ARGS.to_vec();
+    uninterrupted.extend(["-o", "uninterrupted.json"]);
+    let output = klo_with_corpus(&dir, &uninterrupted);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    let killed_after = evolve_and_kill(&dir);
+    assert!(
+        (3..6).contains(&killed_after),
+        "killed after {} runs",
+        killed_after
+    );
+    assert!(!dir.join("killed.json").exists());
+
+    let mut resumed = ARGS.to_vec();
+    resumed.extend(["-o", "resumed.json", "--resume", "checkpoint.json"]);
+    let output = klo_with_corpus(&dir, &resumed);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains(&format!(
+        "Resuming after {} of 6 runs from checkpoint.json",
+        killed_after
+    )));
+    assert!(!stderr(&output).contains("was written with other options"));
+
+    let uninterrupted = result(&dir, "uninterrupted.json");
+    let resumed = result(&dir, "resumed.json");
+    assert_eq!(resumed["layout"], uninterrupted["layout"]);
+    assert_eq!(resumed["cost"], uninterrupted["cost"]);
+    assert_eq!(completed_runs(&dir), Some(6));
+
+    // The evolution is complete, so resuming it again only repeats the report.
+    let mut again = ARGS.to_vec();
+    again.extend(["-o", "again.json", "--resume", "checkpoint.json"]);
+    let output = klo_with_corpus(&dir, &again);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Resuming after 6 of 6 runs from checkpoint.json"));
+    assert_eq!(
+        result(&dir, "again.json")["layout"],
+        uninterrupted["layout"]
+    );
+}
+
+#[test]
+fn resume_strict_rejects_a_checkpoint_of_other_options() {
+    let dir = work_dir("resume_strict_rejects_a_checkpoint_of_other_options");
+    let mut first = ARGS.to_vec();
+    first.extend(["-o", "first.json", "--checkpoint", "checkpoint.json"]);
+    let output = klo_with_corpus(&dir, &first);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+
+    let other = [
+        "evolve",
+        "-n",
+        "6",
+        "--steps",
+        "10",
+        "--resume",
+        "checkpoint.json",
+    ];
+    let output = klo_with_corpus(&dir, &other);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains(
+        "checkpoint.json was written with other options than the current ones, resuming anyway"
+    ));
+
+    let mut strict = other.to_vec();
+    strict.push("--resume-strict");
+    let output = klo_with_corpus(&dir, &strict);
+    assert_eq!(exit_code(&output), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("was written with other options than the current ones"));
+}
@@ -0,0 +1,90 @@
+//! Several evolutions appending their runs to one --runs-csv file.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// Evolve two short runs with `seed`, appending them to `runs.csv`.
+fn evolve(dir: &std::path::Path, seed: &str) {
+    let output = klo_with_corpus(
+        dir,
+        &[
+            "evolve",
+            "-n",
+            "2",
+            "--steps",
+            "20",
+            "--prerandomize",
+            "10",
+            "--anneal",
+            "0",
+            "--seed",
+            seed,
+            "--runs-csv",
+            "runs.csv",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+}
+
+/// The rows of a runs csv, without the quoted line breaks of their layout strings.
+fn rows(csv: &str) -> Vec<Vec<&str>> {
+    csv.lines()
+        .filter(|line| {
+            line.split(',')
+                .next()
+                .is_some_and(|hash| hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        })
+        .map(|line| line.split(',').collect())
+        .collect()
+}
+
+#[test]
+fn two_evolutions_share_one_header() {
+    let dir = work_dir("two_evolutions_share_one_header");
+    evolve(&dir, "3");
+    evolve(&dir, "4");
+
+    let csv = fs::read_to_string(dir.join("runs.csv")).unwrap();
+    let headers = csv
+        .lines()
+        .filter(|line| line.starts_with("config_hash,"))
+        .count();
+    assert_eq!(headers, 1, "{}", csv);
+    assert!(csv.starts_with("config_hash,klo_version,seed,run,total,"));
+
+    let rows = rows(&csv);
+    // The runs of one evolution finish in any order on several threads.
+    let mut seeds_and_runs: Vec<(&str, &str)> = rows.iter().map(|row| (row[2], row[3])).collect();
+    seeds_and_runs.sort();
+    assert_eq!(
+        seeds_and_runs,
+        vec![("3", "1"), ("3", "2"), ("4", "1"), ("4", "2")]
+    );
+}
+
+#[test]
+fn every_configuration_is_described_once_next_to_the_runs() {
+    let dir = work_dir("every_configuration_is_described_once_next_to_the_runs");
+    evolve(&dir, "3");
+    evolve(&dir, "3");
+    evolve(&dir, "4");
+
+    let csv = fs::read_to_string(dir.join("runs.csv")).unwrap();
+    let configs = fs::read_to_string(dir.join("runs.csv.configs")).unwrap();
+    let mut hashes: Vec<&str> = rows(&csv).iter().map(|row| row[0]).collect();
+    hashes.dedup();
+    assert_eq!(hashes.len(), 2, "{}", csv);
+    for hash in hashes {
+        let marker = format!("config_hash: {}", hash);
+        assert_eq!(configs.lines().filter(|line| *line == marker).count(), 1);
+    }
+    assert!(configs.contains("seed: 3\n") && configs.contains("seed: 4\n"));
+    assert!(configs.contains("options:\n"));
+    assert!(rows(&csv)
+        .iter()
+        .all(|row| row[1] == env!("CARGO_PKG_VERSION")));
+}
@@ -0,0 +1,67 @@
+//! Evolutions with --seed, which must be reproducible.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, klo_with_corpus, stderr, work_dir};
+
+/// The layout of a short evolution with `args`, as the output file has it, and the log.
+fn evolve(test: &str, args: &[&str]) -> (String, String) {
+    let dir = work_dir(test);
+    let mut all = vec![
+        "evolve",
+        "-n",
+        "2",
+        "--steps",
+        "100",
+        "--prerandomize",
+        "50",
+    ];
+    all.extend(args);
+    all.extend(["--output-format", "json", "-o", "result.json"]);
+    let output = klo_with_corpus(&dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("result.json")).unwrap()).unwrap();
+    (result["layout"].to_string(), stderr(&output))
+}
+
+#[test]
+fn the_same_seed_evolves_the_same_layout() {
+    let (first, _) = evolve("the_same_seed_evolves_the_same_layout", &["--seed", "7"]);
+    let (second, _) = evolve("the_same_seed_evolves_the_same_layout", &["--seed", "7"]);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn the_logged_seed_repeats_an_evolution_without_seed() {
+    let test = "the_logged_seed_repeats_an_evolution_without_seed";
+    let (first, log) = evolve(test, &[]);
+    let seed = log
+        .lines()
+        .find_map(|line| line.split("Seeding the runs with ").nth(1))
+        .and_then(|rest| rest.split(',').next())
+        .expect("the log has the seed")
+        .to_string();
+    let (second, _) = evolve(test, &["--seed", &seed]);
+    assert_eq!(first, second);
+}
+
+/**
+What --seed 9 evolves on the fixture corpus, with and without the parallel
+feature. Run this test with `--no-default-features --features cli,text-corpus`
+too, to check that the serial runs end like the parallel ones.
+*/
+const SEED_9_LAYOUT: &str = "kpgüv zbaocäj\nhitnl sreud⇘\nßqöwx mf,.y";
+
+#[test]
+fn the_seed_evolves_the_same_layout_with_and_without_parallel_runs() {
+    let (layout, _) = evolve(
+        "the_seed_evolves_the_same_layout_with_and_without_parallel_runs",
+        &["--seed", "9", "--threads", "2"],
+    );
+    let layout: serde_json::Value = serde_json::from_str(&layout).unwrap();
+    assert_eq!(layout["layout_string"], SEED_9_LAYOUT);
+}
@@ -0,0 +1,78 @@
+//! Golden files of what a seeded evolution prints and writes, see `common::assert_snapshot`.
+#![cfg(all(feature = "cli", feature = "text-corpus", feature = "exporters"))]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{assert_snapshot, exit_code, klo_with_corpus, normalize, stderr, work_dir};
+
+/// A seeded evolution of two short runs, which writes its result with `args`, and its normalized log.
+///
+/// One thread, so that the runs log in the same order in every build.
+fn evolve(dir: &Path, args: &[&str]) -> String {
+    let mut all = vec![
+        "--threads",
+        "1",
+        "evolve",
+        "-n",
+        "2",
+        "--steps",
+        "200",
+        "--seed",
+        "4",
+    ];
+    all.extend(args);
+    let output = klo_with_corpus(dir, &all);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    normalize(&stderr(&output), dir)
+}
+
+/// The normalized contents of `name` in `dir`.
+fn written(dir: &Path, name: &str) -> String {
+    normalize(&fs::read_to_string(dir.join(name)).unwrap(), dir)
+}
+
+#[test]
+fn the_text_result_and_the_report() {
+    let dir = work_dir("the_text_result_and_the_report");
+    let log = evolve(&dir, &["-o", "result.txt", "--export", "svg:result.svg"]);
+    assert_snapshot("evolve_result.txt", &written(&dir, "result.txt"));
+    assert_snapshot("evolve_report.log", &log);
+    assert_snapshot("evolve_result.svg", &written(&dir, "result.svg"));
+}
+
+#[test]
+fn the_json_result() {
+    let dir = work_dir("the_json_result");
+    evolve(&dir, &["--output-format", "json", "-o", "result.json"]);
+    assert_snapshot("evolve_result.json", &written(&dir, "result.json"));
+}
+
+#[test]
+fn the_markdown_result() {
+    let dir = work_dir("the_markdown_result");
+    evolve(&dir, &["--output-format", "markdown", "-o", "result.md"]);
+    assert_snapshot("evolve_result.md", &written(&dir, "result.md"));
+}
+
+#[test]
+fn the_layout_grids_of_analyze() {
+    let dir = work_dir("the_layout_grids_of_analyze");
+    let output = klo_with_corpus(&dir, &["--optimize-layers", "0,3", "analyze", ""]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert_snapshot("analyze.log", &normalize(&stderr(&output), &dir));
+}
+
+#[test]
+fn normalizing_replaces_what_changes_between_runs() {
+    let dir = Path::new("/tmp/klo-test");
+    let output = format!(
+        "2021-09-14 12:34:56,789 INFO  [klo] took 1.25s and 30ms\n# ======== 2021-09-14T12:34:56.789123+00:00 ========\ncorpus = [\"/tmp/klo-test/corpus.txt\"]\nklo {} (x)\n5m steps in 200 runs",
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(
+        normalize(&output, dir),
+        "<time> INFO  [klo] took <duration> and <duration>\n# ======== <time> ========\ncorpus = [\"<dir>/corpus.txt\"]\nklo <version> (x)\n5m steps in 200 runs"
+    );
+}
@@ -0,0 +1,103 @@
+//! Every subcommand invoked once, and the deprecated invocation without one.
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use common::{exit_code, fixture, klo, klo_with_corpus, stderr, work_dir};
+
+/// The default starting layout.
+const LAYOUT: &str = "bmuaz kdflvjß\ncriey ptsnh⇘\nxäüoö wg,.q";
+
+#[test]
+fn analyze_logs_the_total_cost() {
+    let dir = work_dir("analyze_logs_the_total_cost");
+    let output = klo_with_corpus(&dir, &["analyze", LAYOUT]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Total cost: "));
+}
+
+#[test]
+fn compare_ranks_every_layout() {
+    let dir = work_dir("compare_ranks_every_layout");
+    let output = klo_with_corpus(&dir, &["compare", LAYOUT, "qwertz"]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let log = stderr(&output);
+    assert!(log.contains("#1 ") && log.contains("#2 "), "{}", log);
+}
+
+#[test]
+fn ngrams_generate_writes_the_three_files() {
+    let dir = work_dir("ngrams_generate_writes_the_three_files");
+    let corpus = fixture("corpus.txt");
+    let output = klo(
+        &dir,
+        &["ngrams", "generate", &corpus, "--output-dir", "counted"],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    for name in ["1gramme.txt", "2gramme.txt", "3gramme.txt"] {
+        let contents = fs::read_to_string(dir.join("counted").join(name)).unwrap();
+        assert!(!contents.is_empty(), "{} is empty", name);
+    }
+}
+
+#[test]
+fn ngrams_top_prints_the_most_frequent_letters() {
+    let dir = work_dir("ngrams_top_prints_the_most_frequent_letters");
+    let output = klo_with_corpus(&dir, &["ngrams", "top", "--count", "3"]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("Top 3 letters:"), "{}", stdout);
+}
+
+#[test]
+fn ngrams_export_scales_the_counts_to_the_keystrokes() {
+    let dir = work_dir("ngrams_export_scales_the_counts_to_the_keystrokes");
+    let output = klo_with_corpus(
+        &dir,
+        &[
+            "ngrams",
+            "export",
+            "--output-dir",
+            "mixed",
+            "--keystrokes",
+            "1000",
+        ],
+    );
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let letters = fs::read_to_string(dir.join("mixed/1gramme.txt")).unwrap();
+    let total: f64 = letters
+        .lines()
+        .map(|line| line.split(' ').next().unwrap().parse::<f64>().unwrap())
+        .sum();
+    assert!((total - 1000.0).abs() < 50.0, "{}", total);
+}
+
+#[cfg(feature = "exporters")]
+#[test]
+fn export_writes_the_layout_string() {
+    let dir = work_dir("export_writes_the_layout_string");
+    let output = klo(&dir, &["export", "layout-string", "--layout", LAYOUT]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), LAYOUT);
+}
+
+#[test]
+fn the_bash_completions_have_the_evolve_options() {
+    let dir = work_dir("the_bash_completions_have_the_evolve_options");
+    let output = klo(&dir, &["completions", "bash"]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.contains("--steps"), "{}", script);
+    assert!(script.contains("analyze"), "{}", script);
+}
+
+#[test]
+fn no_subcommand_evolves_with_a_deprecation_warning() {
+    let dir = work_dir("no_subcommand_evolves_with_a_deprecation_warning");
+    let output = klo_with_corpus(&dir, &["-n", "1", "--steps", "0", "-o", "result.txt"]);
+    assert_eq!(exit_code(&output), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Running klo without a subcommand is deprecated"));
+    assert!(dir.join("result.txt").is_file());
+}